@@ -0,0 +1,62 @@
+// Generates an irradiance cube from an environment map using convolution,
+// writing every face of one mip level per dispatch
+
+#version 450
+
+layout (local_size_x = 8, local_size_y = 8, local_size_z = 1) in;
+
+layout (binding = 0) uniform samplerCube samplerEnv;
+layout (binding = 1, rgba32f) uniform writeonly image2DArray outputFaces;
+
+layout(push_constant) uniform PushConsts {
+	float deltaPhi;
+	float deltaTheta;
+} consts;
+
+#define PI 3.1415926535897932384626433832795
+
+// The direction through a texel of a face, matching the views the raster bake renders with
+vec3 cubeDirection(uvec3 texel, vec2 size)
+{
+	vec2 st = (vec2(texel.xy) + 0.5) / size * 2.0 - 1.0;
+	switch (texel.z) {
+		case 0: return vec3(1.0, -st.y, -st.x);
+		case 1: return vec3(-1.0, -st.y, st.x);
+		case 2: return vec3(st.x, 1.0, st.y);
+		case 3: return vec3(st.x, -1.0, -st.y);
+		case 4: return vec3(st.x, -st.y, 1.0);
+		default: return vec3(-st.x, -st.y, -1.0);
+	}
+}
+
+void main()
+{
+	ivec2 size = imageSize(outputFaces).xy;
+	if (any(greaterThanEqual(gl_GlobalInvocationID.xy, uvec2(size)))) {
+		return;
+	}
+
+	vec3 N = normalize(cubeDirection(gl_GlobalInvocationID, vec2(size)));
+	vec3 up = vec3(0.0, 1.0, 0.0);
+	vec3 right = normalize(cross(up, N));
+	up = cross(N, right);
+
+	const float TWO_PI = PI * 2.0;
+	const float HALF_PI = PI * 0.5;
+
+	// Compute shaders have no derivatives, so the level the raster bake's implicit
+	// texture lookups land on is estimated from the ratio of the face sizes
+	float lod = max(log2(float(textureSize(samplerEnv, 0).x) / float(size.x)), 0.0);
+
+	vec3 color = vec3(0.0);
+	uint sampleCount = 0u;
+	for (float phi = 0.0; phi < TWO_PI; phi += consts.deltaPhi) {
+		for (float theta = 0.0; theta < HALF_PI; theta += consts.deltaTheta) {
+			vec3 tempVec = cos(phi) * right + sin(phi) * up;
+			vec3 sampleVector = cos(theta) * N + sin(theta) * tempVec;
+			color += textureLod(samplerEnv, sampleVector, lod).rgb * cos(theta) * sin(theta);
+			sampleCount++;
+		}
+	}
+	imageStore(outputFaces, ivec3(gl_GlobalInvocationID), vec4(PI * color / float(sampleCount), 1.0));
+}
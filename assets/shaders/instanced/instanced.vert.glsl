@@ -0,0 +1,25 @@
+#version 450
+
+layout (location = 0) in vec3 inPos;
+layout (location = 1) in vec3 inNormal;
+layout (location = 2) in vec2 inUV;
+
+// Per instance
+layout (location = 3) in mat4 instanceModel;
+layout (location = 7) in vec4 instanceTint;
+layout (location = 8) in vec4 instanceCustom;
+
+layout(binding = 0) uniform Ubo {
+  mat4 view;
+  mat4 projection;
+} ubo;
+
+layout (location = 0) out vec4 outTint;
+
+void main()
+{
+  vec3 pos = inPos;
+  pos.y *= -1.0;
+  gl_Position =  ubo.projection * ubo.view * instanceModel * vec4(pos, 1.0);
+  outTint = instanceTint;
+}
@@ -14,6 +14,83 @@ layout(binding = 3) uniform samplerCube irradiance_cubemap;
 layout(binding = 4) uniform samplerCube prefilter_cubemap;
 layout(binding = 5) uniform sampler2D brdflut;
 
+#define MAX_LIGHTS 64
+
+#define LIGHT_DIRECTIONAL 0
+#define LIGHT_POINT 1
+#define LIGHT_SPOT 2
+
+struct Light {
+  // XYZ is the position, W is the type
+  vec4 position;
+  // XYZ points from the scene towards the light, or back along a spot's axis.
+  // W is the range, zero when unlimited.
+  vec4 direction;
+  // RGB is the color scaled by the intensity
+  vec4 color;
+  // X and Y scale and offset the cosine of the angle to a spot's axis into its falloff
+  vec4 cone;
+};
+
+layout(binding = 9) uniform UboLights {
+  // X is the number of lights in use
+  vec4 count;
+  Light lights[MAX_LIGHTS];
+} uboLights;
+
+#define MAX_CASCADES 4
+
+// Cascaded shadows of one of the lights
+layout(binding = 10) uniform UboShadows {
+  mat4 viewProjections[MAX_CASCADES];
+  // The distance from the camera each cascade ends at
+  vec4 splits;
+  // X is the number of cascades, zero when nothing casts shadows.
+  // Y is the filter radius in texels.
+  // Z is the size of a texel.
+  // W is the index of the light the shadows are cast by.
+  vec4 parameters;
+} uboShadows;
+
+layout(binding = 11) uniform sampler2DArrayShadow shadowMap;
+
+#define MAX_POINT_SHADOWS 4
+
+struct PointShadow {
+  // XYZ is the light's position.
+  // W is the light's index in the light buffer, or -1 when the cube is unused.
+  vec4 position;
+  // X and Y are the near and far planes.
+  // Z is the filter radius in texels.
+  // W is the size of a texel at a distance of one from the light.
+  vec4 parameters;
+};
+
+// Shadows of point lights, rendered into depth cubes
+layout(binding = 12) uniform UboPointShadows {
+  PointShadow shadows[MAX_POINT_SHADOWS];
+  // The faces of each cube in turn, which the casters are drawn with
+  mat4 viewProjections[MAX_POINT_SHADOWS * 6];
+} uboPointShadows;
+
+layout(binding = 13) uniform samplerCubeShadow pointShadowMaps[MAX_POINT_SHADOWS];
+
+#define MAX_PROBES 4
+
+// Reflection probes captured from the scene
+layout(binding = 6) uniform UboProbes {
+  // X is the number of probes in use
+  vec4 count;
+  // The slots of the probes in use, from the one nearest the camera
+  vec4 order;
+  // W is a sphere's radius, or zero for boxes
+  vec4 positions[MAX_PROBES];
+  // XYZ are a box's extents, W is the distance probes fade out over
+  vec4 extents[MAX_PROBES];
+} uboProbes;
+layout(binding = 7) uniform samplerCube probe_irradiance_cubemaps[MAX_PROBES];
+layout(binding = 8) uniform samplerCube probe_prefilter_cubemaps[MAX_PROBES];
+
 layout(push_constant) uniform Material {
   vec4 baseColorFactor;
   vec3 emissiveFactor;
@@ -26,6 +103,8 @@ layout(push_constant) uniform Material {
   float roughnessFactor;
   int alphaMode;
   float alphaCutoff;
+  // From KHR_materials_emissive_strength, which lifts the emissive factor's limit of one
+  float emissiveStrength;
 } material;
 
 layout(location = 0) out vec4 outColor;
@@ -36,34 +115,202 @@ layout(binding = 0) uniform UboView {
   mat4 view;
   mat4 projection;
   vec4 cameraPosition;
+  vec4 environmentParameters;
+  vec4 irradianceHarmonics[9];
   mat4 jointMatrices[MAX_NUM_JOINTS];
 } uboView;
 
 const float M_PI = 3.141592653589793;
 const float minRoughness = 0.04;
-const vec3 LightColor = vec3(1.0);
 const float OcclusionStrength = 1.0f;
-const float EmissiveFactor = 1.0f;
-const float Gamma = 2.2f;
-const float Exposure = 4.5f;
 
-vec3 Uncharted2Tonemap(vec3 color)
+// Evaluates third order spherical harmonics of the irradiance divided by pi,
+// matching what the irradiance cubemap stores
+vec3 irradianceFromHarmonics(vec3 n)
+{
+  vec4 c[9] = uboView.irradianceHarmonics;
+  return c[0].rgb * 0.282095
+    + c[1].rgb * 0.488603 * n.y
+    + c[2].rgb * 0.488603 * n.z
+    + c[3].rgb * 0.488603 * n.x
+    + c[4].rgb * 1.092548 * n.x * n.y
+    + c[5].rgb * 1.092548 * n.y * n.z
+    + c[6].rgb * 0.315392 * (3.0 * n.z * n.z - 1.0)
+    + c[7].rgb * 1.092548 * n.x * n.z
+    + c[8].rgb * 0.546274 * (n.x * n.x - n.y * n.y);
+}
+
+// One inside a probe's volume, fading to zero towards its edge
+float probeWeight(int probe)
+{
+  vec3 offset = inWorldPos - uboProbes.positions[probe].xyz;
+  float radius = uboProbes.positions[probe].w;
+  float depth;
+  if (radius > 0.0) {
+    depth = radius - length(offset);
+  } else {
+    vec3 inside = uboProbes.extents[probe].xyz - abs(offset);
+    depth = min(min(inside.x, inside.y), inside.z);
+  }
+  return clamp(depth / max(uboProbes.extents[probe].w, 0.0001), 0.0, 1.0);
+}
+
+// Probes only see their surroundings from their own position, so reflections are traced
+// to where they leave the probe's volume and looked up from the probe towards that point
+vec3 parallaxCorrect(int probe, vec3 direction)
+{
+  vec3 center = uboProbes.positions[probe].xyz;
+  float radius = uboProbes.positions[probe].w;
+  float distance;
+  if (radius > 0.0) {
+    vec3 offset = inWorldPos - center;
+    float b = dot(offset, direction);
+    float c = dot(offset, offset) - radius * radius;
+    distance = -b + sqrt(max(b * b - c, 0.0));
+  } else {
+    vec3 extents = uboProbes.extents[probe].xyz;
+    vec3 first = (center + extents - inWorldPos) / direction;
+    vec3 second = (center - extents - inWorldPos) / direction;
+    vec3 furthest = max(first, second);
+    distance = min(min(furthest.x, furthest.y), furthest.z);
+  }
+  return inWorldPos + direction * max(distance, 0.0) - center;
+}
+
+// The light reflected towards v from light arriving along l, per unit of the light's radiance
+vec3 surfaceResponse(vec3 n, vec3 v, vec3 l, vec3 diffuseColor, vec3 specularEnvironmentR0, vec3 specularEnvironmentR90, float alphaRoughness)
+{
+	vec3 h = normalize(l+v);                        // Half vector between both l and v
+  float NdotL = clamp(dot(n, l), 0.001, 1.0);
+	float NdotV = clamp(abs(dot(n, v)), 0.001, 1.0);
+	float NdotH = clamp(dot(n, h), 0.0, 1.0);
+	float VdotH = clamp(dot(v, h), 0.0, 1.0);
+
+	// Calculate the shading terms for the microfacet specular shading model
+
+  // The following equation models the Fresnel reflectance term of the spec equation (aka F())
+  // Implementation of fresnel from [4], Equation 15
+	vec3 F = specularEnvironmentR0 + (specularEnvironmentR90 - specularEnvironmentR0) * pow(clamp(1.0 - VdotH, 0.0, 1.0), 5.0);
+
+  // This calculates the specular geometric attenuation (aka G()),
+  // where rougher material will reflect less light back to the viewer.
+  // This implementation is based on [1] Equation 4, and we adopt their modifications to
+  // alphaRoughness as input as originally proposed in [2].
+	float r = alphaRoughness;
+	float attenuationL = 2.0 * NdotL / (NdotL + sqrt(r * r + (1.0 - r * r) * (NdotL * NdotL)));
+	float attenuationV = 2.0 * NdotV / (NdotV + sqrt(r * r + (1.0 - r * r) * (NdotV * NdotV)));
+	float G = attenuationL * attenuationV;
+
+  // The following equation(s) model the distribution of microfacet normals across the area being drawn (aka D())
+  // Implementation from "Average Irregularity Representation of a Roughened Surface for Ray Reflection" by T. S. Trowbridge, and K. P. Reitz
+  // Follows the distribution function recommended in the SIGGRAPH 2013 course notes from EPIC Games [1], Equation 3.
+  float roughnessSq = alphaRoughness * alphaRoughness;
+	float f = (NdotH * roughnessSq - NdotH) * NdotH + 1.0;
+	float D = roughnessSq / (M_PI * f * f);
+
+  vec3 diffuseContrib = (1.0 - F) * diffuseColor / M_PI;
+  vec3 specContrib = F * G * D / (4.0 * NdotL * NdotV);
+  return NdotL * (diffuseContrib + specContrib);
+}
+
+// Finds the direction towards the light and how much of its radiance reaches this fragment
+float lightAttenuation(Light light, out vec3 l)
+{
+  int type = int(light.position.w);
+  if (type == LIGHT_DIRECTIONAL) {
+    l = normalize(light.direction.xyz);
+    return 1.0;
+  }
+
+  vec3 toLight = light.position.xyz - inWorldPos;
+  float distanceSquared = max(dot(toLight, toLight), 0.0001);
+  l = toLight * inversesqrt(distanceSquared);
+
+  // Lights with a range fade to nothing at it, as recommended by KHR_lights_punctual
+  float attenuation = 1.0 / distanceSquared;
+  float range = light.direction.w;
+  if (range > 0.0) {
+    float ratio = distanceSquared / (range * range);
+    attenuation *= clamp(1.0 - ratio * ratio, 0.0, 1.0);
+  }
+
+  if (type == LIGHT_SPOT) {
+    float cosine = dot(l, normalize(light.direction.xyz));
+    float falloff = clamp(cosine * light.cone.x + light.cone.y, 0.0, 1.0);
+    attenuation *= falloff * falloff;
+  }
+
+  return attenuation;
+}
+
+// How much of the shadowed light reaches this fragment, filtered over a square of texels
+float shadowFactor()
 {
-	float A = 0.15;
-	float B = 0.50;
-	float C = 0.10;
-	float D = 0.20;
-	float E = 0.02;
-	float F = 0.30;
-	float W = 11.2;
-	return ((color*(A*color+C*B)+D*E)/(color*(A*color+B)+D*F))-E/F;
+  int cascadeCount = int(uboShadows.parameters.x);
+  float viewDepth = -(uboView.view * vec4(inWorldPos, 1.0)).z;
+  int cascade = 0;
+  while (cascade < cascadeCount && viewDepth > uboShadows.splits[cascade]) {
+    cascade++;
+  }
+  if (cascade == cascadeCount) {
+    return 1.0;
+  }
+
+  vec4 shadowPosition = uboShadows.viewProjections[cascade] * vec4(inWorldPos, 1.0);
+  shadowPosition.xyz /= shadowPosition.w;
+  vec2 uv = shadowPosition.xy * 0.5 + 0.5;
+
+  int radius = int(uboShadows.parameters.y);
+  float texelSize = uboShadows.parameters.z;
+  float lit = 0.0;
+  for (int x = -radius; x <= radius; x++) {
+    for (int y = -radius; y <= radius; y++) {
+      vec2 offset = vec2(x, y) * texelSize;
+      lit += texture(shadowMap, vec4(uv + offset, float(cascade), shadowPosition.z));
+    }
+  }
+  float taps = float((2 * radius + 1) * (2 * radius + 1));
+  return lit / taps;
 }
 
-vec4 tonemap(vec4 color)
+// Directions spread around the sample, for filtering point shadows
+const vec3 pointShadowOffsets[20] = vec3[](
+  vec3(1, 1, 1), vec3(1, -1, 1), vec3(-1, -1, 1), vec3(-1, 1, 1),
+  vec3(1, 1, -1), vec3(1, -1, -1), vec3(-1, -1, -1), vec3(-1, 1, -1),
+  vec3(1, 1, 0), vec3(1, -1, 0), vec3(-1, -1, 0), vec3(-1, 1, 0),
+  vec3(1, 0, 1), vec3(-1, 0, 1), vec3(1, 0, -1), vec3(-1, 0, -1),
+  vec3(0, 1, 1), vec3(0, -1, 1), vec3(0, -1, -1), vec3(0, 1, -1)
+);
+
+// How much of a point light reaches this fragment, or one when its shadows aren't rendered
+float pointShadowFactor(int lightIndex)
 {
-	vec3 outcol = Uncharted2Tonemap(color.rgb * Exposure);
-	outcol = outcol * (1.0f / Uncharted2Tonemap(vec3(11.2f)));
-	return vec4(pow(outcol, vec3(1.0f / Gamma)), color.a);
+  for (int slot = 0; slot < MAX_POINT_SHADOWS; slot++) {
+    PointShadow shadow = uboPointShadows.shadows[slot];
+    if (int(shadow.position.w) != lightIndex) {
+      continue;
+    }
+
+    // Each face's depth is the distance along its axis, projected like the face was rendered
+    vec3 direction = inWorldPos - shadow.position.xyz;
+    vec3 axes = abs(direction);
+    float distance = max(axes.x, max(axes.y, axes.z));
+    float near = shadow.parameters.x;
+    float far = shadow.parameters.y;
+    if (distance >= far) {
+      return 1.0;
+    }
+    float depth = far / (far - near) * (1.0 - near / max(distance, near));
+
+    float radius = shadow.parameters.z * shadow.parameters.w * distance;
+    float lit = texture(pointShadowMaps[slot], vec4(direction, depth));
+    for (int index = 0; index < 20; index++) {
+      vec3 offset = normalize(pointShadowOffsets[index]) * radius;
+      lit += texture(pointShadowMaps[slot], vec4(direction + offset, depth));
+    }
+    return lit / 21.0;
+  }
+  return 1.0;
 }
 
 // Find the normal for this fragment, pulling either from a predefined normal map
@@ -90,19 +337,8 @@ vec3 getNormal()
 	return normalize(TBN * tangentNormal);
 }
 
-vec4 SRGBtoLINEAR(vec4 srgbIn)
-{
-  vec3 linOut = pow(srgbIn.xyz,vec3(2.2));
-  return vec4(linOut,srgbIn.w);;
-}
-
 void main()
 {
-  vec3 rotation = vec3(radians(75.0f), radians(40.0f), radians(0.0f));
-  vec3 lightDir = vec3(sin(rotation.x) * cos(rotation.y),
-                       sin(rotation.y),
-                       cos(rotation.x) * cos(rotation.y));
-
 	float perceptualRoughness;
 	float metallic;
 	vec3 diffuseColor;
@@ -111,8 +347,8 @@ void main()
   vec3 f0 = vec3(0.04);
 
   if (material.colorTextureSet > -1) {
-    vec4 albedoMap = texture(textures[material.colorTextureSet], inUV0);
-    baseColor = SRGBtoLINEAR(albedoMap) * material.baseColorFactor;
+    // Color textures are sRGB images, so the sampler returns linear values
+    baseColor = texture(textures[material.colorTextureSet], inUV0) * material.baseColorFactor;
   } else {
     baseColor = material.baseColorFactor;
   }
@@ -149,52 +385,62 @@ void main()
 
   vec3 n = getNormal();
   vec3 v = normalize(uboView.cameraPosition.xyz - inWorldPos);    // Vector from surface point to camera
-	vec3 l = normalize(lightDir.xyz);     // Vector from surface point to light
-	vec3 h = normalize(l+v);                        // Half vector between both l and v
 	vec3 reflection = -normalize(reflect(v, n));
+	// Probes are captured with the scene's own view matrices, so they're sampled unflipped
+	vec3 probeReflection = reflection;
 	reflection.y *= -1.0f;
 
-  float NdotL = clamp(dot(n, l), 0.001, 1.0);
 	float NdotV = clamp(abs(dot(n, v)), 0.001, 1.0);
-	float NdotH = clamp(dot(n, h), 0.0, 1.0);
-	float LdotH = clamp(dot(l, h), 0.0, 1.0);
-	float VdotH = clamp(dot(v, h), 0.0, 1.0);
-
-	// Calculate the shading terms for the microfacet specular shading model
 
-  // The following equation models the Fresnel reflectance term of the spec equation (aka F())
-  // Implementation of fresnel from [4], Equation 15
-	vec3 F = specularEnvironmentR0 + (specularEnvironmentR90 - specularEnvironmentR0) * pow(clamp(1.0 - VdotH, 0.0, 1.0), 5.0);
-
-  // This calculates the specular geometric attenuation (aka G()),
-  // where rougher material will reflect less light back to the viewer.
-  // This implementation is based on [1] Equation 4, and we adopt their modifications to
-  // alphaRoughness as input as originally proposed in [2].
-	float r = alphaRoughness;
-	float attenuationL = 2.0 * NdotL / (NdotL + sqrt(r * r + (1.0 - r * r) * (NdotL * NdotL)));
-	float attenuationV = 2.0 * NdotV / (NdotV + sqrt(r * r + (1.0 - r * r) * (NdotV * NdotV)));
-	float G = attenuationL * attenuationV;
-
-  // The following equation(s) model the distribution of microfacet normals across the area being drawn (aka D())
-  // Implementation from "Average Irregularity Representation of a Roughened Surface for Ray Reflection" by T. S. Trowbridge, and K. P. Reitz
-  // Follows the distribution function recommended in the SIGGRAPH 2013 course notes from EPIC Games [1], Equation 3.
-  float roughnessSq = alphaRoughness * alphaRoughness;
-	float f = (NdotH * roughnessSq - NdotH) * NdotH + 1.0;
-	float D = roughnessSq / (M_PI * f * f);
-
-  vec3 diffuseContrib = (1.0 - F) * diffuseColor / M_PI;
-  vec3 specContrib = F * G * D / (4.0 * NdotL * NdotV);
-  vec3 color = NdotL * LightColor * (diffuseContrib + specContrib);
+  float shadow = shadowFactor();
+  int shadowedLight = int(uboShadows.parameters.w);
+
+  vec3 color = vec3(0.0);
+  for (int index = 0; index < int(uboLights.count.x); index++) {
+    Light light = uboLights.lights[index];
+    vec3 l;
+    float attenuation = lightAttenuation(light, l);
+    if (index == shadowedLight) {
+      attenuation *= shadow;
+    }
+    if (int(light.position.w) == LIGHT_POINT) {
+      attenuation *= pointShadowFactor(index);
+    }
+    vec3 response = surfaceResponse(n, v, l, diffuseColor, specularEnvironmentR0, specularEnvironmentR90, alphaRoughness);
+    color += attenuation * light.color.rgb * response;
+  }
 
 	// retrieve a scale and bias to F0
-  float prefilterMipLevels = 10; // mip_levels for a 512x512px cubemap face
-	float lod = (perceptualRoughness * prefilterMipLevels);
+	// W is the exponent the prefilter levels' roughness was mapped with
+	float prefilterMaxLevel = float(textureQueryLevels(prefilter_cubemap) - 1);
+	float lod = pow(perceptualRoughness, 1.0 / uboView.environmentParameters.w) * prefilterMaxLevel;
 	vec3 brdf = (texture(brdflut, vec2(NdotV, 1.0 - perceptualRoughness))).rgb;
 
-	vec3 diffuseLight = SRGBtoLINEAR(tonemap(texture(irradiance_cubemap, n))).rgb;
-	vec3 diffuse = diffuseLight * diffuseColor;
+	// Z selects spherical harmonics over the irradiance cubemap for diffuse lighting
+	vec4 irradiance = uboView.environmentParameters.z > 0.0
+		? vec4(max(irradianceFromHarmonics(n), 0.0), 1.0)
+		: texture(irradiance_cubemap, n);
+	vec3 diffuseLight = irradiance.rgb * uboView.environmentParameters.x;
+	vec3 specularLight = textureLod(prefilter_cubemap, reflection, lod).rgb * uboView.environmentParameters.y;
+
+	// Each probe covers what the nearer probes left uncovered, the environment covers the rest
+	vec3 probeDiffuseLight = vec3(0.0);
+	vec3 probeSpecularLight = vec3(0.0);
+	float probeCoverage = 0.0;
+	for (int index = 0; index < int(uboProbes.count.x); index++) {
+		int probe = int(uboProbes.order[index]);
+		float weight = probeWeight(probe) * (1.0 - probeCoverage);
+		float probeMaxLevel = float(textureQueryLevels(probe_prefilter_cubemaps[probe]) - 1);
+		float probeLod = pow(perceptualRoughness, 1.0 / uboView.environmentParameters.w) * probeMaxLevel;
+		vec3 direction = parallaxCorrect(probe, probeReflection);
+		probeDiffuseLight += weight * textureLod(probe_irradiance_cubemaps[probe], n, 0.0).rgb;
+		probeSpecularLight += weight * textureLod(probe_prefilter_cubemaps[probe], direction, probeLod).rgb;
+		probeCoverage += weight;
+	}
+	diffuseLight = probeDiffuseLight + (1.0 - probeCoverage) * diffuseLight;
+	specularLight = probeSpecularLight + (1.0 - probeCoverage) * specularLight;
 
-	vec3 specularLight = SRGBtoLINEAR(tonemap(textureLod(prefilter_cubemap, reflection, lod))).rgb;
+	vec3 diffuse = diffuseLight * diffuseColor;
 	vec3 specular = specularLight * (specularColor * brdf.x + brdf.y);
 
 	color += diffuse + specular;
@@ -204,10 +450,12 @@ void main()
 		color = mix(color, color * ao, OcclusionStrength);
 	}
 
+	vec3 emissive = material.emissiveFactor;
 	if (material.emissiveTextureSet > -1) {
-		vec3 emissive = SRGBtoLINEAR(texture(textures[material.emissiveTextureSet], inUV0)).rgb * EmissiveFactor;
-		color += emissive;
+		emissive *= texture(textures[material.emissiveTextureSet], inUV0).rgb;
 	}
+	color += emissive * material.emissiveStrength;
 
+  // The radiance is tonemapped and encoded by post processing
   outColor = vec4(color, baseColor.a);
 }
@@ -15,6 +15,8 @@ layout(binding = 0) uniform UboView {
   mat4 view;
   mat4 projection;
   vec4 cameraPosition;
+  vec4 environmentParameters;
+  vec4 irradianceHarmonics[9];
   mat4 jointMatrices[MAX_NUM_JOINTS];
 } uboView;
 
@@ -0,0 +1,36 @@
+#version 450
+
+layout (location = 0) in vec2 inUV;
+
+#define MAX_PASSES 8
+
+layout (binding = 0) uniform sampler2D inputImage;
+
+layout (binding = 1) uniform UboPost {
+  vec4 parameters[MAX_PASSES];
+} uboPost;
+
+layout (binding = 2) uniform sampler2D bloomImage;
+
+layout (push_constant) uniform Pass {
+  int index;
+} pass;
+
+layout (location = 0) out vec4 outColor;
+
+// X is the intensity of the bloom and W is whether it was thresholded.
+// Thresholded bloom is added to the radiance, otherwise the radiance is lerped towards it.
+void main()
+{
+  vec4 parameters = uboPost.parameters[pass.index];
+  vec3 color = texture(inputImage, inUV).rgb;
+  vec3 bloom = texture(bloomImage, inUV).rgb;
+
+  if (parameters.w > 0.0) {
+    color += bloom * parameters.x;
+  } else {
+    color = mix(color, bloom, parameters.x);
+  }
+
+  outColor = vec4(color, 1.0);
+}
@@ -0,0 +1,89 @@
+#version 450
+
+layout (location = 0) in vec2 inUV;
+
+#define MAX_PASSES 8
+
+layout (binding = 0) uniform sampler2D inputImage;
+
+layout (binding = 1) uniform UboPost {
+  vec4 parameters[MAX_PASSES];
+} uboPost;
+
+layout (push_constant) uniform Pass {
+  int index;
+  int level;
+} pass;
+
+layout (location = 0) out vec4 outColor;
+
+float luminance(vec3 color)
+{
+  return dot(color, vec3(0.2126, 0.7152, 0.0722));
+}
+
+// Weighs a group of samples by the inverse of its luminance,
+// so that single very bright texels don't flicker as they move
+vec4 karisWeighted(vec3 color, float weight)
+{
+  float karisWeight = weight / (1.0 + luminance(color));
+  return vec4(color * karisWeight, karisWeight);
+}
+
+// Keeps the radiance above the threshold, easing in over a knee of half the threshold
+vec3 prefilter(vec3 color, float threshold)
+{
+  float knee = threshold * 0.5;
+  float brightness = max(color.r, max(color.g, color.b));
+  float soft = clamp(brightness - threshold + knee, 0.0, 2.0 * knee);
+  soft = soft * soft / (4.0 * knee + 0.0001);
+  float contribution = max(soft, brightness - threshold) / max(brightness, 0.0001);
+  return color * contribution;
+}
+
+// The 13 tap filter from Jimenez's "Next Generation Post Processing in Call of Duty: Advanced Warfare".
+// Y is unused, Z is the threshold and W is whether the threshold is used.
+void main()
+{
+  vec2 texel = 1.0 / vec2(textureSize(inputImage, 0));
+  float x = texel.x;
+  float y = texel.y;
+
+  vec3 a = texture(inputImage, inUV + vec2(-2.0 * x, 2.0 * y)).rgb;
+  vec3 b = texture(inputImage, inUV + vec2(0.0, 2.0 * y)).rgb;
+  vec3 c = texture(inputImage, inUV + vec2(2.0 * x, 2.0 * y)).rgb;
+  vec3 d = texture(inputImage, inUV + vec2(-2.0 * x, 0.0)).rgb;
+  vec3 e = texture(inputImage, inUV).rgb;
+  vec3 f = texture(inputImage, inUV + vec2(2.0 * x, 0.0)).rgb;
+  vec3 g = texture(inputImage, inUV + vec2(-2.0 * x, -2.0 * y)).rgb;
+  vec3 h = texture(inputImage, inUV + vec2(0.0, -2.0 * y)).rgb;
+  vec3 i = texture(inputImage, inUV + vec2(2.0 * x, -2.0 * y)).rgb;
+  vec3 j = texture(inputImage, inUV + vec2(-x, y)).rgb;
+  vec3 k = texture(inputImage, inUV + vec2(x, y)).rgb;
+  vec3 l = texture(inputImage, inUV + vec2(-x, -y)).rgb;
+  vec3 m = texture(inputImage, inUV + vec2(x, -y)).rgb;
+
+  vec3 color;
+  if (pass.level == 0) {
+    // The first level reads the scene, where fireflies are found
+    vec4 sum = karisWeighted((j + k + l + m) * 0.25, 0.5);
+    sum += karisWeighted((a + b + d + e) * 0.25, 0.125);
+    sum += karisWeighted((b + c + e + f) * 0.25, 0.125);
+    sum += karisWeighted((d + e + g + h) * 0.25, 0.125);
+    sum += karisWeighted((e + f + h + i) * 0.25, 0.125);
+    color = sum.rgb / sum.a;
+
+    vec4 parameters = uboPost.parameters[pass.index];
+    if (parameters.w > 0.0) {
+      color = prefilter(color, parameters.z);
+    }
+  } else {
+    color = e * 0.125;
+    color += (a + c + g + i) * 0.03125;
+    color += (b + d + f + h) * 0.0625;
+    color += (j + k + l + m) * 0.125;
+  }
+
+  // Clears out any invalid radiance before it spreads across the chain
+  outColor = vec4(max(color, vec3(0.0)), 1.0);
+}
@@ -0,0 +1,40 @@
+#version 450
+
+layout (location = 0) in vec2 inUV;
+
+#define MAX_PASSES 8
+
+layout (binding = 0) uniform sampler2D inputImage;
+
+layout (binding = 1) uniform UboPost {
+  vec4 parameters[MAX_PASSES];
+} uboPost;
+
+layout (push_constant) uniform Pass {
+  int index;
+  int level;
+} pass;
+
+layout (location = 0) out vec4 outColor;
+
+// A 3x3 tent filter over the smaller level, spread by the radius in Y.
+// The alpha lerps it halfway into the level being written.
+void main()
+{
+  float radius = uboPost.parameters[pass.index].y;
+  vec2 texel = radius / vec2(textureSize(inputImage, 0));
+  float x = texel.x;
+  float y = texel.y;
+
+  vec3 color = texture(inputImage, inUV).rgb * 4.0;
+  color += texture(inputImage, inUV + vec2(0.0, y)).rgb * 2.0;
+  color += texture(inputImage, inUV + vec2(-x, 0.0)).rgb * 2.0;
+  color += texture(inputImage, inUV + vec2(x, 0.0)).rgb * 2.0;
+  color += texture(inputImage, inUV + vec2(0.0, -y)).rgb * 2.0;
+  color += texture(inputImage, inUV + vec2(-x, y)).rgb;
+  color += texture(inputImage, inUV + vec2(x, y)).rgb;
+  color += texture(inputImage, inUV + vec2(-x, -y)).rgb;
+  color += texture(inputImage, inUV + vec2(x, -y)).rgb;
+
+  outColor = vec4(color / 16.0, 0.5);
+}
@@ -0,0 +1,12 @@
+#version 450
+
+layout (location = 0) in vec2 inUV;
+
+layout (binding = 0) uniform sampler2D inputImage;
+
+layout (location = 0) out vec4 outColor;
+
+void main()
+{
+  outColor = vec4(texture(inputImage, inUV).rgb, 1.0);
+}
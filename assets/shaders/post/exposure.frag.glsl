@@ -0,0 +1,24 @@
+#version 450
+
+layout (location = 0) in vec2 inUV;
+
+#define MAX_PASSES 8
+
+layout (binding = 0) uniform sampler2D inputImage;
+
+layout (binding = 1) uniform UboPost {
+  vec4 parameters[MAX_PASSES];
+} uboPost;
+
+layout (push_constant) uniform Pass {
+  int index;
+} pass;
+
+layout (location = 0) out vec4 outColor;
+
+// X is the multiplier of the scene's radiance
+void main()
+{
+  float exposure = uboPost.parameters[pass.index].x;
+  outColor = vec4(texture(inputImage, inUV).rgb * exposure, 1.0);
+}
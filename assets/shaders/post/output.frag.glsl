@@ -0,0 +1,52 @@
+#version 450
+
+layout (location = 0) in vec2 inUV;
+
+#define MAX_PASSES 8
+
+#define TRANSFER_NONE 0
+#define TRANSFER_SRGB 1
+#define TRANSFER_GAMMA 2
+
+layout (binding = 0) uniform sampler2D inputImage;
+
+layout (binding = 1) uniform UboPost {
+  vec4 parameters[MAX_PASSES];
+} uboPost;
+
+layout (push_constant) uniform Pass {
+  int index;
+} pass;
+
+layout (location = 0) out vec4 outColor;
+
+vec3 linearToSrgb(vec3 color)
+{
+  return mix(color * 12.92, 1.055 * pow(color, vec3(1.0 / 2.4)) - 0.055, step(0.0031308, color));
+}
+
+vec3 srgbToLinear(vec3 color)
+{
+  return mix(color / 12.92, pow((color + 0.055) / 1.055, vec3(2.4)), step(0.04045, color));
+}
+
+// X selects the transfer function and Y is the gamma of a power curve.
+// Z is set when the target encodes sRGB itself, which the result is decoded for.
+void main()
+{
+  vec4 parameters = uboPost.parameters[pass.index];
+  vec3 color = clamp(texture(inputImage, inUV).rgb, 0.0, 1.0);
+
+  int transfer = int(parameters.x);
+  if (transfer == TRANSFER_SRGB) {
+    color = linearToSrgb(color);
+  } else if (transfer == TRANSFER_GAMMA) {
+    color = pow(color, vec3(1.0 / parameters.y));
+  }
+
+  if (parameters.z > 0.0) {
+    color = srgbToLinear(color);
+  }
+
+  outColor = vec4(color, 1.0);
+}
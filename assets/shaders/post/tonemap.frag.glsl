@@ -0,0 +1,127 @@
+#version 450
+
+layout (location = 0) in vec2 inUV;
+
+#define MAX_PASSES 8
+
+#define TONEMAP_ACES 0
+#define TONEMAP_AGX 1
+#define TONEMAP_REINHARD 2
+#define TONEMAP_UNCHARTED2 3
+#define TONEMAP_NEUTRAL 4
+
+layout (binding = 0) uniform sampler2D inputImage;
+
+layout (binding = 1) uniform UboPost {
+  vec4 parameters[MAX_PASSES];
+} uboPost;
+
+layout (push_constant) uniform Pass {
+  int index;
+} pass;
+
+layout (location = 0) out vec4 outColor;
+
+// From https://knarkowicz.wordpress.com/2016/01/06/aces-filmic-tone-mapping-curve/
+vec3 aces(vec3 color)
+{
+  const float a = 2.51;
+  const float b = 0.03;
+  const float c = 2.43;
+  const float d = 0.59;
+  const float e = 0.14;
+  return clamp((color * (a * color + b)) / (color * (c * color + d) + e), 0.0, 1.0);
+}
+
+// From https://iolite-engine.com/blog_posts/minimal_agx_implementation
+vec3 agxContrast(vec3 x)
+{
+  vec3 x2 = x * x;
+  vec3 x4 = x2 * x2;
+  return 15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x - 0.00232;
+}
+
+vec3 agx(vec3 color)
+{
+  const mat3 inset = mat3(
+    0.842479062253094, 0.0423282422610123, 0.0423756549057051,
+    0.0784335999999992, 0.878468636469772, 0.0784336,
+    0.0792237451477643, 0.0791661274605434, 0.879142973793104);
+  const mat3 outset = mat3(
+    1.19687900512017, -0.0528968517574562, -0.0529716355144438,
+    -0.0980208811401368, 1.15190312990417, -0.0980434501171241,
+    -0.0990297440797205, -0.0989611768448433, 1.15107367264116);
+  const float minEv = -12.47393;
+  const float maxEv = 4.026069;
+
+  color = inset * color;
+  color = clamp(log2(max(color, vec3(1e-10))), minEv, maxEv);
+  color = (color - minEv) / (maxEv - minEv);
+  color = agxContrast(color);
+
+  // The curve's result is display encoded, so it's decoded back to linear
+  color = outset * color;
+  return pow(max(color, vec3(0.0)), vec3(2.2));
+}
+
+vec3 reinhard(vec3 color)
+{
+  float luminance = dot(color, vec3(0.2126, 0.7152, 0.0722));
+  return color / (1.0 + luminance);
+}
+
+// From http://filmicworlds.com/blog/filmic-tonemapping-operators/
+vec3 uncharted2Curve(vec3 color)
+{
+  float A = 0.15;
+  float B = 0.50;
+  float C = 0.10;
+  float D = 0.20;
+  float E = 0.02;
+  float F = 0.30;
+  return ((color * (A * color + C * B) + D * E) / (color * (A * color + B) + D * F)) - E / F;
+}
+
+vec3 uncharted2(vec3 color)
+{
+  const float whitePoint = 11.2;
+  return uncharted2Curve(color) / uncharted2Curve(vec3(whitePoint));
+}
+
+// From https://github.com/KhronosGroup/ToneMapping/tree/main/PBR_Neutral
+vec3 neutral(vec3 color)
+{
+  const float startCompression = 0.8 - 0.04;
+  const float desaturation = 0.15;
+
+  float x = min(color.r, min(color.g, color.b));
+  float offset = x < 0.08 ? x - 6.25 * x * x : 0.04;
+  color -= offset;
+
+  float peak = max(color.r, max(color.g, color.b));
+  if (peak < startCompression) {
+    return color;
+  }
+
+  const float d = 1.0 - startCompression;
+  float newPeak = 1.0 - d * d / (peak + d - startCompression);
+  color *= newPeak / peak;
+
+  float g = 1.0 - 1.0 / (desaturation * (peak - newPeak) + 1.0);
+  return mix(color, vec3(newPeak), g);
+}
+
+// X selects the operator
+void main()
+{
+  vec3 color = texture(inputImage, inUV).rgb;
+  int operator = int(uboPost.parameters[pass.index].x);
+  switch (operator) {
+    case TONEMAP_ACES: color = aces(color); break;
+    case TONEMAP_AGX: color = agx(color); break;
+    case TONEMAP_REINHARD: color = reinhard(color); break;
+    case TONEMAP_UNCHARTED2: color = uncharted2(color); break;
+    case TONEMAP_NEUTRAL: color = neutral(color); break;
+  }
+  outColor = vec4(color, 1.0);
+}
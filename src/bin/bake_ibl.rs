@@ -0,0 +1,213 @@
+use ash::vk;
+use log::{error, info, LevelFilter};
+use simplelog::{Config, TermLogger, TerminalMode};
+use std::{env, error::Error, process, sync::Arc};
+use support::vulkan::{
+    cubemap_difference, BakeMethod, CommandPool, EnvironmentSet, EnvironmentSource, IblCache,
+    IblSettings, IrradianceMap, IrradianceSettings, PrefilterMap, PrefilterSettings, SamplerCache,
+    ShaderCache, Sibl, UploadManager, VulkanContext,
+};
+use winit::{event_loop::EventLoop, window::WindowBuilder};
+
+const USAGE: &str = "Usage: bake_ibl [--cross] [--compute] [--compare] [--output <directory>]
+                <environment>...
+
+Bakes the image based lighting maps for each environment into the IBL cache,
+replacing any maps baked earlier. Smart IBL .ibl files bake their whole
+image set. Radiance .hdr files are read as HDR equirectangular images, other
+images as LDR equirectangular images, or as cubemap crosses when --cross is
+given.
+
+--compute   Bakes with compute shaders instead of render passes
+--compare   Bakes with both methods and checks they agree, without caching";
+
+// The raster bake samples its irradiance source at levels picked from screen space
+// derivatives, which the compute bake can only estimate
+const COMPARE_TOLERANCE: f32 = 0.02;
+
+fn main() {
+    TermLogger::init(LevelFilter::Info, Config::default(), TerminalMode::Mixed)
+        .expect("Failed to create logger backend!");
+
+    let mut cross = false;
+    let mut method = BakeMethod::Raster;
+    let mut compare = false;
+    let mut directory = IblCache::DEFAULT_DIRECTORY.to_string();
+    let mut paths = Vec::new();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--cross" => cross = true,
+            "--compute" => method = BakeMethod::Compute,
+            "--compare" => compare = true,
+            "--output" => match args.next() {
+                Some(output) => directory = output,
+                None => exit_with_usage(),
+            },
+            "--help" | "-h" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ => paths.push(arg),
+        }
+    }
+    if paths.is_empty() {
+        exit_with_usage();
+    }
+
+    // The context needs a surface, so bake behind a window that is never shown
+    let event_loop = EventLoop::new();
+    let window = WindowBuilder::new()
+        .with_title("IBL Baker")
+        .with_visible(false)
+        .build(&event_loop)
+        .expect("Failed to create window.");
+
+    let context =
+        Arc::new(VulkanContext::new(&window).expect("Failed to create a vulkan context!"));
+    let command_pool =
+        CommandPool::new(context.clone(), vk::CommandPoolCreateFlags::TRANSIENT).unwrap();
+    let mut upload_manager =
+        UploadManager::new(context.clone(), UploadManager::DEFAULT_STAGING_CAPACITY).unwrap();
+    let mut sampler_cache = SamplerCache::new(context.clone());
+    let mut shader_cache = ShaderCache::default();
+
+    let cache = IblCache::new(directory).with_bake_methods(method, method);
+    let mut failed = false;
+    for path in paths {
+        let lowercase_path = path.to_lowercase();
+        let set = if lowercase_path.ends_with(".ibl") {
+            match Sibl::from_file(&path) {
+                Ok(sibl) => sibl.environment_set(),
+                Err(error) => {
+                    error!("Failed to read {}: {}", path, error);
+                    failed = true;
+                    continue;
+                }
+            }
+        } else if cross {
+            EnvironmentSource::Cross(path.clone()).into()
+        } else if lowercase_path.ends_with(".hdr") {
+            EnvironmentSource::Hdr(path.clone()).into()
+        } else {
+            EnvironmentSource::Equirectangular(path.clone()).into()
+        };
+
+        if compare {
+            match compare_methods(
+                context.clone(),
+                &command_pool,
+                &mut upload_manager,
+                &mut sampler_cache,
+                &mut shader_cache,
+                &set,
+                cache.settings(),
+            ) {
+                Ok((irradiance, prefilter)) => {
+                    info!(
+                        "{}: irradiance differs by {:.5}, prefilter by {:.5}",
+                        path, irradiance, prefilter
+                    );
+                    if irradiance.max(prefilter) > COMPARE_TOLERANCE {
+                        error!(
+                            "The bake methods disagree on {} by more than {}",
+                            path, COMPARE_TOLERANCE
+                        );
+                        failed = true;
+                    }
+                }
+                Err(error) => {
+                    error!("Failed to compare bake methods on {}: {}", path, error);
+                    failed = true;
+                }
+            }
+            continue;
+        }
+
+        let baked = cache.key(&set).and_then(|key| {
+            cache.bake(
+                context.clone(),
+                &command_pool,
+                &mut upload_manager,
+                &mut sampler_cache,
+                &mut shader_cache,
+                &set,
+                key,
+            )
+        });
+        match baked {
+            Ok(_) => info!("Baked {} into {}", path, cache.directory().display()),
+            Err(error) => {
+                error!("Failed to bake {}: {}", path, error);
+                failed = true;
+            }
+        }
+    }
+
+    context.logical_device().wait_idle();
+    if failed {
+        process::exit(1);
+    }
+}
+
+// Bakes the set's irradiance and prefilter maps with both methods,
+// returning the largest difference in each
+fn compare_methods(
+    context: Arc<VulkanContext>,
+    command_pool: &CommandPool,
+    upload_manager: &mut UploadManager,
+    sampler_cache: &mut SamplerCache,
+    shader_cache: &mut ShaderCache,
+    set: &EnvironmentSet,
+    settings: &IblSettings,
+) -> Result<(f32, f32), Box<dyn Error>> {
+    let mut load = |source: &EnvironmentSource| {
+        source.load_with_settings(
+            context.clone(),
+            command_pool,
+            upload_manager,
+            sampler_cache,
+            shader_cache,
+            &settings.environment,
+        )
+    };
+    let irradiance_source = load(&set.irradiance)?;
+    let reflection_source = load(&set.reflection)?;
+
+    let [raster, compute] = [BakeMethod::Raster, BakeMethod::Compute].map(|method| {
+        IrradianceMap::with_settings(
+            context.clone(),
+            command_pool,
+            upload_manager,
+            sampler_cache,
+            &irradiance_source,
+            &IrradianceSettings {
+                method,
+                ..settings.irradiance
+            },
+        )
+    });
+    let irradiance = cubemap_difference(command_pool, &raster.cubemap, &compute.cubemap)?;
+
+    let [raster, compute] = [BakeMethod::Raster, BakeMethod::Compute].map(|method| {
+        PrefilterMap::with_settings(
+            context.clone(),
+            command_pool,
+            upload_manager,
+            sampler_cache,
+            &reflection_source,
+            &PrefilterSettings {
+                method,
+                ..settings.prefilter
+            },
+        )
+    });
+    let prefilter = cubemap_difference(command_pool, &raster.cubemap, &compute.cubemap)?;
+
+    Ok((irradiance, prefilter))
+}
+
+fn exit_with_usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2);
+}
@@ -0,0 +1,360 @@
+use ash::{version::DeviceV1_0, vk};
+use nalgebra_glm as glm;
+use std::{boxed::Box, mem, rc::Rc, sync::Arc};
+use support::{
+    app::{run_app, setup_app, App, AppState},
+    camera::FreeCamera,
+    vulkan::{
+        Buffer, Command, DescriptorPool, DescriptorSetLayout, InstanceData, InstancedMesh,
+        ObjModel, ObjVertex, PipelineCache, RenderPass, RenderPipeline,
+        RenderPipelineSettingsBuilder, Renderer, ShaderCache, ShaderPathSetBuilder, UploadManager,
+        VulkanContext,
+    },
+};
+use winit::window::Window;
+
+fn main() {
+    let (window, event_loop, mut renderer) = setup_app("Instancing");
+    run_app(
+        DemoApp::new(renderer.context.clone(), &mut renderer.upload_manager),
+        window,
+        event_loop,
+        renderer,
+    );
+}
+
+struct DemoApp {
+    context: Arc<VulkanContext>,
+    teapots: InstancedMesh,
+    instances: Vec<InstanceData>,
+    pipeline: Option<Rc<RenderPipeline>>,
+    pipeline_data: InstancedPipelineData,
+    rotation: f32,
+    camera: FreeCamera,
+}
+
+impl DemoApp {
+    // Teapots are laid out in a cube of GRID_SIZE^3 instances
+    const GRID_SIZE: usize = 10;
+    const SPACING: f32 = 8.0;
+
+    pub fn new(context: Arc<VulkanContext>, upload_manager: &mut UploadManager) -> Self {
+        let model = ObjModel::new(upload_manager, "assets/models/teapot.obj");
+        let number_of_instances = Self::GRID_SIZE.pow(3);
+        let teapots =
+            InstancedMesh::from_geometry(context.clone(), model.buffers, number_of_instances)
+                .expect("Failed to create instanced mesh!");
+
+        Self {
+            context: context.clone(),
+            teapots,
+            instances: Self::create_instances(),
+            pipeline: None,
+            pipeline_data: InstancedPipelineData::new(context),
+            rotation: 0.0,
+            camera: FreeCamera::default(),
+        }
+    }
+
+    fn create_instances() -> Vec<InstanceData> {
+        let mut instances = Vec::new();
+        let size = Self::GRID_SIZE as f32;
+        for x in 0..Self::GRID_SIZE {
+            for y in 0..Self::GRID_SIZE {
+                for z in 0..Self::GRID_SIZE {
+                    let position = glm::vec3(x as f32, y as f32, z as f32);
+                    let tint =
+                        glm::vec4(position.x / size, position.y / size, position.z / size, 1.0);
+                    instances.push(InstanceData {
+                        tint,
+                        // The custom data holds the resting position of each instance
+                        custom: glm::vec4(position.x, position.y, position.z, 0.0),
+                        ..Default::default()
+                    });
+                }
+            }
+        }
+        instances
+    }
+
+    fn update_instances(&mut self) {
+        let center = (Self::GRID_SIZE - 1) as f32 * Self::SPACING / 2.0;
+        for instance in self.instances.iter_mut() {
+            let translation = glm::vec3(instance.custom.x, instance.custom.y, instance.custom.z)
+                * Self::SPACING
+                - glm::vec3(center, center, center);
+            let offset = instance.custom.x + instance.custom.y + instance.custom.z;
+            instance.model = glm::rotate(
+                &glm::translation(&translation),
+                (self.rotation + offset * 10.0).to_radians(),
+                &glm::vec3(0.0, 1.0, 0.0),
+            );
+        }
+    }
+}
+
+impl Drop for DemoApp {
+    fn drop(&mut self) {
+        self.context.logical_device().wait_idle();
+    }
+}
+
+impl App for DemoApp {
+    fn initialize(
+        &mut self,
+        window: &mut Window,
+        renderer: &mut Renderer,
+        app_state: &AppState,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        renderer.upload_manager.submit()?;
+
+        // The instance count is baked into the recorded command buffers
+        self.update_instances();
+        self.teapots.update(&mut renderer.upload_manager, &self.instances)?;
+
+        let render_pass = renderer.vulkan_swapchain().render_pass.clone();
+        self.recreate_pipelines(
+            renderer.context.clone(),
+            &mut renderer.shader_cache,
+            &mut renderer.pipeline_cache,
+            render_pass,
+        )?;
+        renderer.record_all_command_buffers(self as &mut dyn Command);
+
+        window.set_cursor_visible(false);
+        window
+            .set_cursor_grab(true)
+            .expect("Failed to grab cursor!");
+
+        self.camera.position_at(&glm::vec3(0.0, -60.0, -60.0));
+        self.camera.look_at(&glm::vec3(0.0, 0.0, 0.0));
+
+        window
+            .set_cursor_position(app_state.window_center())
+            .expect("Failed to set cursor position!");
+
+        Ok(())
+    }
+
+    fn update(
+        &mut self,
+        window: &mut Window,
+        renderer: &mut Renderer,
+        app_state: &AppState,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.camera.update(app_state);
+
+        self.rotation += 0.05;
+        if (self.rotation - 360.0) > 0.001 {
+            self.rotation = 0.0;
+        }
+
+        self.update_instances();
+        self.teapots.update(&mut renderer.upload_manager, &self.instances)?;
+
+        let projection = glm::perspective_zo(
+            renderer
+                .vulkan_swapchain()
+                .swapchain
+                .properties()
+                .aspect_ratio(),
+            90_f32.to_radians(),
+            0.1_f32,
+            1000_f32,
+        );
+
+        let ubo = UniformBufferObject {
+            view: self.camera.view_matrix(),
+            projection,
+        };
+        let ubos = [ubo];
+
+        self.pipeline_data
+            .uniform_buffer
+            .upload_to_buffer(&ubos, 0)
+            .unwrap();
+
+        window
+            .set_cursor_position(app_state.window_center())
+            .expect("Failed to set cursor position!");
+
+        Ok(())
+    }
+
+    fn draw(
+        &mut self,
+        renderer: &mut Renderer,
+        app_state: &AppState,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        renderer.render(
+            app_state.window_dimensions.as_vec2(),
+            self as &mut dyn Command,
+        );
+
+        Ok(())
+    }
+}
+
+impl Command for DemoApp {
+    fn issue_commands(
+        &mut self,
+        device: &ash::Device,
+        command_buffer: vk::CommandBuffer,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let pipeline = self.pipeline.as_ref().expect("Failed to get pipeline!");
+
+        pipeline.bind(device, command_buffer);
+
+        unsafe {
+            device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                pipeline.pipeline.layout(),
+                0,
+                &[self.pipeline_data.descriptor_set],
+                &[],
+            );
+        }
+
+        self.teapots.draw(device, command_buffer);
+
+        Ok(())
+    }
+
+    fn recreate_pipelines(
+        &mut self,
+        context: Arc<VulkanContext>,
+        shader_cache: &mut ShaderCache,
+        pipeline_cache: &mut PipelineCache,
+        render_pass: Arc<RenderPass>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let descriptions = InstancedMesh::binding_descriptions::<ObjVertex>();
+        let attributes = InstancedMesh::attribute_descriptions::<ObjVertex>();
+        let vertex_state_info = vk::PipelineVertexInputStateCreateInfo::builder()
+            .vertex_binding_descriptions(&descriptions)
+            .vertex_attribute_descriptions(&attributes)
+            .build();
+
+        let shader_paths = ShaderPathSetBuilder::default()
+            .vertex("assets/shaders/instanced/instanced.vert.spv")
+            .fragment("assets/shaders/instanced/instanced.frag.spv")
+            .build()?;
+        let shader_set = shader_cache.create_shader_set(context.clone(), &shader_paths)?;
+
+        let settings = RenderPipelineSettingsBuilder::default()
+            .render_pass(render_pass)
+            .vertex_state_info(vertex_state_info)
+            .descriptor_set_layout(self.pipeline_data.descriptor_set_layout.clone())
+            .shader_set(shader_set)
+            .rasterization_samples(context.max_usable_samples())
+            .build()
+            .expect("Failed to create render pipeline settings");
+
+        self.pipeline = None;
+        self.pipeline = Some(pipeline_cache.render_pipeline(settings)?);
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct UniformBufferObject {
+    pub view: glm::Mat4,
+    pub projection: glm::Mat4,
+}
+
+pub struct InstancedPipelineData {
+    pub descriptor_pool: DescriptorPool,
+    pub uniform_buffer: Buffer,
+    pub descriptor_set: vk::DescriptorSet,
+    pub descriptor_set_layout: Arc<DescriptorSetLayout>,
+}
+
+impl InstancedPipelineData {
+    pub fn new(context: Arc<VulkanContext>) -> Self {
+        let descriptor_set_layout = Arc::new(Self::descriptor_set_layout(context.clone()));
+        let descriptor_pool = Self::create_descriptor_pool(context.clone());
+        let descriptor_set = descriptor_pool
+            .allocate_descriptor_sets(descriptor_set_layout.layout(), 1)
+            .unwrap()[0];
+
+        let uniform_buffer = Buffer::new_mapped_basic(
+            context.clone(),
+            mem::size_of::<UniformBufferObject>() as _,
+            vk::BufferUsageFlags::UNIFORM_BUFFER,
+            vk_mem::MemoryUsage::CpuToGpu,
+        )
+        .unwrap();
+
+        let data = InstancedPipelineData {
+            descriptor_pool,
+            uniform_buffer,
+            descriptor_set,
+            descriptor_set_layout,
+        };
+
+        data.update_descriptor_set(context);
+
+        data
+    }
+
+    pub fn descriptor_set_layout(context: Arc<VulkanContext>) -> DescriptorSetLayout {
+        let ubo_binding = vk::DescriptorSetLayoutBinding::builder()
+            .binding(0)
+            .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+            .descriptor_count(1)
+            .stage_flags(vk::ShaderStageFlags::VERTEX)
+            .build();
+
+        let bindings = [ubo_binding];
+
+        let layout_create_info = vk::DescriptorSetLayoutCreateInfo::builder()
+            .bindings(&bindings)
+            .build();
+
+        DescriptorSetLayout::new(context, layout_create_info).unwrap()
+    }
+
+    fn create_descriptor_pool(context: Arc<VulkanContext>) -> DescriptorPool {
+        let ubo_pool_size = vk::DescriptorPoolSize {
+            ty: vk::DescriptorType::UNIFORM_BUFFER,
+            descriptor_count: 1,
+        };
+
+        let pool_sizes = [ubo_pool_size];
+
+        let pool_info = vk::DescriptorPoolCreateInfo::builder()
+            .pool_sizes(&pool_sizes)
+            .max_sets(1)
+            .build();
+
+        DescriptorPool::new(context, pool_info).unwrap()
+    }
+
+    fn update_descriptor_set(&self, context: Arc<VulkanContext>) {
+        let uniform_buffer_size = mem::size_of::<UniformBufferObject>() as vk::DeviceSize;
+        let buffer_info = vk::DescriptorBufferInfo::builder()
+            .buffer(self.uniform_buffer.buffer())
+            .offset(0)
+            .range(uniform_buffer_size)
+            .build();
+        let buffer_infos = [buffer_info];
+
+        let ubo_descriptor_write = vk::WriteDescriptorSet::builder()
+            .dst_set(self.descriptor_set)
+            .dst_binding(0)
+            .dst_array_element(0)
+            .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+            .buffer_info(&buffer_infos)
+            .build();
+
+        let descriptor_writes = vec![ubo_descriptor_write];
+
+        unsafe {
+            context
+                .logical_device()
+                .logical_device()
+                .update_descriptor_sets(&descriptor_writes, &[])
+        }
+    }
+}
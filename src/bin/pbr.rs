@@ -3,21 +3,26 @@ use gltf::material::AlphaMode;
 use log::debug;
 use nalgebra_glm as glm;
 use snafu::Snafu;
-use std::{boxed::Box, mem, sync::Arc};
+use std::{boxed::Box, mem, rc::Rc, sync::Arc};
 use support::{
     app::{run_app, setup_app, App, AppState},
     byte_slice_from,
     camera::FreeCamera,
     vulkan::{
-        create_skybox_pipeline, Brdflut, Buffer, Command, CommandPool, DescriptorPool,
-        DescriptorSetLayout, DummyImage, GeometryBuffer, GltfAsset, GraphicsPipeline, HdrCubemap,
-        IrradianceMap, PrefilterMap, Primitive, RenderPass, RenderPipeline,
-        RenderPipelineSettingsBuilder, Renderer, ShaderCache, ShaderPathSetBuilder,
-        SkyboxPipelineData, SkyboxRenderer, SkyboxUniformBufferObject, TextureBundle,
-        VulkanContext,
+        create_skybox_pipeline, create_skybox_pipeline_with_samples, Buffer, CascadeCamera,
+        CascadeSettings, CascadedShadowMap, Command, CommandPool, Cubemap, DepthBias,
+        DescriptorPool, DescriptorSetLayout, DirectionalLight, DummyImage, EnvironmentMaps,
+        GeometryArena, GeometryHandle, GltfAsset, GltfVertex, GraphicsPipeline, IblCache,
+        IndexData, LightBuffer, PipelineCache, PointLight, PointShadowBufferObject,
+        PointShadowMaps, PointShadowSettings, PostEffect, PostProcessChain, Primitive,
+        ProbeCapture, ProbeInfluence, PunctualLight, ReflectionProbe, RenderPass, RenderPipeline,
+        RenderPipelineSettings, RenderPipelineSettingsBuilder, Renderer, ShaderCache,
+        ShaderPathSetBuilder, ShadowUniform, Sibl, SkyboxPipelineData, SkyboxRenderer,
+        SkyboxUniformBufferObject, SphericalHarmonics, SpotLight, TextureBundle, Tonemapper,
+        TypedBuffer, UploadTicket, Vertex, VulkanContext,
     },
 };
-use winit::window::Window;
+use winit::{event::VirtualKeyCode, window::Window};
 
 type Result<T, E = Error> = std::result::Result<T, E>;
 
@@ -50,28 +55,51 @@ fn main() {
     );
 }
 
-pub struct EnvironmentMapSet {
-    brdflut: Brdflut,
-    hdr: HdrCubemap,
-    irradiance: IrradianceMap,
-    prefilter: PrefilterMap,
-}
-
 struct DemoApp {
     context: Arc<VulkanContext>,
-    asset_geometry_buffer: Option<GeometryBuffer>,
-    environment_maps: Option<EnvironmentMapSet>,
-    skybox_pipeline: Option<RenderPipeline>,
+    geometry_arena: Option<GeometryArena<GltfVertex>>,
+    asset_geometry: Vec<GeometryHandle>,
+    environment_maps: Option<EnvironmentMaps>,
+    skybox_pipeline: Option<Rc<RenderPipeline>>,
     skybox_pipeline_data: Option<SkyboxPipelineData>,
-    pbr_pipeline: Option<RenderPipeline>,
-    pbr_pipeline_blend: Option<RenderPipeline>,
+    pbr_pipeline: Option<Rc<RenderPipeline>>,
+    pbr_pipeline_blend: Option<Rc<RenderPipeline>>,
     pbr_pipeline_data: Option<PbrPipelineData>,
     assets: Vec<GltfAsset>,
+    upload_ticket: Option<UploadTicket>,
     camera: FreeCamera,
+    sun: DirectionalLight,
+    environment_parameters: glm::Vec4,
+    irradiance_harmonics: SphericalHarmonics,
+    probe_capture: Option<ProbeCapture>,
+    probe_skybox_pipeline: Option<Rc<RenderPipeline>>,
+    probe_pbr_pipeline: Option<Rc<RenderPipeline>>,
+    probe_pbr_pipeline_blend: Option<Rc<RenderPipeline>>,
+    probes: Vec<ReflectionProbe>,
+    shadow_map: Option<CascadedShadowMap>,
+    shadow_pipeline: Option<Rc<RenderPipeline>>,
+    point_shadow_maps: Option<PointShadowMaps>,
+    point_shadow_pipeline: Option<Rc<RenderPipeline>>,
+    elapsed_time: f32,
+    post_process: Option<PostProcessChain>,
 }
 
 impl DemoApp {
+    const ASSET_SPACING: f32 = 20.0;
+    const PROBE_FACE_SIZE: u32 = 256;
+    const FIELD_OF_VIEW: f32 = 90.0;
+    const NEAR_PLANE: f32 = 0.1;
+    const FAR_PLANE: f32 = 1000.0;
+
     pub fn new(context: Arc<VulkanContext>) -> Self {
+        // Lights scenes whose environment has no sun
+        let (pitch, yaw) = (75_f32.to_radians(), 40_f32.to_radians());
+        let sun = DirectionalLight::new(
+            glm::vec3(pitch.sin() * yaw.cos(), yaw.sin(), pitch.cos() * yaw.cos()),
+            glm::vec3(1.0, 1.0, 1.0),
+            1.0,
+        );
+
         Self {
             context,
             skybox_pipeline: None,
@@ -82,9 +110,533 @@ impl DemoApp {
             camera: FreeCamera::default(),
             environment_maps: None,
             assets: Vec::new(),
-            asset_geometry_buffer: None,
+            upload_ticket: None,
+            sun,
+            environment_parameters: glm::vec4(1.0, 1.0, 0.0, 1.0),
+            irradiance_harmonics: SphericalHarmonics::default(),
+            geometry_arena: None,
+            asset_geometry: Vec::new(),
+            probe_capture: None,
+            probe_skybox_pipeline: None,
+            probe_pbr_pipeline: None,
+            probe_pbr_pipeline_blend: None,
+            probes: Vec::new(),
+            shadow_map: None,
+            shadow_pipeline: None,
+            point_shadow_maps: None,
+            point_shadow_pipeline: None,
+            elapsed_time: 0.0,
+            post_process: None,
         }
     }
+
+    fn pbr_pipeline_settings(
+        &self,
+        context: Arc<VulkanContext>,
+        shader_cache: &mut ShaderCache,
+        render_pass: Arc<RenderPass>,
+        samples: vk::SampleCountFlags,
+    ) -> Result<RenderPipelineSettings, Box<dyn std::error::Error>> {
+        let descriptions = [GltfVertex::binding_description(0)];
+        let attributes = GltfVertex::attribute_descriptions(0, 0);
+        let vertex_state_info = vk::PipelineVertexInputStateCreateInfo::builder()
+            .vertex_binding_descriptions(&descriptions)
+            .vertex_attribute_descriptions(&attributes)
+            .build();
+
+        let push_constant_range = vk::PushConstantRange::builder()
+            .stage_flags(vk::ShaderStageFlags::ALL_GRAPHICS)
+            .size(mem::size_of::<PushConstantBlockMaterial>() as u32)
+            .build();
+
+        let shader_paths = ShaderPathSetBuilder::default()
+            .vertex("assets/shaders/pbr/pbr.vert.spv")
+            .fragment("assets/shaders/pbr/pbr.frag.spv")
+            .build()?;
+        let shader_set = shader_cache.create_shader_set(context, &shader_paths)?;
+
+        let descriptor_set_layout = self
+            .pbr_pipeline_data
+            .as_ref()
+            .expect("Failed to get pbr pipeline data!")
+            .descriptor_set_layout
+            .clone();
+
+        let settings = RenderPipelineSettingsBuilder::default()
+            .render_pass(render_pass)
+            .vertex_state_info(vertex_state_info)
+            .descriptor_set_layout(descriptor_set_layout)
+            .shader_set(shader_set)
+            .rasterization_samples(samples)
+            .sample_shading_enabled(true)
+            .push_constant_range(push_constant_range)
+            .build()
+            .expect("Failed to create render pipeline settings");
+
+        Ok(settings)
+    }
+
+    // Casters are drawn depth only, with the PBR pipeline's vertex layout and descriptors
+    fn shadow_pipeline_settings(
+        &self,
+        context: Arc<VulkanContext>,
+        shader_cache: &mut ShaderCache,
+        render_pass: Arc<RenderPass>,
+        vertex_shader_path: &str,
+        push_constant_size: usize,
+        depth_bias: DepthBias,
+    ) -> Result<RenderPipelineSettings, Box<dyn std::error::Error>> {
+        let descriptions = [GltfVertex::binding_description(0)];
+        let attributes = GltfVertex::attribute_descriptions(0, 0);
+        let vertex_state_info = vk::PipelineVertexInputStateCreateInfo::builder()
+            .vertex_binding_descriptions(&descriptions)
+            .vertex_attribute_descriptions(&attributes)
+            .build();
+
+        let push_constant_range = vk::PushConstantRange::builder()
+            .stage_flags(vk::ShaderStageFlags::VERTEX)
+            .size(push_constant_size as u32)
+            .build();
+
+        let shader_paths = ShaderPathSetBuilder::default()
+            .vertex(vertex_shader_path)
+            .build()?;
+        let shader_set = shader_cache.create_shader_set(context, &shader_paths)?;
+
+        let descriptor_set_layout = self
+            .pbr_pipeline_data
+            .as_ref()
+            .expect("Failed to get pbr pipeline data!")
+            .descriptor_set_layout
+            .clone();
+
+        let settings = RenderPipelineSettingsBuilder::default()
+            .render_pass(render_pass)
+            .vertex_state_info(vertex_state_info)
+            .descriptor_set_layout(descriptor_set_layout)
+            .shader_set(shader_set)
+            .rasterization_samples(vk::SampleCountFlags::TYPE_1)
+            .push_constant_range(push_constant_range)
+            .depth_bias(depth_bias)
+            .depth_only(true)
+            .build()
+            .expect("Failed to create render pipeline settings");
+
+        Ok(settings)
+    }
+
+    // Cascades are selected with an index and cube faces with their view projection
+    fn create_shadow_pipelines(
+        &mut self,
+        context: Arc<VulkanContext>,
+        shader_cache: &mut ShaderCache,
+        pipeline_cache: &mut PipelineCache,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let shadow_map = self.shadow_map.as_ref().expect("Failed to get shadow map!");
+        let settings = self.shadow_pipeline_settings(
+            context.clone(),
+            shader_cache,
+            shadow_map.render_pass(),
+            "assets/shaders/pbr/shadow.vert.spv",
+            mem::size_of::<i32>(),
+            shadow_map.settings().depth_bias,
+        )?;
+        self.shadow_pipeline = Some(pipeline_cache.render_pipeline(settings)?);
+
+        let point_shadow_maps = self
+            .point_shadow_maps
+            .as_ref()
+            .expect("Failed to get point shadow maps!");
+        let settings = self.shadow_pipeline_settings(
+            context,
+            shader_cache,
+            point_shadow_maps.render_pass(),
+            "assets/shaders/pbr/point_shadow.vert.spv",
+            mem::size_of::<i32>(),
+            point_shadow_maps.settings().depth_bias,
+        )?;
+        self.point_shadow_pipeline = Some(pipeline_cache.render_pipeline(settings)?);
+
+        Ok(())
+    }
+
+    // The point lights nearest to the camera cast shadows
+    fn update_point_shadows(
+        &mut self,
+        lights: &[PunctualLight],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let (point_shadow_maps, pbr_data) = match (
+            self.point_shadow_maps.as_mut(),
+            self.pbr_pipeline_data.as_ref(),
+        ) {
+            (Some(maps), Some(pbr_data)) => (maps, pbr_data),
+            _ => return Ok(()),
+        };
+
+        let casters = point_shadow_maps.casters(lights, &self.camera.position);
+        point_shadow_maps.update(&casters);
+        pbr_data
+            .point_shadow_uniform_buffer
+            .upload_to_buffer(&[point_shadow_maps.uniform()], 0)?;
+
+        Ok(())
+    }
+
+    // Number keys 1 to 5 pick the tonemapper, plus and minus change the exposure,
+    // and holding T shows the scene without tonemapping
+    fn update_post_process(
+        &mut self,
+        renderer: &mut Renderer,
+        app_state: &AppState,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let post_process = match self.post_process.as_mut() {
+            Some(post_process) => post_process,
+            None => return Ok(()),
+        };
+
+        let input = &app_state.input;
+        let tonemapper_keys = [
+            VirtualKeyCode::Key1,
+            VirtualKeyCode::Key2,
+            VirtualKeyCode::Key3,
+            VirtualKeyCode::Key4,
+            VirtualKeyCode::Key5,
+        ];
+        let selected_tonemapper = tonemapper_keys
+            .iter()
+            .zip(Tonemapper::ALL.iter())
+            .find(|(key, _)| input.is_key_pressed(**key))
+            .map(|(_, tonemapper)| *tonemapper);
+
+        let mut exposure_stops = 0.0;
+        if input.is_key_pressed(VirtualKeyCode::Equals) {
+            exposure_stops += app_state.delta_time as f32;
+        }
+        if input.is_key_pressed(VirtualKeyCode::Minus) {
+            exposure_stops -= app_state.delta_time as f32;
+        }
+
+        let mut bloom_intensity_change = 0.0;
+        if input.is_key_pressed(VirtualKeyCode::RBracket) {
+            bloom_intensity_change += 0.1 * app_state.delta_time as f32;
+        }
+        if input.is_key_pressed(VirtualKeyCode::LBracket) {
+            bloom_intensity_change -= 0.1 * app_state.delta_time as f32;
+        }
+
+        let mut bloom_radius_change = 0.0;
+        if input.is_key_pressed(VirtualKeyCode::Period) {
+            bloom_radius_change += app_state.delta_time as f32;
+        }
+        if input.is_key_pressed(VirtualKeyCode::Comma) {
+            bloom_radius_change -= app_state.delta_time as f32;
+        }
+
+        for pass in post_process.passes_mut().iter_mut() {
+            match pass.effect {
+                PostEffect::Bloom(ref mut settings) => {
+                    settings.intensity =
+                        (settings.intensity + bloom_intensity_change).clamp(0.0, 1.0);
+                    settings.radius = (settings.radius + bloom_radius_change).max(0.0);
+                    pass.enabled = !input.is_key_pressed(VirtualKeyCode::B);
+                }
+                PostEffect::Exposure(ref mut exposure) => *exposure *= exposure_stops.exp2(),
+                PostEffect::Tonemap(ref mut tonemapper) => {
+                    if let Some(selected_tonemapper) = selected_tonemapper {
+                        *tonemapper = selected_tonemapper;
+                    }
+                    pass.enabled = !input.is_key_pressed(VirtualKeyCode::T);
+                }
+                PostEffect::Output(_) => {}
+            }
+        }
+        post_process.upload()?;
+
+        if post_process.needs_recording() {
+            self.context.logical_device().wait_idle();
+            renderer.record_all_command_buffers(self as &mut dyn Command);
+        }
+
+        Ok(())
+    }
+
+    // Probe captures render single sampled into their own render pass
+    fn create_probe_pipelines(
+        &mut self,
+        context: Arc<VulkanContext>,
+        shader_cache: &mut ShaderCache,
+        pipeline_cache: &mut PipelineCache,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let render_pass = self
+            .probe_capture
+            .as_ref()
+            .expect("Failed to get probe capture!")
+            .render_pass();
+        let samples = vk::SampleCountFlags::TYPE_1;
+
+        let mut settings = self.pbr_pipeline_settings(
+            context.clone(),
+            shader_cache,
+            render_pass.clone(),
+            samples,
+        )?;
+        self.probe_pbr_pipeline = Some(pipeline_cache.render_pipeline(settings.clone())?);
+        settings.blended = true;
+        self.probe_pbr_pipeline_blend = Some(pipeline_cache.render_pipeline(settings)?);
+
+        self.probe_skybox_pipeline = Some(create_skybox_pipeline_with_samples(
+            context,
+            shader_cache,
+            pipeline_cache,
+            render_pass,
+            samples,
+        )?);
+
+        Ok(())
+    }
+
+    // Each asset gets a box shaped probe between it and the camera's starting point
+    fn capture_probes(
+        &mut self,
+        renderer: &mut Renderer,
+        ubo: &UniformBufferObject,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        debug!("Capturing reflection probes");
+
+        // The scene's uniforms are rewritten for every face, so no frames can be in flight
+        self.context.logical_device().wait_idle();
+
+        let pbr_data = self
+            .pbr_pipeline_data
+            .as_ref()
+            .expect("Failed to get pbr pipeline data!");
+        let skybox_data = self
+            .skybox_pipeline_data
+            .as_ref()
+            .expect("Failed to get skybox pipeline data!");
+        let capture = self
+            .probe_capture
+            .as_ref()
+            .expect("Failed to get probe capture!");
+        let skybox_pipeline = self
+            .probe_skybox_pipeline
+            .as_ref()
+            .expect("Failed to get probe skybox pipeline!");
+        let pbr_pipeline = self
+            .probe_pbr_pipeline
+            .as_ref()
+            .expect("Failed to get probe pbr pipeline!");
+        let pbr_pipeline_blend = self
+            .probe_pbr_pipeline_blend
+            .as_ref()
+            .expect("Failed to get probe pbr pipeline!");
+
+        // Probes don't see each other's light
+        pbr_data
+            .probe_uniform_buffer
+            .upload_to_buffer(&[ProbeUniformBufferObject::new(&[])], 0)?;
+
+        let half_spacing = 0.5 * Self::ASSET_SPACING;
+        let mut probes = Vec::new();
+        for index in 0..self.assets.len() {
+            let position = glm::vec3(index as f32 * Self::ASSET_SPACING, -2.0, -3.0);
+            let influence = ProbeInfluence::Box {
+                extents: glm::vec3(half_spacing, half_spacing, half_spacing),
+            };
+            let probe = ReflectionProbe::capture(
+                capture,
+                &renderer.transient_command_pool,
+                &mut renderer.upload_manager,
+                &mut renderer.sampler_cache,
+                position,
+                influence,
+                |face, device, command_buffer| {
+                    let face_ubo = UniformBufferObject {
+                        view: face.view,
+                        projection: face.projection,
+                        camera_position: glm::vec4(
+                            face.position.x,
+                            face.position.y,
+                            face.position.z,
+                            1.0,
+                        ),
+                        ..*ubo
+                    };
+                    pbr_data
+                        .uniform_buffer
+                        .upload_to_buffer(&[face_ubo], 0)
+                        .expect("Failed to upload probe face uniforms!");
+
+                    let skybox_ubo = SkyboxUniformBufferObject {
+                        view: face.view,
+                        projection: face.projection,
+                    };
+                    skybox_data
+                        .uniform_buffer
+                        .upload_to_buffer(&[skybox_ubo], 0)
+                        .expect("Failed to upload probe face uniforms!");
+
+                    self.record_scene(
+                        device,
+                        command_buffer,
+                        skybox_pipeline,
+                        pbr_pipeline,
+                        pbr_pipeline_blend,
+                    );
+                },
+            )?;
+            probes.push(probe);
+        }
+
+        // Descriptors of recorded command buffers can't change, so they're recorded again
+        let environment_maps = self
+            .environment_maps
+            .as_ref()
+            .expect("Failed to get environment maps!");
+        pbr_data.update_probe_descriptors(self.context.clone(), environment_maps, &probes);
+        self.probes = probes;
+        renderer.record_all_command_buffers(self as &mut dyn Command);
+
+        Ok(())
+    }
+
+    // The sun, the lights of each asset, a lamp and optionally a flashlight held by the camera
+    fn lights(&self, flashlight: bool) -> Vec<PunctualLight> {
+        let mut lights = vec![PunctualLight::from(self.sun)];
+
+        // The vertex shader mirrors the scene vertically, so the assets' lights are mirrored too
+        let mirror = glm::scaling(&glm::vec3(1.0, -1.0, 1.0));
+        let spacing = glm::vec3(Self::ASSET_SPACING, 0.0, 0.0);
+        let mut asset_transform = glm::Mat4::identity();
+        for asset in self.assets.iter() {
+            lights.extend(asset.lights(&(mirror * asset_transform)));
+            asset_transform = glm::translate(&asset_transform, &spacing);
+        }
+
+        // A lamp circling the first asset, which shadows the assets around it
+        let angle = 0.5 * self.elapsed_time;
+        let mut lamp = PointLight::new(
+            glm::vec3(3.0 * angle.cos(), -3.0, 3.0 * angle.sin()),
+            glm::vec3(1.0, 0.8, 0.6),
+            20.0,
+        );
+        lamp.range = Some(15.0);
+        lights.push(lamp.into());
+
+        if flashlight {
+            let mut light = SpotLight::new(
+                self.camera.position,
+                self.camera.front,
+                glm::vec3(1.0, 1.0, 1.0),
+                50.0,
+            );
+            light.range = Some(30.0);
+            light.inner_cone_angle = 15_f32.to_radians();
+            light.outer_cone_angle = 25_f32.to_radians();
+            lights.push(light.into());
+        }
+
+        lights
+    }
+
+    // The sun casts the shadows, and is the first light in the light buffer
+    fn shadow_uniforms(&self, aspect_ratio: f32, disable_shadows: bool) -> ShadowUniform {
+        let shadow_map = match self.shadow_map.as_ref() {
+            Some(shadow_map) if !disable_shadows => shadow_map,
+            _ => return ShadowUniform::disabled(),
+        };
+        let camera = CascadeCamera {
+            view: self.camera.view_matrix(),
+            fov_y: Self::FIELD_OF_VIEW.to_radians(),
+            aspect_ratio,
+            near: Self::NEAR_PLANE,
+        };
+        let cascades = shadow_map.cascades(&camera, &self.sun.direction);
+        shadow_map.uniform(&cascades, 0)
+    }
+
+    // Probes are blended nearest to the camera first
+    fn probe_uniforms(&self, ignore_probes: bool) -> ProbeUniformBufferObject {
+        if ignore_probes {
+            return ProbeUniformBufferObject::new(&[]);
+        }
+        let mut probes = self.probes.iter().enumerate().collect::<Vec<_>>();
+        probes.sort_by(|(_, first), (_, second)| {
+            let first = glm::distance(&self.camera.position, &first.position);
+            let second = glm::distance(&self.camera.position, &second.position);
+            first
+                .partial_cmp(&second)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        ProbeUniformBufferObject::new(&probes)
+    }
+
+    fn record_scene(
+        &self,
+        device: &ash::Device,
+        command_buffer: vk::CommandBuffer,
+        skybox_pipeline: &RenderPipeline,
+        pbr_pipeline: &RenderPipeline,
+        pbr_pipeline_blended: &RenderPipeline,
+    ) {
+        let skybox_pipeline_data = self
+            .skybox_pipeline_data
+            .as_ref()
+            .expect("Failed to get skybox pipeline data!");
+
+        skybox_pipeline.bind(device, command_buffer);
+
+        let skybox_renderer =
+            SkyboxRenderer::new(command_buffer, &skybox_pipeline, &skybox_pipeline_data);
+
+        skybox_renderer.draw(device, &skybox_pipeline_data.cube);
+
+        // Render pbr assets
+        let pbr_pipeline_data = self
+            .pbr_pipeline_data
+            .as_ref()
+            .expect("Failed to get pbr pipeline data!");
+
+        let pbr_renderer =
+            PbrRenderer::new(command_buffer, &pbr_pipeline.pipeline, &pbr_pipeline_data);
+        let pbr_renderer_blended =
+            PbrRenderer::new(command_buffer, &pbr_pipeline.pipeline, &pbr_pipeline_data);
+
+        let geometry_arena = self
+            .geometry_arena
+            .as_ref()
+            .expect("Failed to get geometry arena!");
+
+        geometry_arena.bind_vertex_buffer(device, command_buffer);
+
+        [AlphaMode::Opaque, AlphaMode::Mask, AlphaMode::Blend]
+            .iter()
+            .for_each(|alpha_mode| {
+                match alpha_mode {
+                    AlphaMode::Opaque => pbr_pipeline.bind(device, command_buffer),
+                    AlphaMode::Blend => pbr_pipeline_blended.bind(device, command_buffer),
+                    _ => {}
+                }
+
+                let mut offsets = GltfOffsets::default();
+                for (asset, handle) in self.assets.iter().zip(self.asset_geometry.iter()) {
+                    let range = geometry_arena
+                        .range(*handle)
+                        .expect("Failed to get asset geometry!");
+                    geometry_arena.bind_index_buffer(device, command_buffer, range.index_type);
+                    offsets.index_offset = range.first_index;
+                    offsets.vertex_offset = range.vertex_offset;
+
+                    if *alpha_mode == AlphaMode::Blend {
+                        pbr_renderer_blended.draw_asset(device, &asset, &offsets, *alpha_mode);
+                    } else {
+                        pbr_renderer.draw_asset(device, &asset, &offsets, *alpha_mode);
+                    }
+                    offsets.texture_offset += asset.textures.len() as i32;
+                    offsets.mesh_offset += asset.number_of_meshes;
+                }
+            });
+    }
 }
 
 impl Drop for DemoApp {
@@ -110,40 +662,33 @@ impl App for DemoApp {
 
         window.set_cursor_position(app_state.window_center())?;
 
-        debug!("Creating Brdflut");
-        let brdflut = Brdflut::new(self.context.clone(), &renderer.transient_command_pool);
-
-        let cubemap_path = "assets/skyboxes/walk_of_fame/walk_of_fame.hdr";
+        let sibl = Sibl::from_file("assets/skyboxes/walk_of_fame/Walk_Of_Fame.ibl")?;
 
-        debug!("Creating HDR cubemap");
-        let hdr = HdrCubemap::new(
+        debug!("Loading environment maps");
+        let ibl_cache = IblCache::new(IblCache::DEFAULT_DIRECTORY);
+        let environment_maps = ibl_cache.load_or_bake(
             self.context.clone(),
             &renderer.transient_command_pool,
-            &cubemap_path,
+            &mut renderer.upload_manager,
+            &mut renderer.sampler_cache,
             &mut renderer.shader_cache,
-        );
+            &sibl.environment_set(),
+        )?;
 
-        debug!("Creating Irradiance cubemap");
-        let irradiance = IrradianceMap::new(
-            self.context.clone(),
-            &renderer.transient_command_pool,
-            &hdr.as_ref().expect("Failed to lookup hdr cubemap!").cubemap,
-        );
+        debug!("Projecting environment onto spherical harmonics");
+        self.irradiance_harmonics =
+            SphericalHarmonics::from_source(&sibl.environment.source())?.irradiance();
 
-        debug!("Creating Prefilter cubemap");
-        let prefilter = PrefilterMap::new(
-            self.context.clone(),
-            &renderer.transient_command_pool,
-            &hdr.as_ref().expect("Failed to lookup hdr cubemap!").cubemap,
+        if let Some(sun) = sibl.sun_light() {
+            self.sun = sun;
+        }
+        self.environment_parameters = glm::vec4(
+            sibl.environment.multiplier,
+            sibl.reflection_image().multiplier,
+            0.0,
+            ibl_cache.settings().prefilter.roughness.exponent(),
         );
 
-        let environment_maps = EnvironmentMapSet {
-            brdflut,
-            hdr: hdr.unwrap(),
-            irradiance,
-            prefilter,
-        };
-
         let asset_names = vec![
             "assets/models/DamagedHelmet.glb",
             "assets/models/CesiumMan.glb",
@@ -156,7 +701,8 @@ impl App for DemoApp {
             .map(|name| {
                 GltfAsset::new(
                     self.context.clone(),
-                    &renderer.transient_command_pool,
+                    &mut renderer.upload_manager,
+                    &mut renderer.sampler_cache,
                     &name,
                 )
             })
@@ -168,22 +714,51 @@ impl App for DemoApp {
             total_meshes + asset.number_of_meshes
         });
 
-        let vertices = self
+        let number_of_vertices = self
             .assets
             .iter()
-            .flat_map(|asset| asset.vertices.iter().copied())
-            .collect::<Vec<_>>();
-
-        let indices = self
+            .fold(0, |total, asset| total + asset.vertices.len());
+        let number_of_indices = self
             .assets
             .iter()
-            .flat_map(|asset| asset.indices.iter().copied())
-            .collect::<Vec<_>>();
+            .fold(0, |total, asset| total + asset.indices.len());
 
-        let asset_geometry_buffer =
-            GeometryBuffer::new(&renderer.transient_command_pool, &vertices, Some(&indices));
+        let mut geometry_arena = GeometryArena::new(
+            self.context.clone(),
+            number_of_vertices as _,
+            (number_of_indices * mem::size_of::<u32>()) as _,
+        )?;
+
+        self.asset_geometry = self
+            .assets
+            .iter()
+            .map(|asset| {
+                // Assets that fit in 16 bit indices use half the index memory
+                if asset.vertices.len() <= u16::MAX as usize + 1 {
+                    let indices = asset
+                        .indices
+                        .iter()
+                        .map(|index| *index as u16)
+                        .collect::<Vec<_>>();
+                    geometry_arena.allocate(
+                        &mut renderer.upload_manager,
+                        &asset.vertices,
+                        IndexData::U16(&indices),
+                    )
+                } else {
+                    geometry_arena.allocate(
+                        &mut renderer.upload_manager,
+                        &asset.vertices,
+                        IndexData::U32(&asset.indices),
+                    )
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        // The ticket of the batch submitted below covers this one
+        geometry_arena.flush(&mut renderer.upload_manager)?;
+        geometry_arena.release_retired_buffers();
 
-        self.asset_geometry_buffer = Some(asset_geometry_buffer);
+        self.geometry_arena = Some(geometry_arena);
 
         let textures = self
             .assets
@@ -191,33 +766,64 @@ impl App for DemoApp {
             .flat_map(|asset| &asset.textures)
             .collect::<Vec<_>>();
 
+        let shadow_map = CascadedShadowMap::new(self.context.clone(), CascadeSettings::default())?;
+        let point_shadow_maps =
+            PointShadowMaps::new(self.context.clone(), PointShadowSettings::default())?;
+
         let pbr_pipeline_data = PbrPipelineData::new(
             self.context.clone(),
             &renderer.transient_command_pool,
             number_of_meshes,
             &textures,
             &environment_maps,
+            &shadow_map,
+            &point_shadow_maps,
         );
 
         self.pbr_pipeline_data = Some(pbr_pipeline_data);
+        self.shadow_map = Some(shadow_map);
+        self.point_shadow_maps = Some(point_shadow_maps);
 
         let skybox_pipeline_data = SkyboxPipelineData::new(
             self.context.clone(),
-            &renderer.transient_command_pool,
-            &environment_maps.hdr.cubemap,
+            &mut renderer.upload_manager,
+            &environment_maps.environment,
         );
 
         self.skybox_pipeline_data = Some(skybox_pipeline_data);
 
+        // Frames can be rendered while the uploads finish in the background
+        self.upload_ticket = Some(renderer.upload_manager.submit()?);
+
         self.environment_maps = Some(environment_maps);
 
+        let swapchain_properties = renderer.vulkan_swapchain().swapchain.properties();
+        self.post_process = Some(PostProcessChain::new(
+            self.context.clone(),
+            swapchain_properties.extent,
+            swapchain_properties.format.format,
+            &PostProcessChain::default_effects(),
+        )?);
+
         let render_pass = renderer.vulkan_swapchain().render_pass.clone();
         self.recreate_pipelines(
             renderer.context.clone(),
             &mut renderer.shader_cache,
+            &mut renderer.pipeline_cache,
             render_pass,
         )?;
 
+        // Probes are captured once the assets have finished uploading
+        self.probe_capture = Some(ProbeCapture::new(
+            self.context.clone(),
+            Self::PROBE_FACE_SIZE,
+        )?);
+        self.create_probe_pipelines(
+            renderer.context.clone(),
+            &mut renderer.shader_cache,
+            &mut renderer.pipeline_cache,
+        )?;
+
         renderer.record_all_command_buffers(self as &mut dyn Command);
 
         Ok(())
@@ -230,16 +836,25 @@ impl App for DemoApp {
         app_state: &AppState,
     ) -> Result<(), Box<dyn std::error::Error>> {
         self.camera.update(&app_state);
+        self.elapsed_time += app_state.delta_time as f32;
+
+        if let Some(ticket) = self.upload_ticket {
+            if renderer.upload_manager.is_complete(ticket)? {
+                debug!("Finished uploading assets");
+                self.upload_ticket = None;
+            }
+        }
 
+        let aspect_ratio = renderer
+            .vulkan_swapchain()
+            .swapchain
+            .properties()
+            .aspect_ratio();
         let projection = glm::perspective_zo(
-            renderer
-                .vulkan_swapchain()
-                .swapchain
-                .properties()
-                .aspect_ratio(),
-            90_f32.to_radians(),
-            0.1_f32,
-            1000_f32,
+            aspect_ratio,
+            Self::FIELD_OF_VIEW.to_radians(),
+            Self::NEAR_PLANE,
+            Self::FAR_PLANE,
         );
 
         let view = self.camera.view_matrix();
@@ -271,10 +886,22 @@ impl App for DemoApp {
             ),
             view: self.camera.view_matrix(),
             projection,
+            environment_parameters: glm::vec4(
+                self.environment_parameters.x,
+                self.environment_parameters.y,
+                // Holding H lights the scene with the harmonics instead of the irradiance map
+                if app_state.input.is_key_pressed(VirtualKeyCode::H) {
+                    1.0
+                } else {
+                    0.0
+                },
+                self.environment_parameters.w,
+            ),
+            irradiance_harmonics: self.irradiance_harmonics.uniform_coefficients(),
             joint_matrices: [glm::Mat4::identity(); UniformBufferObject::MAX_NUM_JOINTS],
         };
 
-        let spacing = glm::vec3(20.0, 0.0, 0.0);
+        let spacing = glm::vec3(Self::ASSET_SPACING, 0.0, 0.0);
         let mut asset_transform = glm::Mat4::identity();
         let mut mesh_offset = 0;
         let mut joint_offset = 0;
@@ -312,24 +939,11 @@ impl App for DemoApp {
                             joint_offset += joint_count;
                         }
 
-                        let dynamic_ubos = [dynamic_ubo];
-                        let buffer = &pbr_data.dynamic_uniform_buffer;
-                        let offset = (pbr_data.dynamic_alignment
-                            * (mesh_offset + mesh.mesh_id) as u64)
-                            as usize;
-
-                        buffer.upload_to_buffer_aligned(
-                            &dynamic_ubos,
-                            offset,
-                            pbr_data.dynamic_alignment,
-                        ).unwrap();
-
-                        let dynamic_ubo_size = (asset.number_of_meshes as u64
-                            * pbr_data.dynamic_alignment)
-                            as u64;
-                        buffer
-                            .flush(offset, dynamic_ubo_size as _)
-                            .expect("Failed to flush buffer!");
+                        let index = mesh_offset + mesh.mesh_id;
+                        pbr_data
+                            .dynamic_uniform_buffer
+                            .write(index..index + 1, &[dynamic_ubo])
+                            .expect("Failed to write dynamic uniform buffer!");
                     }
                 }
             });
@@ -337,11 +951,39 @@ impl App for DemoApp {
             asset_transform = glm::translate(&asset_transform, &spacing)
         }
 
+        // Holding L turns on a flashlight
+        let flashlight = app_state.input.is_key_pressed(VirtualKeyCode::L);
+        let lights = self.lights(flashlight);
+        // Holding K turns off shadows
+        let shadow_ubos = [self.shadow_uniforms(
+            aspect_ratio,
+            app_state.input.is_key_pressed(VirtualKeyCode::K),
+        )];
+        if let Some(pbr_data) = &self.pbr_pipeline_data.as_ref() {
+            pbr_data.light_buffer.upload(&lights)?;
+            pbr_data
+                .shadow_uniform_buffer
+                .upload_to_buffer(&shadow_ubos, 0)?;
+        }
+
+        if self.upload_ticket.is_none() && self.probes.is_empty() {
+            self.capture_probes(renderer, &ubo)?;
+        }
+
         let ubos = [ubo];
+        // Holding P lights the scene with the environment alone
+        let probe_ubos = [self.probe_uniforms(app_state.input.is_key_pressed(VirtualKeyCode::P))];
         if let Some(pbr_data) = &self.pbr_pipeline_data.as_ref() {
             pbr_data.uniform_buffer.upload_to_buffer(&ubos, 0).unwrap();
+            pbr_data
+                .probe_uniform_buffer
+                .upload_to_buffer(&probe_ubos, 0)
+                .unwrap();
         }
 
+        self.update_point_shadows(&lights)?;
+        self.update_post_process(renderer, app_state)?;
+
         window.set_cursor_position(app_state.window_center())?;
 
         Ok(())
@@ -362,30 +1004,72 @@ impl App for DemoApp {
 }
 
 impl Command for DemoApp {
-    fn issue_commands(
+    fn issue_offscreen_commands(
         &mut self,
         device: &ash::Device,
         command_buffer: vk::CommandBuffer,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        // Render skybox
-        let skybox_pipeline = self
-            .skybox_pipeline
+        let shadow_map = self.shadow_map.as_ref().expect("Failed to get shadow map!");
+
+        let shadow_pipeline = self
+            .shadow_pipeline
             .as_ref()
-            .expect("Failed to get skybox pipeline!");
+            .expect("Failed to get shadow pipeline!");
 
-        let skybox_pipeline_data = self
-            .skybox_pipeline_data
+        let pbr_pipeline_data = self
+            .pbr_pipeline_data
             .as_ref()
-            .expect("Failed to get skybox pipeline data!");
+            .expect("Failed to get pbr pipeline data!");
 
-        skybox_pipeline.bind(device, command_buffer);
+        let geometry_arena = self
+            .geometry_arena
+            .as_ref()
+            .expect("Failed to get geometry arena!");
 
-        let skybox_renderer =
-            SkyboxRenderer::new(command_buffer, &skybox_pipeline, &skybox_pipeline_data);
+        let shadow_renderer = ShadowRenderer::new(
+            command_buffer,
+            &shadow_pipeline.pipeline,
+            &pbr_pipeline_data,
+        );
 
-        skybox_renderer.draw(device, &skybox_pipeline_data.cube);
+        shadow_map.record(device, command_buffer, |cascade| {
+            shadow_pipeline.bind(device, command_buffer);
+            shadow_renderer.push_constants(device, &(cascade as i32));
+            shadow_renderer.draw_assets(device, geometry_arena, &self.assets, &self.asset_geometry);
+        });
+
+        let point_shadow_maps = self
+            .point_shadow_maps
+            .as_ref()
+            .expect("Failed to get point shadow maps!");
+
+        let point_shadow_pipeline = self
+            .point_shadow_pipeline
+            .as_ref()
+            .expect("Failed to get point shadow pipeline!");
+
+        let point_shadow_renderer = ShadowRenderer::new(
+            command_buffer,
+            &point_shadow_pipeline.pipeline,
+            &pbr_pipeline_data,
+        );
+
+        point_shadow_maps.record(device, command_buffer, |face| {
+            point_shadow_pipeline.bind(device, command_buffer);
+            point_shadow_renderer.push_constants(device, &(face as i32));
+            point_shadow_renderer.draw_assets(
+                device,
+                geometry_arena,
+                &self.assets,
+                &self.asset_geometry,
+            );
+        });
+
+        let skybox_pipeline = self
+            .skybox_pipeline
+            .as_ref()
+            .expect("Failed to get skybox pipeline!");
 
-        // Render pbr assets
         let pbr_pipeline = self
             .pbr_pipeline
             .as_ref()
@@ -396,111 +1080,89 @@ impl Command for DemoApp {
             .as_ref()
             .expect("Failed to get pbr pipeline!");
 
-        let pbr_pipeline_data = self
-            .pbr_pipeline_data
+        let post_process = self
+            .post_process
             .as_ref()
-            .expect("Failed to get pbr pipeline data!");
-
-        let pbr_renderer =
-            PbrRenderer::new(command_buffer, &pbr_pipeline.pipeline, &pbr_pipeline_data);
-        let pbr_renderer_blended =
-            PbrRenderer::new(command_buffer, &pbr_pipeline.pipeline, &pbr_pipeline_data);
-
-        let geometry_buffer = self
-            .asset_geometry_buffer
-            .as_ref()
-            .expect("Failed to get geometry buffer!");
-
-        let offsets = [0];
-        let vertex_buffers = [geometry_buffer.vertex_buffer.buffer()];
-
-        unsafe {
-            device.cmd_bind_vertex_buffers(command_buffer, 0, &vertex_buffers, &offsets);
-            device.cmd_bind_index_buffer(
-                command_buffer,
-                geometry_buffer
-                    .index_buffer
-                    .as_ref()
-                    .expect("Failed to get an index buffer!")
-                    .buffer(),
-                0,
-                vk::IndexType::UINT32,
-            );
-        }
-
-        [AlphaMode::Opaque, AlphaMode::Mask, AlphaMode::Blend]
-            .iter()
-            .for_each(|alpha_mode| {
-                match alpha_mode {
-                    AlphaMode::Opaque => pbr_pipeline.bind(device, command_buffer),
-                    AlphaMode::Blend => pbr_pipeline_blended.bind(device, command_buffer),
-                    _ => {}
-                }
-
-                let mut offsets = GltfOffsets::default();
-                for asset in self.assets.iter() {
-                    if *alpha_mode == AlphaMode::Blend {
-                        pbr_renderer_blended.draw_asset(device, &asset, &offsets, *alpha_mode);
-                    } else {
-                        pbr_renderer.draw_asset(device, &asset, &offsets, *alpha_mode);
-                    }
-                    offsets.texture_offset += asset.textures.len() as i32;
-                    offsets.mesh_offset += asset.number_of_meshes;
-                    offsets.index_offset += asset.indices.len() as u32;
-                    offsets.vertex_offset +=
-                        (asset.vertices.len() / GltfAsset::vertex_stride()) as u32;
-                }
+            .expect("Failed to get post processing chain!");
+
+        post_process
+            .target()
+            .record(device, command_buffer, [0.39, 0.58, 0.93, 1.0], || {
+                self.record_scene(
+                    device,
+                    command_buffer,
+                    skybox_pipeline,
+                    pbr_pipeline,
+                    pbr_pipeline_blended,
+                );
             });
 
+        self.post_process
+            .as_mut()
+            .expect("Failed to get post processing chain!")
+            .record(device, command_buffer);
+
         Ok(())
     }
 
-    fn recreate_pipelines(
+    fn issue_commands(
         &mut self,
-        context: Arc<VulkanContext>,
-        shader_cache: &mut ShaderCache,
-        render_pass: Arc<RenderPass>,
+        device: &ash::Device,
+        command_buffer: vk::CommandBuffer,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        let descriptions = GltfAsset::create_vertex_input_descriptions();
-        let attributes = GltfAsset::create_vertex_attributes();
-        let vertex_state_info = vk::PipelineVertexInputStateCreateInfo::builder()
-            .vertex_binding_descriptions(&descriptions)
-            .vertex_attribute_descriptions(&attributes)
-            .build();
-
-        let push_constant_range = vk::PushConstantRange::builder()
-            .stage_flags(vk::ShaderStageFlags::ALL_GRAPHICS)
-            .size(mem::size_of::<PushConstantBlockMaterial>() as u32)
-            .build();
-
-        let shader_paths = ShaderPathSetBuilder::default()
-            .vertex("assets/shaders/pbr/pbr.vert.spv")
-            .fragment("assets/shaders/pbr/pbr.frag.spv")
-            .build()?;
-        let shader_set = shader_cache.create_shader_set(context.clone(), &shader_paths)?;
+        self.post_process
+            .as_ref()
+            .expect("Failed to get post processing chain!")
+            .record_output(device, command_buffer);
 
-        let descriptor_set_layout =
-            Arc::new(PbrPipelineData::descriptor_set_layout(context.clone()));
+        Ok(())
+    }
 
-        let mut settings = RenderPipelineSettingsBuilder::default()
-            .render_pass(render_pass.clone())
-            .vertex_state_info(vertex_state_info)
-            .descriptor_set_layout(descriptor_set_layout)
-            .shader_set(shader_set)
-            .rasterization_samples(context.max_usable_samples())
-            .sample_shading_enabled(true)
-            .push_constant_range(push_constant_range)
-            .build()
-            .expect("Failed to create render pipeline settings");
+    fn recreate_targets(&mut self, extent: vk::Extent2D) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(post_process) = self.post_process.as_mut() {
+            post_process.resize(extent)?;
+        }
+        Ok(())
+    }
+
+    fn recreate_pipelines(
+        &mut self,
+        context: Arc<VulkanContext>,
+        shader_cache: &mut ShaderCache,
+        pipeline_cache: &mut PipelineCache,
+        render_pass: Arc<RenderPass>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        // The scene is drawn into the post processing's target, which is drawn into the swapchain
+        let post_process = self
+            .post_process
+            .as_mut()
+            .expect("Failed to get post processing chain!");
+        post_process.create_pipelines(shader_cache, pipeline_cache, render_pass)?;
+        let scene_render_pass = post_process.target().render_pass();
+        let samples = post_process.target().samples();
+
+        let mut settings = self.pbr_pipeline_settings(
+            context.clone(),
+            shader_cache,
+            scene_render_pass.clone(),
+            samples,
+        )?;
 
         self.pbr_pipeline = None;
         self.pbr_pipeline_blend = None;
-        self.pbr_pipeline = Some(RenderPipeline::new(context.clone(), settings.clone()));
+        self.pbr_pipeline = Some(pipeline_cache.render_pipeline(settings.clone())?);
         settings.blended = true;
-        self.pbr_pipeline_blend = Some(RenderPipeline::new(context.clone(), settings));
+        self.pbr_pipeline_blend = Some(pipeline_cache.render_pipeline(settings)?);
 
         self.skybox_pipeline = None;
-        self.skybox_pipeline = Some(create_skybox_pipeline(context, shader_cache, render_pass));
+        self.skybox_pipeline = Some(create_skybox_pipeline(
+            context.clone(),
+            shader_cache,
+            pipeline_cache,
+            scene_render_pass,
+        )?);
+
+        self.create_shadow_pipelines(context, shader_cache, pipeline_cache)?;
 
         Ok(())
     }
@@ -518,6 +1180,7 @@ pub struct PushConstantBlockMaterial {
     pub roughness_factor: f32,
     pub alpha_mode: i32,
     pub alpha_cutoff: f32,
+    pub emissive_strength: f32,
 }
 
 #[derive(Clone, Copy)]
@@ -525,6 +1188,12 @@ pub struct UniformBufferObject {
     pub view: glm::Mat4,
     pub projection: glm::Mat4,
     pub camera_position: glm::Vec4,
+    // X value scales the diffuse irradiance.
+    // Y value scales the specular reflections.
+    // Z value is 1 when diffuse lighting comes from the spherical harmonics.
+    // W value is the exponent of the prefiltered levels' roughness mapping.
+    pub environment_parameters: glm::Vec4,
+    pub irradiance_harmonics: [glm::Vec4; SphericalHarmonics::COEFFICIENT_COUNT],
     pub joint_matrices: [glm::Mat4; UniformBufferObject::MAX_NUM_JOINTS],
 }
 
@@ -533,6 +1202,43 @@ impl UniformBufferObject {
     pub const MAX_NUM_JOINTS: usize = 128;
 }
 
+#[derive(Debug, Clone, Copy)]
+pub struct ProbeUniformBufferObject {
+    // X value is the number of probes in use
+    pub count: glm::Vec4,
+    // The probes' slots, from the first to be blended to the last
+    pub order: glm::Vec4,
+    pub positions: [glm::Vec4; ProbeUniformBufferObject::MAX_PROBES],
+    pub extents: [glm::Vec4; ProbeUniformBufferObject::MAX_PROBES],
+}
+
+impl ProbeUniformBufferObject {
+    // This needs to match the defined value in the shaders
+    pub const MAX_PROBES: usize = 4;
+
+    // Probes are given with the slot their maps are bound to, in the order they're blended
+    pub fn new(probes: &[(usize, &ReflectionProbe)]) -> Self {
+        let mut ubo = Self {
+            count: glm::Vec4::zeros(),
+            order: glm::Vec4::zeros(),
+            positions: [glm::Vec4::zeros(); Self::MAX_PROBES],
+            extents: [glm::Vec4::zeros(); Self::MAX_PROBES],
+        };
+        let probes = probes
+            .iter()
+            .filter(|(slot, _)| *slot < Self::MAX_PROBES)
+            .collect::<Vec<_>>();
+        ubo.count.x = probes.len() as f32;
+        for (index, (slot, probe)) in probes.into_iter().enumerate() {
+            let uniform = probe.uniform();
+            ubo.order[index] = *slot as f32;
+            ubo.positions[*slot] = uniform.position;
+            ubo.extents[*slot] = uniform.extents;
+        }
+        ubo
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct DynamicUniformBufferObject {
     pub model: glm::Mat4,
@@ -545,9 +1251,13 @@ pub struct DynamicUniformBufferObject {
 pub struct PbrPipelineData {
     pub descriptor_pool: DescriptorPool,
     pub uniform_buffer: Buffer,
-    pub dynamic_uniform_buffer: Buffer,
-    pub dynamic_alignment: u64,
+    pub probe_uniform_buffer: Buffer,
+    pub light_buffer: LightBuffer,
+    pub shadow_uniform_buffer: Buffer,
+    pub point_shadow_uniform_buffer: Buffer,
+    pub dynamic_uniform_buffer: TypedBuffer<DynamicUniformBufferObject>,
     pub descriptor_set: vk::DescriptorSet,
+    pub descriptor_set_layout: Arc<DescriptorSetLayout>,
     pub dummy: DummyImage,
 }
 
@@ -560,9 +1270,11 @@ impl PbrPipelineData {
         command_pool: &CommandPool,
         number_of_meshes: usize,
         textures: &[&TextureBundle],
-        environment_maps: &EnvironmentMapSet,
+        environment_maps: &EnvironmentMaps,
+        shadow_map: &CascadedShadowMap,
+        point_shadow_maps: &PointShadowMaps,
     ) -> Self {
-        let descriptor_set_layout = Self::descriptor_set_layout(context.clone());
+        let descriptor_set_layout = Arc::new(Self::descriptor_set_layout(context.clone()));
         let descriptor_pool = Self::create_descriptor_pool(context.clone());
         let descriptor_set = descriptor_pool
             .allocate_descriptor_sets(descriptor_set_layout.layout(), 1)
@@ -576,43 +1288,65 @@ impl PbrPipelineData {
         )
         .unwrap();
 
-        let dynamic_alignment = Self::calculate_dynamic_alignment(context.clone());
+        let probe_uniform_buffer = Buffer::new_mapped_basic(
+            context.clone(),
+            mem::size_of::<ProbeUniformBufferObject>() as _,
+            vk::BufferUsageFlags::UNIFORM_BUFFER,
+            vk_mem::MemoryUsage::CpuToGpu,
+        )
+        .unwrap();
+
+        let light_buffer = LightBuffer::new(context.clone()).unwrap();
+
+        let shadow_uniform_buffer = Buffer::new_mapped_basic(
+            context.clone(),
+            mem::size_of::<ShadowUniform>() as _,
+            vk::BufferUsageFlags::UNIFORM_BUFFER,
+            vk_mem::MemoryUsage::CpuToGpu,
+        )
+        .unwrap();
+        shadow_uniform_buffer
+            .upload_to_buffer(&[ShadowUniform::disabled()], 0)
+            .unwrap();
 
-        let dynamic_uniform_buffer = Buffer::new_mapped_basic(
+        let point_shadow_uniform_buffer = Buffer::new_mapped_basic(
             context.clone(),
-            (number_of_meshes as u64 * dynamic_alignment) as vk::DeviceSize,
+            mem::size_of::<PointShadowBufferObject>() as _,
             vk::BufferUsageFlags::UNIFORM_BUFFER,
             vk_mem::MemoryUsage::CpuToGpu,
         )
         .unwrap();
+        point_shadow_uniform_buffer
+            .upload_to_buffer(&[point_shadow_maps.uniform()], 0)
+            .unwrap();
+
+        let dynamic_uniform_buffer =
+            TypedBuffer::new_dynamic_uniform(context.clone(), number_of_meshes).unwrap();
 
         let data = PbrPipelineData {
             descriptor_pool,
             uniform_buffer,
+            probe_uniform_buffer,
+            light_buffer,
+            shadow_uniform_buffer,
+            point_shadow_uniform_buffer,
             dynamic_uniform_buffer,
             descriptor_set,
-            dynamic_alignment,
+            descriptor_set_layout,
             dummy: DummyImage::new(context.clone(), &command_pool),
         };
 
-        data.update_descriptor_set(context, number_of_meshes, textures, environment_maps);
+        data.update_descriptor_set(
+            context,
+            textures,
+            environment_maps,
+            shadow_map,
+            point_shadow_maps,
+        );
 
         data
     }
 
-    fn calculate_dynamic_alignment(context: Arc<VulkanContext>) -> u64 {
-        let minimum_ubo_alignment = context
-            .physical_device_properties()
-            .limits
-            .min_uniform_buffer_offset_alignment;
-        let dynamic_alignment = std::mem::size_of::<DynamicUniformBufferObject>() as u64;
-        if minimum_ubo_alignment > 0 {
-            (dynamic_alignment + minimum_ubo_alignment - 1) & !(minimum_ubo_alignment - 1)
-        } else {
-            dynamic_alignment
-        }
-    }
-
     pub fn descriptor_set_layout(context: Arc<VulkanContext>) -> DescriptorSetLayout {
         let ubo_binding = vk::DescriptorSetLayoutBinding::builder()
             .binding(0)
@@ -650,6 +1384,54 @@ impl PbrPipelineData {
             .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
             .stage_flags(vk::ShaderStageFlags::FRAGMENT)
             .build();
+        let probe_ubo_binding = vk::DescriptorSetLayoutBinding::builder()
+            .binding(6)
+            .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+            .descriptor_count(1)
+            .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+            .build();
+        let probe_irradiance_cubemap_binding = vk::DescriptorSetLayoutBinding::builder()
+            .binding(7)
+            .descriptor_count(ProbeUniformBufferObject::MAX_PROBES as _)
+            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+            .build();
+        let probe_prefilter_cubemap_binding = vk::DescriptorSetLayoutBinding::builder()
+            .binding(8)
+            .descriptor_count(ProbeUniformBufferObject::MAX_PROBES as _)
+            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+            .build();
+        let light_ubo_binding = vk::DescriptorSetLayoutBinding::builder()
+            .binding(9)
+            .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+            .descriptor_count(1)
+            .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+            .build();
+        let shadow_ubo_binding = vk::DescriptorSetLayoutBinding::builder()
+            .binding(10)
+            .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+            .descriptor_count(1)
+            .stage_flags(vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT)
+            .build();
+        let shadow_map_binding = vk::DescriptorSetLayoutBinding::builder()
+            .binding(11)
+            .descriptor_count(1)
+            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+            .build();
+        let point_shadow_ubo_binding = vk::DescriptorSetLayoutBinding::builder()
+            .binding(12)
+            .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+            .descriptor_count(1)
+            .stage_flags(vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT)
+            .build();
+        let point_shadow_map_binding = vk::DescriptorSetLayoutBinding::builder()
+            .binding(13)
+            .descriptor_count(PointShadowMaps::MAX_SHADOWS as _)
+            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+            .build();
 
         let bindings = [
             ubo_binding,
@@ -658,6 +1440,14 @@ impl PbrPipelineData {
             irradiance_cubemap_binding,
             prefilter_cubemap_binding,
             brdflut_binding,
+            probe_ubo_binding,
+            probe_irradiance_cubemap_binding,
+            probe_prefilter_cubemap_binding,
+            light_ubo_binding,
+            shadow_ubo_binding,
+            shadow_map_binding,
+            point_shadow_ubo_binding,
+            point_shadow_map_binding,
         ];
 
         let layout_create_info = vk::DescriptorSetLayoutCreateInfo::builder()
@@ -697,6 +1487,41 @@ impl PbrPipelineData {
             descriptor_count: 1,
         };
 
+        let probe_ubo_pool_size = vk::DescriptorPoolSize {
+            ty: vk::DescriptorType::UNIFORM_BUFFER,
+            descriptor_count: 1,
+        };
+
+        let probe_cubemap_pool_size = vk::DescriptorPoolSize {
+            ty: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            descriptor_count: 2 * ProbeUniformBufferObject::MAX_PROBES as u32,
+        };
+
+        let light_ubo_pool_size = vk::DescriptorPoolSize {
+            ty: vk::DescriptorType::UNIFORM_BUFFER,
+            descriptor_count: 1,
+        };
+
+        let shadow_ubo_pool_size = vk::DescriptorPoolSize {
+            ty: vk::DescriptorType::UNIFORM_BUFFER,
+            descriptor_count: 1,
+        };
+
+        let shadow_map_pool_size = vk::DescriptorPoolSize {
+            ty: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            descriptor_count: 1,
+        };
+
+        let point_shadow_ubo_pool_size = vk::DescriptorPoolSize {
+            ty: vk::DescriptorType::UNIFORM_BUFFER,
+            descriptor_count: 1,
+        };
+
+        let point_shadow_map_pool_size = vk::DescriptorPoolSize {
+            ty: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            descriptor_count: PointShadowMaps::MAX_SHADOWS as _,
+        };
+
         let pool_sizes = [
             ubo_pool_size,
             dynamic_ubo_pool_size,
@@ -704,6 +1529,13 @@ impl PbrPipelineData {
             irradiance_cubemap_pool_size,
             prefilter_cubemap_pool_size,
             brdflut_pool_size,
+            probe_ubo_pool_size,
+            probe_cubemap_pool_size,
+            light_ubo_pool_size,
+            shadow_ubo_pool_size,
+            shadow_map_pool_size,
+            point_shadow_ubo_pool_size,
+            point_shadow_map_pool_size,
         ];
 
         let pool_info = vk::DescriptorPoolCreateInfo::builder()
@@ -717,9 +1549,10 @@ impl PbrPipelineData {
     fn update_descriptor_set(
         &self,
         context: Arc<VulkanContext>,
-        number_of_meshes: usize,
         textures: &[&TextureBundle],
-        environment_maps: &EnvironmentMapSet,
+        environment_maps: &EnvironmentMaps,
+        shadow_map: &CascadedShadowMap,
+        point_shadow_maps: &PointShadowMaps,
     ) {
         let uniform_buffer_size = mem::size_of::<UniformBufferObject>() as vk::DeviceSize;
         let buffer_info = vk::DescriptorBufferInfo::builder()
@@ -729,12 +1562,11 @@ impl PbrPipelineData {
             .build();
         let buffer_infos = [buffer_info];
 
-        let dynamic_uniform_buffer_size =
-            (number_of_meshes as u64 * self.dynamic_alignment) as vk::DeviceSize;
+        // Dynamic descriptors cover a single element, which is selected by the dynamic offset
         let dynamic_buffer_info = vk::DescriptorBufferInfo::builder()
             .buffer(self.dynamic_uniform_buffer.buffer())
             .offset(0)
-            .range(dynamic_uniform_buffer_size)
+            .range(self.dynamic_uniform_buffer.element_size())
             .build();
         let dynamic_buffer_infos = [dynamic_buffer_info];
 
@@ -833,6 +1665,101 @@ impl PbrPipelineData {
             .image_info(&brdflut_image_infos)
             .build();
 
+        let probe_buffer_info = vk::DescriptorBufferInfo::builder()
+            .buffer(self.probe_uniform_buffer.buffer())
+            .offset(0)
+            .range(mem::size_of::<ProbeUniformBufferObject>() as vk::DeviceSize)
+            .build();
+        let probe_buffer_infos = [probe_buffer_info];
+
+        let probe_ubo_descriptor_write = vk::WriteDescriptorSet::builder()
+            .dst_set(self.descriptor_set)
+            .dst_binding(6)
+            .dst_array_element(0)
+            .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+            .buffer_info(&probe_buffer_infos)
+            .build();
+
+        let light_buffer_info = vk::DescriptorBufferInfo::builder()
+            .buffer(self.light_buffer.buffer())
+            .offset(0)
+            .range(LightBuffer::size())
+            .build();
+        let light_buffer_infos = [light_buffer_info];
+
+        let light_ubo_descriptor_write = vk::WriteDescriptorSet::builder()
+            .dst_set(self.descriptor_set)
+            .dst_binding(9)
+            .dst_array_element(0)
+            .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+            .buffer_info(&light_buffer_infos)
+            .build();
+
+        let shadow_buffer_info = vk::DescriptorBufferInfo::builder()
+            .buffer(self.shadow_uniform_buffer.buffer())
+            .offset(0)
+            .range(mem::size_of::<ShadowUniform>() as vk::DeviceSize)
+            .build();
+        let shadow_buffer_infos = [shadow_buffer_info];
+
+        let shadow_ubo_descriptor_write = vk::WriteDescriptorSet::builder()
+            .dst_set(self.descriptor_set)
+            .dst_binding(10)
+            .dst_array_element(0)
+            .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+            .buffer_info(&shadow_buffer_infos)
+            .build();
+
+        let shadow_map_image_info = vk::DescriptorImageInfo::builder()
+            .image_layout(vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL)
+            .image_view(shadow_map.view().view())
+            .sampler(shadow_map.sampler().sampler())
+            .build();
+        let shadow_map_image_infos = [shadow_map_image_info];
+
+        let shadow_map_descriptor_write = vk::WriteDescriptorSet::builder()
+            .dst_set(self.descriptor_set)
+            .dst_binding(11)
+            .dst_array_element(0)
+            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .image_info(&shadow_map_image_infos)
+            .build();
+
+        let point_shadow_buffer_info = vk::DescriptorBufferInfo::builder()
+            .buffer(self.point_shadow_uniform_buffer.buffer())
+            .offset(0)
+            .range(mem::size_of::<PointShadowBufferObject>() as vk::DeviceSize)
+            .build();
+        let point_shadow_buffer_infos = [point_shadow_buffer_info];
+
+        let point_shadow_ubo_descriptor_write = vk::WriteDescriptorSet::builder()
+            .dst_set(self.descriptor_set)
+            .dst_binding(12)
+            .dst_array_element(0)
+            .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+            .buffer_info(&point_shadow_buffer_infos)
+            .build();
+
+        let point_shadow_map_image_infos = point_shadow_maps
+            .views()
+            .iter()
+            .map(|view| {
+                vk::DescriptorImageInfo::builder()
+                    .image_layout(vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL)
+                    .image_view(view.view())
+                    .sampler(point_shadow_maps.sampler().sampler())
+                    .build()
+            })
+            .collect::<Vec<_>>();
+
+        let point_shadow_map_descriptor_write = vk::WriteDescriptorSet::builder()
+            .dst_set(self.descriptor_set)
+            .dst_binding(13)
+            .dst_array_element(0)
+            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .image_info(&point_shadow_map_image_infos)
+            .build();
+
         let descriptor_writes = vec![
             ubo_descriptor_write,
             dynamic_ubo_descriptor_write,
@@ -840,6 +1767,12 @@ impl PbrPipelineData {
             irradiance_cubemap_descriptor_write,
             prefilter_cubemap_descriptor_write,
             brdflut_descriptor_write,
+            probe_ubo_descriptor_write,
+            light_ubo_descriptor_write,
+            shadow_ubo_descriptor_write,
+            shadow_map_descriptor_write,
+            point_shadow_ubo_descriptor_write,
+            point_shadow_map_descriptor_write,
         ];
 
         unsafe {
@@ -848,6 +1781,172 @@ impl PbrPipelineData {
                 .logical_device()
                 .update_descriptor_sets(&descriptor_writes, &[])
         }
+
+        self.update_probe_descriptors(context, environment_maps, &[]);
+    }
+
+    // Slots without a probe are bound to the environment's maps
+    fn update_probe_descriptors(
+        &self,
+        context: Arc<VulkanContext>,
+        environment_maps: &EnvironmentMaps,
+        probes: &[ReflectionProbe],
+    ) {
+        let image_infos = |probe_cubemap: fn(&ReflectionProbe) -> &Cubemap,
+                           environment_cubemap: &Cubemap| {
+            (0..ProbeUniformBufferObject::MAX_PROBES)
+                .map(|slot| {
+                    let cubemap = probes
+                        .get(slot)
+                        .map(probe_cubemap)
+                        .unwrap_or(environment_cubemap);
+                    vk::DescriptorImageInfo::builder()
+                        .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+                        .image_view(cubemap.view.view())
+                        .sampler(cubemap.sampler.sampler())
+                        .build()
+                })
+                .collect::<Vec<_>>()
+        };
+        let irradiance_image_infos = image_infos(
+            |probe| &probe.irradiance.cubemap,
+            &environment_maps.irradiance.cubemap,
+        );
+        let prefilter_image_infos = image_infos(
+            |probe| &probe.prefilter.cubemap,
+            &environment_maps.prefilter.cubemap,
+        );
+
+        let irradiance_descriptor_write = vk::WriteDescriptorSet::builder()
+            .dst_set(self.descriptor_set)
+            .dst_binding(7)
+            .dst_array_element(0)
+            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .image_info(&irradiance_image_infos)
+            .build();
+
+        let prefilter_descriptor_write = vk::WriteDescriptorSet::builder()
+            .dst_set(self.descriptor_set)
+            .dst_binding(8)
+            .dst_array_element(0)
+            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .image_info(&prefilter_image_infos)
+            .build();
+
+        let descriptor_writes = vec![irradiance_descriptor_write, prefilter_descriptor_write];
+
+        unsafe {
+            context
+                .logical_device()
+                .logical_device()
+                .update_descriptor_sets(&descriptor_writes, &[])
+        }
+    }
+}
+
+// Draws the casters of the shadow map's cascades and the point shadows' cube faces
+pub struct ShadowRenderer {
+    command_buffer: vk::CommandBuffer,
+    pipeline_layout: vk::PipelineLayout,
+    dynamic_alignment: u64,
+    descriptor_set: vk::DescriptorSet,
+}
+
+impl ShadowRenderer {
+    pub fn new(
+        command_buffer: vk::CommandBuffer,
+        pipeline: &GraphicsPipeline,
+        pipeline_data: &PbrPipelineData,
+    ) -> Self {
+        Self {
+            command_buffer,
+            pipeline_layout: pipeline.layout(),
+            dynamic_alignment: pipeline_data.dynamic_uniform_buffer.stride(),
+            descriptor_set: pipeline_data.descriptor_set,
+        }
+    }
+
+    // Shadow pipelines only take push constants in the vertex stage
+    pub fn push_constants<T>(&self, device: &ash::Device, constants: &T) {
+        unsafe {
+            device.cmd_push_constants(
+                self.command_buffer,
+                self.pipeline_layout,
+                vk::ShaderStageFlags::VERTEX,
+                0,
+                byte_slice_from(constants),
+            );
+        }
+    }
+
+    pub fn draw_assets(
+        &self,
+        device: &ash::Device,
+        geometry_arena: &GeometryArena<GltfVertex>,
+        assets: &[GltfAsset],
+        asset_geometry: &[GeometryHandle],
+    ) {
+        geometry_arena.bind_vertex_buffer(device, self.command_buffer);
+
+        let mut offsets = GltfOffsets::default();
+        for (asset, handle) in assets.iter().zip(asset_geometry.iter()) {
+            let range = geometry_arena
+                .range(*handle)
+                .expect("Failed to get asset geometry!");
+            geometry_arena.bind_index_buffer(device, self.command_buffer, range.index_type);
+            offsets.index_offset = range.first_index;
+            offsets.vertex_offset = range.vertex_offset;
+
+            self.draw_asset(device, &asset, &offsets);
+            offsets.mesh_offset += asset.number_of_meshes;
+        }
+    }
+
+    // Masked primitives cast shadows as if they were opaque, and blended primitives cast none
+    pub fn draw_asset(&self, device: &ash::Device, asset: &GltfAsset, offsets: &GltfOffsets) {
+        asset.walk(|node_index, graph| {
+            if let Some(mesh) = graph[node_index].mesh.as_ref() {
+                unsafe {
+                    device.cmd_bind_descriptor_sets(
+                        self.command_buffer,
+                        vk::PipelineBindPoint::GRAPHICS,
+                        self.pipeline_layout,
+                        0,
+                        &[self.descriptor_set],
+                        &[
+                            ((offsets.mesh_offset + mesh.mesh_id) as u64 * self.dynamic_alignment)
+                                as _,
+                        ],
+                    );
+                }
+
+                for primitive in mesh.primitives.iter() {
+                    let blended = primitive.material_index.is_some_and(|material_index| {
+                        asset
+                            .gltf
+                            .materials()
+                            .nth(material_index)
+                            .expect("Failed to retrieve material!")
+                            .alpha_mode()
+                            == AlphaMode::Blend
+                    });
+                    if blended {
+                        continue;
+                    }
+
+                    unsafe {
+                        device.cmd_draw_indexed(
+                            self.command_buffer,
+                            primitive.number_of_indices,
+                            1,
+                            offsets.index_offset + primitive.first_index,
+                            offsets.vertex_offset,
+                            0,
+                        );
+                    }
+                }
+            }
+        });
     }
 }
 
@@ -856,7 +1955,7 @@ pub struct GltfOffsets {
     pub texture_offset: i32,
     pub mesh_offset: usize,
     pub index_offset: u32,
-    pub vertex_offset: u32,
+    pub vertex_offset: i32,
 }
 
 pub struct PbrRenderer {
@@ -875,7 +1974,7 @@ impl PbrRenderer {
         Self {
             command_buffer,
             pipeline_layout: pipeline.layout(),
-            dynamic_alignment: pipeline_data.dynamic_alignment,
+            dynamic_alignment: pipeline_data.dynamic_uniform_buffer.stride(),
             descriptor_set: pipeline_data.descriptor_set,
         }
     }
@@ -934,7 +2033,7 @@ impl PbrRenderer {
                             primitive.number_of_indices,
                             1,
                             offsets.index_offset + primitive.first_index,
-                            offsets.vertex_offset as _,
+                            offsets.vertex_offset,
                             0,
                         );
                     }
@@ -950,7 +2049,7 @@ impl PbrRenderer {
     ) -> PushConstantBlockMaterial {
         let mut material = PushConstantBlockMaterial {
             base_color_factor: glm::vec4(0.0, 0.0, 0.0, 1.0),
-            emissive_factor: glm::Vec3::identity(),
+            emissive_factor: glm::Vec3::zeros(),
             color_texture_set: -1,
             metallic_roughness_texture_set: -1,
             normal_texture_set: -1,
@@ -960,6 +2059,7 @@ impl PbrRenderer {
             roughness_factor: 0.0,
             alpha_mode: gltf::material::AlphaMode::Opaque as i32,
             alpha_cutoff: 0.0,
+            emissive_strength: 1.0,
         };
 
         if let Some(material_index) = primitive.material_index {
@@ -974,8 +2074,11 @@ impl PbrRenderer {
             material.metallic_factor = pbr.metallic_factor();
             material.roughness_factor = pbr.roughness_factor();
             material.emissive_factor = glm::Vec3::from(primitive_material.emissive_factor());
+            // Materials without KHR_materials_emissive_strength keep their emissive factor as is
+            material.emissive_strength = primitive_material.emissive_strength().unwrap_or(1.0);
             material.alpha_mode = primitive_material.alpha_mode() as i32;
-            material.alpha_cutoff = primitive_material.alpha_cutoff();
+            // The cutoff defaults to 0.5 when the material doesn't set one
+            material.alpha_cutoff = primitive_material.alpha_cutoff().unwrap_or(0.5);
 
             if let Some(base_color_texture) = pbr.base_color_texture() {
                 material.color_texture_set =
@@ -1,16 +1,22 @@
 use ash::vk;
 use log::debug;
 use nalgebra_glm as glm;
-use std::{boxed::Box, sync::Arc};
+use std::{boxed::Box, env, rc::Rc, sync::Arc};
 use support::{
     app::{run_app, setup_app, App, AppState},
     camera::FreeCamera,
     vulkan::{
-        create_skybox_pipeline, Command, HdrCubemap, RenderPass, RenderPipeline, Renderer,
-        ShaderCache, SkyboxPipelineData, SkyboxRenderer, SkyboxUniformBufferObject, VulkanContext,
+        create_skybox_pipeline, Command, Cubemap, OutputTransfer, PipelineCache, PostEffect,
+        PostProcessChain, ProceduralSky, RenderPass, RenderPipeline, Renderer, ShaderCache, Sibl,
+        SkyboxPipelineData, SkyboxRenderer, SkyboxUniformBufferObject, Tonemapper, VulkanContext,
     },
 };
-use winit::window::Window;
+use winit::{event::VirtualKeyCode, window::Window};
+
+// Passing --sky shows a procedural sky instead, with the up and down keys moving the sun
+const SKY_FACE_SIZE: u32 = 128;
+const SUN_AZIMUTH: f32 = 0.6;
+const SUN_SPEED: f32 = 0.25;
 
 fn main() {
     let (window, event_loop, renderer) = setup_app("Physically Based Rendering - Gltf models");
@@ -24,20 +30,38 @@ fn main() {
 
 struct DemoApp {
     context: Arc<VulkanContext>,
-    skybox_pipeline: Option<RenderPipeline>,
+    skybox_pipeline: Option<Rc<RenderPipeline>>,
     skybox_pipeline_data: Option<SkyboxPipelineData>,
-    cubemap: Option<HdrCubemap>,
+    cubemap: Option<Cubemap>,
     camera: FreeCamera,
+    sky: Option<ProceduralSky>,
+    sun_elevation: f32,
+    post_process: Option<PostProcessChain>,
 }
 
 impl DemoApp {
     pub fn new(context: Arc<VulkanContext>) -> Self {
+        let sun_elevation = 30_f32.to_radians();
+        let sky = if env::args().any(|arg| arg == "--sky") {
+            Some(ProceduralSky::from_sun_angles(
+                sun_elevation,
+                SUN_AZIMUTH,
+                3.0,
+                glm::vec3(0.3, 0.3, 0.3),
+            ))
+        } else {
+            None
+        };
+
         Self {
             context,
             skybox_pipeline: None,
             skybox_pipeline_data: None,
             cubemap: None,
             camera: FreeCamera::default(),
+            sky,
+            sun_elevation,
+            post_process: None,
         }
     }
 }
@@ -65,30 +89,52 @@ impl App for DemoApp {
 
         window.set_cursor_position(app_state.window_center())?;
 
-        let cubemap_path = "assets/skyboxes/walk_of_fame/walk_of_fame.hdr";
-
-        debug!("Creating HDR cubemap");
-        let hdr = HdrCubemap::new(
-            self.context.clone(),
-            &renderer.transient_command_pool,
-            &cubemap_path,
-            &mut renderer.shader_cache,
-        )?;
-
-        let skybox_pipeline_data = SkyboxPipelineData::new(
-            self.context.clone(),
-            &renderer.transient_command_pool,
-            &hdr.cubemap,
-        );
+        debug!("Creating environment cubemap");
+        let cubemap = match self.sky.as_ref() {
+            Some(sky) => sky.create_cubemap(
+                self.context.clone(),
+                &mut renderer.upload_manager,
+                &mut renderer.sampler_cache,
+                SKY_FACE_SIZE,
+            )?,
+            None => {
+                let sibl = Sibl::from_file("assets/skyboxes/walk_of_fame/Walk_Of_Fame.ibl")?;
+                sibl.background_image().source().load(
+                    self.context.clone(),
+                    &renderer.transient_command_pool,
+                    &mut renderer.upload_manager,
+                    &mut renderer.sampler_cache,
+                    &mut renderer.shader_cache,
+                )?
+            }
+        };
+
+        let skybox_pipeline_data =
+            SkyboxPipelineData::new(self.context.clone(), &mut renderer.upload_manager, &cubemap);
+        renderer.upload_manager.submit()?;
 
         self.skybox_pipeline_data = Some(skybox_pipeline_data);
 
-        self.cubemap = Some(hdr);
+        self.cubemap = Some(cubemap);
+
+        // The sky used to be tonemapped in its own shader with this curve and exposure
+        let swapchain_properties = renderer.vulkan_swapchain().swapchain.properties();
+        self.post_process = Some(PostProcessChain::new(
+            self.context.clone(),
+            swapchain_properties.extent,
+            swapchain_properties.format.format,
+            &[
+                PostEffect::Exposure(4.5),
+                PostEffect::Tonemap(Tonemapper::Uncharted2),
+                PostEffect::Output(OutputTransfer::Srgb),
+            ],
+        )?);
 
         let render_pass = renderer.vulkan_swapchain().render_pass.clone();
         self.recreate_pipelines(
             renderer.context.clone(),
             &mut renderer.shader_cache,
+            &mut renderer.pipeline_cache,
             render_pass,
         )?;
 
@@ -105,6 +151,32 @@ impl App for DemoApp {
     ) -> Result<(), Box<dyn std::error::Error>> {
         self.camera.update(&app_state);
 
+        if let (Some(sky), Some(cubemap)) = (self.sky.as_mut(), self.cubemap.as_ref()) {
+            let mut direction = 0.0;
+            if app_state.input.is_key_pressed(VirtualKeyCode::Up) {
+                direction += 1.0;
+            }
+            if app_state.input.is_key_pressed(VirtualKeyCode::Down) {
+                direction -= 1.0;
+            }
+            if direction != 0.0 {
+                self.sun_elevation = (self.sun_elevation
+                    + direction * SUN_SPEED * app_state.delta_time as f32)
+                    .clamp(-0.1, 0.5 * std::f32::consts::PI);
+                *sky = ProceduralSky::from_sun_angles(
+                    self.sun_elevation,
+                    SUN_AZIMUTH,
+                    sky.turbidity,
+                    sky.ground_albedo,
+                );
+
+                // The cubemap is rewritten in place, so the skybox descriptors stay valid
+                self.context.logical_device().wait_idle();
+                let ticket = sky.update_cubemap(&mut renderer.upload_manager, cubemap)?;
+                renderer.upload_manager.wait(ticket)?;
+            }
+        }
+
         let projection = glm::perspective_zo(
             renderer
                 .vulkan_swapchain()
@@ -147,7 +219,7 @@ impl App for DemoApp {
 }
 
 impl Command for DemoApp {
-    fn issue_commands(
+    fn issue_offscreen_commands(
         &mut self,
         device: &ash::Device,
         command_buffer: vk::CommandBuffer,
@@ -162,24 +234,67 @@ impl Command for DemoApp {
             .as_ref()
             .expect("Failed to get skybox pipeline data!");
 
-        skybox_pipeline.bind(device, command_buffer);
+        let post_process = self
+            .post_process
+            .as_mut()
+            .expect("Failed to get post processing chain!");
+
+        post_process
+            .target()
+            .record(device, command_buffer, [0.0, 0.0, 0.0, 1.0], || {
+                skybox_pipeline.bind(device, command_buffer);
+
+                let skybox_renderer =
+                    SkyboxRenderer::new(command_buffer, &skybox_pipeline, &skybox_pipeline_data);
 
-        let skybox_renderer =
-            SkyboxRenderer::new(command_buffer, &skybox_pipeline, &skybox_pipeline_data);
+                skybox_renderer.draw(device, &skybox_pipeline_data.cube);
+            });
 
-        skybox_renderer.draw(device, &skybox_pipeline_data.cube);
+        post_process.record(device, command_buffer);
 
         Ok(())
     }
 
+    fn issue_commands(
+        &mut self,
+        device: &ash::Device,
+        command_buffer: vk::CommandBuffer,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.post_process
+            .as_ref()
+            .expect("Failed to get post processing chain!")
+            .record_output(device, command_buffer);
+
+        Ok(())
+    }
+
+    fn recreate_targets(&mut self, extent: vk::Extent2D) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(post_process) = self.post_process.as_mut() {
+            post_process.resize(extent)?;
+        }
+        Ok(())
+    }
+
     fn recreate_pipelines(
         &mut self,
         context: Arc<VulkanContext>,
         shader_cache: &mut ShaderCache,
+        pipeline_cache: &mut PipelineCache,
         render_pass: Arc<RenderPass>,
     ) -> Result<(), Box<dyn std::error::Error>> {
+        let post_process = self
+            .post_process
+            .as_mut()
+            .expect("Failed to get post processing chain!");
+        post_process.create_pipelines(shader_cache, pipeline_cache, render_pass)?;
+
         self.skybox_pipeline = None;
-        self.skybox_pipeline = Some(create_skybox_pipeline(context, shader_cache, render_pass));
+        self.skybox_pipeline = Some(create_skybox_pipeline(
+            context,
+            shader_cache,
+            pipeline_cache,
+            post_process.target().render_pass(),
+        )?);
 
         Ok(())
     }
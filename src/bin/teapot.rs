@@ -1,14 +1,14 @@
 use ash::{version::DeviceV1_0, vk};
 use nalgebra_glm as glm;
 use snafu::Snafu;
-use std::{boxed::Box, mem, sync::Arc};
+use std::{boxed::Box, mem, rc::Rc, sync::Arc};
 use support::{
     app::{run_app, setup_app, App, AppState},
     camera::FreeCamera,
     vulkan::{
-        Buffer, Command, CommandPool, DescriptorPool, DescriptorSetLayout, ObjModel, RenderPass,
-        RenderPipeline, RenderPipelineSettingsBuilder, Renderer, ShaderCache, ShaderPathSetBuilder,
-        VulkanContext,
+        Buffer, Command, DescriptorPool, DescriptorSetLayout, ObjModel, ObjVertex, PipelineCache,
+        RenderPass, RenderPipeline, RenderPipelineSettingsBuilder, Renderer, ShaderCache,
+        ShaderPathSetBuilder, UploadManager, Vertex, VulkanContext,
     },
 };
 use winit::window::Window;
@@ -35,9 +35,9 @@ pub enum Error {
 }
 
 fn main() {
-    let (window, event_loop, renderer) = setup_app("Model");
+    let (window, event_loop, mut renderer) = setup_app("Model");
     run_app(
-        DemoApp::new(renderer.context.clone(), &renderer.transient_command_pool),
+        DemoApp::new(renderer.context.clone(), &mut renderer.upload_manager),
         window,
         event_loop,
         renderer,
@@ -47,17 +47,17 @@ fn main() {
 struct DemoApp {
     context: Arc<VulkanContext>,
     model: ObjModel,
-    pipeline: Option<RenderPipeline>,
+    pipeline: Option<Rc<RenderPipeline>>,
     pipeline_data: ModelPipelineData,
     rotation: f32,
     camera: FreeCamera,
 }
 
 impl DemoApp {
-    pub fn new(context: Arc<VulkanContext>, command_pool: &CommandPool) -> Self {
+    pub fn new(context: Arc<VulkanContext>, upload_manager: &mut UploadManager) -> Self {
         Self {
             context: context.clone(),
-            model: ObjModel::new(&command_pool, "assets/models/teapot.obj"),
+            model: ObjModel::new(upload_manager, "assets/models/teapot.obj"),
             pipeline: None,
             pipeline_data: ModelPipelineData::new(context),
             rotation: 0.0,
@@ -79,10 +79,13 @@ impl App for DemoApp {
         renderer: &mut Renderer,
         app_state: &AppState,
     ) -> Result<(), Box<dyn std::error::Error>> {
+        renderer.upload_manager.submit()?;
+
         let render_pass = renderer.vulkan_swapchain().render_pass.clone();
         self.recreate_pipelines(
             renderer.context.clone(),
             &mut renderer.shader_cache,
+            &mut renderer.pipeline_cache,
             render_pass,
         )?;
         renderer.record_all_command_buffers(self as &mut dyn Command);
@@ -204,10 +207,11 @@ impl Command for DemoApp {
         &mut self,
         context: Arc<VulkanContext>,
         shader_cache: &mut ShaderCache,
+        pipeline_cache: &mut PipelineCache,
         render_pass: Arc<RenderPass>,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        let descriptions = ObjModel::create_vertex_input_descriptions();
-        let attributes = ObjModel::create_vertex_attributes();
+        let descriptions = [ObjVertex::binding_description(0)];
+        let attributes = ObjVertex::attribute_descriptions(0, 0);
         let vertex_state_info = vk::PipelineVertexInputStateCreateInfo::builder()
             .vertex_binding_descriptions(&descriptions)
             .vertex_attribute_descriptions(&attributes)
@@ -219,20 +223,17 @@ impl Command for DemoApp {
             .build()?;
         let shader_set = shader_cache.create_shader_set(context.clone(), &shader_paths)?;
 
-        let descriptor_set_layout =
-            Arc::new(ModelPipelineData::descriptor_set_layout(context.clone()));
-
         let settings = RenderPipelineSettingsBuilder::default()
             .render_pass(render_pass)
             .vertex_state_info(vertex_state_info)
-            .descriptor_set_layout(descriptor_set_layout)
+            .descriptor_set_layout(self.pipeline_data.descriptor_set_layout.clone())
             .shader_set(shader_set)
             .rasterization_samples(context.max_usable_samples())
             .build()
             .expect("Failed to create render pipeline settings");
 
         self.pipeline = None;
-        self.pipeline = Some(RenderPipeline::new(context, settings));
+        self.pipeline = Some(pipeline_cache.render_pipeline(settings)?);
 
         Ok(())
     }
@@ -249,12 +250,12 @@ pub struct ModelPipelineData {
     pub descriptor_pool: DescriptorPool,
     pub uniform_buffer: Buffer,
     pub descriptor_set: vk::DescriptorSet,
-    pub descriptor_set_layout: DescriptorSetLayout,
+    pub descriptor_set_layout: Arc<DescriptorSetLayout>,
 }
 
 impl ModelPipelineData {
     pub fn new(context: Arc<VulkanContext>) -> Self {
-        let descriptor_set_layout = Self::descriptor_set_layout(context.clone());
+        let descriptor_set_layout = Arc::new(Self::descriptor_set_layout(context.clone()));
         let descriptor_pool = Self::create_descriptor_pool(context.clone());
         let descriptor_set = descriptor_pool
             .allocate_descriptor_sets(descriptor_set_layout.layout(), 1)
@@ -1,3 +1,6 @@
+// Allows derive macros to refer to this crate as `support` from within it
+extern crate self as support;
+
 pub mod app;
 pub mod camera;
 pub mod input;
@@ -1,6 +1,13 @@
-use crate::vulkan::{CommandPool, TextureBundle, TextureDescription, VulkanContext};
+use crate::vulkan::{
+    ColorSpace, DirectionalLight, PointLight, PunctualLight, SamplerCache, SpotLight,
+    TextureBundle, TextureDescription, UploadManager, Vertex, VulkanContext,
+};
 use ash::vk;
-use gltf::animation::{util::ReadOutputs, Interpolation};
+use gltf::{
+    animation::{util::ReadOutputs, Interpolation},
+    khr_lights_punctual::Kind,
+    texture::{MagFilter, MinFilter, WrappingMode},
+};
 use log::trace;
 use nalgebra::{Matrix4, Quaternion, UnitQuaternion};
 use nalgebra_glm as glm;
@@ -10,7 +17,18 @@ use petgraph::{
     prelude::*,
     visit::Dfs,
 };
-use std::{fmt, sync::Arc};
+use std::{collections::HashSet, fmt, sync::Arc};
+
+#[repr(C)]
+#[derive(Vertex, Debug, Clone, Copy)]
+pub struct GltfVertex {
+    pub position: glm::Vec3,
+    pub normal: glm::Vec3,
+    pub tex_coords_0: glm::Vec2,
+    pub tex_coords_1: glm::Vec2,
+    pub joints_0: glm::Vec4,
+    pub weights_0: glm::Vec4,
+}
 
 #[derive(Debug)]
 pub enum TransformationSet {
@@ -59,6 +77,8 @@ pub struct Node {
     pub local_transform: Transform,
     pub mesh: Option<Mesh>,
     pub skin: Option<Skin>,
+    // In the node's space, so it follows the node's transform
+    pub light: Option<PunctualLight>,
     pub gltf_index: usize,
     pub name: String,
 }
@@ -120,7 +140,7 @@ pub struct GltfAsset {
     pub scenes: Vec<Scene>,
     pub number_of_meshes: usize,
     pub animations: Vec<Animation>,
-    pub vertices: Vec<f32>,
+    pub vertices: Vec<GltfVertex>,
     pub indices: Vec<u32>,
 }
 
@@ -129,17 +149,36 @@ impl GltfAsset {
 
     pub fn new(
         context: Arc<VulkanContext>,
-        command_pool: &CommandPool,
+        upload_manager: &mut UploadManager,
+        sampler_cache: &mut SamplerCache,
         asset_name: &str,
     ) -> GltfAsset {
-        let (gltf, buffers, asset_textures) =
+        let (gltf, buffers, asset_images) =
             gltf::import(&asset_name).expect("Couldn't import file!");
 
-        let textures: Result<Vec<_>, _> = asset_textures
+        let color_images = Self::color_image_indices(&gltf);
+        let images: Result<Vec<_>, _> = asset_images
             .iter()
-            .map(|image_data| {
-                let description = TextureDescription::from_gltf(&image_data).unwrap();
-                TextureBundle::new(context.clone(), command_pool, &description)
+            .enumerate()
+            .map(|(index, image_data)| {
+                let color_space = if color_images.contains(&index) {
+                    ColorSpace::Srgb
+                } else {
+                    ColorSpace::Linear
+                };
+                let description = TextureDescription::from_gltf(image_data, color_space).unwrap();
+                TextureBundle::new(context.clone(), upload_manager, sampler_cache, &description)
+            })
+            .collect();
+        let images = images.unwrap();
+
+        // Materials refer to textures, which pair an image with a sampler
+        let textures: Result<Vec<_>, _> = gltf
+            .textures()
+            .map(|texture| {
+                sampler_cache
+                    .sampler(Self::sampler_info(&texture.sampler()))
+                    .map(|sampler| images[texture.source().index()].resampled(sampler))
             })
             .collect();
         let textures = textures.unwrap();
@@ -162,6 +201,83 @@ impl GltfAsset {
         }
     }
 
+    fn sampler_info(sampler: &gltf::texture::Sampler) -> vk::SamplerCreateInfo {
+        let mag_filter = match sampler.mag_filter() {
+            Some(MagFilter::Nearest) => vk::Filter::NEAREST,
+            Some(MagFilter::Linear) | None => vk::Filter::LINEAR,
+        };
+
+        // Filters without a mipmap mode only sample the first level
+        let (min_filter, mipmap_mode, max_lod) = match sampler.min_filter() {
+            Some(MinFilter::Nearest) => (vk::Filter::NEAREST, vk::SamplerMipmapMode::NEAREST, 0.25),
+            Some(MinFilter::Linear) => (vk::Filter::LINEAR, vk::SamplerMipmapMode::NEAREST, 0.25),
+            Some(MinFilter::NearestMipmapNearest) => (
+                vk::Filter::NEAREST,
+                vk::SamplerMipmapMode::NEAREST,
+                vk::LOD_CLAMP_NONE,
+            ),
+            Some(MinFilter::LinearMipmapNearest) => (
+                vk::Filter::LINEAR,
+                vk::SamplerMipmapMode::NEAREST,
+                vk::LOD_CLAMP_NONE,
+            ),
+            Some(MinFilter::NearestMipmapLinear) => (
+                vk::Filter::NEAREST,
+                vk::SamplerMipmapMode::LINEAR,
+                vk::LOD_CLAMP_NONE,
+            ),
+            Some(MinFilter::LinearMipmapLinear) | None => (
+                vk::Filter::LINEAR,
+                vk::SamplerMipmapMode::LINEAR,
+                vk::LOD_CLAMP_NONE,
+            ),
+        };
+
+        let address_mode = |mode: WrappingMode| match mode {
+            WrappingMode::ClampToEdge => vk::SamplerAddressMode::CLAMP_TO_EDGE,
+            WrappingMode::MirroredRepeat => vk::SamplerAddressMode::MIRRORED_REPEAT,
+            WrappingMode::Repeat => vk::SamplerAddressMode::REPEAT,
+        };
+
+        // Anisotropic filtering only makes sense for linearly filtered mipmaps
+        let anisotropic = min_filter == vk::Filter::LINEAR
+            && mipmap_mode == vk::SamplerMipmapMode::LINEAR
+            && mag_filter == vk::Filter::LINEAR;
+
+        vk::SamplerCreateInfo::builder()
+            .mag_filter(mag_filter)
+            .min_filter(min_filter)
+            .address_mode_u(address_mode(sampler.wrap_s()))
+            .address_mode_v(address_mode(sampler.wrap_t()))
+            .address_mode_w(vk::SamplerAddressMode::REPEAT)
+            .anisotropy_enable(anisotropic)
+            .max_anisotropy(if anisotropic { 16.0 } else { 1.0 })
+            .border_color(vk::BorderColor::INT_OPAQUE_BLACK)
+            .unnormalized_coordinates(false)
+            .compare_enable(false)
+            .compare_op(vk::CompareOp::ALWAYS)
+            .mipmap_mode(mipmap_mode)
+            .mip_lod_bias(0.0)
+            .min_lod(0.0)
+            .max_lod(max_lod)
+            .build()
+    }
+
+    // Base color and emissive images hold sRGB encoded colors.
+    // Every other image holds linear data.
+    fn color_image_indices(gltf: &gltf::Document) -> HashSet<usize> {
+        gltf.materials()
+            .flat_map(|material| {
+                let base_color = material.pbr_metallic_roughness().base_color_texture();
+                let emissive = material.emissive_texture();
+                base_color
+                    .into_iter()
+                    .chain(emissive)
+                    .map(|info| info.texture().source().index())
+            })
+            .collect()
+    }
+
     fn determine_transform(node: &gltf::Node) -> Transform {
         let (translation, rotation, scale) = node.transform().decomposed();
 
@@ -175,7 +291,7 @@ impl GltfAsset {
     fn prepare_scenes(
         gltf: &gltf::Document,
         buffers: &[gltf::buffer::Data],
-    ) -> (Vec<Scene>, Vec<f32>, Vec<u32>) {
+    ) -> (Vec<Scene>, Vec<GltfVertex>, Vec<u32>) {
         let mut vertices = Vec::new();
         let mut indices = Vec::new();
         let mut scenes: Vec<Scene> = Vec::new();
@@ -229,21 +345,48 @@ impl GltfAsset {
         }
     }
 
+    // Lights sit at their node's origin and shine down its negative z axis
+    fn load_light(light: &gltf::khr_lights_punctual::Light) -> PunctualLight {
+        let color = glm::Vec3::from(light.color());
+        let intensity = light.intensity();
+        let forward = glm::vec3(0.0, 0.0, -1.0);
+        match light.kind() {
+            Kind::Directional => DirectionalLight::new(-forward, color, intensity).into(),
+            Kind::Point => PointLight {
+                range: light.range(),
+                ..PointLight::new(glm::Vec3::zeros(), color, intensity)
+            }
+            .into(),
+            Kind::Spot {
+                inner_cone_angle,
+                outer_cone_angle,
+            } => SpotLight {
+                range: light.range(),
+                inner_cone_angle,
+                outer_cone_angle,
+                ..SpotLight::new(glm::Vec3::zeros(), forward, color, intensity)
+            }
+            .into(),
+        }
+    }
+
     fn visit_children(
         node: &gltf::Node,
         buffers: &[gltf::buffer::Data],
         node_graph: &mut NodeGraph,
         parent_index: NodeIndex,
-        vertices: &mut Vec<f32>,
+        vertices: &mut Vec<GltfVertex>,
         indices: &mut Vec<u32>,
     ) {
         let mesh = Self::load_mesh(node, buffers, vertices, indices);
         let skin = Self::load_skin(node, buffers);
+        let light = node.light().map(|light| Self::load_light(&light));
         let name = node.name().unwrap_or(&Self::DEFAULT_NAME).to_string();
         let node_info = Node {
             local_transform: Self::determine_transform(node),
             mesh,
             skin,
+            light,
             gltf_index: node.index(),
             name,
         };
@@ -258,35 +401,16 @@ impl GltfAsset {
         }
     }
 
-    pub fn vertex_stride() -> usize {
-        let position_length = 3;
-        let normal_length = 3;
-        let tex_coords_0_length = 2;
-        let tex_coords_1_length = 2;
-        let joints_0_length = 4;
-        let weights_0_length = 4;
-
-        position_length
-            + normal_length
-            + tex_coords_0_length
-            + tex_coords_1_length
-            + joints_0_length
-            + weights_0_length
-    }
-
     fn load_mesh(
         node: &gltf::Node,
         buffers: &[gltf::buffer::Data],
-        vertices: &mut Vec<f32>,
+        vertices: &mut Vec<GltfVertex>,
         indices: &mut Vec<u32>,
     ) -> Option<Mesh> {
         if let Some(mesh) = node.mesh() {
             let mut all_mesh_primitives = Vec::new();
             for primitive in mesh.primitives() {
-                let stride = Self::vertex_stride() * std::mem::size_of::<f32>();
-
-                let vertex_list_size = vertices.len() * std::mem::size_of::<u32>();
-                let vertex_count = (vertex_list_size / stride) as u32;
+                let vertex_count = vertices.len() as u32;
 
                 // Start reading primitive data
                 let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
@@ -342,12 +466,14 @@ impl GltfAsset {
                 );
 
                 for index in 0..positions.len() {
-                    vertices.extend_from_slice(positions[index].as_slice());
-                    vertices.extend_from_slice(normals[index].as_slice());
-                    vertices.extend_from_slice(tex_coords_0[index].as_slice());
-                    vertices.extend_from_slice(tex_coords_1[index].as_slice());
-                    vertices.extend_from_slice(joints_0[index].as_slice());
-                    vertices.extend_from_slice(weights_0[index].as_slice());
+                    vertices.push(GltfVertex {
+                        position: positions[index],
+                        normal: normals[index],
+                        tex_coords_0: tex_coords_0[index],
+                        tex_coords_1: tex_coords_1[index],
+                        joints_0: joints_0[index],
+                        weights_0: weights_0[index],
+                    });
                 }
 
                 let first_index = indices.len() as u32;
@@ -621,6 +747,18 @@ impl GltfAsset {
             })
     }
 
+    // Every light in the asset's scenes, posed by the current node transforms
+    pub fn lights(&self, transform: &glm::Mat4) -> Vec<PunctualLight> {
+        let mut lights = Vec::new();
+        self.walk_mut(|node_index, graph| {
+            if let Some(light) = graph[node_index].light.as_ref() {
+                let global_transform = Self::calculate_global_transform(node_index, graph);
+                lights.push(light.transformed(&(transform * global_transform)));
+            }
+        });
+        lights
+    }
+
     pub fn walk<F>(&self, action: F)
     where
         F: Fn(NodeIndex, &NodeGraph),
@@ -648,69 +786,6 @@ impl GltfAsset {
             }
         }
     }
-
-    pub fn create_vertex_attributes() -> [vk::VertexInputAttributeDescription; 6] {
-        let float_size = std::mem::size_of::<f32>();
-        let position_description = vk::VertexInputAttributeDescription::builder()
-            .binding(0)
-            .location(0)
-            .format(vk::Format::R32G32B32_SFLOAT)
-            .offset(0)
-            .build();
-
-        let normal_description = vk::VertexInputAttributeDescription::builder()
-            .binding(0)
-            .location(1)
-            .format(vk::Format::R32G32B32_SFLOAT)
-            .offset((3 * float_size) as _)
-            .build();
-
-        let tex_coord_0_description = vk::VertexInputAttributeDescription::builder()
-            .binding(0)
-            .location(2)
-            .format(vk::Format::R32G32_SFLOAT)
-            .offset((6 * float_size) as _)
-            .build();
-
-        let tex_coord_1_description = vk::VertexInputAttributeDescription::builder()
-            .binding(0)
-            .location(3)
-            .format(vk::Format::R32G32_SFLOAT)
-            .offset((8 * float_size) as _)
-            .build();
-
-        let joint_0_description = vk::VertexInputAttributeDescription::builder()
-            .binding(0)
-            .location(4)
-            .format(vk::Format::R32G32B32A32_SFLOAT)
-            .offset((10 * float_size) as _)
-            .build();
-
-        let weight_0_description = vk::VertexInputAttributeDescription::builder()
-            .binding(0)
-            .location(5)
-            .format(vk::Format::R32G32B32A32_SFLOAT)
-            .offset((14 * float_size) as _)
-            .build();
-
-        [
-            position_description,
-            normal_description,
-            tex_coord_0_description,
-            tex_coord_1_description,
-            joint_0_description,
-            weight_0_description,
-        ]
-    }
-
-    pub fn create_vertex_input_descriptions() -> [vk::VertexInputBindingDescription; 1] {
-        let vertex_input_binding_description = vk::VertexInputBindingDescription::builder()
-            .binding(0)
-            .stride((18 * std::mem::size_of::<f32>()) as _)
-            .input_rate(vk::VertexInputRate::VERTEX)
-            .build();
-        [vertex_input_binding_description]
-    }
 }
 
 pub struct NodeLocation {
@@ -1,8 +1,8 @@
 pub use self::{
     context::*, debug_layer::*, descriptor_pool::*, descriptor_set_layout::*, framebuffer::*,
     image_view::*, instance::*, logical_device::*, physical_device::*, pipeline::*,
-    pipeline_layout::*, queue_family_index_set::*, renderpass::*, sampler::*, surface::*,
-    swapchain::*, sync::*, vulkan_swapchain::VulkanSwapchain,
+    pipeline_layout::*, queue_family_index_set::*, renderpass::*, sampler::*, sampler_cache::*,
+    surface::*, swapchain::*, sync::*, vulkan_swapchain::VulkanSwapchain,
 };
 
 pub mod context;
@@ -19,6 +19,7 @@ pub mod pipeline_layout;
 pub mod queue_family_index_set;
 pub mod renderpass;
 pub mod sampler;
+pub mod sampler_cache;
 pub mod surface;
 pub mod swapchain;
 pub mod sync;
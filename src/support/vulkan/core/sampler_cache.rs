@@ -0,0 +1,95 @@
+use crate::vulkan::{Sampler, VulkanContext};
+use ash::vk;
+use std::{collections::HashMap, sync::Arc};
+
+type Result<T, E = crate::vulkan::sampler::Error> = std::result::Result<T, E>;
+
+// Every field of a vk::SamplerCreateInfo that affects the resulting vk::Sampler.
+// Floats are compared by their bits.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SamplerKey {
+    mag_filter: vk::Filter,
+    min_filter: vk::Filter,
+    mipmap_mode: vk::SamplerMipmapMode,
+    address_mode_u: vk::SamplerAddressMode,
+    address_mode_v: vk::SamplerAddressMode,
+    address_mode_w: vk::SamplerAddressMode,
+    mip_lod_bias: u32,
+    anisotropy_enable: vk::Bool32,
+    max_anisotropy: u32,
+    compare_enable: vk::Bool32,
+    compare_op: vk::CompareOp,
+    min_lod: u32,
+    max_lod: u32,
+    border_color: vk::BorderColor,
+    unnormalized_coordinates: vk::Bool32,
+}
+
+impl SamplerKey {
+    pub fn new(create_info: &vk::SamplerCreateInfo) -> Self {
+        Self {
+            mag_filter: create_info.mag_filter,
+            min_filter: create_info.min_filter,
+            mipmap_mode: create_info.mipmap_mode,
+            address_mode_u: create_info.address_mode_u,
+            address_mode_v: create_info.address_mode_v,
+            address_mode_w: create_info.address_mode_w,
+            mip_lod_bias: create_info.mip_lod_bias.to_bits(),
+            anisotropy_enable: create_info.anisotropy_enable,
+            max_anisotropy: create_info.max_anisotropy.to_bits(),
+            compare_enable: create_info.compare_enable,
+            compare_op: create_info.compare_op,
+            min_lod: create_info.min_lod.to_bits(),
+            max_lod: create_info.max_lod.to_bits(),
+            border_color: create_info.border_color,
+            unnormalized_coordinates: create_info.unnormalized_coordinates,
+        }
+    }
+}
+
+// Shares samplers between textures that are sampled the same way
+pub struct SamplerCache {
+    context: Arc<VulkanContext>,
+    samplers: HashMap<SamplerKey, Arc<Sampler>>,
+}
+
+impl SamplerCache {
+    pub fn new(context: Arc<VulkanContext>) -> Self {
+        Self {
+            context,
+            samplers: HashMap::new(),
+        }
+    }
+
+    // The requested anisotropy is clamped to the device limit before lookup
+    pub fn sampler(&mut self, mut create_info: vk::SamplerCreateInfo) -> Result<Arc<Sampler>> {
+        let max_anisotropy = self
+            .context
+            .physical_device_properties()
+            .limits
+            .max_sampler_anisotropy;
+        create_info.max_anisotropy = create_info.max_anisotropy.clamp(1.0, max_anisotropy);
+
+        let key = SamplerKey::new(&create_info);
+        if let Some(sampler) = self.samplers.get(&key) {
+            return Ok(sampler.clone());
+        }
+
+        let sampler = Arc::new(Sampler::new(self.context.clone(), create_info)?);
+        self.samplers.insert(key, sampler.clone());
+        Ok(sampler)
+    }
+
+    pub fn len(&self) -> usize {
+        self.samplers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samplers.is_empty()
+    }
+
+    // Samplers that are still referenced by textures stay alive until those textures are dropped
+    pub fn clear(&mut self) {
+        self.samplers.clear();
+    }
+}
@@ -1,4 +1,4 @@
-use crate::vulkan::{CurrentFrameSynchronization, ImageView, VulkanContext};
+use crate::vulkan::{ColorSpace, CurrentFrameSynchronization, ImageView, VulkanContext};
 use ash::{extensions::khr::Swapchain as AshSwapchain, vk};
 use log::info;
 use snafu::{ResultExt, Snafu};
@@ -99,13 +99,13 @@ impl SwapchainSupportDetails {
     }
 
     fn choose_surface_format(available_formats: &[vk::SurfaceFormatKHR]) -> vk::SurfaceFormatKHR {
-        // Specify a default format and color space
-        let (default_format, default_color_space) = (
-            vk::Format::R8G8B8A8_UNORM,
-            vk::ColorSpaceKHR::SRGB_NONLINEAR,
-        );
+        // Specify a default format and color space.
+        // An sRGB format lets the hardware encode the linear shader output.
+        let (default_format, default_color_space) =
+            (vk::Format::B8G8R8A8_SRGB, vk::ColorSpaceKHR::SRGB_NONLINEAR);
 
-        // Choose the default format if available or choose the first available format
+        // Choose the default format if available,
+        // then any sRGB format, or otherwise the first available format
         if available_formats.len() == 1 && available_formats[0].format == vk::Format::UNDEFINED {
             // If only one format is available
             // but it is undefined, assign a default
@@ -119,6 +119,12 @@ impl SwapchainSupportDetails {
                 .find(|format| {
                     format.format == default_format && format.color_space == default_color_space
                 })
+                .or_else(|| {
+                    available_formats.iter().find(|format| {
+                        ColorSpace::of(format.format) == ColorSpace::Srgb
+                            && format.color_space == default_color_space
+                    })
+                })
                 .unwrap_or_else(|| {
                     available_formats
                         .first()
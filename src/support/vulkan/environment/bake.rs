@@ -0,0 +1,299 @@
+use crate::vulkan::{
+    CommandPool, ComputePipeline, Cubemap, DescriptorPool, DescriptorSetLayout,
+    ImageLayoutTransition, ImageView, PipelineLayout, Shader, TexelEncoding, VulkanContext,
+};
+use ash::{version::DeviceV1_0, vk};
+use snafu::{ensure, OptionExt, ResultExt, Snafu};
+use std::sync::Arc;
+
+type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility = "pub(crate)")]
+pub enum Error {
+    #[snafu(display("Failed to create compute shader {}: {}", path, source))]
+    CreateComputeShader {
+        source: crate::vulkan::shader::Error,
+        path: String,
+    },
+
+    #[snafu(display("Failed to create compute descriptor set layout: {}", source))]
+    CreateComputeDescriptorSetLayout {
+        source: crate::vulkan::descriptor_set_layout::Error,
+    },
+
+    #[snafu(display("Failed to create compute descriptor pool: {}", source))]
+    CreateComputeDescriptorPool {
+        source: crate::vulkan::descriptor_pool::Error,
+    },
+
+    #[snafu(display("Failed to allocate compute descriptor sets: {}", source))]
+    AllocateComputeDescriptorSets {
+        source: crate::vulkan::descriptor_pool::Error,
+    },
+
+    #[snafu(display("Failed to create compute pipeline layout: {}", source))]
+    CreateComputePipelineLayout {
+        source: crate::vulkan::pipeline_layout::Error,
+    },
+
+    #[snafu(display("Failed to create storage view of mip level {}: {}", mip_level, source))]
+    CreateStorageView {
+        source: crate::vulkan::image_view::Error,
+        mip_level: u32,
+    },
+
+    #[snafu(display("Failed to transition baked cubemap: {}", source))]
+    TransitionBakedCubemap {
+        source: crate::vulkan::texture::Error,
+    },
+
+    #[snafu(display("Failed to dispatch compute bake: {}", source))]
+    DispatchComputeBake {
+        source: crate::vulkan::command_pool::Error,
+    },
+
+    #[snafu(display("Failed to read back cubemap: {}", source))]
+    ReadBackCubemap {
+        source: crate::vulkan::texture::Error,
+    },
+
+    #[snafu(display("Only cubemaps with the same size, format and mip levels can be compared"))]
+    MismatchedCubemaps,
+
+    #[snafu(display("Cubemaps in format {:?} can't be compared", format))]
+    IncomparableFormat { format: vk::Format },
+}
+
+// How the IBL generators fill their cubemaps
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BakeMethod {
+    // Renders each face and mip level into an offscreen target and copies it into the cubemap
+    #[default]
+    Raster,
+
+    // Writes every face of a mip level at once through a storage image view of the cubemap
+    Compute,
+}
+
+// Compute bake shaders use 8x8 workgroups and one workgroup layer per face
+const WORKGROUP_SIZE: u32 = 8;
+
+// Runs a compute shader once per texel of every face and mip level of `output`.
+// The shader samples `source` at binding 0 and writes the level at binding 1,
+// taking the push constants returned for each mip level.
+pub(crate) fn dispatch_cube_compute<F>(
+    context: Arc<VulkanContext>,
+    command_pool: &CommandPool,
+    source: &Cubemap,
+    output: &Cubemap,
+    shader_path: &str,
+    push_constant_size: u32,
+    push_constants: F,
+) -> Result<()>
+where
+    F: Fn(u32) -> Vec<u8>,
+{
+    let mip_levels = output.description.mip_levels;
+
+    let sampler_binding = vk::DescriptorSetLayoutBinding::builder()
+        .binding(0)
+        .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+        .descriptor_count(1)
+        .stage_flags(vk::ShaderStageFlags::COMPUTE)
+        .build();
+    let storage_binding = vk::DescriptorSetLayoutBinding::builder()
+        .binding(1)
+        .descriptor_type(vk::DescriptorType::STORAGE_IMAGE)
+        .descriptor_count(1)
+        .stage_flags(vk::ShaderStageFlags::COMPUTE)
+        .build();
+    let bindings = [sampler_binding, storage_binding];
+    let layout_create_info = vk::DescriptorSetLayoutCreateInfo::builder()
+        .bindings(&bindings)
+        .build();
+    let descriptor_set_layout = DescriptorSetLayout::new(context.clone(), layout_create_info)
+        .context(CreateComputeDescriptorSetLayout {})?;
+
+    let pool_sizes = [
+        vk::DescriptorPoolSize {
+            ty: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            descriptor_count: mip_levels,
+        },
+        vk::DescriptorPoolSize {
+            ty: vk::DescriptorType::STORAGE_IMAGE,
+            descriptor_count: mip_levels,
+        },
+    ];
+    let pool_info = vk::DescriptorPoolCreateInfo::builder()
+        .pool_sizes(&pool_sizes)
+        .max_sets(mip_levels)
+        .build();
+    let descriptor_pool =
+        DescriptorPool::new(context.clone(), pool_info).context(CreateComputeDescriptorPool {})?;
+    let descriptor_sets = descriptor_pool
+        .allocate_descriptor_sets(descriptor_set_layout.layout(), mip_levels)
+        .context(AllocateComputeDescriptorSets {})?;
+
+    // Each mip level gets its own view, since storage image views only cover a single level
+    let storage_views = (0..mip_levels)
+        .map(|mip_level| {
+            let create_info = vk::ImageViewCreateInfo::builder()
+                .image(output.texture.image())
+                .view_type(vk::ImageViewType::TYPE_2D_ARRAY)
+                .format(output.description.format)
+                .subresource_range(vk::ImageSubresourceRange {
+                    aspect_mask: vk::ImageAspectFlags::COLOR,
+                    base_mip_level: mip_level,
+                    level_count: 1,
+                    base_array_layer: 0,
+                    layer_count: 6,
+                })
+                .build();
+            ImageView::new(context.clone(), create_info).context(CreateStorageView { mip_level })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let device = context.logical_device().logical_device();
+    for (descriptor_set, storage_view) in descriptor_sets.iter().zip(storage_views.iter()) {
+        let source_infos = [vk::DescriptorImageInfo::builder()
+            .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+            .image_view(source.view.view())
+            .sampler(source.sampler.sampler())
+            .build()];
+        let storage_infos = [vk::DescriptorImageInfo::builder()
+            .image_layout(vk::ImageLayout::GENERAL)
+            .image_view(storage_view.view())
+            .build()];
+        let descriptor_writes = [
+            vk::WriteDescriptorSet::builder()
+                .dst_set(*descriptor_set)
+                .dst_binding(0)
+                .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .image_info(&source_infos)
+                .build(),
+            vk::WriteDescriptorSet::builder()
+                .dst_set(*descriptor_set)
+                .dst_binding(1)
+                .descriptor_type(vk::DescriptorType::STORAGE_IMAGE)
+                .image_info(&storage_infos)
+                .build(),
+        ];
+        unsafe { device.update_descriptor_sets(&descriptor_writes, &[]) }
+    }
+
+    let descriptor_set_layouts = [descriptor_set_layout.layout()];
+    let push_constant_ranges = [vk::PushConstantRange::builder()
+        .stage_flags(vk::ShaderStageFlags::COMPUTE)
+        .size(push_constant_size)
+        .build()];
+    let pipeline_layout_create_info = vk::PipelineLayoutCreateInfo::builder()
+        .set_layouts(&descriptor_set_layouts)
+        .push_constant_ranges(&push_constant_ranges)
+        .build();
+    let pipeline_layout = PipelineLayout::new(context.clone(), pipeline_layout_create_info)
+        .context(CreateComputePipelineLayout {})?;
+
+    let shader = Shader::from_file(
+        context.clone(),
+        shader_path,
+        vk::ShaderStageFlags::COMPUTE,
+        Shader::SHADER_ENTRY_POINT_NAME,
+    )
+    .context(CreateComputeShader { path: shader_path })?;
+    let pipeline_create_info = vk::ComputePipelineCreateInfo::builder()
+        .stage(shader.state_info())
+        .layout(pipeline_layout.layout())
+        .build();
+    let pipeline = ComputePipeline::new(context.clone(), pipeline_create_info, pipeline_layout);
+
+    let transition = ImageLayoutTransition {
+        old_layout: vk::ImageLayout::UNDEFINED,
+        new_layout: vk::ImageLayout::GENERAL,
+        src_access_mask: vk::AccessFlags::empty(),
+        dst_access_mask: vk::AccessFlags::SHADER_WRITE,
+        src_stage_mask: vk::PipelineStageFlags::TOP_OF_PIPE,
+        dst_stage_mask: vk::PipelineStageFlags::COMPUTE_SHADER,
+    };
+    output
+        .transition(command_pool, &transition)
+        .context(TransitionBakedCubemap {})?;
+
+    // Mip levels are written independently, so they all go in a single submission
+    command_pool
+        .execute_command_once(context.graphics_queue(), |command_buffer| unsafe {
+            device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::COMPUTE,
+                pipeline.pipeline(),
+            );
+            for (mip_level, descriptor_set) in descriptor_sets.iter().enumerate() {
+                let mip_level = mip_level as u32;
+                device.cmd_bind_descriptor_sets(
+                    command_buffer,
+                    vk::PipelineBindPoint::COMPUTE,
+                    pipeline.layout(),
+                    0,
+                    &[*descriptor_set],
+                    &[],
+                );
+                device.cmd_push_constants(
+                    command_buffer,
+                    pipeline.layout(),
+                    vk::ShaderStageFlags::COMPUTE,
+                    0,
+                    &push_constants(mip_level),
+                );
+                let dimension = (output.description.width >> mip_level).max(1);
+                let groups = dimension.div_ceil(WORKGROUP_SIZE);
+                device.cmd_dispatch(command_buffer, groups, groups, 6);
+            }
+        })
+        .context(DispatchComputeBake {})?;
+
+    let transition = ImageLayoutTransition {
+        old_layout: vk::ImageLayout::GENERAL,
+        new_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        src_access_mask: vk::AccessFlags::SHADER_WRITE,
+        dst_access_mask: vk::AccessFlags::SHADER_READ,
+        src_stage_mask: vk::PipelineStageFlags::COMPUTE_SHADER,
+        dst_stage_mask: vk::PipelineStageFlags::FRAGMENT_SHADER,
+    };
+    output
+        .transition(command_pool, &transition)
+        .context(TransitionBakedCubemap {})
+}
+
+// The largest difference between two texels of the cubemaps, over every face and mip level.
+// Differences are relative to the larger of the two values, or absolute below one,
+// so bright HDR texels and dark texels are held to the same tolerance.
+pub fn cubemap_difference(
+    command_pool: &CommandPool,
+    first: &Cubemap,
+    second: &Cubemap,
+) -> Result<f32> {
+    let (a, b) = (&first.description, &second.description);
+    ensure!(
+        a.width == b.width && a.format == b.format && a.mip_levels == b.mip_levels,
+        MismatchedCubemaps
+    );
+    let encoding = TexelEncoding::of(a.format).context(IncomparableFormat { format: a.format })?;
+
+    let layout = vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL;
+    let first = first
+        .texture
+        .download(command_pool, a, layout)
+        .context(ReadBackCubemap {})?;
+    let second = second
+        .texture
+        .download(command_pool, b, layout)
+        .context(ReadBackCubemap {})?;
+
+    let difference = encoding
+        .decode_channels(&first)
+        .iter()
+        .zip(encoding.decode_channels(&second).iter())
+        .map(|(a, b)| (a - b).abs() / a.abs().max(b.abs()).max(1.0))
+        .fold(0.0, f32::max);
+    Ok(difference)
+}
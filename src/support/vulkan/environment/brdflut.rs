@@ -1,10 +1,13 @@
 use crate::vulkan::{
-    CommandPool, DescriptorSetLayout, Framebuffer, GraphicsPipeline, ImageView, PipelineLayout,
-    RenderPass, Sampler, Shader, Texture, VulkanContext,
+    BrdflutSettings, CommandPool, DescriptorSetLayout, Framebuffer, GraphicsPipeline, ImageView,
+    PipelineLayout, RenderPass, Sampler, Shader, Texture, TextureDescription, UploadManager,
+    VulkanContext,
 };
 use ash::{version::DeviceV1_0, vk};
 use std::{ffi::CString, sync::Arc};
 
+type Result<T, E = crate::vulkan::texture::Error> = std::result::Result<T, E>;
+
 pub struct Brdflut {
     pub texture: Texture,
     pub view: ImageView,
@@ -13,8 +16,16 @@ pub struct Brdflut {
 
 impl Brdflut {
     pub fn new(context: Arc<VulkanContext>, command_pool: &CommandPool) -> Self {
-        let dimension = 512;
-        let format = vk::Format::R16G16_SFLOAT;
+        Self::with_settings(context, command_pool, &BrdflutSettings::default())
+    }
+
+    pub fn with_settings(
+        context: Arc<VulkanContext>,
+        command_pool: &CommandPool,
+        settings: &BrdflutSettings,
+    ) -> Self {
+        let dimension = settings.size;
+        let format = settings.format;
         let texture = Self::create_texture(context.clone(), dimension, format);
         let view = Self::create_image_view(context.clone(), &texture, format);
         let sampler = Self::create_sampler(context.clone());
@@ -53,7 +64,7 @@ impl Brdflut {
 
         let device = context.logical_device().logical_device();
 
-        let pipeline = Self::create_pipeline(context.clone(), &render_pass);
+        let pipeline = Self::create_pipeline(context.clone(), &render_pass, settings.sample_count);
 
         command_pool
             .execute_command_once(context.graphics_queue(), |command_buffer| unsafe {
@@ -94,6 +105,29 @@ impl Brdflut {
         }
     }
 
+    // Uploads a previously rendered lookup table
+    pub fn from_description(
+        context: Arc<VulkanContext>,
+        upload_manager: &mut UploadManager,
+        description: &TextureDescription,
+    ) -> Result<Self> {
+        let texture = Texture::from_description(
+            context.clone(),
+            description,
+            vk::ImageUsageFlags::TRANSFER_SRC
+                | vk::ImageUsageFlags::TRANSFER_DST
+                | vk::ImageUsageFlags::SAMPLED,
+        )?;
+        texture.upload_texture_data(upload_manager, description)?;
+        let view = texture.create_view(description)?;
+        let sampler = Self::create_sampler(context);
+        Ok(Self {
+            texture,
+            view,
+            sampler,
+        })
+    }
+
     fn create_texture(context: Arc<VulkanContext>, dimension: u32, format: vk::Format) -> Texture {
         let image_create_info = vk::ImageCreateInfo::builder()
             .image_type(vk::ImageType::TYPE_2D)
@@ -107,7 +141,11 @@ impl Brdflut {
             .format(format)
             .tiling(vk::ImageTiling::OPTIMAL)
             .initial_layout(vk::ImageLayout::UNDEFINED)
-            .usage(vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED)
+            .usage(
+                vk::ImageUsageFlags::COLOR_ATTACHMENT
+                    | vk::ImageUsageFlags::SAMPLED
+                    | vk::ImageUsageFlags::TRANSFER_SRC,
+            )
             .sharing_mode(vk::SharingMode::EXCLUSIVE)
             .samples(vk::SampleCountFlags::TYPE_1)
             .flags(vk::ImageCreateFlags::empty())
@@ -250,7 +288,11 @@ impl Brdflut {
         (vertex_shader, fragment_shader, shader_entry_point_name)
     }
 
-    fn create_pipeline(context: Arc<VulkanContext>, render_pass: &RenderPass) -> GraphicsPipeline {
+    fn create_pipeline(
+        context: Arc<VulkanContext>,
+        render_pass: &RenderPass,
+        sample_count: u32,
+    ) -> GraphicsPipeline {
         let layout_create_info = vk::DescriptorSetLayoutCreateInfo::builder()
             .bindings(&[])
             .build();
@@ -318,7 +360,21 @@ impl Brdflut {
 
         let (vertex_shader, fragment_shader, _shader_entry_point_name) =
             Self::create_shaders(context.clone());
-        let shader_state_info = [vertex_shader.state_info(), fragment_shader.state_info()];
+
+        // The sample count is the fragment shader's NUM_SAMPLES specialization constant
+        let specialization_entries = [vk::SpecializationMapEntry::builder()
+            .constant_id(0)
+            .offset(0)
+            .size(std::mem::size_of::<u32>())
+            .build()];
+        let specialization_data = sample_count.to_ne_bytes();
+        let specialization_info = vk::SpecializationInfo::builder()
+            .map_entries(&specialization_entries)
+            .data(&specialization_data)
+            .build();
+        let mut fragment_state_info = fragment_shader.state_info();
+        fragment_state_info.p_specialization_info = &specialization_info;
+        let shader_state_info = [vertex_shader.state_info(), fragment_state_info];
 
         let pipeline_create_info = vk::GraphicsPipelineCreateInfo::builder()
             .stages(&shader_state_info)
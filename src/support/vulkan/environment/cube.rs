@@ -1,5 +1,6 @@
-use crate::vulkan::{CommandPool, GeometryBuffer};
+use crate::vulkan::{GeometryBuffer, UploadManager, Vertex};
 use ash::{version::DeviceV1_0, vk};
+use nalgebra_glm as glm;
 
 #[rustfmt::skip]
 pub const VERTICES: &[f32; 108] =
@@ -53,41 +54,66 @@ pub const VERTICES: &[f32; 108] =
        -1.0,  1.0, -1.0
     ];
 
+#[repr(C)]
+#[derive(Vertex, Debug, Clone, Copy)]
+pub struct CubeVertex {
+    pub position: glm::Vec3,
+}
+
 pub struct UnitCube {
     pub buffers: GeometryBuffer,
 }
 
 impl UnitCube {
-    pub fn new(command_pool: &CommandPool) -> Self {
+    pub fn new(upload_manager: &mut UploadManager) -> Self {
+        let vertices = VERTICES
+            .chunks(3)
+            .map(|position| CubeVertex {
+                position: glm::vec3(position[0], position[1], position[2]),
+            })
+            .collect::<Vec<_>>();
+
         Self {
-            buffers: GeometryBuffer::new(command_pool, VERTICES, None),
+            buffers: GeometryBuffer::new(upload_manager, &vertices, None),
         }
     }
 
-    pub fn vertex_attributes() -> [vk::VertexInputAttributeDescription; 1] {
-        let position_description = vk::VertexInputAttributeDescription::builder()
-            .binding(0)
-            .location(0)
-            .format(vk::Format::R32G32B32_SFLOAT)
-            .offset(0)
-            .build();
-
-        [position_description]
-    }
-
-    pub fn vertex_input_descriptions() -> [vk::VertexInputBindingDescription; 1] {
-        let vertex_input_binding_description = vk::VertexInputBindingDescription::builder()
-            .binding(0)
-            .stride((3 * std::mem::size_of::<f32>()) as _)
-            .input_rate(vk::VertexInputRate::VERTEX)
-            .build();
-        [vertex_input_binding_description]
-    }
-
     pub fn draw(&self, device: &ash::Device, command_buffer: vk::CommandBuffer) {
         self.buffers.bind(device, command_buffer);
         unsafe {
-            device.cmd_draw(command_buffer, VERTICES.len() as _, 1, 0, 0);
+            device.cmd_draw(command_buffer, self.buffers.number_of_vertices, 1, 0, 0);
         }
     }
 }
+
+// The direction through a point of a face, in the layer order and orientation Vulkan
+// samples cubemaps with. `s` and `t` run from -1 to 1, left to right and top to bottom.
+pub fn cube_face_direction(face: usize, s: f32, t: f32) -> glm::Vec3 {
+    let direction = match face {
+        0 => glm::vec3(1.0, -t, -s),
+        1 => glm::vec3(-1.0, -t, s),
+        2 => glm::vec3(s, 1.0, t),
+        3 => glm::vec3(s, -1.0, -t),
+        4 => glm::vec3(s, -t, 1.0),
+        _ => glm::vec3(-s, -t, -1.0),
+    };
+    glm::normalize(&direction)
+}
+
+// The view from a point through the center of each face, in the same layer order.
+// With a square projection and a 90 degree field of view, each view covers its whole face.
+pub fn cube_face_views(position: &glm::Vec3) -> [glm::Mat4; 6] {
+    let targets = [
+        (glm::vec3(1.0, 0.0, 0.0), glm::vec3(0.0, -1.0, 0.0)),
+        (glm::vec3(-1.0, 0.0, 0.0), glm::vec3(0.0, -1.0, 0.0)),
+        (glm::vec3(0.0, 1.0, 0.0), glm::vec3(0.0, 0.0, 1.0)),
+        (glm::vec3(0.0, -1.0, 0.0), glm::vec3(0.0, 0.0, -1.0)),
+        (glm::vec3(0.0, 0.0, 1.0), glm::vec3(0.0, -1.0, 0.0)),
+        (glm::vec3(0.0, 0.0, -1.0), glm::vec3(0.0, -1.0, 0.0)),
+    ];
+    let mut views = [glm::Mat4::identity(); 6];
+    for (view, (direction, up)) in views.iter_mut().zip(targets.iter()) {
+        *view = glm::look_at(position, &(position + direction), up);
+    }
+    views
+}
@@ -0,0 +1,224 @@
+use crate::vulkan::{
+    cube_face_direction, decode_base_level, CommandPool, Cubemap, EnvironmentSource,
+    TextureDescription,
+};
+use ash::vk;
+use nalgebra_glm as glm;
+use snafu::{ensure, ResultExt, Snafu};
+use std::f32::consts::PI;
+
+type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility = "pub(crate)")]
+pub enum Error {
+    #[snafu(display("Failed to load environment for projection: {}", source))]
+    LoadProjectedEnvironment {
+        source: crate::vulkan::environment::source::Error,
+    },
+
+    #[snafu(display("Failed to read back cubemap for projection: {}", source))]
+    ReadBackProjectedCubemap {
+        source: crate::vulkan::texture::Error,
+    },
+
+    #[snafu(display("Failed to decode environment for projection: {}", source))]
+    DecodeProjectedEnvironment {
+        source: crate::vulkan::conversion::Error,
+    },
+
+    #[snafu(display(
+        "A {}x{} image with {} layers is neither a cubemap nor an equirectangular panorama",
+        width,
+        height,
+        layers
+    ))]
+    UnprojectableLayout {
+        width: u32,
+        height: u32,
+        layers: u32,
+    },
+}
+
+// Third order (nine coefficient) spherical harmonics of an environment's RGB radiance,
+// or of the irradiance it produces. Coefficients follow the real basis order
+// Y00, Y1-1, Y10, Y11, Y2-2, Y2-1, Y20, Y21, Y22.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SphericalHarmonics {
+    pub coefficients: [glm::Vec3; SphericalHarmonics::COEFFICIENT_COUNT],
+}
+
+impl Default for SphericalHarmonics {
+    fn default() -> Self {
+        Self {
+            coefficients: [glm::Vec3::zeros(); Self::COEFFICIENT_COUNT],
+        }
+    }
+}
+
+impl SphericalHarmonics {
+    pub const COEFFICIENT_COUNT: usize = 9;
+
+    // Projects the radiance of a six layer cube or an equirectangular panorama.
+    // Directions match the ones the environment cubemaps are sampled with.
+    pub fn from_description(description: &TextureDescription) -> Result<Self> {
+        let texels = decode_base_level(description).context(DecodeProjectedEnvironment {})?;
+        let (width, height) = (description.width, description.height);
+        let mut harmonics = Self::default();
+
+        if description.cubemap {
+            ensure!(
+                description.layers == 6 && width == height,
+                UnprojectableLayout {
+                    width,
+                    height,
+                    layers: description.layers
+                }
+            );
+            let size = width as usize;
+            for (index, texel) in texels.iter().enumerate() {
+                let face = index / (size * size);
+                let (x, y) = (index % size, index / size % size);
+                let s = (x as f32 + 0.5) / size as f32 * 2.0 - 1.0;
+                let t = (y as f32 + 0.5) / size as f32 * 2.0 - 1.0;
+                let direction = cube_face_direction(face, s, t);
+                harmonics.accumulate(&direction, texel, cube_texel_solid_angle(s, t, size));
+            }
+        } else {
+            ensure!(
+                description.layers == 1,
+                UnprojectableLayout {
+                    width,
+                    height,
+                    layers: description.layers
+                }
+            );
+            // Rows near the poles cover less of the sphere than rows near the horizon
+            let texel_area = (2.0 * PI / width as f32) * (PI / height as f32);
+            for (index, texel) in texels.iter().enumerate() {
+                let (x, y) = (index as u32 % width, index as u32 / width);
+                let u = (x as f32 + 0.5) / width as f32;
+                let v = (y as f32 + 0.5) / height as f32;
+                let longitude = (u - 0.5) * 2.0 * PI;
+                let latitude = (v - 0.5) * PI;
+                let direction = glm::vec3(
+                    latitude.cos() * longitude.cos(),
+                    latitude.sin(),
+                    latitude.cos() * longitude.sin(),
+                );
+                harmonics.accumulate(&direction, texel, latitude.cos() * texel_area);
+            }
+        }
+
+        Ok(harmonics)
+    }
+
+    pub fn from_source(source: &EnvironmentSource) -> Result<Self> {
+        let description = source
+            .load_description()
+            .context(LoadProjectedEnvironment {})?;
+        Self::from_description(&description)
+    }
+
+    // Reads back the base level of a cubemap that's only on the GPU, such as a baked one
+    pub fn from_cubemap(command_pool: &CommandPool, cubemap: &Cubemap) -> Result<Self> {
+        let description = TextureDescription {
+            mip_levels: 1,
+            ..cubemap.description.clone()
+        };
+        let pixels = cubemap
+            .texture
+            .download(
+                command_pool,
+                &description,
+                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            )
+            .context(ReadBackProjectedCubemap {})?;
+        Self::from_description(&TextureDescription {
+            pixels,
+            stored_mip_levels: 1,
+            ..description
+        })
+    }
+
+    // Convolves radiance with a clamped cosine lobe, giving the irradiance divided by pi.
+    // This is the value `IrradianceMap` stores, ready to be multiplied by the diffuse color.
+    pub fn irradiance(&self) -> Self {
+        const BAND_SCALES: [f32; 3] = [1.0, 2.0 / 3.0, 1.0 / 4.0];
+        let mut irradiance = *self;
+        for (index, coefficient) in irradiance.coefficients.iter_mut().enumerate() {
+            *coefficient *= BAND_SCALES[band(index)];
+        }
+        irradiance
+    }
+
+    pub fn evaluate(&self, direction: &glm::Vec3) -> glm::Vec3 {
+        basis(&glm::normalize(direction))
+            .iter()
+            .zip(self.coefficients.iter())
+            .fold(glm::Vec3::zeros(), |sum, (basis, coefficient)| {
+                sum + coefficient * *basis
+            })
+    }
+
+    // Blending coefficients blends the lighting they describe
+    pub fn lerp(&self, other: &Self, amount: f32) -> Self {
+        let mut blended = *self;
+        for (coefficient, other) in blended
+            .coefficients
+            .iter_mut()
+            .zip(other.coefficients.iter())
+        {
+            *coefficient = glm::lerp(coefficient, other, amount);
+        }
+        blended
+    }
+
+    // std140 arrays pad every element to a vec4
+    pub fn uniform_coefficients(&self) -> [glm::Vec4; SphericalHarmonics::COEFFICIENT_COUNT] {
+        let mut coefficients = [glm::Vec4::zeros(); Self::COEFFICIENT_COUNT];
+        for (padded, coefficient) in coefficients.iter_mut().zip(self.coefficients.iter()) {
+            *padded = glm::vec3_to_vec4(coefficient);
+        }
+        coefficients
+    }
+
+    fn accumulate(&mut self, direction: &glm::Vec3, texel: &[f32; 4], solid_angle: f32) {
+        let radiance = glm::vec3(texel[0], texel[1], texel[2]) * solid_angle;
+        for (coefficient, basis) in self.coefficients.iter_mut().zip(basis(direction).iter()) {
+            *coefficient += radiance * *basis;
+        }
+    }
+}
+
+fn band(index: usize) -> usize {
+    match index {
+        0 => 0,
+        1..=3 => 1,
+        _ => 2,
+    }
+}
+
+fn basis(direction: &glm::Vec3) -> [f32; SphericalHarmonics::COEFFICIENT_COUNT] {
+    let (x, y, z) = (direction.x, direction.y, direction.z);
+    [
+        0.282_095,
+        0.488_603 * y,
+        0.488_603 * z,
+        0.488_603 * x,
+        1.092_548 * x * y,
+        1.092_548 * y * z,
+        0.315_392 * (3.0 * z * z - 1.0),
+        1.092_548 * x * z,
+        0.546_274 * (x * x - y * y),
+    ]
+}
+
+// The exact solid angle of the texel centered on (s, t), so the weights sum to 4 pi
+fn cube_texel_solid_angle(s: f32, t: f32, size: usize) -> f32 {
+    let half_texel = 1.0 / size as f32;
+    let area = |x: f32, y: f32| (x * y).atan2((x * x + y * y + 1.0).sqrt());
+    let (x0, x1) = (s - half_texel, s + half_texel);
+    let (y0, y1) = (t - half_texel, t + half_texel);
+    area(x0, y0) - area(x0, y1) - area(x1, y0) + area(x1, y1)
+}
@@ -1,15 +1,15 @@
 use crate::{
     byte_slice_from,
     vulkan::{
-        CommandPool, Cubemap, DescriptorPool, DescriptorSetLayout, Framebuffer,
-        ImageLayoutTransition, Offscreen, RenderPass, RenderPipeline,
-        RenderPipelineSettingsBuilder, ShaderCache, ShaderPathSetBuilder, TextureBundle,
-        TextureDescription, UnitCube, VulkanContext,
+        CommandPool, CubeVertex, Cubemap, DescriptorPool, DescriptorSetLayout, EnvironmentSettings,
+        Framebuffer, ImageLayoutTransition, Offscreen, RenderPass, RenderPipeline,
+        RenderPipelineSettingsBuilder, SamplerCache, ShaderCache, ShaderPathSetBuilder,
+        TextureBundle, TextureDescription, UnitCube, UploadManager, Vertex, VulkanContext,
     },
 };
 use ash::{version::DeviceV1_0, vk};
 use nalgebra_glm as glm;
-use snafu::Snafu;
+use snafu::{ResultExt, Snafu};
 use std::sync::Arc;
 
 type Result<T, E = Error> = std::result::Result<T, E>;
@@ -19,7 +19,7 @@ type Result<T, E = Error> = std::result::Result<T, E>;
 pub enum Error {
     #[snafu(display("Failed to create render pipeline: {}", source))]
     CreateRenderPipeline {
-        source: crate::vulkan::shader::Error,
+        source: crate::vulkan::pipeline::Error,
     },
 
     #[snafu(display("Failed to create shader: {}", source))]
@@ -46,16 +46,42 @@ impl HdrCubemap {
     pub fn new(
         context: Arc<VulkanContext>,
         command_pool: &CommandPool,
+        upload_manager: &mut UploadManager,
+        sampler_cache: &mut SamplerCache,
         path: &str,
         shader_cache: &mut ShaderCache,
     ) -> Result<Self> {
         let description = TextureDescription::from_hdr(path).unwrap();
+        Self::from_equirectangular(
+            context,
+            command_pool,
+            upload_manager,
+            sampler_cache,
+            &description,
+            shader_cache,
+            &EnvironmentSettings::default(),
+        )
+    }
+
+    // Projects any equirectangular image onto the faces of a floating point cubemap.
+    // Each face covers a quarter of the image's width, up to the settings' cap.
+    pub fn from_equirectangular(
+        context: Arc<VulkanContext>,
+        command_pool: &CommandPool,
+        upload_manager: &mut UploadManager,
+        sampler_cache: &mut SamplerCache,
+        description: &TextureDescription,
+        shader_cache: &mut ShaderCache,
+        settings: &EnvironmentSettings,
+    ) -> Result<Self> {
         let hdr_texture_bundle =
-            TextureBundle::new(context.clone(), &command_pool, &description).unwrap();
+            TextureBundle::new(context.clone(), upload_manager, sampler_cache, description)
+                .unwrap();
 
-        let dimension = description.width;
-        let format = vk::Format::R32G32B32A32_SFLOAT;
-        let output_cubemap = Cubemap::new(context.clone(), dimension, format).unwrap();
+        let dimension = settings.face_size(description.width);
+        let format = settings.format;
+        let output_cubemap =
+            Cubemap::new(context.clone(), sampler_cache, dimension, format).unwrap();
 
         let render_pass = Arc::new(Self::create_render_pass(context.clone(), format));
 
@@ -93,8 +119,8 @@ impl HdrCubemap {
 
         Self::update_descriptor_set(context.clone(), descriptor_set, &hdr_texture_bundle);
 
-        let descriptions = UnitCube::vertex_input_descriptions();
-        let attributes = UnitCube::vertex_attributes();
+        let descriptions = [CubeVertex::binding_description(0)];
+        let attributes = CubeVertex::attribute_descriptions(0, 0);
         let vertex_state_info = vk::PipelineVertexInputStateCreateInfo::builder()
             .vertex_binding_descriptions(&descriptions)
             .vertex_attribute_descriptions(&attributes)
@@ -124,7 +150,8 @@ impl HdrCubemap {
             .build()
             .expect("Failed to create render pipeline settings!");
 
-        let render_pipeline = RenderPipeline::new(context.clone(), settings);
+        let render_pipeline =
+            RenderPipeline::new(context.clone(), settings).context(CreateRenderPipeline {})?;
 
         let clear_values = [vk::ClearValue {
             color: vk::ClearColorValue {
@@ -211,7 +238,8 @@ impl HdrCubemap {
         };
         let scissors = [scissor];
 
-        let unit_cube = UnitCube::new(command_pool);
+        let unit_cube = UnitCube::new(upload_manager);
+        upload_manager.submit().unwrap();
 
         for mip_level in 0..output_cubemap.description.mip_levels {
             for (face, matrix) in matrices.iter().enumerate() {
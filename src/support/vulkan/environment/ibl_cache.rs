@@ -0,0 +1,423 @@
+use crate::vulkan::{
+    write_ktx2, BakeMethod, Brdflut, CommandPool, Cubemap, EnvironmentSet, EnvironmentSource,
+    IblSettings, IrradianceMap, PrefilterMap, SamplerCache, ShaderCache, Texture,
+    TextureDescription, UploadManager, VulkanContext,
+};
+use ash::vk;
+use log::info;
+use snafu::{ResultExt, Snafu};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::UNIX_EPOCH,
+};
+
+type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility = "pub(crate)")]
+pub enum Error {
+    #[snafu(display("Failed to read environment source {}: {}", path, source))]
+    ReadEnvironmentSource {
+        source: std::io::Error,
+        path: String,
+    },
+
+    #[snafu(display("Failed to load environment: {}", source))]
+    LoadEnvironment {
+        source: crate::vulkan::environment::source::Error,
+    },
+
+    #[snafu(display("Failed to read back the baked {} map: {}", name, source))]
+    DownloadBakedMap {
+        source: crate::vulkan::texture::Error,
+        name: &'static str,
+    },
+
+    #[snafu(display("Failed to convert the baked {} map: {}", name, source))]
+    ConvertBakedMap {
+        source: crate::vulkan::texture::Error,
+        name: &'static str,
+    },
+
+    #[snafu(display("Failed to encode the baked {} map: {}", name, source))]
+    EncodeBakedMap {
+        source: crate::vulkan::ktx2::Error,
+        name: &'static str,
+    },
+
+    #[snafu(display("Failed to create IBL cache directory {}: {}", path, source))]
+    CreateCacheDirectory {
+        source: std::io::Error,
+        path: String,
+    },
+
+    #[snafu(display("Failed to write IBL cache file {}: {}", path, source))]
+    WriteCacheFile {
+        source: std::io::Error,
+        path: String,
+    },
+
+    #[snafu(display("Failed to load IBL cache file {}: {}", path, source))]
+    LoadCacheFile {
+        source: crate::vulkan::texture::Error,
+        path: String,
+    },
+
+    #[snafu(display("Failed to submit IBL cache upload: {}", source))]
+    SubmitCacheUpload {
+        source: crate::vulkan::upload_manager::Error,
+    },
+}
+
+// Bumped whenever the bake shaders change in a way that invalidates cached maps
+const BAKE_VERSION: u32 = 1;
+
+// The environment cubemap is stored at half precision to keep cache files small.
+// Face and cross sources are sRGB, so they are decoded to linear values first.
+const CACHED_ENVIRONMENT_FORMAT: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
+
+// Everything image based lighting needs, either baked on the GPU or loaded from the cache
+pub struct EnvironmentMaps {
+    pub brdflut: Brdflut,
+    pub environment: Cubemap,
+    pub irradiance: IrradianceMap,
+    pub prefilter: PrefilterMap,
+}
+
+// Baked maps are stored as KTX2 files named after a hash of the environment set's files
+// and the bake settings, so editing either one results in a fresh bake.
+// Files are identified by their path, size and modification time unless contents are hashed.
+pub struct IblCache {
+    directory: PathBuf,
+    settings: IblSettings,
+    hash_contents: bool,
+}
+
+impl IblCache {
+    pub const DEFAULT_DIRECTORY: &'static str = "cache/ibl";
+
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+            settings: IblSettings::default(),
+            hash_contents: false,
+        }
+    }
+
+    pub fn with_settings(mut self, settings: IblSettings) -> Self {
+        self.settings = settings;
+        self
+    }
+
+    // Reads every source file when computing keys, so that files that were replaced
+    // without changing their size or modification time are still detected
+    pub fn with_content_hashing(mut self, hash_contents: bool) -> Self {
+        self.hash_contents = hash_contents;
+        self
+    }
+
+    // Both methods produce the same maps within a small tolerance, so they share cache files
+    pub fn with_bake_methods(mut self, irradiance: BakeMethod, prefilter: BakeMethod) -> Self {
+        self.settings.irradiance.method = irradiance;
+        self.settings.prefilter.method = prefilter;
+        self
+    }
+
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    pub fn settings(&self) -> &IblSettings {
+        &self.settings
+    }
+
+    pub fn key(&self, set: &EnvironmentSet) -> Result<u64> {
+        let mut hash = fnv1a(FNV_OFFSET_BASIS, self.bake_parameters().as_bytes());
+        let roles = [
+            ("background", &set.background),
+            ("irradiance", &set.irradiance),
+            ("reflection", &set.reflection),
+        ];
+        for (role, source) in roles.iter() {
+            let (kind, paths) = match source {
+                EnvironmentSource::Faces(faces) => ("faces", faces.ordered_faces().collect()),
+                EnvironmentSource::Cross(path) => ("cross", vec![path.clone()]),
+                EnvironmentSource::Equirectangular(path) => ("equirectangular", vec![path.clone()]),
+                EnvironmentSource::Hdr(path) => ("hdr", vec![path.clone()]),
+            };
+            hash = fnv1a(hash, role.as_bytes());
+            hash = fnv1a(hash, kind.as_bytes());
+            for path in paths {
+                let metadata =
+                    fs::metadata(&path).context(ReadEnvironmentSource { path: path.clone() })?;
+                let modified = metadata
+                    .modified()
+                    .ok()
+                    .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                    .unwrap_or_default();
+                hash = fnv1a(hash, path.as_bytes());
+                hash = fnv1a(hash, &metadata.len().to_le_bytes());
+                hash = fnv1a(hash, &modified.as_nanos().to_le_bytes());
+                if self.hash_contents {
+                    let bytes = fs::read(&path).context(ReadEnvironmentSource { path })?;
+                    hash = fnv1a(hash, &bytes);
+                }
+            }
+        }
+        Ok(hash)
+    }
+
+    // Loads the maps baked from this source by an earlier run, or bakes and caches them
+    pub fn load_or_bake(
+        &self,
+        context: Arc<VulkanContext>,
+        command_pool: &CommandPool,
+        upload_manager: &mut UploadManager,
+        sampler_cache: &mut SamplerCache,
+        shader_cache: &mut ShaderCache,
+        set: &EnvironmentSet,
+    ) -> Result<EnvironmentMaps> {
+        let key = self.key(set)?;
+        if self.is_cached(key) {
+            info!("Loading cached IBL maps {:016x}", key);
+            return self.load(context, upload_manager, sampler_cache, key);
+        }
+
+        self.bake(
+            context,
+            command_pool,
+            upload_manager,
+            sampler_cache,
+            shader_cache,
+            set,
+            key,
+        )
+    }
+
+    // Bakes the maps on the GPU and writes them to the cache under the set's key,
+    // replacing any earlier bake
+    #[allow(clippy::too_many_arguments)]
+    pub fn bake(
+        &self,
+        context: Arc<VulkanContext>,
+        command_pool: &CommandPool,
+        upload_manager: &mut UploadManager,
+        sampler_cache: &mut SamplerCache,
+        shader_cache: &mut ShaderCache,
+        set: &EnvironmentSet,
+        key: u64,
+    ) -> Result<EnvironmentMaps> {
+        info!("Baking IBL maps {:016x}", key);
+
+        let mut load = |source: &EnvironmentSource| {
+            source
+                .load_with_settings(
+                    context.clone(),
+                    command_pool,
+                    upload_manager,
+                    sampler_cache,
+                    shader_cache,
+                    &self.settings.environment,
+                )
+                .context(LoadEnvironment {})
+        };
+
+        // Sources shared between roles are only loaded once
+        let environment = load(&set.background)?;
+        let irradiance_source = if set.irradiance == set.background {
+            None
+        } else {
+            Some(load(&set.irradiance)?)
+        };
+        let reflection_source =
+            if set.reflection == set.background || set.reflection == set.irradiance {
+                None
+            } else {
+                Some(load(&set.reflection)?)
+            };
+        let irradiance_cubemap = irradiance_source.as_ref().unwrap_or(&environment);
+        let reflection_cubemap = match &reflection_source {
+            Some(cubemap) => cubemap,
+            None if set.reflection == set.irradiance => irradiance_cubemap,
+            None => &environment,
+        };
+
+        let irradiance = IrradianceMap::with_settings(
+            context.clone(),
+            command_pool,
+            upload_manager,
+            sampler_cache,
+            irradiance_cubemap,
+            &self.settings.irradiance,
+        );
+        let prefilter = PrefilterMap::with_settings(
+            context.clone(),
+            command_pool,
+            upload_manager,
+            sampler_cache,
+            reflection_cubemap,
+            &self.settings.prefilter,
+        );
+        let brdflut = Brdflut::with_settings(context, command_pool, &self.settings.brdflut);
+
+        fs::create_dir_all(&self.directory).context(CreateCacheDirectory {
+            path: self.directory.display().to_string(),
+        })?;
+
+        let description = Self::download(
+            "environment",
+            command_pool,
+            &environment.texture,
+            &environment.description,
+        )?
+        .linearize(CACHED_ENVIRONMENT_FORMAT)
+        .context(ConvertBakedMap {
+            name: "environment",
+        })?;
+        Self::write(&self.path(key, "environment"), "environment", &description)?;
+
+        let description = Self::download(
+            "irradiance",
+            command_pool,
+            &irradiance.cubemap.texture,
+            &irradiance.cubemap.description,
+        )?;
+        Self::write(&self.path(key, "irradiance"), "irradiance", &description)?;
+
+        let description = Self::download(
+            "prefilter",
+            command_pool,
+            &prefilter.cubemap.texture,
+            &prefilter.cubemap.description,
+        )?;
+        Self::write(&self.path(key, "prefilter"), "prefilter", &description)?;
+
+        let description = Self::download(
+            "brdflut",
+            command_pool,
+            &brdflut.texture,
+            &self.settings.brdflut.description(),
+        )?;
+        Self::write(&self.brdflut_path(), "brdflut", &description)?;
+
+        Ok(EnvironmentMaps {
+            brdflut,
+            environment,
+            irradiance,
+            prefilter,
+        })
+    }
+
+    pub fn is_cached(&self, key: u64) -> bool {
+        ["environment", "irradiance", "prefilter"]
+            .iter()
+            .all(|name| self.path(key, name).is_file())
+            && self.brdflut_path().is_file()
+    }
+
+    fn load(
+        &self,
+        context: Arc<VulkanContext>,
+        upload_manager: &mut UploadManager,
+        sampler_cache: &mut SamplerCache,
+        key: u64,
+    ) -> Result<EnvironmentMaps> {
+        let mut load_cubemap = |path: PathBuf| {
+            let path = path.display().to_string();
+            TextureDescription::from_ktx2(&path)
+                .and_then(|description| {
+                    Cubemap::from_description(
+                        context.clone(),
+                        upload_manager,
+                        sampler_cache,
+                        &description,
+                    )
+                })
+                .context(LoadCacheFile { path })
+        };
+        let environment = load_cubemap(self.path(key, "environment"))?;
+        let irradiance = IrradianceMap {
+            cubemap: load_cubemap(self.path(key, "irradiance"))?,
+        };
+        let prefilter = PrefilterMap {
+            cubemap: load_cubemap(self.path(key, "prefilter"))?,
+        };
+
+        let path = self.brdflut_path().display().to_string();
+        let brdflut = TextureDescription::from_ktx2(&path)
+            .and_then(|description| {
+                Brdflut::from_description(context, upload_manager, &description)
+            })
+            .context(LoadCacheFile { path })?;
+
+        upload_manager.submit().context(SubmitCacheUpload {})?;
+
+        Ok(EnvironmentMaps {
+            brdflut,
+            environment,
+            irradiance,
+            prefilter,
+        })
+    }
+
+    fn download(
+        name: &'static str,
+        command_pool: &CommandPool,
+        texture: &Texture,
+        description: &TextureDescription,
+    ) -> Result<TextureDescription> {
+        let pixels = texture
+            .download(
+                command_pool,
+                description,
+                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            )
+            .context(DownloadBakedMap { name })?;
+        Ok(TextureDescription {
+            pixels,
+            stored_mip_levels: description.mip_levels,
+            ..description.clone()
+        })
+    }
+
+    fn write(path: &Path, name: &'static str, description: &TextureDescription) -> Result<()> {
+        let bytes = write_ktx2(description).context(EncodeBakedMap { name })?;
+        fs::write(path, bytes).context(WriteCacheFile {
+            path: path.display().to_string(),
+        })
+    }
+
+    fn path(&self, key: u64, name: &str) -> PathBuf {
+        self.directory.join(format!("{:016x}_{}.ktx2", key, name))
+    }
+
+    // The lookup table doesn't depend on the environment, so every bake with the same
+    // lookup table settings shares it
+    fn brdflut_path(&self) -> PathBuf {
+        let parameters = format!("version {} {:?}", BAKE_VERSION, self.settings.brdflut);
+        let hash = fnv1a(FNV_OFFSET_BASIS, parameters.as_bytes());
+        self.directory.join(format!("{:016x}_brdflut.ktx2", hash))
+    }
+
+    fn bake_parameters(&self) -> String {
+        let mut settings = self.settings;
+        settings.irradiance.method = BakeMethod::default();
+        settings.prefilter.method = BakeMethod::default();
+        format!(
+            "version {} cached environment {:?} {:?}",
+            BAKE_VERSION, CACHED_ENVIRONMENT_FORMAT, settings
+        )
+    }
+}
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+// FNV-1a is stable across runs and platforms, unlike the standard library's hasher
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(FNV_PRIME)
+    })
+}
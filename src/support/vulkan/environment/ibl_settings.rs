@@ -0,0 +1,192 @@
+use crate::vulkan::{BakeMethod, TextureDescription};
+use ash::vk;
+use derive_builder::Builder;
+
+// How prefiltered mip levels map to the roughness they were convolved with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RoughnessMapping {
+    // Roughness rises linearly from zero at the base level to one at the smallest level
+    #[default]
+    Linear,
+
+    // Roughness rises with the square of the level, leaving more levels for glossy reflections
+    Quadratic,
+}
+
+impl RoughnessMapping {
+    pub fn roughness(self, mip_level: u32, mip_levels: u32) -> f32 {
+        if mip_levels < 2 {
+            return 0.0;
+        }
+        (mip_level as f32 / (mip_levels - 1) as f32).powf(self.exponent())
+    }
+
+    // The power the level's position in the chain is raised to.
+    // The PBR shader takes the inverse power of the roughness to pick a level.
+    pub fn exponent(self) -> f32 {
+        match self {
+            RoughnessMapping::Linear => 1.0,
+            RoughnessMapping::Quadratic => 2.0,
+        }
+    }
+}
+
+// Controls how panoramas are projected onto the environment cubemap
+#[derive(Builder, Debug, Clone, Copy, PartialEq)]
+#[builder(default, setter(into))]
+pub struct EnvironmentSettings {
+    // Faces cover a quarter of the panorama's width, up to this size.
+    // An 8k panorama would otherwise need 2k faces with a full mip chain, in 32 bit floats.
+    pub max_face_size: u32,
+
+    pub format: vk::Format,
+}
+
+impl Default for EnvironmentSettings {
+    fn default() -> Self {
+        Self {
+            max_face_size: 2048,
+            format: vk::Format::R32G32B32A32_SFLOAT,
+        }
+    }
+}
+
+impl EnvironmentSettings {
+    pub fn face_size(&self, panorama_width: u32) -> u32 {
+        (panorama_width / 4)
+            .next_power_of_two()
+            .min(self.max_face_size)
+            .max(1)
+    }
+}
+
+#[derive(Builder, Debug, Clone, Copy, PartialEq)]
+#[builder(default, setter(into))]
+pub struct IrradianceSettings {
+    pub method: BakeMethod,
+    pub face_size: u32,
+    pub format: vk::Format,
+
+    // The full mip chain when unset
+    #[builder(setter(strip_option))]
+    pub mip_levels: Option<u32>,
+
+    // The hemisphere around each texel is sampled on a grid of this many steps
+    // around the normal and this many steps from the normal to the horizon
+    pub azimuth_samples: u32,
+    pub zenith_samples: u32,
+}
+
+impl Default for IrradianceSettings {
+    fn default() -> Self {
+        Self {
+            method: BakeMethod::default(),
+            face_size: 64,
+            format: vk::Format::R32G32B32A32_SFLOAT,
+            mip_levels: None,
+            azimuth_samples: 180,
+            zenith_samples: 64,
+        }
+    }
+}
+
+impl IrradianceSettings {
+    pub fn description(&self) -> TextureDescription {
+        cube_description(self.face_size, self.mip_levels, self.format)
+    }
+
+    // The step sizes the shaders walk the hemisphere with, in radians
+    pub fn delta_phi(&self) -> f32 {
+        2.0 * std::f32::consts::PI / self.azimuth_samples.max(1) as f32
+    }
+
+    pub fn delta_theta(&self) -> f32 {
+        0.5 * std::f32::consts::PI / self.zenith_samples.max(1) as f32
+    }
+}
+
+#[derive(Builder, Debug, Clone, Copy, PartialEq)]
+#[builder(default, setter(into))]
+pub struct PrefilterSettings {
+    pub method: BakeMethod,
+    pub face_size: u32,
+    pub format: vk::Format,
+
+    // The full mip chain when unset
+    #[builder(setter(strip_option))]
+    pub mip_levels: Option<u32>,
+
+    // Importance samples taken per texel
+    pub sample_count: u32,
+
+    pub roughness: RoughnessMapping,
+}
+
+impl Default for PrefilterSettings {
+    fn default() -> Self {
+        Self {
+            method: BakeMethod::default(),
+            face_size: 512,
+            format: vk::Format::R16G16B16A16_SFLOAT,
+            mip_levels: None,
+            sample_count: 32,
+            roughness: RoughnessMapping::default(),
+        }
+    }
+}
+
+impl PrefilterSettings {
+    pub fn description(&self) -> TextureDescription {
+        cube_description(self.face_size, self.mip_levels, self.format)
+    }
+}
+
+#[derive(Builder, Debug, Clone, Copy, PartialEq)]
+#[builder(default, setter(into))]
+pub struct BrdflutSettings {
+    pub size: u32,
+    pub format: vk::Format,
+
+    // Importance samples taken per texel
+    pub sample_count: u32,
+}
+
+impl Default for BrdflutSettings {
+    fn default() -> Self {
+        Self {
+            size: 512,
+            format: vk::Format::R16G16_SFLOAT,
+            sample_count: 1024,
+        }
+    }
+}
+
+impl BrdflutSettings {
+    // The lookup table is rendered once, so it has a single mip level
+    pub fn description(&self) -> TextureDescription {
+        TextureDescription {
+            mip_levels: 1,
+            ..TextureDescription::empty(self.size, self.size, self.format)
+        }
+    }
+}
+
+// Everything that shapes the maps `IblCache` bakes
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct IblSettings {
+    pub environment: EnvironmentSettings,
+    pub irradiance: IrradianceSettings,
+    pub prefilter: PrefilterSettings,
+    pub brdflut: BrdflutSettings,
+}
+
+// Mip level counts are clamped to the length of a full chain
+fn cube_description(size: u32, mip_levels: Option<u32>, format: vk::Format) -> TextureDescription {
+    let description = TextureDescription::empty_cube(size, 1, format);
+    TextureDescription {
+        mip_levels: mip_levels
+            .unwrap_or(description.mip_levels)
+            .clamp(1, description.mip_levels),
+        ..description
+    }
+}
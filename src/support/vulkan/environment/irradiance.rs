@@ -1,12 +1,14 @@
 use crate::{
     byte_slice_from,
     vulkan::{
-        CommandPool, Cubemap, DescriptorPool, DescriptorSetLayout, Framebuffer, GraphicsPipeline,
-        ImageLayoutTransition, Offscreen, PipelineLayout, RenderPass, Shader, UnitCube,
-        VulkanContext,
+        dispatch_cube_compute, BakeMethod, CommandPool, CubeVertex, Cubemap, DescriptorPool,
+        DescriptorSetLayout, Framebuffer, GraphicsPipeline, ImageLayoutTransition,
+        IrradianceSettings, Offscreen, PipelineLayout, RenderPass, SamplerCache, Shader, UnitCube,
+        UploadManager, Vertex, VulkanContext,
     },
 };
 use ash::{version::DeviceV1_0, vk};
+use log::warn;
 use nalgebra_glm as glm;
 use std::{ffi::CString, sync::Arc};
 
@@ -17,15 +19,90 @@ struct PushBlockIrradiance {
     delta_theta: f32,
 }
 
+#[allow(dead_code)]
+struct ComputePushBlockIrradiance {
+    delta_phi: f32,
+    delta_theta: f32,
+}
+
 pub struct IrradianceMap {
     pub cubemap: Cubemap,
 }
 
 impl IrradianceMap {
-    pub fn new(context: Arc<VulkanContext>, command_pool: &CommandPool, cubemap: &Cubemap) -> Self {
-        let dimension = 64;
-        let format = vk::Format::R32G32B32A32_SFLOAT;
-        let output_cubemap = Cubemap::new(context.clone(), dimension, format).unwrap();
+    // Matches the image format declared in `irradiancecube.comp.glsl`
+    pub const COMPUTE_FORMAT: vk::Format = vk::Format::R32G32B32A32_SFLOAT;
+
+    pub fn new(
+        context: Arc<VulkanContext>,
+        command_pool: &CommandPool,
+        upload_manager: &mut UploadManager,
+        sampler_cache: &mut SamplerCache,
+        cubemap: &Cubemap,
+    ) -> Self {
+        Self::with_settings(
+            context,
+            command_pool,
+            upload_manager,
+            sampler_cache,
+            cubemap,
+            &IrradianceSettings::default(),
+        )
+    }
+
+    // The compute shader declares the format of the image it writes,
+    // so other formats are baked with the raster path
+    pub fn with_settings(
+        context: Arc<VulkanContext>,
+        command_pool: &CommandPool,
+        upload_manager: &mut UploadManager,
+        sampler_cache: &mut SamplerCache,
+        cubemap: &Cubemap,
+        settings: &IrradianceSettings,
+    ) -> Self {
+        let method = match settings.method {
+            BakeMethod::Compute if settings.format != Self::COMPUTE_FORMAT => {
+                warn!(
+                    "Irradiance maps in format {:?} can't be baked by compute, rendering instead",
+                    settings.format
+                );
+                BakeMethod::Raster
+            }
+            method => method,
+        };
+        let cubemap = match method {
+            BakeMethod::Raster => Self::render(
+                context,
+                command_pool,
+                upload_manager,
+                sampler_cache,
+                cubemap,
+                settings,
+            ),
+            BakeMethod::Compute => {
+                Self::dispatch(context, command_pool, sampler_cache, cubemap, settings)
+            }
+        };
+        Self { cubemap }
+    }
+
+    fn render(
+        context: Arc<VulkanContext>,
+        command_pool: &CommandPool,
+        upload_manager: &mut UploadManager,
+        sampler_cache: &mut SamplerCache,
+        cubemap: &Cubemap,
+        settings: &IrradianceSettings,
+    ) -> Cubemap {
+        let dimension = settings.face_size;
+        let format = settings.format;
+        let output_cubemap = Cubemap::empty(
+            context.clone(),
+            sampler_cache,
+            &settings.description(),
+            vk::ImageUsageFlags::empty(),
+        )
+        .unwrap();
 
         let render_pass = Self::create_render_pass(context.clone(), format);
 
@@ -151,7 +228,8 @@ impl IrradianceMap {
         };
         let scissors = [scissor];
 
-        let unit_cube = UnitCube::new(command_pool);
+        let unit_cube = UnitCube::new(upload_manager);
+        upload_manager.submit().unwrap();
 
         for mip_level in 0..output_cubemap.description.mip_levels {
             for (face, matrix) in matrices.iter().enumerate() {
@@ -170,8 +248,8 @@ impl IrradianceMap {
                             let push_block_irradiance = PushBlockIrradiance {
                                 mvp: glm::perspective_zo(1.0, 90_f32.to_radians(), 0.1, 512.0)
                                     * matrix,
-                                delta_phi: 2_f32.to_radians(),
-                                delta_theta: (0.5_f32 * std::f32::consts::PI) / 64_f32,
+                                delta_phi: settings.delta_phi(),
+                                delta_theta: settings.delta_theta(),
                             };
 
                             device.cmd_push_constants(
@@ -282,9 +360,40 @@ impl IrradianceMap {
             .transition(&command_pool, &transition)
             .unwrap();
 
-        Self {
-            cubemap: output_cubemap,
-        }
+        output_cubemap
+    }
+
+    fn dispatch(
+        context: Arc<VulkanContext>,
+        command_pool: &CommandPool,
+        sampler_cache: &mut SamplerCache,
+        cubemap: &Cubemap,
+        settings: &IrradianceSettings,
+    ) -> Cubemap {
+        let output_cubemap = Cubemap::empty(
+            context.clone(),
+            sampler_cache,
+            &settings.description(),
+            vk::ImageUsageFlags::STORAGE,
+        )
+        .unwrap();
+
+        let push_block = ComputePushBlockIrradiance {
+            delta_phi: settings.delta_phi(),
+            delta_theta: settings.delta_theta(),
+        };
+        dispatch_cube_compute(
+            context,
+            command_pool,
+            cubemap,
+            &output_cubemap,
+            "assets/shaders/environment/irradiancecube.comp.spv",
+            std::mem::size_of::<ComputePushBlockIrradiance>() as u32,
+            |_| unsafe { byte_slice_from(&push_block) }.to_vec(),
+        )
+        .unwrap();
+
+        output_cubemap
     }
 
     fn create_render_pass(context: Arc<VulkanContext>, format: vk::Format) -> RenderPass {
@@ -477,8 +586,8 @@ impl IrradianceMap {
             .dynamic_states(&dynamic_states)
             .build();
 
-        let descriptions = Self::create_vertex_input_descriptions();
-        let attributes = Self::create_vertex_attributes();
+        let descriptions = [CubeVertex::binding_description(0)];
+        let attributes = CubeVertex::attribute_descriptions(0, 0);
         let vertex_input_create_info = vk::PipelineVertexInputStateCreateInfo::builder()
             .vertex_binding_descriptions(&descriptions)
             .vertex_attribute_descriptions(&attributes)
@@ -506,25 +615,6 @@ impl IrradianceMap {
         GraphicsPipeline::new(context, pipeline_create_info, pipeline_layout)
     }
 
-    fn create_vertex_attributes() -> [vk::VertexInputAttributeDescription; 1] {
-        let position_description = vk::VertexInputAttributeDescription::builder()
-            .binding(0)
-            .location(0)
-            .format(vk::Format::R32G32B32_SFLOAT)
-            .offset(0)
-            .build();
-        [position_description]
-    }
-
-    fn create_vertex_input_descriptions() -> [vk::VertexInputBindingDescription; 1] {
-        let vertex_input_binding_description = vk::VertexInputBindingDescription::builder()
-            .binding(0)
-            .stride((3 * std::mem::size_of::<f32>()) as _)
-            .input_rate(vk::VertexInputRate::VERTEX)
-            .build();
-        [vertex_input_binding_description]
-    }
-
     fn create_shaders(context: Arc<VulkanContext>) -> (Shader, Shader, CString) {
         let shader_entry_point_name =
             CString::new("main").expect("Failed to create CString for shader entry point name!");
@@ -1,9 +1,42 @@
-pub use self::{brdflut::*, cube::*, hdr::*, irradiance::*, offscreen::*, prefilter::*, skybox::*};
+pub use self::{
+    bake::{cubemap_difference, BakeMethod},
+    brdflut::Brdflut,
+    cube::{cube_face_direction, cube_face_views, CubeVertex, UnitCube, VERTICES},
+    harmonics::SphericalHarmonics,
+    hdr::HdrCubemap,
+    ibl_cache::{EnvironmentMaps, IblCache},
+    ibl_settings::{
+        BrdflutSettings, BrdflutSettingsBuilder, EnvironmentSettings, EnvironmentSettingsBuilder,
+        IblSettings, IrradianceSettings, IrradianceSettingsBuilder, PrefilterSettings,
+        PrefilterSettingsBuilder, RoughnessMapping,
+    },
+    irradiance::IrradianceMap,
+    offscreen::Offscreen,
+    prefilter::PrefilterMap,
+    probe::{ProbeCapture, ProbeFace, ProbeInfluence, ProbeUniform, ReflectionProbe},
+    sibl::{Sibl, SiblImage, SiblSun},
+    sky::ProceduralSky,
+    skybox::{
+        create_skybox_pipeline, create_skybox_pipeline_with_samples, SkyboxPipelineData,
+        SkyboxRenderer, SkyboxUniformBufferObject,
+    },
+    source::{CrossLayout, EnvironmentSet, EnvironmentSource},
+};
 
+pub(crate) use self::bake::dispatch_cube_compute;
+
+pub mod bake;
 pub mod brdflut;
 pub mod cube;
+pub mod harmonics;
 pub mod hdr;
+pub mod ibl_cache;
+pub mod ibl_settings;
 pub mod irradiance;
 pub mod offscreen;
 pub mod prefilter;
+pub mod probe;
+pub mod sibl;
+pub mod sky;
 pub mod skybox;
+pub mod source;
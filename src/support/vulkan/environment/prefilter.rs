@@ -1,12 +1,14 @@
 use crate::{
     byte_slice_from,
     vulkan::{
-        CommandPool, Cubemap, DescriptorPool, DescriptorSetLayout, Framebuffer, GraphicsPipeline,
-        ImageLayoutTransition, Offscreen, PipelineLayout, RenderPass, Shader, UnitCube,
-        VulkanContext,
+        dispatch_cube_compute, BakeMethod, CommandPool, CubeVertex, Cubemap, DescriptorPool,
+        DescriptorSetLayout, Framebuffer, GraphicsPipeline, ImageLayoutTransition, Offscreen,
+        PipelineLayout, PrefilterSettings, RenderPass, SamplerCache, Shader, UnitCube,
+        UploadManager, Vertex, VulkanContext,
     },
 };
 use ash::{version::DeviceV1_0, vk};
+use log::warn;
 use nalgebra_glm as glm;
 use std::{ffi::CString, sync::Arc};
 
@@ -17,16 +19,91 @@ struct PushBlockPrefilterEnv {
     num_samples: u32,
 }
 
+#[allow(dead_code)]
+struct ComputePushBlockPrefilterEnv {
+    roughness: f32,
+    num_samples: u32,
+}
+
 pub struct PrefilterMap {
     pub cubemap: Cubemap,
 }
 
 impl PrefilterMap {
-    pub fn new(context: Arc<VulkanContext>, command_pool: &CommandPool, cubemap: &Cubemap) -> Self {
-        let dimension = 512;
-        let format = vk::Format::R16G16B16A16_SFLOAT;
+    // Matches the image format declared in `prefilterenvmap.comp.glsl`
+    pub const COMPUTE_FORMAT: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
+
+    pub fn new(
+        context: Arc<VulkanContext>,
+        command_pool: &CommandPool,
+        upload_manager: &mut UploadManager,
+        sampler_cache: &mut SamplerCache,
+        cubemap: &Cubemap,
+    ) -> Self {
+        Self::with_settings(
+            context,
+            command_pool,
+            upload_manager,
+            sampler_cache,
+            cubemap,
+            &PrefilterSettings::default(),
+        )
+    }
+
+    // The compute shader declares the format of the image it writes,
+    // so other formats are baked with the raster path
+    pub fn with_settings(
+        context: Arc<VulkanContext>,
+        command_pool: &CommandPool,
+        upload_manager: &mut UploadManager,
+        sampler_cache: &mut SamplerCache,
+        cubemap: &Cubemap,
+        settings: &PrefilterSettings,
+    ) -> Self {
+        let method = match settings.method {
+            BakeMethod::Compute if settings.format != Self::COMPUTE_FORMAT => {
+                warn!(
+                    "Prefilter maps in format {:?} can't be baked by compute, rendering instead",
+                    settings.format
+                );
+                BakeMethod::Raster
+            }
+            method => method,
+        };
+        let cubemap = match method {
+            BakeMethod::Raster => Self::render(
+                context,
+                command_pool,
+                upload_manager,
+                sampler_cache,
+                cubemap,
+                settings,
+            ),
+            BakeMethod::Compute => {
+                Self::dispatch(context, command_pool, sampler_cache, cubemap, settings)
+            }
+        };
+        Self { cubemap }
+    }
+
+    fn render(
+        context: Arc<VulkanContext>,
+        command_pool: &CommandPool,
+        upload_manager: &mut UploadManager,
+        sampler_cache: &mut SamplerCache,
+        cubemap: &Cubemap,
+        settings: &PrefilterSettings,
+    ) -> Cubemap {
+        let dimension = settings.face_size;
+        let format = settings.format;
 
-        let output_cubemap = Cubemap::new(context.clone(), dimension, format).unwrap();
+        let output_cubemap = Cubemap::empty(
+            context.clone(),
+            sampler_cache,
+            &settings.description(),
+            vk::ImageUsageFlags::empty(),
+        )
+        .unwrap();
 
         let render_pass = Self::create_render_pass(context.clone(), format);
 
@@ -152,7 +229,8 @@ impl PrefilterMap {
         };
         let scissors = [scissor];
 
-        let unit_cube = UnitCube::new(command_pool);
+        let unit_cube = UnitCube::new(upload_manager);
+        upload_manager.submit().unwrap();
 
         for mip_level in 0..output_cubemap.description.mip_levels {
             for (face, matrix) in matrices.iter().enumerate() {
@@ -171,9 +249,10 @@ impl PrefilterMap {
                             let push_block_irradiance = PushBlockPrefilterEnv {
                                 mvp: glm::perspective_zo(1.0, 90_f32.to_radians(), 0.1, 512.0)
                                     * matrix,
-                                roughness: mip_level as f32
-                                    / (output_cubemap.description.mip_levels - 1) as f32,
-                                num_samples: 32,
+                                roughness: settings
+                                    .roughness
+                                    .roughness(mip_level, output_cubemap.description.mip_levels),
+                                num_samples: settings.sample_count,
                             };
 
                             device.cmd_push_constants(
@@ -283,9 +362,43 @@ impl PrefilterMap {
             .transition(&command_pool, &transition)
             .unwrap();
 
-        Self {
-            cubemap: output_cubemap,
-        }
+        output_cubemap
+    }
+
+    fn dispatch(
+        context: Arc<VulkanContext>,
+        command_pool: &CommandPool,
+        sampler_cache: &mut SamplerCache,
+        cubemap: &Cubemap,
+        settings: &PrefilterSettings,
+    ) -> Cubemap {
+        let output_cubemap = Cubemap::empty(
+            context.clone(),
+            sampler_cache,
+            &settings.description(),
+            vk::ImageUsageFlags::STORAGE,
+        )
+        .unwrap();
+
+        let mip_levels = output_cubemap.description.mip_levels;
+        dispatch_cube_compute(
+            context,
+            command_pool,
+            cubemap,
+            &output_cubemap,
+            "assets/shaders/environment/prefilterenvmap.comp.spv",
+            std::mem::size_of::<ComputePushBlockPrefilterEnv>() as u32,
+            |mip_level| {
+                let push_block = ComputePushBlockPrefilterEnv {
+                    roughness: settings.roughness.roughness(mip_level, mip_levels),
+                    num_samples: settings.sample_count,
+                };
+                unsafe { byte_slice_from(&push_block) }.to_vec()
+            },
+        )
+        .unwrap();
+
+        output_cubemap
     }
 
     fn create_render_pass(context: Arc<VulkanContext>, format: vk::Format) -> RenderPass {
@@ -478,8 +591,8 @@ impl PrefilterMap {
             .dynamic_states(&dynamic_states)
             .build();
 
-        let descriptions = Self::create_vertex_input_descriptions();
-        let attributes = Self::create_vertex_attributes();
+        let descriptions = [CubeVertex::binding_description(0)];
+        let attributes = CubeVertex::attribute_descriptions(0, 0);
         let vertex_input_create_info = vk::PipelineVertexInputStateCreateInfo::builder()
             .vertex_binding_descriptions(&descriptions)
             .vertex_attribute_descriptions(&attributes)
@@ -507,25 +620,6 @@ impl PrefilterMap {
         GraphicsPipeline::new(context, pipeline_create_info, pipeline_layout)
     }
 
-    fn create_vertex_attributes() -> [vk::VertexInputAttributeDescription; 1] {
-        let position_description = vk::VertexInputAttributeDescription::builder()
-            .binding(0)
-            .location(0)
-            .format(vk::Format::R32G32B32_SFLOAT)
-            .offset(0)
-            .build();
-        [position_description]
-    }
-
-    fn create_vertex_input_descriptions() -> [vk::VertexInputBindingDescription; 1] {
-        let vertex_input_binding_description = vk::VertexInputBindingDescription::builder()
-            .binding(0)
-            .stride((3 * std::mem::size_of::<f32>()) as _)
-            .input_rate(vk::VertexInputRate::VERTEX)
-            .build();
-        [vertex_input_binding_description]
-    }
-
     fn create_shaders(context: Arc<VulkanContext>) -> (Shader, Shader, CString) {
         let shader_entry_point_name =
             CString::new("main").expect("Failed to create CString for shader entry point name!");
@@ -0,0 +1,559 @@
+use crate::vulkan::{
+    cube_face_views, CommandPool, Cubemap, Framebuffer, IblSettings, ImageLayoutTransition,
+    ImageView, IrradianceMap, IrradianceSettings, Offscreen, PrefilterMap, PrefilterSettings,
+    RenderPass, SamplerCache, Texture, TextureDescription, UploadManager, VulkanContext,
+};
+use ash::{version::DeviceV1_0, vk};
+use nalgebra_glm as glm;
+use snafu::{ResultExt, Snafu};
+use std::sync::Arc;
+
+type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility = "pub(crate)")]
+pub enum Error {
+    #[snafu(display("Failed to create probe render pass: {}", source))]
+    CreateProbeRenderPass {
+        source: crate::vulkan::renderpass::Error,
+    },
+
+    #[snafu(display("Failed to create probe depth buffer: {}", source))]
+    CreateProbeDepthTexture {
+        source: crate::vulkan::texture::Error,
+    },
+
+    #[snafu(display("Failed to create probe depth buffer view: {}", source))]
+    CreateProbeDepthView {
+        source: crate::vulkan::image_view::Error,
+    },
+
+    #[snafu(display("Failed to create probe framebuffer: {}", source))]
+    CreateProbeFramebuffer {
+        source: crate::vulkan::framebuffer::Error,
+    },
+
+    #[snafu(display("Failed to create probe cubemap: {}", source))]
+    CreateProbeCubemap {
+        source: crate::vulkan::texture::Error,
+    },
+
+    #[snafu(display("Failed to transition probe cubemap: {}", source))]
+    TransitionProbeCubemap {
+        source: crate::vulkan::texture::Error,
+    },
+
+    #[snafu(display("Failed to render probe face: {}", source))]
+    RenderProbeFace {
+        source: crate::vulkan::command_pool::Error,
+    },
+
+    #[snafu(display("Failed to generate probe mipmaps: {}", source))]
+    GenerateProbeMipmaps {
+        source: crate::vulkan::texture::Error,
+    },
+}
+
+// The region of the scene a probe lights, centered on the probe
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProbeInfluence {
+    Sphere { radius: f32 },
+
+    // Half the box's size along each axis
+    Box { extents: glm::Vec3 },
+}
+
+impl ProbeInfluence {
+    // How far a point lies inside the volume, negative outside of it
+    pub fn depth(&self, offset: &glm::Vec3) -> f32 {
+        match self {
+            ProbeInfluence::Sphere { radius } => radius - glm::length(offset),
+            ProbeInfluence::Box { extents } => {
+                let inside = extents - glm::abs(offset);
+                inside.x.min(inside.y).min(inside.z)
+            }
+        }
+    }
+}
+
+// The camera looking through one face of a probe
+#[derive(Debug, Clone, Copy)]
+pub struct ProbeFace {
+    pub index: usize,
+    pub position: glm::Vec3,
+    pub view: glm::Mat4,
+    pub projection: glm::Mat4,
+}
+
+// Renders the scene into cubemaps, one face at a time.
+// Faces are laid out like the baked environment maps, so a captured cubemap
+// is sampled with world space directions.
+pub struct ProbeCapture {
+    render_pass: Arc<RenderPass>,
+    offscreen: Offscreen,
+    _depth_texture: Texture,
+    _depth_view: ImageView,
+    framebuffer: Framebuffer,
+    face_size: u32,
+    context: Arc<VulkanContext>,
+}
+
+impl ProbeCapture {
+    // Captures are filtered into mip levels for the prefilter pass, so the format
+    // has to support rendering and linear blits
+    pub const FORMAT: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
+
+    pub const NEAR_PLANE: f32 = 0.1;
+    pub const FAR_PLANE: f32 = 1000.0;
+
+    pub fn new(context: Arc<VulkanContext>, face_size: u32) -> Result<Self> {
+        let depth_format = context.determine_depth_format(
+            vk::ImageTiling::OPTIMAL,
+            vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT,
+        );
+        let render_pass = Arc::new(Self::create_render_pass(context.clone(), depth_format)?);
+        let offscreen = Offscreen::new(context.clone(), face_size, Self::FORMAT);
+        let depth_texture = Self::create_depth_texture(context.clone(), face_size, depth_format)?;
+        let depth_view = Self::create_depth_view(context.clone(), &depth_texture, depth_format)?;
+
+        let attachments = [offscreen.view.view(), depth_view.view()];
+        let create_info = vk::FramebufferCreateInfo::builder()
+            .render_pass(render_pass.render_pass())
+            .attachments(&attachments)
+            .width(face_size)
+            .height(face_size)
+            .layers(1)
+            .build();
+        let framebuffer =
+            Framebuffer::new(context.clone(), create_info).context(CreateProbeFramebuffer {})?;
+
+        Ok(Self {
+            render_pass,
+            offscreen,
+            _depth_texture: depth_texture,
+            _depth_view: depth_view,
+            framebuffer,
+            face_size,
+            context,
+        })
+    }
+
+    // Pipelines that draw into captures are created against this render pass.
+    // It has a color and a depth attachment, both single sampled.
+    pub fn render_pass(&self) -> Arc<RenderPass> {
+        self.render_pass.clone()
+    }
+
+    pub fn face_size(&self) -> u32 {
+        self.face_size
+    }
+
+    pub fn faces(&self, position: &glm::Vec3) -> Vec<ProbeFace> {
+        let projection =
+            glm::perspective_zo(1.0, 90_f32.to_radians(), Self::NEAR_PLANE, Self::FAR_PLANE);
+        cube_face_views(position)
+            .iter()
+            .enumerate()
+            .map(|(index, view)| ProbeFace {
+                index,
+                position: *position,
+                view: *view,
+                projection,
+            })
+            .collect()
+    }
+
+    // The recorder is called inside the render pass once per face, with the viewport set.
+    // Each face is submitted and finished before the next one is recorded,
+    // so per-face uniforms can be written while recording.
+    pub fn capture<T>(
+        &self,
+        command_pool: &CommandPool,
+        sampler_cache: &mut SamplerCache,
+        position: &glm::Vec3,
+        mut recorder: T,
+    ) -> Result<Cubemap>
+    where
+        T: FnMut(&ProbeFace, &ash::Device, vk::CommandBuffer),
+    {
+        let description = TextureDescription::empty_cube(self.face_size, 1, Self::FORMAT);
+        let cubemap = Cubemap::empty(
+            self.context.clone(),
+            sampler_cache,
+            &description,
+            vk::ImageUsageFlags::empty(),
+        )
+        .context(CreateProbeCubemap {})?;
+
+        let transition = ImageLayoutTransition {
+            old_layout: vk::ImageLayout::UNDEFINED,
+            new_layout: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            src_access_mask: vk::AccessFlags::empty(),
+            dst_access_mask: vk::AccessFlags::TRANSFER_WRITE,
+            src_stage_mask: vk::PipelineStageFlags::TOP_OF_PIPE,
+            dst_stage_mask: vk::PipelineStageFlags::TRANSFER,
+        };
+        cubemap
+            .transition(command_pool, &transition)
+            .context(TransitionProbeCubemap {})?;
+
+        let extent = vk::Extent2D {
+            width: self.face_size,
+            height: self.face_size,
+        };
+        let clear_values = [
+            vk::ClearValue {
+                color: vk::ClearColorValue {
+                    float32: [0.0, 0.0, 0.0, 1.0],
+                },
+            },
+            vk::ClearValue {
+                depth_stencil: vk::ClearDepthStencilValue {
+                    depth: 1.0,
+                    stencil: 0,
+                },
+            },
+        ];
+        let render_pass_begin_info = vk::RenderPassBeginInfo::builder()
+            .render_pass(self.render_pass.render_pass())
+            .framebuffer(self.framebuffer.framebuffer())
+            .render_area(vk::Rect2D {
+                offset: vk::Offset2D { x: 0, y: 0 },
+                extent,
+            })
+            .clear_values(&clear_values)
+            .build();
+        let viewports = [vk::Viewport {
+            x: 0.0,
+            y: 0.0,
+            width: self.face_size as _,
+            height: self.face_size as _,
+            min_depth: 0.0,
+            max_depth: 1.0,
+        }];
+        let scissors = [vk::Rect2D {
+            offset: vk::Offset2D { x: 0, y: 0 },
+            extent,
+        }];
+
+        let device = self.context.logical_device().logical_device();
+        for face in self.faces(position) {
+            command_pool
+                .execute_command_once(self.context.graphics_queue(), |command_buffer| {
+                    self.render_pass
+                        .record(command_buffer, &render_pass_begin_info, || unsafe {
+                            device.cmd_set_viewport(command_buffer, 0, &viewports);
+                            device.cmd_set_scissor(command_buffer, 0, &scissors);
+                            recorder(&face, device, command_buffer);
+                        });
+
+                    // The render pass leaves the color attachment ready to be copied from
+                    let region = vk::ImageCopy::builder()
+                        .src_subresource(vk::ImageSubresourceLayers {
+                            aspect_mask: vk::ImageAspectFlags::COLOR,
+                            mip_level: 0,
+                            base_array_layer: 0,
+                            layer_count: 1,
+                        })
+                        .dst_subresource(vk::ImageSubresourceLayers {
+                            aspect_mask: vk::ImageAspectFlags::COLOR,
+                            mip_level: 0,
+                            base_array_layer: face.index as _,
+                            layer_count: 1,
+                        })
+                        .extent(vk::Extent3D {
+                            width: self.face_size,
+                            height: self.face_size,
+                            depth: 1,
+                        })
+                        .build();
+                    unsafe {
+                        device.cmd_copy_image(
+                            command_buffer,
+                            self.offscreen.texture.image(),
+                            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                            cubemap.texture.image(),
+                            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                            &[region],
+                        );
+                    }
+                })
+                .context(RenderProbeFace {})?;
+        }
+
+        cubemap
+            .texture
+            .generate_mipmaps(command_pool, &cubemap.description)
+            .context(GenerateProbeMipmaps {})?;
+
+        Ok(cubemap)
+    }
+
+    fn create_render_pass(
+        context: Arc<VulkanContext>,
+        depth_format: vk::Format,
+    ) -> Result<RenderPass> {
+        let color_attachment_description = vk::AttachmentDescription::builder()
+            .format(Self::FORMAT)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .load_op(vk::AttachmentLoadOp::CLEAR)
+            .store_op(vk::AttachmentStoreOp::STORE)
+            .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+            .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .final_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
+            .build();
+
+        let depth_attachment_description = vk::AttachmentDescription::builder()
+            .format(depth_format)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .load_op(vk::AttachmentLoadOp::CLEAR)
+            .store_op(vk::AttachmentStoreOp::DONT_CARE)
+            .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+            .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .final_layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)
+            .build();
+
+        let attachment_descriptions = [color_attachment_description, depth_attachment_description];
+
+        let color_attachment_reference = vk::AttachmentReference::builder()
+            .attachment(0)
+            .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+            .build();
+        let color_attachment_references = [color_attachment_reference];
+
+        let depth_attachment_reference = vk::AttachmentReference::builder()
+            .attachment(1)
+            .layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)
+            .build();
+
+        let subpass_description = vk::SubpassDescription::builder()
+            .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+            .color_attachments(&color_attachment_references)
+            .depth_stencil_attachment(&depth_attachment_reference)
+            .build();
+        let subpass_descriptions = [subpass_description];
+
+        // The previous face's copy has to finish reading before the next face is drawn,
+        // and the drawing has to finish before this face is copied
+        let subpass_dependencies = [
+            vk::SubpassDependency::builder()
+                .src_subpass(vk::SUBPASS_EXTERNAL)
+                .dst_subpass(0)
+                .src_stage_mask(vk::PipelineStageFlags::TRANSFER)
+                .src_access_mask(vk::AccessFlags::TRANSFER_READ)
+                .dst_stage_mask(
+                    vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+                        | vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS,
+                )
+                .dst_access_mask(
+                    vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+                        | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+                )
+                .build(),
+            vk::SubpassDependency::builder()
+                .src_subpass(0)
+                .dst_subpass(vk::SUBPASS_EXTERNAL)
+                .src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+                .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+                .dst_stage_mask(vk::PipelineStageFlags::TRANSFER)
+                .dst_access_mask(vk::AccessFlags::TRANSFER_READ)
+                .build(),
+        ];
+
+        let create_info = vk::RenderPassCreateInfo::builder()
+            .attachments(&attachment_descriptions)
+            .subpasses(&subpass_descriptions)
+            .dependencies(&subpass_dependencies)
+            .build();
+
+        RenderPass::new(context, &create_info).context(CreateProbeRenderPass {})
+    }
+
+    fn create_depth_texture(
+        context: Arc<VulkanContext>,
+        face_size: u32,
+        depth_format: vk::Format,
+    ) -> Result<Texture> {
+        let image_create_info = vk::ImageCreateInfo::builder()
+            .image_type(vk::ImageType::TYPE_2D)
+            .extent(vk::Extent3D {
+                width: face_size,
+                height: face_size,
+                depth: 1,
+            })
+            .mip_levels(1)
+            .array_layers(1)
+            .format(depth_format)
+            .tiling(vk::ImageTiling::OPTIMAL)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .usage(vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .flags(vk::ImageCreateFlags::empty())
+            .build();
+
+        let allocation_create_info = vk_mem::AllocationCreateInfo {
+            usage: vk_mem::MemoryUsage::GpuOnly,
+            ..Default::default()
+        };
+
+        Texture::new(context, &allocation_create_info, &image_create_info)
+            .context(CreateProbeDepthTexture {})
+    }
+
+    fn create_depth_view(
+        context: Arc<VulkanContext>,
+        depth_texture: &Texture,
+        depth_format: vk::Format,
+    ) -> Result<ImageView> {
+        let create_info = vk::ImageViewCreateInfo::builder()
+            .image(depth_texture.image())
+            .view_type(vk::ImageViewType::TYPE_2D)
+            .format(depth_format)
+            .components(vk::ComponentMapping {
+                r: vk::ComponentSwizzle::IDENTITY,
+                g: vk::ComponentSwizzle::IDENTITY,
+                b: vk::ComponentSwizzle::IDENTITY,
+                a: vk::ComponentSwizzle::IDENTITY,
+            })
+            .subresource_range(vk::ImageSubresourceRange {
+                aspect_mask: vk::ImageAspectFlags::DEPTH,
+                base_mip_level: 0,
+                level_count: 1,
+                base_array_layer: 0,
+                layer_count: 1,
+            })
+            .build();
+        ImageView::new(context, create_info).context(CreateProbeDepthView {})
+    }
+}
+
+// Probes are packed into two vectors for the shaders
+#[derive(Debug, Clone, Copy)]
+pub struct ProbeUniform {
+    // W is the sphere's radius, or zero for boxes
+    pub position: glm::Vec4,
+
+    // XYZ are the box's extents, W is the blend distance
+    pub extents: glm::Vec4,
+}
+
+// Lights the part of a scene inside its influence volume with the scene itself,
+// captured from the probe's position.
+// Reflections are parallax corrected by intersecting them with the volume,
+// so volumes should hug the walls of the space they light.
+pub struct ReflectionProbe {
+    pub position: glm::Vec3,
+    pub influence: ProbeInfluence,
+
+    // Probes fade out over this distance towards the edge of their volume
+    pub blend_distance: f32,
+
+    pub environment: Cubemap,
+    pub irradiance: IrradianceMap,
+    pub prefilter: PrefilterMap,
+}
+
+impl ReflectionProbe {
+    pub const DEFAULT_BLEND_DISTANCE: f32 = 1.0;
+
+    // Probes are rebaked whenever the scene changes, so their maps are smaller than
+    // the ones baked from environment images
+    pub fn default_settings() -> IblSettings {
+        IblSettings {
+            irradiance: IrradianceSettings {
+                face_size: 32,
+                azimuth_samples: 90,
+                zenith_samples: 32,
+                ..IrradianceSettings::default()
+            },
+            prefilter: PrefilterSettings {
+                face_size: 128,
+                ..PrefilterSettings::default()
+            },
+            ..IblSettings::default()
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        context: Arc<VulkanContext>,
+        command_pool: &CommandPool,
+        upload_manager: &mut UploadManager,
+        sampler_cache: &mut SamplerCache,
+        position: glm::Vec3,
+        influence: ProbeInfluence,
+        environment: Cubemap,
+        settings: &IblSettings,
+    ) -> Self {
+        let irradiance = IrradianceMap::with_settings(
+            context.clone(),
+            command_pool,
+            upload_manager,
+            sampler_cache,
+            &environment,
+            &settings.irradiance,
+        );
+        let prefilter = PrefilterMap::with_settings(
+            context,
+            command_pool,
+            upload_manager,
+            sampler_cache,
+            &environment,
+            &settings.prefilter,
+        );
+        Self {
+            position,
+            influence,
+            blend_distance: Self::DEFAULT_BLEND_DISTANCE,
+            environment,
+            irradiance,
+            prefilter,
+        }
+    }
+
+    // Renders the scene around the position and bakes it
+    pub fn capture<T>(
+        capture: &ProbeCapture,
+        command_pool: &CommandPool,
+        upload_manager: &mut UploadManager,
+        sampler_cache: &mut SamplerCache,
+        position: glm::Vec3,
+        influence: ProbeInfluence,
+        recorder: T,
+    ) -> Result<Self>
+    where
+        T: FnMut(&ProbeFace, &ash::Device, vk::CommandBuffer),
+    {
+        let environment = capture.capture(command_pool, sampler_cache, &position, recorder)?;
+        Ok(Self::new(
+            capture.context.clone(),
+            command_pool,
+            upload_manager,
+            sampler_cache,
+            position,
+            influence,
+            environment,
+            &Self::default_settings(),
+        ))
+    }
+
+    // One inside the volume, fading to zero over the blend distance at its edge
+    pub fn weight(&self, point: &glm::Vec3) -> f32 {
+        let depth = self.influence.depth(&(point - self.position));
+        (depth / self.blend_distance.max(1e-4)).clamp(0.0, 1.0)
+    }
+
+    pub fn uniform(&self) -> ProbeUniform {
+        let (radius, extents) = match self.influence {
+            ProbeInfluence::Sphere { radius } => (radius, glm::Vec3::zeros()),
+            ProbeInfluence::Box { extents } => (0.0, extents),
+        };
+        ProbeUniform {
+            position: glm::vec4(self.position.x, self.position.y, self.position.z, radius),
+            extents: glm::vec4(extents.x, extents.y, extents.z, self.blend_distance),
+        }
+    }
+}
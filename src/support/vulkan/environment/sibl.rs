@@ -0,0 +1,344 @@
+use crate::vulkan::{DirectionalLight, EnvironmentSet, EnvironmentSource};
+use log::warn;
+use nalgebra_glm as glm;
+use snafu::{ensure, OptionExt, ResultExt, Snafu};
+use std::{collections::HashMap, f32::consts::PI, fs, path::Path};
+
+type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility = "pub(crate)")]
+pub enum Error {
+    #[snafu(display("Failed to read sIBL file {}: {}", path, source))]
+    ReadSiblFile {
+        source: std::io::Error,
+        path: String,
+    },
+
+    #[snafu(display("Line {} of sIBL file {} is not a section or an entry", line, path))]
+    InvalidLine { path: String, line: usize },
+
+    #[snafu(display("sIBL file {} has an invalid {} value: {}", path, key, value))]
+    InvalidValue {
+        path: String,
+        key: String,
+        value: String,
+    },
+
+    #[snafu(display("sIBL file {} has no {} entry", path, key))]
+    MissingEntry { path: String, key: String },
+
+    #[snafu(display(
+        "The {} image of sIBL file {} uses projection {}, only equirectangular (1) is supported",
+        key,
+        path,
+        projection
+    ))]
+    UnsupportedProjection {
+        path: String,
+        key: String,
+        projection: u32,
+    },
+}
+
+// An image of an sIBL set.
+// Gamma entries aren't read, since LDR images are decoded as sRGB and HDR images are linear.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SiblImage {
+    pub path: String,
+
+    // Scales the image's radiance when it lights the scene
+    pub multiplier: f32,
+}
+
+impl SiblImage {
+    pub fn source(&self) -> EnvironmentSource {
+        if self.path.to_lowercase().ends_with(".hdr") {
+            EnvironmentSource::Hdr(self.path.clone())
+        } else {
+            EnvironmentSource::Equirectangular(self.path.clone())
+        }
+    }
+
+    fn exists(&self) -> bool {
+        Path::new(&self.path).is_file()
+    }
+}
+
+// The sun's position in the set's images
+#[derive(Debug, Clone, PartialEq)]
+pub struct SiblSun {
+    pub color: glm::Vec3,
+    pub multiplier: f32,
+
+    // Texture coordinates of the sun in the equirectangular images
+    pub u: f32,
+    pub v: f32,
+}
+
+impl SiblSun {
+    // Measured from north along the images' u axis
+    pub fn azimuth(&self, north: f32) -> f32 {
+        (self.u - north).rem_euclid(1.0) * 2.0 * PI
+    }
+
+    // Zero on the horizon, the middle row of the images
+    pub fn elevation(&self) -> f32 {
+        (0.5 - self.v) * PI
+    }
+
+    // The longitude matches the projection in `equirectangular_to_cubemap.frag.glsl`,
+    // and y is flipped the same way the PBR shader flips its cubemap lookups.
+    // The result is turned about +y by `north_rotation`, so north faces -z.
+    pub fn direction(&self, north: f32) -> glm::Vec3 {
+        let longitude = (self.u - 0.5) * 2.0 * PI;
+        let elevation = self.elevation();
+        let direction = glm::vec3(
+            elevation.cos() * longitude.cos(),
+            elevation.sin(),
+            elevation.cos() * longitude.sin(),
+        );
+        glm::rotate_y_vec3(&direction, north_rotation(north))
+    }
+}
+
+// The angle about +y that turns the images' north, given as a u coordinate, to face -z
+fn north_rotation(north: f32) -> f32 {
+    (north - 0.5) * 2.0 * PI + PI / 2.0
+}
+
+// A Smart IBL (.ibl) file, describing a matched set of environment images and the sun
+// See http://www.hdrlabs.com/sibl/formatspecs.html
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sibl {
+    pub name: Option<String>,
+
+    // The u coordinate of north in the images
+    pub north: f32,
+
+    // A high resolution image for the skybox
+    pub background: Option<SiblImage>,
+
+    // A small, blurry image for diffuse lighting
+    pub environment: SiblImage,
+
+    // A medium resolution image for specular reflections
+    pub reflection: Option<SiblImage>,
+
+    pub sun: Option<SiblSun>,
+}
+
+impl Sibl {
+    pub fn from_file(path: &str) -> Result<Self> {
+        let text = fs::read_to_string(path).context(ReadSiblFile { path })?;
+        let directory = Path::new(path).parent().unwrap_or_else(|| Path::new(""));
+        Self::parse(&text, directory, path)
+    }
+
+    // Image paths are resolved relative to the directory the set is in
+    pub fn parse(text: &str, directory: &Path, path: &str) -> Result<Self> {
+        let mut sections = HashMap::new();
+        let mut section = String::new();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(';') || line.starts_with('#') {
+                continue;
+            }
+
+            if line.starts_with('[') && line.ends_with(']') {
+                section = line[1..line.len() - 1].trim().to_lowercase();
+                continue;
+            }
+
+            let (key, value) = line
+                .find('=')
+                .map(|equals| (line[..equals].trim(), line[equals + 1..].trim()))
+                .context(InvalidLine {
+                    path,
+                    line: index + 1,
+                })?;
+            sections
+                .entry(section.clone())
+                .or_insert_with(HashMap::new)
+                .insert(key.to_lowercase(), value.trim_matches('"').to_string());
+        }
+
+        let parser = EntryParser {
+            sections,
+            directory,
+            path,
+        };
+
+        let environment = parser
+            .image(&["enviroment", "environment"], "ev")?
+            .context(MissingEntry {
+                path,
+                key: "EVfile",
+            })?;
+
+        Ok(Self {
+            name: parser.entry("header", "name").map(str::to_string),
+            north: parser.float("header", "north")?.unwrap_or(0.0),
+            background: parser.image(&["background"], "bg")?,
+            environment,
+            reflection: parser.image(&["reflection"], "ref")?,
+            sun: parser.sun()?,
+        })
+    }
+
+    // Falls back to the lighting image when the set has no background or it's missing
+    pub fn background_image(&self) -> &SiblImage {
+        self.background
+            .as_ref()
+            .filter(|image| image.exists())
+            .unwrap_or(&self.environment)
+    }
+
+    // Falls back to the lighting image when the set has no reflection image or it's missing.
+    // Many published sets leave the reflection image out of the download.
+    pub fn reflection_image(&self) -> &SiblImage {
+        self.reflection
+            .as_ref()
+            .filter(|image| image.exists())
+            .unwrap_or(&self.environment)
+    }
+
+    pub fn environment_set(&self) -> EnvironmentSet {
+        for (image, name) in [
+            (&self.background, "background"),
+            (&self.reflection, "reflection"),
+        ]
+        .iter()
+        {
+            if let Some(image) = image.as_ref().filter(|image| !image.exists()) {
+                warn!(
+                    "The {} image {} is missing, the environment image is used instead",
+                    name, image.path
+                );
+            }
+        }
+
+        EnvironmentSet {
+            background: self.background_image().source(),
+            irradiance: self.environment.source(),
+            reflection: self.reflection_image().source(),
+        }
+    }
+
+    // Environment lookups have to be turned by the same angle for the sky to match the sun
+    pub fn north_rotation(&self) -> f32 {
+        north_rotation(self.north)
+    }
+
+    pub fn sun_light(&self) -> Option<DirectionalLight> {
+        self.sun
+            .as_ref()
+            .map(|sun| DirectionalLight::new(sun.direction(self.north), sun.color, sun.multiplier))
+    }
+}
+
+struct EntryParser<'a> {
+    sections: HashMap<String, HashMap<String, String>>,
+    directory: &'a Path,
+    path: &'a str,
+}
+
+impl EntryParser<'_> {
+    fn entry(&self, section: &str, key: &str) -> Option<&str> {
+        self.sections
+            .get(section)
+            .and_then(|entries| entries.get(key))
+            .map(String::as_str)
+    }
+
+    fn float(&self, section: &str, key: &str) -> Result<Option<f32>> {
+        self.entry(section, key)
+            .map(|value| value.parse().map_err(|_| self.invalid(key, value)))
+            .transpose()
+    }
+
+    // Images are described by entries sharing a prefix, such as EVfile and EVmulti.
+    // The sIBL specification spells the environment section "Enviroment".
+    fn image(&self, sections: &[&str], prefix: &str) -> Result<Option<SiblImage>> {
+        let section = match sections
+            .iter()
+            .find(|section| self.sections.contains_key(**section))
+        {
+            Some(section) => section,
+            None => return Ok(None),
+        };
+        let key = |suffix: &str| format!("{}{}", prefix, suffix);
+
+        let file = match self.entry(section, &key("file")) {
+            Some(file) => file,
+            None => return Ok(None),
+        };
+
+        if let Some(projection) = self.entry(section, &key("map")) {
+            let projection = projection
+                .parse::<u32>()
+                .map_err(|_| self.invalid(&key("map"), projection))?;
+            ensure!(
+                projection == 1,
+                UnsupportedProjection {
+                    path: self.path,
+                    key: key("file"),
+                    projection
+                }
+            );
+        }
+
+        let path = self.directory.join(file);
+        Ok(Some(SiblImage {
+            path: path.display().to_string(),
+            multiplier: self.float(section, &key("multi"))?.unwrap_or(1.0),
+        }))
+    }
+
+    fn sun(&self) -> Result<Option<SiblSun>> {
+        let (u, v) = match (self.float("sun", "sunu")?, self.float("sun", "sunv")?) {
+            (Some(u), Some(v)) => (u, v),
+            _ => return Ok(None),
+        };
+
+        // Colors are listed as 8 bit red, green and blue values
+        let color = match self.entry("sun", "suncolor") {
+            Some(value) => {
+                let channels = value
+                    .split(',')
+                    .map(|channel| channel.trim().parse::<u8>())
+                    .collect::<std::result::Result<Vec<_>, _>>()
+                    .map_err(|_| self.invalid("suncolor", value))?;
+                ensure!(
+                    channels.len() == 3,
+                    InvalidValue {
+                        path: self.path,
+                        key: "suncolor",
+                        value
+                    }
+                );
+                glm::vec3(
+                    f32::from(channels[0]),
+                    f32::from(channels[1]),
+                    f32::from(channels[2]),
+                ) / 255.0
+            }
+            None => glm::vec3(1.0, 1.0, 1.0),
+        };
+
+        Ok(Some(SiblSun {
+            color,
+            multiplier: self.float("sun", "sunmulti")?.unwrap_or(1.0),
+            u,
+            v,
+        }))
+    }
+
+    fn invalid(&self, key: &str, value: &str) -> Error {
+        Error::InvalidValue {
+            path: self.path.to_string(),
+            key: key.to_string(),
+            value: value.to_string(),
+        }
+    }
+}
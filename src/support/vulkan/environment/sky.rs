@@ -0,0 +1,340 @@
+use crate::vulkan::{
+    cube_face_direction, Cubemap, DirectionalLight, SamplerCache, TexelEncoding,
+    TextureDescription, UploadManager, UploadTicket, VulkanContext,
+};
+use ash::vk;
+use nalgebra_glm as glm;
+use snafu::{ResultExt, Snafu};
+use std::{f32::consts::PI, sync::Arc};
+
+type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility = "pub(crate)")]
+pub enum Error {
+    #[snafu(display("Failed to create sky cubemap: {}", source))]
+    CreateSkyCubemap {
+        source: crate::vulkan::texture::Error,
+    },
+
+    #[snafu(display("Failed to upload sky cubemap: {}", source))]
+    UploadSkyCubemap {
+        source: crate::vulkan::texture::Error,
+    },
+
+    #[snafu(display("Failed to submit sky cubemap upload: {}", source))]
+    SubmitSkyUpload {
+        source: crate::vulkan::upload_manager::Error,
+    },
+}
+
+// Perez distribution coefficients A through E, as linear functions of turbidity
+type PerezCoefficients = [(f32, f32); 5];
+
+const LUMINANCE_COEFFICIENTS: PerezCoefficients = [
+    (0.1787, -1.4630),
+    (-0.3554, 0.4275),
+    (-0.0227, 5.3251),
+    (0.1206, -2.5771),
+    (-0.0670, 0.3703),
+];
+
+const X_COEFFICIENTS: PerezCoefficients = [
+    (-0.0193, -0.2592),
+    (-0.0665, 0.0008),
+    (-0.0004, 0.2125),
+    (-0.0641, -0.8989),
+    (-0.0033, 0.0452),
+];
+
+const Y_COEFFICIENTS: PerezCoefficients = [
+    (-0.0167, -0.2608),
+    (-0.0950, 0.0092),
+    (-0.0079, 0.2102),
+    (-0.0441, -1.6537),
+    (-0.0109, 0.0529),
+];
+
+// Zenith luminance is in kcd/m², this brings a clear midday sky to around one
+const LUMINANCE_SCALE: f32 = 0.125;
+
+// The sun's light above the atmosphere, relative to the scaled sky
+const SUN_INTENSITY: f32 = 4.0;
+
+// Red, green and blue wavelengths in micrometers, for the sun's transmittance
+const WAVELENGTHS: [f32; 3] = [0.680, 0.550, 0.440];
+
+// The hemisphere is integrated on a coarse grid to light the ground
+const GROUND_ZENITH_STEPS: u32 = 16;
+const GROUND_AZIMUTH_STEPS: u32 = 32;
+
+// Preetham, Shirley and Smits' analytic model of a clear daytime sky,
+// from "A Practical Analytic Model for Daylight" (1999).
+// Directions have y up, like `DirectionalLight`, and the sky is rendered into cubemaps
+// the same way panoramas are projected, so it can stand in for any environment.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProceduralSky {
+    // Points from the scene towards the sun.
+    // Nights aren't modelled, so a sun below the horizon lights the sky as if it were on it.
+    pub sun_direction: glm::Vec3,
+
+    // Haziness of the atmosphere, from 2 for a very clear sky to 10 for a hazy one
+    pub turbidity: f32,
+
+    // Reflectance of the ground below the horizon, which the sky and the sun light
+    pub ground_albedo: glm::Vec3,
+}
+
+impl Default for ProceduralSky {
+    fn default() -> Self {
+        Self::new(glm::vec3(0.0, 0.5, 1.0), 3.0, glm::vec3(0.3, 0.3, 0.3))
+    }
+}
+
+impl ProceduralSky {
+    pub const FORMAT: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
+
+    pub const MIN_TURBIDITY: f32 = 2.0;
+    pub const MAX_TURBIDITY: f32 = 10.0;
+
+    pub fn new(sun_direction: glm::Vec3, turbidity: f32, ground_albedo: glm::Vec3) -> Self {
+        Self {
+            sun_direction: glm::normalize(&sun_direction),
+            turbidity: turbidity.clamp(Self::MIN_TURBIDITY, Self::MAX_TURBIDITY),
+            ground_albedo,
+        }
+    }
+
+    // The sun at an elevation above the horizon and an azimuth around it, both in radians.
+    // An azimuth of zero faces +z and a quarter turn faces +x.
+    pub fn from_sun_angles(
+        elevation: f32,
+        azimuth: f32,
+        turbidity: f32,
+        ground_albedo: glm::Vec3,
+    ) -> Self {
+        let direction = glm::vec3(
+            elevation.cos() * azimuth.sin(),
+            elevation.sin(),
+            elevation.cos() * azimuth.cos(),
+        );
+        Self::new(direction, turbidity, ground_albedo)
+    }
+
+    // Linear RGB radiance arriving from a direction
+    pub fn radiance(&self, direction: &glm::Vec3) -> glm::Vec3 {
+        let direction = glm::normalize(direction);
+        if direction.y < 0.0 {
+            return self.ground_radiance();
+        }
+        self.sky_radiance(&direction)
+    }
+
+    // The sun as a light, reddened by the air and haze it passes through and fading out as it sets
+    pub fn sun_light(&self) -> DirectionalLight {
+        let fade = smoothstep(-0.05, 0.1, self.sun_direction.y);
+        DirectionalLight::new(
+            self.sun_direction,
+            self.sun_transmittance(),
+            SUN_INTENSITY * fade,
+        )
+    }
+
+    // Renders the base level of every face, leaving the rest of the mip chain to be generated
+    pub fn description(&self, face_size: u32) -> TextureDescription {
+        let ground = self.ground_radiance();
+        let size = face_size as usize;
+        let mut values = Vec::with_capacity(size * size * 6 * 4);
+        for face in 0..6 {
+            for y in 0..size {
+                for x in 0..size {
+                    let s = (x as f32 + 0.5) / size as f32 * 2.0 - 1.0;
+                    let t = (y as f32 + 0.5) / size as f32 * 2.0 - 1.0;
+
+                    // Panoramas are projected with their top row at -y,
+                    // so the sky is flipped to match
+                    let direction = cube_face_direction(face, s, t);
+                    let direction = glm::vec3(direction.x, -direction.y, direction.z);
+                    let radiance = if direction.y < 0.0 {
+                        ground
+                    } else {
+                        self.sky_radiance(&direction)
+                    };
+                    values.extend_from_slice(&[radiance.x, radiance.y, radiance.z, 1.0]);
+                }
+            }
+        }
+
+        let encoding = TexelEncoding::of(Self::FORMAT).expect("The sky format is uncompressed");
+        TextureDescription {
+            pixels: encoding.encode_channels(&values),
+            ..TextureDescription::empty_cube(face_size, 1, Self::FORMAT)
+        }
+    }
+
+    pub fn create_cubemap(
+        &self,
+        context: Arc<VulkanContext>,
+        upload_manager: &mut UploadManager,
+        sampler_cache: &mut SamplerCache,
+        face_size: u32,
+    ) -> Result<Cubemap> {
+        let cubemap = Cubemap::from_description(
+            context,
+            upload_manager,
+            sampler_cache,
+            &self.description(face_size),
+        )
+        .context(CreateSkyCubemap {})?;
+        upload_manager.submit().context(SubmitSkyUpload {})?;
+        Ok(cubemap)
+    }
+
+    // Renders into a cubemap made by `create_cubemap`, keeping descriptor sets that use it valid.
+    // The cubemap must not be in use on the GPU. Wait on the ticket before baking from it.
+    pub fn update_cubemap(
+        &self,
+        upload_manager: &mut UploadManager,
+        cubemap: &Cubemap,
+    ) -> Result<UploadTicket> {
+        let mut description = TextureDescription {
+            mip_levels: cubemap.description.mip_levels,
+            ..self.description(cubemap.description.width)
+        };
+        // The cubemap may have been created in a different format the device supports
+        if description.format != cubemap.description.format {
+            description = description
+                .convert(cubemap.description.format)
+                .context(UploadSkyCubemap {})?;
+        }
+        cubemap
+            .texture
+            .upload_texture_data(upload_manager, &description)
+            .context(UploadSkyCubemap {})?;
+        upload_manager.submit().context(SubmitSkyUpload {})
+    }
+
+    // Rayleigh scattering and Ångström's aerosol extinction along the sun's path, as in the
+    // paper's appendix, with Kasten and Young's relative air mass
+    fn sun_transmittance(&self) -> glm::Vec3 {
+        let zenith = self.sky_sun_direction().y.min(1.0).acos();
+        let air_mass = 1.0 / (zenith.cos() + 0.15 * (93.885 - zenith.to_degrees()).powf(-1.253));
+        let haze = 0.04608 * self.turbidity - 0.04586;
+        let mut transmittance = glm::Vec3::zeros();
+        for (channel, wavelength) in transmittance.iter_mut().zip(WAVELENGTHS.iter()) {
+            let rayleigh = 0.008735 * wavelength.powf(-4.08);
+            let aerosol = haze * wavelength.powf(-1.3);
+            *channel = (-(rayleigh + aerosol) * air_mass).exp();
+        }
+        transmittance
+    }
+
+    fn sky_sun_direction(&self) -> glm::Vec3 {
+        glm::normalize(&glm::vec3(
+            self.sun_direction.x,
+            self.sun_direction.y.max(0.0),
+            self.sun_direction.z,
+        ))
+    }
+
+    fn sky_radiance(&self, direction: &glm::Vec3) -> glm::Vec3 {
+        let sun = self.sky_sun_direction();
+        let turbidity = self.turbidity;
+        let sun_zenith = sun.y.min(1.0).acos();
+
+        // Angle between the view direction and the sun
+        let gamma = glm::dot(direction, &sun).clamp(-1.0, 1.0).acos();
+        // Rays just above the horizon are kept off it, where the model divides by zero
+        let zenith = direction.y.max(0.01).acos();
+
+        let (zenith_luminance, zenith_x, zenith_y) = zenith_values(turbidity, sun_zenith);
+        let distribute = |coefficients: &PerezCoefficients, zenith_value: f32| {
+            let coefficients = perez_coefficients(coefficients, turbidity);
+            zenith_value * perez(&coefficients, zenith, gamma)
+                / perez(&coefficients, 0.0, sun_zenith)
+        };
+        let luminance = distribute(&LUMINANCE_COEFFICIENTS, zenith_luminance) * LUMINANCE_SCALE;
+        let x = distribute(&X_COEFFICIENTS, zenith_x);
+        let y = distribute(&Y_COEFFICIENTS, zenith_y);
+
+        xyy_to_linear_srgb(x, y, luminance)
+    }
+
+    // A diffuse ground reflects the irradiance the sky and sun cast on it
+    fn ground_radiance(&self) -> glm::Vec3 {
+        let zenith_step = 0.5 * PI / GROUND_ZENITH_STEPS as f32;
+        let azimuth_step = 2.0 * PI / GROUND_AZIMUTH_STEPS as f32;
+        let mut irradiance = glm::Vec3::zeros();
+        for zenith_index in 0..GROUND_ZENITH_STEPS {
+            let zenith = (zenith_index as f32 + 0.5) * zenith_step;
+            let weight = zenith.cos() * zenith.sin() * zenith_step * azimuth_step;
+            for azimuth_index in 0..GROUND_AZIMUTH_STEPS {
+                let azimuth = (azimuth_index as f32 + 0.5) * azimuth_step;
+                let direction = glm::vec3(
+                    zenith.sin() * azimuth.cos(),
+                    zenith.cos(),
+                    zenith.sin() * azimuth.sin(),
+                );
+                irradiance += self.sky_radiance(&direction) * weight;
+            }
+        }
+
+        let sun = self.sun_light();
+        irradiance += sun.radiance() * sun.direction.y.max(0.0);
+
+        self.ground_albedo.component_mul(&irradiance) / PI
+    }
+}
+
+fn perez_coefficients(coefficients: &PerezCoefficients, turbidity: f32) -> [f32; 5] {
+    let mut values = [0.0; 5];
+    for (value, (slope, offset)) in values.iter_mut().zip(coefficients.iter()) {
+        *value = slope * turbidity + offset;
+    }
+    values
+}
+
+// The Perez sky luminance distribution, relative to the zenith
+fn perez(coefficients: &[f32; 5], zenith: f32, gamma: f32) -> f32 {
+    let [a, b, c, d, e] = *coefficients;
+    (1.0 + a * (b / zenith.cos()).exp()) * (1.0 + c * (d * gamma).exp() + e * gamma.cos().powi(2))
+}
+
+// Luminance and chromaticity straight up, for a sun at the given zenith angle
+fn zenith_values(turbidity: f32, sun_zenith: f32) -> (f32, f32, f32) {
+    let chi = (4.0 / 9.0 - turbidity / 120.0) * (PI - 2.0 * sun_zenith);
+    let luminance = (4.0453 * turbidity - 4.9710) * chi.tan() - 0.2155 * turbidity + 2.4192;
+
+    let powers = glm::vec4(sun_zenith.powi(3), sun_zenith.powi(2), sun_zenith, 1.0);
+    let turbidities = glm::vec3(turbidity * turbidity, turbidity, 1.0);
+    let x_matrix = glm::mat3x4(
+        0.00166, -0.00375, 0.00209, 0.0, //
+        -0.02903, 0.06377, -0.03202, 0.00394, //
+        0.11693, -0.21196, 0.06052, 0.25886,
+    );
+    let y_matrix = glm::mat3x4(
+        0.00275, -0.00610, 0.00317, 0.0, //
+        -0.04214, 0.08970, -0.04153, 0.00516, //
+        0.15346, -0.26756, 0.06670, 0.26688,
+    );
+    let x = glm::dot(&turbidities, &(x_matrix * powers));
+    let y = glm::dot(&turbidities, &(y_matrix * powers));
+
+    (luminance.max(0.0), x, y)
+}
+
+fn xyy_to_linear_srgb(x: f32, y: f32, luminance: f32) -> glm::Vec3 {
+    let y = y.max(f32::EPSILON);
+    let xyz = glm::vec3(x / y * luminance, luminance, (1.0 - x - y) / y * luminance);
+    let rgb = glm::mat3(
+        3.2406, -1.5372, -0.4986, //
+        -0.9689, 1.8758, 0.0415, //
+        0.0557, -0.2040, 1.0570,
+    ) * xyz;
+    glm::max(&rgb, 0.0)
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
@@ -1,18 +1,32 @@
 use crate::vulkan::{
-    Buffer, CommandPool, Cubemap, DescriptorPool, DescriptorSetLayout, RenderPass, RenderPipeline,
-    RenderPipelineSettingsBuilder, ShaderCache, ShaderPathSetBuilder, UnitCube, VulkanContext,
+    Buffer, CubeVertex, Cubemap, DescriptorPool, DescriptorSetLayout, PipelineCache, RenderPass,
+    RenderPipeline, RenderPipelineSettingsBuilder, ShaderCache, ShaderPathSetBuilder, UnitCube,
+    UploadManager, Vertex, VulkanContext,
 };
 use ash::{version::DeviceV1_0, vk};
 use nalgebra_glm as glm;
-use std::{mem, sync::Arc};
+use std::{mem, rc::Rc, sync::Arc};
 
 pub fn create_skybox_pipeline(
     context: Arc<VulkanContext>,
     shader_cache: &mut ShaderCache,
+    pipeline_cache: &mut PipelineCache,
     render_pass: Arc<RenderPass>,
-) -> RenderPipeline {
-    let descriptions = UnitCube::vertex_input_descriptions();
-    let attributes = UnitCube::vertex_attributes();
+) -> Result<Rc<RenderPipeline>, crate::vulkan::pipeline::Error> {
+    let samples = context.max_usable_samples();
+    create_skybox_pipeline_with_samples(context, shader_cache, pipeline_cache, render_pass, samples)
+}
+
+// For render passes that don't multisample like the swapchain's, such as probe captures
+pub fn create_skybox_pipeline_with_samples(
+    context: Arc<VulkanContext>,
+    shader_cache: &mut ShaderCache,
+    pipeline_cache: &mut PipelineCache,
+    render_pass: Arc<RenderPass>,
+    samples: vk::SampleCountFlags,
+) -> Result<Rc<RenderPipeline>, crate::vulkan::pipeline::Error> {
+    let descriptions = [CubeVertex::binding_description(0)];
+    let attributes = CubeVertex::attribute_descriptions(0, 0);
     let vertex_state_info = vk::PipelineVertexInputStateCreateInfo::builder()
         .vertex_binding_descriptions(&descriptions)
         .vertex_attribute_descriptions(&attributes)
@@ -34,14 +48,14 @@ pub fn create_skybox_pipeline(
         .descriptor_set_layout(descriptor_set_layout)
         .shader_set(shader_set)
         .sample_shading_enabled(true)
-        .rasterization_samples(context.max_usable_samples())
+        .rasterization_samples(samples)
         .depth_test_enabled(false)
         .depth_write_enabled(false)
         .cull_mode(vk::CullModeFlags::FRONT)
         .build()
         .expect("Failed to create render pipeline settings!");
 
-    RenderPipeline::new(context, settings)
+    pipeline_cache.render_pipeline(settings)
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -58,7 +72,11 @@ pub struct SkyboxPipelineData {
 }
 
 impl SkyboxPipelineData {
-    pub fn new(context: Arc<VulkanContext>, command_pool: &CommandPool, cubemap: &Cubemap) -> Self {
+    pub fn new(
+        context: Arc<VulkanContext>,
+        upload_manager: &mut UploadManager,
+        cubemap: &Cubemap,
+    ) -> Self {
         let descriptor_set_layout = Self::descriptor_set_layout(context.clone());
         let descriptor_pool = Self::create_descriptor_pool(context.clone());
         let descriptor_set = descriptor_pool
@@ -73,7 +91,7 @@ impl SkyboxPipelineData {
         )
         .unwrap();
 
-        let cube = UnitCube::new(command_pool);
+        let cube = UnitCube::new(upload_manager);
 
         let data = SkyboxPipelineData {
             descriptor_pool,
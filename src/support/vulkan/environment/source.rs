@@ -0,0 +1,283 @@
+use crate::vulkan::{
+    format_info, ColorSpace, CommandPool, Cubemap, CubemapFaces, EnvironmentSettings, HdrCubemap,
+    SamplerCache, ShaderCache, TextureDescription, UploadManager, VulkanContext,
+};
+use snafu::{ensure, OptionExt, ResultExt, Snafu};
+use std::sync::Arc;
+
+type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility = "pub(crate)")]
+pub enum Error {
+    #[snafu(display("Failed to load environment image {}: {}", path, source))]
+    LoadEnvironmentImage {
+        source: crate::vulkan::texture::Error,
+        path: String,
+    },
+
+    #[snafu(display("Failed to assemble cubemap faces: {}", source))]
+    AssembleFaces {
+        source: crate::vulkan::texture::Error,
+    },
+
+    #[snafu(display("Cubemap faces must be square, but they are {}x{}", width, height))]
+    NonSquareFaces { width: u32, height: u32 },
+
+    #[snafu(display(
+        "A {}x{} image is neither a horizontal (4:3) nor a vertical (3:4) cross",
+        width,
+        height
+    ))]
+    UnknownCrossLayout { width: u32, height: u32 },
+
+    #[snafu(display("Failed to create cubemap: {}", source))]
+    CreateEnvironmentCubemap {
+        source: crate::vulkan::texture::Error,
+    },
+
+    #[snafu(display("Failed to project equirectangular image onto a cubemap: {}", source))]
+    ProjectEquirectangular {
+        source: crate::vulkan::environment::hdr::Error,
+    },
+
+    #[snafu(display("Failed to submit cubemap upload: {}", source))]
+    SubmitCubemapUpload {
+        source: crate::vulkan::upload_manager::Error,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CrossLayout {
+    // Four faces wide and three faces tall, with the side faces in the middle row
+    Horizontal,
+
+    // Three faces wide and four faces tall, with the back face upside down in the bottom row
+    Vertical,
+}
+
+impl CrossLayout {
+    pub fn of(width: u32, height: u32) -> Option<Self> {
+        if width * 3 == height * 4 {
+            Some(CrossLayout::Horizontal)
+        } else if width * 4 == height * 3 {
+            Some(CrossLayout::Vertical)
+        } else {
+            None
+        }
+    }
+
+    // The column and row of each face, in the same order as `CubemapFaces::ordered_faces`.
+    // The front face is in the middle of the cross.
+    fn face_tiles(self) -> [(u32, u32); 6] {
+        match self {
+            CrossLayout::Horizontal => [(2, 1), (0, 1), (1, 0), (1, 2), (3, 1), (1, 1)],
+            CrossLayout::Vertical => [(2, 1), (0, 1), (1, 0), (1, 2), (1, 3), (1, 1)],
+        }
+    }
+
+    fn face_dimension(self, width: u32) -> u32 {
+        match self {
+            CrossLayout::Horizontal => width / 4,
+            CrossLayout::Vertical => width / 3,
+        }
+    }
+}
+
+// Every way an environment can be provided.
+// They all produce a cubemap that the skybox and the IBL generators can sample.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EnvironmentSource {
+    // Six separate images, one per face
+    Faces(CubemapFaces),
+
+    // A single image with the faces laid out in a horizontal or vertical cross
+    Cross(String),
+
+    // A single low dynamic range image in an equirectangular projection
+    Equirectangular(String),
+
+    // A Radiance .hdr image in an equirectangular projection
+    Hdr(String),
+}
+
+impl EnvironmentSource {
+    pub fn load(
+        &self,
+        context: Arc<VulkanContext>,
+        command_pool: &CommandPool,
+        upload_manager: &mut UploadManager,
+        sampler_cache: &mut SamplerCache,
+        shader_cache: &mut ShaderCache,
+    ) -> Result<Cubemap> {
+        self.load_with_settings(
+            context,
+            command_pool,
+            upload_manager,
+            sampler_cache,
+            shader_cache,
+            &EnvironmentSettings::default(),
+        )
+    }
+
+    // The settings only apply to panoramas, face sets and crosses keep their own size and format
+    pub fn load_with_settings(
+        &self,
+        context: Arc<VulkanContext>,
+        command_pool: &CommandPool,
+        upload_manager: &mut UploadManager,
+        sampler_cache: &mut SamplerCache,
+        shader_cache: &mut ShaderCache,
+        settings: &EnvironmentSettings,
+    ) -> Result<Cubemap> {
+        let description = self.load_description()?;
+        if !description.cubemap {
+            return Self::project(
+                context,
+                command_pool,
+                upload_manager,
+                sampler_cache,
+                &description,
+                shader_cache,
+                settings,
+            );
+        }
+
+        let cubemap =
+            Cubemap::from_description(context, upload_manager, sampler_cache, &description)
+                .context(CreateEnvironmentCubemap {})?;
+        upload_manager.submit().context(SubmitCubemapUpload {})?;
+        Ok(cubemap)
+    }
+
+    // Reads the source's pixels without uploading them.
+    // Face sets and crosses become six layer cubes, while panoramas stay equirectangular.
+    pub fn load_description(&self) -> Result<TextureDescription> {
+        match self {
+            EnvironmentSource::Faces(faces) => Self::load_faces(faces),
+            EnvironmentSource::Cross(path) => Self::load_cross(path),
+            EnvironmentSource::Equirectangular(path) => {
+                let mut description = TextureDescription::from_file(path)
+                    .context(LoadEnvironmentImage { path: path.clone() })?;
+                description.set_color_space(ColorSpace::Srgb);
+                Ok(description)
+            }
+            EnvironmentSource::Hdr(path) => TextureDescription::from_hdr(path)
+                .context(LoadEnvironmentImage { path: path.clone() }),
+        }
+    }
+
+    fn project(
+        context: Arc<VulkanContext>,
+        command_pool: &CommandPool,
+        upload_manager: &mut UploadManager,
+        sampler_cache: &mut SamplerCache,
+        description: &TextureDescription,
+        shader_cache: &mut ShaderCache,
+        settings: &EnvironmentSettings,
+    ) -> Result<Cubemap> {
+        let hdr = HdrCubemap::from_equirectangular(
+            context,
+            command_pool,
+            upload_manager,
+            sampler_cache,
+            description,
+            shader_cache,
+            settings,
+        )
+        .context(ProjectEquirectangular {})?;
+        Ok(hdr.cubemap)
+    }
+
+    fn load_faces(faces: &CubemapFaces) -> Result<TextureDescription> {
+        let descriptions = faces
+            .ordered_faces()
+            .map(|path| {
+                let mut description =
+                    TextureDescription::from_file(&path).context(LoadEnvironmentImage { path })?;
+                description.set_color_space(ColorSpace::Srgb);
+                Ok(description)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Self::assemble(&descriptions)
+    }
+
+    fn load_cross(path: &str) -> Result<TextureDescription> {
+        let mut cross = TextureDescription::from_file(path).context(LoadEnvironmentImage {
+            path: path.to_string(),
+        })?;
+        cross.set_color_space(ColorSpace::Srgb);
+
+        let layout = CrossLayout::of(cross.width, cross.height).context(UnknownCrossLayout {
+            width: cross.width,
+            height: cross.height,
+        })?;
+        let dimension = layout.face_dimension(cross.width);
+        let mut faces = layout
+            .face_tiles()
+            .iter()
+            .map(|(column, row)| {
+                cross
+                    .crop(column * dimension, row * dimension, dimension, dimension)
+                    .context(AssembleFaces {})
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        if layout == CrossLayout::Vertical {
+            rotate_half_turn(&mut faces[4]);
+        }
+        Self::assemble(&faces)
+    }
+
+    fn assemble(faces: &[TextureDescription]) -> Result<TextureDescription> {
+        let mut description = TextureDescription::from_layers(faces).context(AssembleFaces {})?;
+        ensure!(
+            description.width == description.height,
+            NonSquareFaces {
+                width: description.width,
+                height: description.height
+            }
+        );
+        description.cubemap = true;
+        Ok(description)
+    }
+}
+
+// The images an environment is drawn and lit with.
+// Sets such as sIBL pair a detailed background with smaller, blurrier lighting images,
+// while a single image can serve all three purposes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnvironmentSet {
+    // Drawn behind the scene by the skybox
+    pub background: EnvironmentSource,
+
+    // Convolved into the diffuse irradiance map
+    pub irradiance: EnvironmentSource,
+
+    // Filtered into the specular prefilter map
+    pub reflection: EnvironmentSource,
+}
+
+impl From<EnvironmentSource> for EnvironmentSet {
+    fn from(source: EnvironmentSource) -> Self {
+        Self {
+            background: source.clone(),
+            irradiance: source.clone(),
+            reflection: source,
+        }
+    }
+}
+
+// Reversing the order of the texels turns a single level image upside down and back to front
+fn rotate_half_turn(description: &mut TextureDescription) {
+    let texel_size = format_info(description.format)
+        .expect("Cropped faces are always uncompressed")
+        .block_size as usize;
+    description.pixels = description
+        .pixels
+        .chunks_exact(texel_size)
+        .rev()
+        .flatten()
+        .copied()
+        .collect();
+}
@@ -0,0 +1,293 @@
+use crate::vulkan::{Buffer, VulkanContext};
+use ash::vk;
+use log::warn;
+use nalgebra_glm as glm;
+use snafu::{ResultExt, Snafu};
+use std::{mem, sync::Arc};
+
+type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility = "pub(crate)")]
+pub enum Error {
+    #[snafu(display("Failed to create light buffer: {}", source))]
+    CreateLightBuffer {
+        source: crate::vulkan::buffer::Error,
+    },
+
+    #[snafu(display("Failed to upload lights: {}", source))]
+    UploadLights {
+        source: crate::vulkan::buffer::Error,
+    },
+}
+
+// A light infinitely far away, such as the sun
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DirectionalLight {
+    // Points from the scene towards the light
+    pub direction: glm::Vec3,
+    pub color: glm::Vec3,
+    pub intensity: f32,
+}
+
+impl DirectionalLight {
+    pub fn new(direction: glm::Vec3, color: glm::Vec3, intensity: f32) -> Self {
+        Self {
+            direction: glm::normalize(&direction),
+            color,
+            intensity,
+        }
+    }
+
+    // The color scaled by the intensity, as the shaders consume it
+    pub fn radiance(&self) -> glm::Vec3 {
+        self.color * self.intensity
+    }
+}
+
+// A light shining equally in every direction from a point
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PointLight {
+    pub position: glm::Vec3,
+    pub color: glm::Vec3,
+    pub intensity: f32,
+
+    // The distance the light fades out at, unlimited when unset
+    pub range: Option<f32>,
+}
+
+impl PointLight {
+    pub fn new(position: glm::Vec3, color: glm::Vec3, intensity: f32) -> Self {
+        Self {
+            position,
+            color,
+            intensity,
+            range: None,
+        }
+    }
+
+    pub fn radiance(&self) -> glm::Vec3 {
+        self.color * self.intensity
+    }
+}
+
+// A light shining from a point in a cone.
+// It's at full strength inside the inner angle and fades out towards the outer angle.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpotLight {
+    pub position: glm::Vec3,
+
+    // Points from the light along the cone's axis
+    pub direction: glm::Vec3,
+
+    pub color: glm::Vec3,
+    pub intensity: f32,
+    pub range: Option<f32>,
+
+    // Angles from the axis, in radians
+    pub inner_cone_angle: f32,
+    pub outer_cone_angle: f32,
+}
+
+impl SpotLight {
+    // The defaults from `KHR_lights_punctual`
+    pub const DEFAULT_INNER_CONE_ANGLE: f32 = 0.0;
+    pub const DEFAULT_OUTER_CONE_ANGLE: f32 = std::f32::consts::FRAC_PI_4;
+
+    pub fn new(
+        position: glm::Vec3,
+        direction: glm::Vec3,
+        color: glm::Vec3,
+        intensity: f32,
+    ) -> Self {
+        Self {
+            position,
+            direction: glm::normalize(&direction),
+            color,
+            intensity,
+            range: None,
+            inner_cone_angle: Self::DEFAULT_INNER_CONE_ANGLE,
+            outer_cone_angle: Self::DEFAULT_OUTER_CONE_ANGLE,
+        }
+    }
+
+    pub fn radiance(&self) -> glm::Vec3 {
+        self.color * self.intensity
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PunctualLight {
+    Directional(DirectionalLight),
+    Point(PointLight),
+    Spot(SpotLight),
+}
+
+impl PunctualLight {
+    // Moves the light into the space the matrix transforms to.
+    // Scale only affects where the light is, not how bright it is or the shape of its cone.
+    pub fn transformed(&self, transform: &glm::Mat4) -> Self {
+        let point =
+            |position: &glm::Vec3| glm::vec4_to_vec3(&(transform * position.insert_row(3, 1.0)));
+        let direction = |direction: &glm::Vec3| {
+            glm::normalize(&glm::vec4_to_vec3(
+                &(transform * glm::vec3_to_vec4(direction)),
+            ))
+        };
+        match self {
+            PunctualLight::Directional(light) => PunctualLight::Directional(DirectionalLight {
+                direction: direction(&light.direction),
+                ..*light
+            }),
+            PunctualLight::Point(light) => PunctualLight::Point(PointLight {
+                position: point(&light.position),
+                ..*light
+            }),
+            PunctualLight::Spot(light) => PunctualLight::Spot(SpotLight {
+                position: point(&light.position),
+                direction: direction(&light.direction),
+                ..*light
+            }),
+        }
+    }
+
+    pub fn uniform(&self) -> LightUniform {
+        match self {
+            PunctualLight::Directional(light) => LightUniform {
+                position: glm::vec4(0.0, 0.0, 0.0, LightUniform::DIRECTIONAL),
+                direction: glm::vec3_to_vec4(&light.direction),
+                color: glm::vec3_to_vec4(&light.radiance()),
+                cone: glm::Vec4::zeros(),
+            },
+            PunctualLight::Point(light) => LightUniform {
+                position: light.position.insert_row(3, LightUniform::POINT),
+                direction: glm::vec4(0.0, 0.0, 0.0, light.range.unwrap_or(0.0)),
+                color: glm::vec3_to_vec4(&light.radiance()),
+                cone: glm::Vec4::zeros(),
+            },
+            PunctualLight::Spot(light) => {
+                // The cone's falloff is a linear ramp of the cosine of the angle to the axis,
+                // as recommended by `KHR_lights_punctual`
+                let inner = light.inner_cone_angle.cos();
+                let outer = light.outer_cone_angle.cos();
+                let scale = 1.0 / (inner - outer).max(0.001);
+                let towards_light = -light.direction;
+                LightUniform {
+                    position: light.position.insert_row(3, LightUniform::SPOT),
+                    direction: towards_light.insert_row(3, light.range.unwrap_or(0.0)),
+                    color: glm::vec3_to_vec4(&light.radiance()),
+                    cone: glm::vec4(scale, -outer * scale, 0.0, 0.0),
+                }
+            }
+        }
+    }
+}
+
+impl From<DirectionalLight> for PunctualLight {
+    fn from(light: DirectionalLight) -> Self {
+        PunctualLight::Directional(light)
+    }
+}
+
+impl From<PointLight> for PunctualLight {
+    fn from(light: PointLight) -> Self {
+        PunctualLight::Point(light)
+    }
+}
+
+impl From<SpotLight> for PunctualLight {
+    fn from(light: SpotLight) -> Self {
+        PunctualLight::Spot(light)
+    }
+}
+
+// A light packed into four vectors for the shaders
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct LightUniform {
+    // XYZ is the light's position, W is its type
+    pub position: glm::Vec4,
+
+    // XYZ points from the scene towards the light, or back along a spot's axis.
+    // W is the range, zero when unlimited.
+    pub direction: glm::Vec4,
+
+    // RGB is the color scaled by the intensity
+    pub color: glm::Vec4,
+
+    // X and Y scale and offset the cosine of the angle to a spot's axis into its falloff
+    pub cone: glm::Vec4,
+}
+
+impl LightUniform {
+    pub const DIRECTIONAL: f32 = 0.0;
+    pub const POINT: f32 = 1.0;
+    pub const SPOT: f32 = 2.0;
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct LightBufferObject {
+    // X value is the number of lights in use
+    count: glm::Vec4,
+    lights: [LightUniform; LightBuffer::MAX_LIGHTS],
+}
+
+// A uniform buffer holding the lights of a scene
+pub struct LightBuffer {
+    buffer: Buffer,
+}
+
+impl LightBuffer {
+    // This needs to match the defined value in the shaders
+    pub const MAX_LIGHTS: usize = 64;
+
+    pub fn new(context: Arc<VulkanContext>) -> Result<Self> {
+        let buffer = Buffer::new_mapped_basic(
+            context,
+            Self::size(),
+            vk::BufferUsageFlags::UNIFORM_BUFFER,
+            vk_mem::MemoryUsage::CpuToGpu,
+        )
+        .context(CreateLightBuffer {})?;
+        Ok(Self { buffer })
+    }
+
+    pub fn buffer(&self) -> vk::Buffer {
+        self.buffer.buffer()
+    }
+
+    pub fn size() -> vk::DeviceSize {
+        mem::size_of::<LightBufferObject>() as _
+    }
+
+    // Lights past the maximum are left out
+    pub fn upload(&self, lights: &[PunctualLight]) -> Result<()> {
+        if lights.len() > Self::MAX_LIGHTS {
+            warn!(
+                "Only {} of {} lights will be rendered",
+                Self::MAX_LIGHTS,
+                lights.len()
+            );
+        }
+
+        let empty = LightUniform {
+            position: glm::Vec4::zeros(),
+            direction: glm::Vec4::zeros(),
+            color: glm::Vec4::zeros(),
+            cone: glm::Vec4::zeros(),
+        };
+        let mut object = LightBufferObject {
+            count: glm::Vec4::zeros(),
+            lights: [empty; Self::MAX_LIGHTS],
+        };
+        for (uniform, light) in object.lights.iter_mut().zip(lights.iter()) {
+            *uniform = light.uniform();
+        }
+        object.count.x = lights.len().min(Self::MAX_LIGHTS) as f32;
+
+        self.buffer
+            .upload_to_buffer(&[object], 0)
+            .context(UploadLights {})
+    }
+}
@@ -1,11 +1,16 @@
 pub use self::{
-    asset::*, core::*, environment::*, pipeline::*, renderer::*, resource::*, shader_compilation::*,
+    asset::*, core::*, environment::*, light::*, pipeline::*, pipeline_cache::*, post::*,
+    renderer::*, resource::*, shader_compilation::*, shadow::*,
 };
 
 pub mod asset;
 pub mod core;
 pub mod environment;
+pub mod light;
 pub mod pipeline;
+pub mod pipeline_cache;
+pub mod post;
 pub mod renderer;
 pub mod resource;
 pub mod shader_compilation;
+pub mod shadow;
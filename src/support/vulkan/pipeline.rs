@@ -3,8 +3,32 @@ use crate::vulkan::{
 };
 use ash::{version::DeviceV1_0, vk};
 use derive_builder::Builder;
+use snafu::{ResultExt, Snafu};
 use std::sync::Arc;
 
+type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility = "pub(crate)")]
+pub enum Error {
+    #[snafu(display("Vertex attributes do not match the vertex shader inputs: {}", source))]
+    InvalidVertexAttributes {
+        source: crate::vulkan::shader::Error,
+    },
+}
+
+// Offsets the depth of each fragment, scaled by the depth format's smallest step
+// and by the polygon's depth slope, such as to keep shadow maps from shadowing themselves
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct DepthBias {
+    pub constant_factor: f32,
+    pub slope_factor: f32,
+
+    // The largest offset allowed, or zero for no limit.
+    // Other values need the depthBiasClamp device feature.
+    pub clamp: f32,
+}
+
 // TODO: Add a builder for this struct
 // TODO: Move shader paths into separate struct to be constructed with the builder pattern
 #[derive(Builder, Clone)]
@@ -44,6 +68,39 @@ pub struct RenderPipelineSettings {
 
     #[builder(default = "vk::CullModeFlags::NONE")]
     pub cull_mode: vk::CullModeFlags,
+
+    #[builder(default)]
+    pub depth_bias: Option<DepthBias>,
+
+    // Depth only pipelines render into passes without color attachments,
+    // and may leave out the fragment shader
+    #[builder(default)]
+    pub depth_only: bool,
+}
+
+impl RenderPipelineSettings {
+    // The vertex state info holds raw pointers,
+    // so these are only valid while the descriptions it was built from are alive
+    pub fn vertex_binding_descriptions(&self) -> &[vk::VertexInputBindingDescription] {
+        Self::slice_from(
+            self.vertex_state_info.p_vertex_binding_descriptions,
+            self.vertex_state_info.vertex_binding_description_count,
+        )
+    }
+
+    pub fn vertex_attribute_descriptions(&self) -> &[vk::VertexInputAttributeDescription] {
+        Self::slice_from(
+            self.vertex_state_info.p_vertex_attribute_descriptions,
+            self.vertex_state_info.vertex_attribute_description_count,
+        )
+    }
+
+    fn slice_from<'a, T>(pointer: *const T, count: u32) -> &'a [T] {
+        if pointer.is_null() || count == 0 {
+            return &[];
+        }
+        unsafe { std::slice::from_raw_parts(pointer, count as usize) }
+    }
 }
 
 pub struct RenderPipeline {
@@ -52,22 +109,26 @@ pub struct RenderPipeline {
 }
 
 impl RenderPipeline {
-    pub fn new(context: Arc<VulkanContext>, settings: RenderPipelineSettings) -> Self {
-        let shader_state_info = [
-            settings.shader_set.vertex_shader.state_info(),
-            settings
-                .shader_set
-                .fragment_shader
-                .as_ref()
-                .expect("Failed to lookup fragment shader!")
-                .state_info(),
-        ];
+    pub fn new(context: Arc<VulkanContext>, settings: RenderPipelineSettings) -> Result<Self> {
+        settings
+            .shader_set
+            .vertex_shader
+            .validate_vertex_attributes(settings.vertex_attribute_descriptions())
+            .context(InvalidVertexAttributes {})?;
+
+        let mut shader_state_info = vec![settings.shader_set.vertex_shader.state_info()];
+        match settings.shader_set.fragment_shader.as_ref() {
+            Some(fragment_shader) => shader_state_info.push(fragment_shader.state_info()),
+            None if settings.depth_only => {}
+            None => panic!("Failed to lookup fragment shader!"),
+        }
 
         let input_assembly_create_info = vk::PipelineInputAssemblyStateCreateInfo::builder()
             .topology(vk::PrimitiveTopology::TRIANGLE_LIST)
             .primitive_restart_enable(false)
             .build();
 
+        let depth_bias = settings.depth_bias.unwrap_or_default();
         let rasterizer_create_info = vk::PipelineRasterizationStateCreateInfo::builder()
             .depth_clamp_enable(false)
             .rasterizer_discard_enable(false)
@@ -75,10 +136,10 @@ impl RenderPipeline {
             .line_width(1.0)
             .cull_mode(settings.cull_mode)
             .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
-            .depth_bias_enable(false)
-            .depth_bias_constant_factor(0.0)
-            .depth_bias_clamp(0.0)
-            .depth_bias_slope_factor(0.0)
+            .depth_bias_enable(settings.depth_bias.is_some())
+            .depth_bias_constant_factor(depth_bias.constant_factor)
+            .depth_bias_clamp(depth_bias.clamp)
+            .depth_bias_slope_factor(depth_bias.slope_factor)
             .build();
 
         let multisampling_create_info = vk::PipelineMultisampleStateCreateInfo::builder()
@@ -101,16 +162,19 @@ impl RenderPipeline {
             .back(settings.stencil_back_state)
             .build();
 
-        let color_blend_attachments = if settings.blended {
-            Self::create_color_blend_attachments_blended()
-        } else {
-            Self::create_color_blend_attachments_opaque()
-        };
+        let color_blend_attachments: &[vk::PipelineColorBlendAttachmentState] =
+            if settings.depth_only {
+                &[]
+            } else if settings.blended {
+                &Self::create_color_blend_attachments_blended()
+            } else {
+                &Self::create_color_blend_attachments_opaque()
+            };
 
         let color_blending_info = vk::PipelineColorBlendStateCreateInfo::builder()
             .logic_op_enable(false)
             .logic_op(vk::LogicOp::COPY)
-            .attachments(&color_blend_attachments)
+            .attachments(color_blend_attachments)
             .blend_constants([0.0, 0.0, 0.0, 0.0])
             .build();
 
@@ -143,7 +207,7 @@ impl RenderPipeline {
 
         let pipeline = GraphicsPipeline::new(context, pipeline_create_info, pipeline_layout);
 
-        Self { pipeline, settings }
+        Ok(Self { pipeline, settings })
     }
 
     pub fn create_color_blend_attachments_opaque() -> [vk::PipelineColorBlendAttachmentState; 1] {
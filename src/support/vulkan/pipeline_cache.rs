@@ -0,0 +1,204 @@
+use crate::vulkan::{RenderPipeline, RenderPipelineSettings, VulkanContext};
+use ash::vk;
+use std::{collections::HashMap, rc::Rc, sync::Arc};
+
+type StencilOpKey = (
+    vk::StencilOp,
+    vk::StencilOp,
+    vk::StencilOp,
+    vk::CompareOp,
+    u32,
+    u32,
+    u32,
+);
+
+// The depth bias' constant factor, slope factor and clamp, compared by their bits
+type DepthBiasKey = (u32, u32, u32);
+
+// Everything that affects the resulting vk::Pipeline.
+// Handles are used for the render pass, layouts and shader modules,
+// so a recreated render pass or a reloaded shader produces a different key.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RenderPipelineKey {
+    render_pass: vk::RenderPass,
+    descriptor_set_layout: vk::DescriptorSetLayout,
+    shader_modules: Vec<(vk::ShaderStageFlags, vk::ShaderModule)>,
+    vertex_bindings: Vec<(u32, u32, vk::VertexInputRate)>,
+    vertex_attributes: Vec<(u32, u32, vk::Format, u32)>,
+    blended: bool,
+    depth_test_enabled: bool,
+    depth_write_enabled: bool,
+    stencil_test_enabled: bool,
+    stencil_front_state: StencilOpKey,
+    stencil_back_state: StencilOpKey,
+    push_constant_range: Option<(vk::ShaderStageFlags, u32, u32)>,
+    rasterization_samples: vk::SampleCountFlags,
+    sample_shading_enabled: bool,
+    cull_mode: vk::CullModeFlags,
+    depth_bias: Option<DepthBiasKey>,
+    depth_only: bool,
+}
+
+impl RenderPipelineKey {
+    // The key must be created while the vertex descriptions the settings point to are alive
+    pub fn new(settings: &RenderPipelineSettings) -> Self {
+        let vertex_bindings = settings
+            .vertex_binding_descriptions()
+            .iter()
+            .map(|binding| (binding.binding, binding.stride, binding.input_rate))
+            .collect();
+
+        let vertex_attributes = settings
+            .vertex_attribute_descriptions()
+            .iter()
+            .map(|attribute| {
+                (
+                    attribute.location,
+                    attribute.binding,
+                    attribute.format,
+                    attribute.offset,
+                )
+            })
+            .collect();
+
+        let shader_modules = settings
+            .shader_set
+            .shaders()
+            .iter()
+            .map(|shader| (shader.state_info().stage, shader.module()))
+            .collect();
+
+        Self {
+            render_pass: settings.render_pass.render_pass(),
+            descriptor_set_layout: settings.descriptor_set_layout.layout(),
+            shader_modules,
+            vertex_bindings,
+            vertex_attributes,
+            blended: settings.blended,
+            depth_test_enabled: settings.depth_test_enabled,
+            depth_write_enabled: settings.depth_write_enabled,
+            stencil_test_enabled: settings.stencil_test_enabled,
+            stencil_front_state: Self::stencil_op_key(&settings.stencil_front_state),
+            stencil_back_state: Self::stencil_op_key(&settings.stencil_back_state),
+            push_constant_range: settings
+                .push_constant_range
+                .map(|range| (range.stage_flags, range.offset, range.size)),
+            rasterization_samples: settings.rasterization_samples,
+            sample_shading_enabled: settings.sample_shading_enabled,
+            cull_mode: settings.cull_mode,
+            depth_bias: settings.depth_bias.map(|bias| {
+                (
+                    bias.constant_factor.to_bits(),
+                    bias.slope_factor.to_bits(),
+                    bias.clamp.to_bits(),
+                )
+            }),
+            depth_only: settings.depth_only,
+        }
+    }
+
+    fn stencil_op_key(state: &vk::StencilOpState) -> StencilOpKey {
+        (
+            state.fail_op,
+            state.pass_op,
+            state.depth_fail_op,
+            state.compare_op,
+            state.compare_mask,
+            state.write_mask,
+            state.reference,
+        )
+    }
+}
+
+pub struct PipelineCache {
+    context: Arc<VulkanContext>,
+    pipelines: HashMap<RenderPipelineKey, Rc<RenderPipeline>>,
+}
+
+impl PipelineCache {
+    pub fn new(context: Arc<VulkanContext>) -> Self {
+        Self {
+            context,
+            pipelines: HashMap::new(),
+        }
+    }
+
+    pub fn render_pipeline(
+        &mut self,
+        settings: RenderPipelineSettings,
+    ) -> Result<Rc<RenderPipeline>, crate::vulkan::pipeline::Error> {
+        self.remove_stale_pipelines();
+
+        let key = RenderPipelineKey::new(&settings);
+        if let Some(pipeline) = self.pipelines.get(&key) {
+            return Ok(pipeline.clone());
+        }
+
+        let pipeline = Rc::new(RenderPipeline::new(self.context.clone(), settings)?);
+        self.pipelines.insert(key, pipeline.clone());
+        Ok(pipeline)
+    }
+
+    pub fn len(&self) -> usize {
+        self.pipelines.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pipelines.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.pipelines.clear();
+    }
+
+    // A cached pipeline is stale when the only remaining references to its
+    // render pass, descriptor set layout or one of its shaders are held by cached pipelines.
+    // This happens when the swapchain recreates its render pass or the shader cache reloads a shader.
+    pub fn remove_stale_pipelines(&mut self) {
+        let mut references: HashMap<usize, usize> = HashMap::new();
+        for pipeline in self.pipelines.values() {
+            for (address, _) in Self::dependencies(pipeline) {
+                *references.entry(address).or_insert(0) += 1;
+            }
+        }
+
+        // Collect the keys before removing anything, as dropping a pipeline lowers the strong counts
+        let stale_keys = self
+            .pipelines
+            .iter()
+            .filter(|(_, pipeline)| {
+                Self::dependencies(pipeline)
+                    .iter()
+                    .any(|(address, strong_count)| *strong_count <= references[address])
+            })
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<_>>();
+
+        for key in stale_keys.iter() {
+            self.pipelines.remove(key);
+        }
+    }
+
+    // Returns the address and strong count of each shared resource the pipeline depends on
+    fn dependencies(pipeline: &RenderPipeline) -> Vec<(usize, usize)> {
+        let settings = &pipeline.settings;
+        let mut dependencies = vec![
+            (
+                Arc::as_ptr(&settings.render_pass) as usize,
+                Arc::strong_count(&settings.render_pass),
+            ),
+            (
+                Arc::as_ptr(&settings.descriptor_set_layout) as usize,
+                Arc::strong_count(&settings.descriptor_set_layout),
+            ),
+        ];
+        dependencies.extend(
+            settings
+                .shader_set
+                .shaders()
+                .iter()
+                .map(|shader| (Arc::as_ptr(shader) as usize, Arc::strong_count(shader))),
+        );
+        dependencies
+    }
+}
@@ -0,0 +1,554 @@
+use crate::{
+    byte_slice_from,
+    vulkan::{
+        Buffer, DescriptorPool, DescriptorSetLayout, Framebuffer, HdrTarget, ImageView,
+        PipelineCache, RenderPass, RenderPipeline, RenderPipelineSettingsBuilder, Sampler,
+        ShaderCache, ShaderPathSetBuilder, Texture, VulkanContext,
+    },
+};
+use ash::{version::DeviceV1_0, vk};
+use snafu::{ResultExt, Snafu};
+use std::{cmp, mem, rc::Rc, sync::Arc};
+
+type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility = "pub(crate)")]
+pub enum Error {
+    #[snafu(display("Failed to create bloom render pass: {}", source))]
+    CreateBloomRenderPass {
+        source: crate::vulkan::renderpass::Error,
+    },
+
+    #[snafu(display("Failed to create bloom texture: {}", source))]
+    CreateBloomTexture {
+        source: crate::vulkan::texture::Error,
+    },
+
+    #[snafu(display("Failed to create bloom view: {}", source))]
+    CreateBloomView {
+        source: crate::vulkan::image_view::Error,
+    },
+
+    #[snafu(display("Failed to create bloom framebuffer: {}", source))]
+    CreateBloomFramebuffer {
+        source: crate::vulkan::framebuffer::Error,
+    },
+
+    #[snafu(display("Failed to create bloom pipeline: {}", source))]
+    CreateBloomPipeline {
+        source: crate::vulkan::pipeline::Error,
+    },
+
+    #[snafu(display("Failed to create bloom descriptor pool: {}", source))]
+    CreateBloomDescriptorPool {
+        source: crate::vulkan::descriptor_pool::Error,
+    },
+
+    #[snafu(display("Failed to allocate bloom descriptor sets: {}", source))]
+    AllocateBloomDescriptorSets {
+        source: crate::vulkan::descriptor_pool::Error,
+    },
+}
+
+// A level of the mip chain, with a view and framebuffer of its own
+// so that it can be read while a neighbouring level is written
+struct BloomMip {
+    extent: vk::Extent2D,
+    view: ImageView,
+    framebuffer: Framebuffer,
+}
+
+// The mip chain depends on the size of the scene, the render passes don't
+struct BloomMips {
+    _texture: Texture,
+    mips: Vec<BloomMip>,
+}
+
+struct BloomPipelines {
+    downsample: Rc<RenderPipeline>,
+    upsample: Rc<RenderPipeline>,
+}
+
+// Blurs the scene's radiance through a mip chain starting at half its resolution.
+// Each level is downsampled from the one above it with a 13 tap filter, then the levels
+// are upsampled back up with a tent filter, each one lerped halfway into the level above.
+// The weights of the levels add up to one, so the blurred radiance keeps the scene's energy.
+pub struct Bloom {
+    context: Arc<VulkanContext>,
+    downsample_render_pass: Arc<RenderPass>,
+    upsample_render_pass: RenderPass,
+    descriptor_set_layout: Arc<DescriptorSetLayout>,
+    _descriptor_pool: DescriptorPool,
+
+    // Reading the hdr target, then each of the levels
+    descriptor_sets: Vec<vk::DescriptorSet>,
+
+    mips: BloomMips,
+    pipelines: Option<BloomPipelines>,
+}
+
+impl Bloom {
+    pub const MAX_MIPS: usize = 6;
+
+    pub const DOWNSAMPLE_SHADER_PATH: &'static str =
+        "assets/shaders/post/bloom_downsample.frag.spv";
+    pub const UPSAMPLE_SHADER_PATH: &'static str = "assets/shaders/post/bloom_upsample.frag.spv";
+
+    // Downsampling writes every texel of a level, so it shares the chain's render pass.
+    // The descriptor sets share the chain's layout, reading the image at binding 0
+    // and the bloom pass' settings from the uniform buffer at binding 1.
+    pub fn new(
+        context: Arc<VulkanContext>,
+        downsample_render_pass: Arc<RenderPass>,
+        descriptor_set_layout: Arc<DescriptorSetLayout>,
+        extent: vk::Extent2D,
+    ) -> Result<Self> {
+        let upsample_render_pass = Self::create_upsample_render_pass(context.clone())?;
+        let mips = Self::create_mips(context.clone(), &downsample_render_pass, extent)?;
+
+        let descriptor_pool = Self::create_descriptor_pool(context.clone())?;
+        let descriptor_sets = descriptor_pool
+            .allocate_descriptor_sets(descriptor_set_layout.layout(), (Self::MAX_MIPS + 1) as _)
+            .context(AllocateBloomDescriptorSets {})?;
+
+        Ok(Self {
+            context,
+            downsample_render_pass,
+            upsample_render_pass,
+            descriptor_set_layout,
+            _descriptor_pool: descriptor_pool,
+            descriptor_sets,
+            mips,
+            pipelines: None,
+        })
+    }
+
+    // The descriptor sets have to be updated afterwards
+    pub fn resize(&mut self, extent: vk::Extent2D) -> Result<()> {
+        self.mips = Self::create_mips(self.context.clone(), &self.downsample_render_pass, extent)?;
+        Ok(())
+    }
+
+    // The blurred radiance at half the scene's resolution
+    pub fn view(&self) -> &ImageView {
+        &self.mips.mips[0].view
+    }
+
+    pub fn create_pipelines(
+        &mut self,
+        shader_cache: &mut ShaderCache,
+        pipeline_cache: &mut PipelineCache,
+    ) -> Result<()> {
+        let downsample = self.create_pipeline(
+            shader_cache,
+            pipeline_cache,
+            Self::DOWNSAMPLE_SHADER_PATH,
+            self.downsample_render_pass.clone(),
+            false,
+        )?;
+
+        // The render passes are compatible, so the pipeline can be created for either of them
+        let upsample = self.create_pipeline(
+            shader_cache,
+            pipeline_cache,
+            Self::UPSAMPLE_SHADER_PATH,
+            self.downsample_render_pass.clone(),
+            true,
+        )?;
+
+        self.pipelines = Some(BloomPipelines {
+            downsample,
+            upsample,
+        });
+        Ok(())
+    }
+
+    pub fn update_descriptor_sets(
+        &self,
+        hdr_view: &ImageView,
+        uniform_buffer: &Buffer,
+        uniform_buffer_size: vk::DeviceSize,
+        sampler: &Sampler,
+    ) {
+        let buffer_infos = [vk::DescriptorBufferInfo::builder()
+            .buffer(uniform_buffer.buffer())
+            .offset(0)
+            .range(uniform_buffer_size)
+            .build()];
+
+        let image_infos = Some(hdr_view)
+            .into_iter()
+            .chain(self.mips.mips.iter().map(|mip| &mip.view))
+            .map(|view| {
+                [vk::DescriptorImageInfo::builder()
+                    .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+                    .image_view(view.view())
+                    .sampler(sampler.sampler())
+                    .build()]
+            })
+            .collect::<Vec<_>>();
+
+        let descriptor_writes = self
+            .descriptor_sets
+            .iter()
+            .zip(image_infos.iter())
+            .flat_map(|(descriptor_set, image_info)| {
+                vec![
+                    vk::WriteDescriptorSet::builder()
+                        .dst_set(*descriptor_set)
+                        .dst_binding(0)
+                        .dst_array_element(0)
+                        .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                        .image_info(image_info)
+                        .build(),
+                    vk::WriteDescriptorSet::builder()
+                        .dst_set(*descriptor_set)
+                        .dst_binding(1)
+                        .dst_array_element(0)
+                        .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+                        .buffer_info(&buffer_infos)
+                        .build(),
+                ]
+            })
+            .collect::<Vec<_>>();
+
+        unsafe {
+            self.context
+                .logical_device()
+                .logical_device()
+                .update_descriptor_sets(&descriptor_writes, &[])
+        }
+    }
+
+    // Downsamples the resolved hdr target through every level, then upsamples back into the first.
+    // The pass index selects the bloom pass whose settings are used.
+    pub fn record(
+        &self,
+        device: &ash::Device,
+        command_buffer: vk::CommandBuffer,
+        pass_index: usize,
+    ) {
+        let pipelines = self
+            .pipelines
+            .as_ref()
+            .expect("Failed to get bloom pipelines!");
+        let mips = &self.mips.mips;
+
+        // The first level reads the hdr target, the others read the level before them
+        for level in 0..mips.len() {
+            self.draw(
+                device,
+                command_buffer,
+                &self.downsample_render_pass,
+                &pipelines.downsample,
+                pass_index,
+                level,
+                self.descriptor_sets[level],
+            );
+        }
+
+        // Each level is blended into the one above it, from the smallest up
+        for level in (0..mips.len().saturating_sub(1)).rev() {
+            self.draw(
+                device,
+                command_buffer,
+                &self.upsample_render_pass,
+                &pipelines.upsample,
+                pass_index,
+                level,
+                self.descriptor_sets[level + 2],
+            );
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn draw(
+        &self,
+        device: &ash::Device,
+        command_buffer: vk::CommandBuffer,
+        render_pass: &RenderPass,
+        pipeline: &RenderPipeline,
+        pass_index: usize,
+        level: usize,
+        descriptor_set: vk::DescriptorSet,
+    ) {
+        let mip = &self.mips.mips[level];
+        let render_area = vk::Rect2D {
+            offset: vk::Offset2D { x: 0, y: 0 },
+            extent: mip.extent,
+        };
+        let viewports = [vk::Viewport {
+            x: 0.0,
+            y: 0.0,
+            width: mip.extent.width as _,
+            height: mip.extent.height as _,
+            min_depth: 0.0,
+            max_depth: 1.0,
+        }];
+        let scissors = [render_area];
+        let render_pass_begin_info = vk::RenderPassBeginInfo::builder()
+            .render_pass(render_pass.render_pass())
+            .framebuffer(mip.framebuffer.framebuffer())
+            .render_area(render_area)
+            .build();
+
+        // This needs to match the push constant block in the bloom shaders
+        let push_constants = [pass_index as i32, level as i32];
+
+        render_pass.record(command_buffer, &render_pass_begin_info, || unsafe {
+            device.cmd_set_viewport(command_buffer, 0, &viewports);
+            device.cmd_set_scissor(command_buffer, 0, &scissors);
+            pipeline.bind(device, command_buffer);
+            device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                pipeline.pipeline.layout(),
+                0,
+                &[descriptor_set],
+                &[],
+            );
+            device.cmd_push_constants(
+                command_buffer,
+                pipeline.pipeline.layout(),
+                vk::ShaderStageFlags::FRAGMENT,
+                0,
+                byte_slice_from(&push_constants),
+            );
+            device.cmd_draw(command_buffer, 3, 1, 0, 0);
+        });
+    }
+
+    fn create_pipeline(
+        &self,
+        shader_cache: &mut ShaderCache,
+        pipeline_cache: &mut PipelineCache,
+        fragment_shader_path: &str,
+        render_pass: Arc<RenderPass>,
+        blended: bool,
+    ) -> Result<Rc<RenderPipeline>> {
+        let vertex_state_info = vk::PipelineVertexInputStateCreateInfo::builder().build();
+
+        let push_constant_range = vk::PushConstantRange::builder()
+            .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+            .size(mem::size_of::<[i32; 2]>() as u32)
+            .build();
+
+        let shader_paths = ShaderPathSetBuilder::default()
+            .vertex("assets/shaders/post/fullscreen.vert.spv")
+            .fragment(fragment_shader_path)
+            .build()
+            .unwrap();
+        let shader_set = shader_cache
+            .create_shader_set(self.context.clone(), &shader_paths)
+            .unwrap();
+
+        let settings = RenderPipelineSettingsBuilder::default()
+            .render_pass(render_pass)
+            .vertex_state_info(vertex_state_info)
+            .descriptor_set_layout(self.descriptor_set_layout.clone())
+            .shader_set(shader_set)
+            .rasterization_samples(vk::SampleCountFlags::TYPE_1)
+            .depth_test_enabled(false)
+            .depth_write_enabled(false)
+            .push_constant_range(push_constant_range)
+            .blended(blended)
+            .build()
+            .expect("Failed to create render pipeline settings!");
+
+        pipeline_cache
+            .render_pipeline(settings)
+            .context(CreateBloomPipeline {})
+    }
+
+    fn create_descriptor_pool(context: Arc<VulkanContext>) -> Result<DescriptorPool> {
+        let number_of_sets = (Self::MAX_MIPS + 1) as u32;
+
+        let sampler_pool_size = vk::DescriptorPoolSize {
+            ty: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            descriptor_count: number_of_sets,
+        };
+
+        let ubo_pool_size = vk::DescriptorPoolSize {
+            ty: vk::DescriptorType::UNIFORM_BUFFER,
+            descriptor_count: number_of_sets,
+        };
+
+        let pool_sizes = [sampler_pool_size, ubo_pool_size];
+
+        let pool_info = vk::DescriptorPoolCreateInfo::builder()
+            .pool_sizes(&pool_sizes)
+            .max_sets(number_of_sets)
+            .build();
+
+        DescriptorPool::new(context, pool_info).context(CreateBloomDescriptorPool {})
+    }
+
+    // Upsampling blends into what the downsampling left in the level, so it has to be loaded
+    fn create_upsample_render_pass(context: Arc<VulkanContext>) -> Result<RenderPass> {
+        let color_attachment_description = vk::AttachmentDescription::builder()
+            .format(HdrTarget::FORMAT)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .load_op(vk::AttachmentLoadOp::LOAD)
+            .store_op(vk::AttachmentStoreOp::STORE)
+            .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+            .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+            .initial_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+            .final_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+            .build();
+        let attachment_descriptions = [color_attachment_description];
+
+        let color_attachment_references = [vk::AttachmentReference::builder()
+            .attachment(0)
+            .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+            .build()];
+
+        let subpass_description = vk::SubpassDescription::builder()
+            .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+            .color_attachments(&color_attachment_references)
+            .build();
+        let subpass_descriptions = [subpass_description];
+
+        // The level has to be written by its downsampling and no longer be read
+        // before it's blended into, and it has to be written before the next pass reads it
+        let subpass_dependencies = [
+            vk::SubpassDependency::builder()
+                .src_subpass(vk::SUBPASS_EXTERNAL)
+                .dst_subpass(0)
+                .src_stage_mask(
+                    vk::PipelineStageFlags::FRAGMENT_SHADER
+                        | vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                )
+                .src_access_mask(
+                    vk::AccessFlags::SHADER_READ | vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+                )
+                .dst_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+                .dst_access_mask(
+                    vk::AccessFlags::COLOR_ATTACHMENT_READ
+                        | vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+                )
+                .build(),
+            vk::SubpassDependency::builder()
+                .src_subpass(0)
+                .dst_subpass(vk::SUBPASS_EXTERNAL)
+                .src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+                .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+                .dst_stage_mask(vk::PipelineStageFlags::FRAGMENT_SHADER)
+                .dst_access_mask(vk::AccessFlags::SHADER_READ)
+                .build(),
+        ];
+
+        let create_info = vk::RenderPassCreateInfo::builder()
+            .attachments(&attachment_descriptions)
+            .subpasses(&subpass_descriptions)
+            .dependencies(&subpass_dependencies)
+            .build();
+
+        RenderPass::new(context, &create_info).context(CreateBloomRenderPass {})
+    }
+
+    // Levels are halved until the smallest side is a single texel or the maximum is reached
+    fn mip_count(extent: vk::Extent2D) -> usize {
+        let smallest_side = cmp::max(cmp::min(extent.width, extent.height) / 2, 1);
+        let levels = (32 - smallest_side.leading_zeros()) as usize;
+        cmp::min(levels, Self::MAX_MIPS)
+    }
+
+    fn create_mips(
+        context: Arc<VulkanContext>,
+        render_pass: &RenderPass,
+        extent: vk::Extent2D,
+    ) -> Result<BloomMips> {
+        let mip_count = Self::mip_count(extent);
+        let base_extent = vk::Extent2D {
+            width: cmp::max(extent.width / 2, 1),
+            height: cmp::max(extent.height / 2, 1),
+        };
+
+        let image_create_info = vk::ImageCreateInfo::builder()
+            .image_type(vk::ImageType::TYPE_2D)
+            .extent(vk::Extent3D {
+                width: base_extent.width,
+                height: base_extent.height,
+                depth: 1,
+            })
+            .mip_levels(mip_count as _)
+            .array_layers(1)
+            .format(HdrTarget::FORMAT)
+            .tiling(vk::ImageTiling::OPTIMAL)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .usage(vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .flags(vk::ImageCreateFlags::empty())
+            .build();
+
+        let allocation_create_info = vk_mem::AllocationCreateInfo {
+            usage: vk_mem::MemoryUsage::GpuOnly,
+            ..Default::default()
+        };
+
+        let texture = Texture::new(context.clone(), &allocation_create_info, &image_create_info)
+            .context(CreateBloomTexture {})?;
+
+        let mips = (0..mip_count)
+            .map(|level| {
+                let extent = vk::Extent2D {
+                    width: cmp::max(base_extent.width >> level, 1),
+                    height: cmp::max(base_extent.height >> level, 1),
+                };
+                Self::create_mip(context.clone(), render_pass, &texture, level as _, extent)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(BloomMips {
+            _texture: texture,
+            mips,
+        })
+    }
+
+    fn create_mip(
+        context: Arc<VulkanContext>,
+        render_pass: &RenderPass,
+        texture: &Texture,
+        level: u32,
+        extent: vk::Extent2D,
+    ) -> Result<BloomMip> {
+        let view_create_info = vk::ImageViewCreateInfo::builder()
+            .image(texture.image())
+            .view_type(vk::ImageViewType::TYPE_2D)
+            .format(HdrTarget::FORMAT)
+            .components(vk::ComponentMapping {
+                r: vk::ComponentSwizzle::IDENTITY,
+                g: vk::ComponentSwizzle::IDENTITY,
+                b: vk::ComponentSwizzle::IDENTITY,
+                a: vk::ComponentSwizzle::IDENTITY,
+            })
+            .subresource_range(vk::ImageSubresourceRange {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                base_mip_level: level,
+                level_count: 1,
+                base_array_layer: 0,
+                layer_count: 1,
+            })
+            .build();
+        let view = ImageView::new(context.clone(), view_create_info).context(CreateBloomView {})?;
+
+        let attachments = [view.view()];
+        let framebuffer_create_info = vk::FramebufferCreateInfo::builder()
+            .render_pass(render_pass.render_pass())
+            .attachments(&attachments)
+            .width(extent.width)
+            .height(extent.height)
+            .layers(1)
+            .build();
+        let framebuffer = Framebuffer::new(context, framebuffer_create_info)
+            .context(CreateBloomFramebuffer {})?;
+
+        Ok(BloomMip {
+            extent,
+            view,
+            framebuffer,
+        })
+    }
+}
@@ -0,0 +1,731 @@
+use crate::{
+    byte_slice_from,
+    vulkan::{
+        Bloom, Buffer, ColorSpace, DescriptorPool, DescriptorSetLayout, Framebuffer, HdrTarget,
+        ImageView, PipelineCache, PostEffect, PostPass, RenderPass, RenderPipeline,
+        RenderPipelineSettingsBuilder, Sampler, ShaderCache, ShaderPathSetBuilder, Texture,
+        VulkanContext,
+    },
+};
+use ash::{version::DeviceV1_0, vk};
+use log::warn;
+use nalgebra_glm as glm;
+use snafu::{ResultExt, Snafu};
+use std::{collections::HashMap, mem, rc::Rc, sync::Arc};
+
+type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility = "pub(crate)")]
+pub enum Error {
+    #[snafu(display("Failed to create hdr target: {}", source))]
+    CreateHdrTarget {
+        source: crate::vulkan::post::target::Error,
+    },
+
+    #[snafu(display("Failed to create bloom: {}", source))]
+    CreateBloom {
+        source: crate::vulkan::post::bloom::Error,
+    },
+
+    #[snafu(display("Failed to create post processing pipeline: {}", source))]
+    CreatePostPipeline {
+        source: crate::vulkan::pipeline::Error,
+    },
+
+    #[snafu(display("Failed to create post processing render pass: {}", source))]
+    CreatePostRenderPass {
+        source: crate::vulkan::renderpass::Error,
+    },
+
+    #[snafu(display("Failed to create post processing texture: {}", source))]
+    CreatePostTexture {
+        source: crate::vulkan::texture::Error,
+    },
+
+    #[snafu(display("Failed to create post processing view: {}", source))]
+    CreatePostView {
+        source: crate::vulkan::image_view::Error,
+    },
+
+    #[snafu(display("Failed to create post processing framebuffer: {}", source))]
+    CreatePostFramebuffer {
+        source: crate::vulkan::framebuffer::Error,
+    },
+
+    #[snafu(display("Failed to create post processing sampler: {}", source))]
+    CreatePostSampler {
+        source: crate::vulkan::sampler::Error,
+    },
+
+    #[snafu(display("Failed to create post processing descriptor set layout: {}", source))]
+    CreatePostDescriptorSetLayout {
+        source: crate::vulkan::descriptor_set_layout::Error,
+    },
+
+    #[snafu(display("Failed to create post processing descriptor pool: {}", source))]
+    CreatePostDescriptorPool {
+        source: crate::vulkan::descriptor_pool::Error,
+    },
+
+    #[snafu(display("Failed to allocate post processing descriptor sets: {}", source))]
+    AllocatePostDescriptorSets {
+        source: crate::vulkan::descriptor_pool::Error,
+    },
+
+    #[snafu(display("Failed to create post processing uniform buffer: {}", source))]
+    CreatePostUniformBuffer {
+        source: crate::vulkan::buffer::Error,
+    },
+
+    #[snafu(display("Failed to upload post processing uniforms: {}", source))]
+    UploadPostUniforms {
+        source: crate::vulkan::buffer::Error,
+    },
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct PostProcessBufferObject {
+    pub parameters: [glm::Vec4; PostProcessChain::MAX_PASSES],
+}
+
+// A single sampled target that passes render into for the next pass to read
+struct PostImage {
+    _texture: Texture,
+    view: ImageView,
+    framebuffer: Framebuffer,
+}
+
+// Each effect is drawn into the chain's own targets when another pass follows it,
+// and into the output's render pass when it's the last
+struct PostPipelines {
+    intermediate: Rc<RenderPipeline>,
+    output: Rc<RenderPipeline>,
+}
+
+// Scenes render into the chain's hdr target, which the enabled passes then process in order.
+// Every pass but the last renders into one of two targets that take turns being read and written,
+// and the last one is drawn into the output's render pass, such as the swapchain's.
+//
+// The effects' settings are read from a uniform buffer, so they can change from frame to frame.
+// Enabling or disabling passes changes what's drawn, so command buffers have to be recorded again.
+pub struct PostProcessChain {
+    context: Arc<VulkanContext>,
+    target: HdrTarget,
+    render_pass: Arc<RenderPass>,
+    images: [PostImage; 2],
+    sampler: Sampler,
+    descriptor_set_layout: Arc<DescriptorSetLayout>,
+    _descriptor_pool: DescriptorPool,
+
+    // Reading the hdr target, then each of the two images
+    descriptor_sets: Vec<vk::DescriptorSet>,
+
+    uniform_buffer: Buffer,
+    bloom: Bloom,
+    passes: Vec<PostPass>,
+    pipelines: HashMap<&'static str, PostPipelines>,
+    srgb_output: bool,
+    recorded_passes: Vec<usize>,
+}
+
+impl PostProcessChain {
+    // This needs to match the defined value in the shaders
+    pub const MAX_PASSES: usize = 8;
+
+    // Blooms, exposes, tonemaps and encodes the scene for an sRGB display
+    pub fn default_effects() -> Vec<PostEffect> {
+        vec![
+            PostEffect::Bloom(Default::default()),
+            PostEffect::Exposure(1.0),
+            PostEffect::Tonemap(Default::default()),
+            PostEffect::Output(Default::default()),
+        ]
+    }
+
+    // The output format decides whether the output pass has to undo the target's sRGB encoding.
+    // Effects past the maximum are left out.
+    pub fn new(
+        context: Arc<VulkanContext>,
+        extent: vk::Extent2D,
+        output_format: vk::Format,
+        effects: &[PostEffect],
+    ) -> Result<Self> {
+        if effects.len() > Self::MAX_PASSES {
+            warn!(
+                "Only {} of {} post processing effects will be used",
+                Self::MAX_PASSES,
+                effects.len()
+            );
+        }
+        let passes = effects
+            .iter()
+            .take(Self::MAX_PASSES)
+            .map(|effect| PostPass::new(*effect))
+            .collect::<Vec<_>>();
+
+        let target = HdrTarget::new(context.clone(), extent).context(CreateHdrTarget {})?;
+        let render_pass = Arc::new(Self::create_render_pass(context.clone())?);
+        let images = Self::create_images(context.clone(), &render_pass, extent)?;
+        let sampler = Self::create_sampler(context.clone())?;
+
+        let descriptor_set_layout = Arc::new(Self::descriptor_set_layout(context.clone())?);
+        let descriptor_pool = Self::create_descriptor_pool(context.clone())?;
+        let descriptor_sets = descriptor_pool
+            .allocate_descriptor_sets(descriptor_set_layout.layout(), 3)
+            .context(AllocatePostDescriptorSets {})?;
+
+        let uniform_buffer = Buffer::new_mapped_basic(
+            context.clone(),
+            mem::size_of::<PostProcessBufferObject>() as _,
+            vk::BufferUsageFlags::UNIFORM_BUFFER,
+            vk_mem::MemoryUsage::CpuToGpu,
+        )
+        .context(CreatePostUniformBuffer {})?;
+
+        let bloom = Bloom::new(
+            context.clone(),
+            render_pass.clone(),
+            descriptor_set_layout.clone(),
+            extent,
+        )
+        .context(CreateBloom {})?;
+
+        let chain = Self {
+            context,
+            target,
+            render_pass,
+            images,
+            sampler,
+            descriptor_set_layout,
+            _descriptor_pool: descriptor_pool,
+            descriptor_sets,
+            uniform_buffer,
+            bloom,
+            passes,
+            pipelines: HashMap::new(),
+            srgb_output: ColorSpace::of(output_format) == ColorSpace::Srgb,
+            recorded_passes: Vec::new(),
+        };
+        chain.update_descriptor_sets();
+        chain.upload()?;
+        Ok(chain)
+    }
+
+    // No command buffers reading the targets can be in flight
+    pub fn resize(&mut self, extent: vk::Extent2D) -> Result<()> {
+        self.target.resize(extent).context(CreateHdrTarget {})?;
+        self.images = Self::create_images(self.context.clone(), &self.render_pass, extent)?;
+        self.bloom.resize(extent).context(CreateBloom {})?;
+        self.update_descriptor_sets();
+        Ok(())
+    }
+
+    pub fn target(&self) -> &HdrTarget {
+        &self.target
+    }
+
+    pub fn passes(&self) -> &[PostPass] {
+        &self.passes
+    }
+
+    pub fn passes_mut(&mut self) -> &mut [PostPass] {
+        &mut self.passes
+    }
+
+    // Whether passes were enabled or disabled since the command buffers were recorded
+    pub fn needs_recording(&self) -> bool {
+        self.recorded_passes != self.enabled_passes()
+    }
+
+    // Creates a pipeline for every effect, so that effects can be swapped without creating any
+    pub fn create_pipelines(
+        &mut self,
+        shader_cache: &mut ShaderCache,
+        pipeline_cache: &mut PipelineCache,
+        output_render_pass: Arc<RenderPass>,
+    ) -> Result<()> {
+        let samples = self.context.max_usable_samples();
+        let mut shader_paths = PostEffect::SHADER_PATHS.to_vec();
+        shader_paths.push(PostEffect::COPY_SHADER_PATH);
+
+        self.pipelines = shader_paths
+            .into_iter()
+            .map(|path| {
+                let intermediate = self.create_pipeline(
+                    shader_cache,
+                    pipeline_cache,
+                    path,
+                    self.render_pass.clone(),
+                    vk::SampleCountFlags::TYPE_1,
+                )?;
+                let output = self.create_pipeline(
+                    shader_cache,
+                    pipeline_cache,
+                    path,
+                    output_render_pass.clone(),
+                    samples,
+                )?;
+                Ok((
+                    path,
+                    PostPipelines {
+                        intermediate,
+                        output,
+                    },
+                ))
+            })
+            .collect::<Result<_>>()?;
+
+        self.bloom
+            .create_pipelines(shader_cache, pipeline_cache)
+            .context(CreateBloom {})
+    }
+
+    pub fn upload(&self) -> Result<()> {
+        let mut object = PostProcessBufferObject {
+            parameters: [glm::Vec4::zeros(); Self::MAX_PASSES],
+        };
+        for (parameters, pass) in object.parameters.iter_mut().zip(self.passes.iter()) {
+            *parameters = pass.effect.parameters(self.srgb_output);
+        }
+        self.uniform_buffer
+            .upload_to_buffer(&[object], 0)
+            .context(UploadPostUniforms {})
+    }
+
+    // Draws every enabled pass but the last, after the scene has been drawn into the target.
+    // The bloom is blurred from the scene's radiance once, with the first enabled bloom pass' settings.
+    pub fn record(&mut self, device: &ash::Device, command_buffer: vk::CommandBuffer) {
+        let enabled_passes = self.enabled_passes();
+        let bloom_pass = enabled_passes
+            .iter()
+            .find(|pass_index| matches!(self.passes[**pass_index].effect, PostEffect::Bloom(_)));
+        if let Some(bloom_pass) = bloom_pass {
+            self.bloom.record(device, command_buffer, *bloom_pass);
+        }
+
+        let extent = self.target.extent();
+        let render_area = vk::Rect2D {
+            offset: vk::Offset2D { x: 0, y: 0 },
+            extent,
+        };
+        let viewports = [vk::Viewport {
+            x: 0.0,
+            y: 0.0,
+            width: extent.width as _,
+            height: extent.height as _,
+            min_depth: 0.0,
+            max_depth: 1.0,
+        }];
+        let scissors = [render_area];
+
+        let intermediate_passes = enabled_passes.len().saturating_sub(1);
+        for (position, pass_index) in enabled_passes.iter().take(intermediate_passes).enumerate() {
+            let image = &self.images[position % 2];
+            let render_pass_begin_info = vk::RenderPassBeginInfo::builder()
+                .render_pass(self.render_pass.render_pass())
+                .framebuffer(image.framebuffer.framebuffer())
+                .render_area(render_area)
+                .build();
+
+            let shader_path = self.passes[*pass_index].effect.fragment_shader_path();
+            let pipeline = &self.pipelines(shader_path).intermediate;
+            self.render_pass
+                .record(command_buffer, &render_pass_begin_info, || unsafe {
+                    device.cmd_set_viewport(command_buffer, 0, &viewports);
+                    device.cmd_set_scissor(command_buffer, 0, &scissors);
+                    self.draw(device, command_buffer, pipeline, position, *pass_index);
+                });
+        }
+
+        self.recorded_passes = enabled_passes;
+    }
+
+    // Draws the last enabled pass, inside the output's render pass
+    pub fn record_output(&self, device: &ash::Device, command_buffer: vk::CommandBuffer) {
+        let enabled_passes = self.enabled_passes();
+        let (shader_path, position, pass_index) = match enabled_passes.last() {
+            Some(pass_index) => (
+                self.passes[*pass_index].effect.fragment_shader_path(),
+                enabled_passes.len() - 1,
+                *pass_index,
+            ),
+            None => (PostEffect::COPY_SHADER_PATH, 0, 0),
+        };
+        let pipeline = &self.pipelines(shader_path).output;
+        self.draw(device, command_buffer, pipeline, position, pass_index);
+    }
+
+    fn enabled_passes(&self) -> Vec<usize> {
+        self.passes
+            .iter()
+            .enumerate()
+            .filter(|(_, pass)| pass.enabled)
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    fn pipelines(&self, shader_path: &str) -> &PostPipelines {
+        self.pipelines
+            .get(shader_path)
+            .expect("Failed to get post processing pipeline!")
+    }
+
+    // The first enabled pass reads the hdr target, the others read what the pass before wrote
+    fn draw(
+        &self,
+        device: &ash::Device,
+        command_buffer: vk::CommandBuffer,
+        pipeline: &RenderPipeline,
+        position: usize,
+        pass_index: usize,
+    ) {
+        let descriptor_set = if position == 0 {
+            self.descriptor_sets[0]
+        } else {
+            self.descriptor_sets[1 + (position - 1) % 2]
+        };
+        let pass_index = pass_index as i32;
+
+        pipeline.bind(device, command_buffer);
+        unsafe {
+            device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                pipeline.pipeline.layout(),
+                0,
+                &[descriptor_set],
+                &[],
+            );
+            device.cmd_push_constants(
+                command_buffer,
+                pipeline.pipeline.layout(),
+                vk::ShaderStageFlags::FRAGMENT,
+                0,
+                byte_slice_from(&pass_index),
+            );
+            device.cmd_draw(command_buffer, 3, 1, 0, 0);
+        }
+    }
+
+    fn create_pipeline(
+        &self,
+        shader_cache: &mut ShaderCache,
+        pipeline_cache: &mut PipelineCache,
+        fragment_shader_path: &str,
+        render_pass: Arc<RenderPass>,
+        samples: vk::SampleCountFlags,
+    ) -> Result<Rc<RenderPipeline>> {
+        // The fullscreen triangle is made from the vertex indices alone
+        let vertex_state_info = vk::PipelineVertexInputStateCreateInfo::builder().build();
+
+        let push_constant_range = vk::PushConstantRange::builder()
+            .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+            .size(mem::size_of::<i32>() as u32)
+            .build();
+
+        let shader_paths = ShaderPathSetBuilder::default()
+            .vertex("assets/shaders/post/fullscreen.vert.spv")
+            .fragment(fragment_shader_path)
+            .build()
+            .unwrap();
+        let shader_set = shader_cache
+            .create_shader_set(self.context.clone(), &shader_paths)
+            .unwrap();
+
+        let settings = RenderPipelineSettingsBuilder::default()
+            .render_pass(render_pass)
+            .vertex_state_info(vertex_state_info)
+            .descriptor_set_layout(self.descriptor_set_layout.clone())
+            .shader_set(shader_set)
+            .rasterization_samples(samples)
+            .depth_test_enabled(false)
+            .depth_write_enabled(false)
+            .push_constant_range(push_constant_range)
+            .build()
+            .expect("Failed to create render pipeline settings!");
+
+        pipeline_cache
+            .render_pipeline(settings)
+            .context(CreatePostPipeline {})
+    }
+
+    fn update_descriptor_sets(&self) {
+        let buffer_infos = [vk::DescriptorBufferInfo::builder()
+            .buffer(self.uniform_buffer.buffer())
+            .offset(0)
+            .range(mem::size_of::<PostProcessBufferObject>() as vk::DeviceSize)
+            .build()];
+
+        let views = [
+            self.target.view(),
+            &self.images[0].view,
+            &self.images[1].view,
+        ];
+        let image_infos = views
+            .iter()
+            .map(|view| {
+                [vk::DescriptorImageInfo::builder()
+                    .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+                    .image_view(view.view())
+                    .sampler(self.sampler.sampler())
+                    .build()]
+            })
+            .collect::<Vec<_>>();
+
+        let bloom_image_infos = [vk::DescriptorImageInfo::builder()
+            .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+            .image_view(self.bloom.view().view())
+            .sampler(self.sampler.sampler())
+            .build()];
+
+        let descriptor_writes = self
+            .descriptor_sets
+            .iter()
+            .zip(image_infos.iter())
+            .flat_map(|(descriptor_set, image_info)| {
+                vec![
+                    vk::WriteDescriptorSet::builder()
+                        .dst_set(*descriptor_set)
+                        .dst_binding(0)
+                        .dst_array_element(0)
+                        .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                        .image_info(image_info)
+                        .build(),
+                    vk::WriteDescriptorSet::builder()
+                        .dst_set(*descriptor_set)
+                        .dst_binding(1)
+                        .dst_array_element(0)
+                        .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+                        .buffer_info(&buffer_infos)
+                        .build(),
+                    vk::WriteDescriptorSet::builder()
+                        .dst_set(*descriptor_set)
+                        .dst_binding(2)
+                        .dst_array_element(0)
+                        .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                        .image_info(&bloom_image_infos)
+                        .build(),
+                ]
+            })
+            .collect::<Vec<_>>();
+
+        unsafe {
+            self.context
+                .logical_device()
+                .logical_device()
+                .update_descriptor_sets(&descriptor_writes, &[])
+        }
+
+        self.bloom.update_descriptor_sets(
+            self.target.view(),
+            &self.uniform_buffer,
+            mem::size_of::<PostProcessBufferObject>() as _,
+            &self.sampler,
+        );
+    }
+
+    fn descriptor_set_layout(context: Arc<VulkanContext>) -> Result<DescriptorSetLayout> {
+        let input_binding = vk::DescriptorSetLayoutBinding::builder()
+            .binding(0)
+            .descriptor_count(1)
+            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+            .build();
+        let ubo_binding = vk::DescriptorSetLayoutBinding::builder()
+            .binding(1)
+            .descriptor_count(1)
+            .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+            .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+            .build();
+        let bloom_binding = vk::DescriptorSetLayoutBinding::builder()
+            .binding(2)
+            .descriptor_count(1)
+            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+            .build();
+        let bindings = [input_binding, ubo_binding, bloom_binding];
+
+        let layout_create_info = vk::DescriptorSetLayoutCreateInfo::builder()
+            .bindings(&bindings)
+            .build();
+        DescriptorSetLayout::new(context, layout_create_info)
+            .context(CreatePostDescriptorSetLayout {})
+    }
+
+    fn create_descriptor_pool(context: Arc<VulkanContext>) -> Result<DescriptorPool> {
+        let sampler_pool_size = vk::DescriptorPoolSize {
+            ty: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            descriptor_count: 6,
+        };
+
+        let ubo_pool_size = vk::DescriptorPoolSize {
+            ty: vk::DescriptorType::UNIFORM_BUFFER,
+            descriptor_count: 3,
+        };
+
+        let pool_sizes = [sampler_pool_size, ubo_pool_size];
+
+        let pool_info = vk::DescriptorPoolCreateInfo::builder()
+            .pool_sizes(&pool_sizes)
+            .max_sets(3)
+            .build();
+
+        DescriptorPool::new(context, pool_info).context(CreatePostDescriptorPool {})
+    }
+
+    fn create_render_pass(context: Arc<VulkanContext>) -> Result<RenderPass> {
+        // Every pass covers the whole target, so what was there before is never loaded
+        let color_attachment_description = vk::AttachmentDescription::builder()
+            .format(HdrTarget::FORMAT)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .load_op(vk::AttachmentLoadOp::DONT_CARE)
+            .store_op(vk::AttachmentStoreOp::STORE)
+            .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+            .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .final_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+            .build();
+        let attachment_descriptions = [color_attachment_description];
+
+        let color_attachment_references = [vk::AttachmentReference::builder()
+            .attachment(0)
+            .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+            .build()];
+
+        let subpass_description = vk::SubpassDescription::builder()
+            .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+            .color_attachments(&color_attachment_references)
+            .build();
+        let subpass_descriptions = [subpass_description];
+
+        // Whichever pass read the image last has to finish before it's written again,
+        // and the image has to be written before the next pass reads it
+        let subpass_dependencies = [
+            vk::SubpassDependency::builder()
+                .src_subpass(vk::SUBPASS_EXTERNAL)
+                .dst_subpass(0)
+                .src_stage_mask(vk::PipelineStageFlags::FRAGMENT_SHADER)
+                .src_access_mask(vk::AccessFlags::SHADER_READ)
+                .dst_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+                .dst_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+                .build(),
+            vk::SubpassDependency::builder()
+                .src_subpass(0)
+                .dst_subpass(vk::SUBPASS_EXTERNAL)
+                .src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+                .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+                .dst_stage_mask(vk::PipelineStageFlags::FRAGMENT_SHADER)
+                .dst_access_mask(vk::AccessFlags::SHADER_READ)
+                .build(),
+        ];
+
+        let create_info = vk::RenderPassCreateInfo::builder()
+            .attachments(&attachment_descriptions)
+            .subpasses(&subpass_descriptions)
+            .dependencies(&subpass_dependencies)
+            .build();
+
+        RenderPass::new(context, &create_info).context(CreatePostRenderPass {})
+    }
+
+    fn create_images(
+        context: Arc<VulkanContext>,
+        render_pass: &RenderPass,
+        extent: vk::Extent2D,
+    ) -> Result<[PostImage; 2]> {
+        Ok([
+            Self::create_image(context.clone(), render_pass, extent)?,
+            Self::create_image(context, render_pass, extent)?,
+        ])
+    }
+
+    fn create_image(
+        context: Arc<VulkanContext>,
+        render_pass: &RenderPass,
+        extent: vk::Extent2D,
+    ) -> Result<PostImage> {
+        let image_create_info = vk::ImageCreateInfo::builder()
+            .image_type(vk::ImageType::TYPE_2D)
+            .extent(vk::Extent3D {
+                width: extent.width,
+                height: extent.height,
+                depth: 1,
+            })
+            .mip_levels(1)
+            .array_layers(1)
+            .format(HdrTarget::FORMAT)
+            .tiling(vk::ImageTiling::OPTIMAL)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .usage(vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .flags(vk::ImageCreateFlags::empty())
+            .build();
+
+        let allocation_create_info = vk_mem::AllocationCreateInfo {
+            usage: vk_mem::MemoryUsage::GpuOnly,
+            ..Default::default()
+        };
+
+        let texture = Texture::new(context.clone(), &allocation_create_info, &image_create_info)
+            .context(CreatePostTexture {})?;
+
+        let view_create_info = vk::ImageViewCreateInfo::builder()
+            .image(texture.image())
+            .view_type(vk::ImageViewType::TYPE_2D)
+            .format(HdrTarget::FORMAT)
+            .components(vk::ComponentMapping {
+                r: vk::ComponentSwizzle::IDENTITY,
+                g: vk::ComponentSwizzle::IDENTITY,
+                b: vk::ComponentSwizzle::IDENTITY,
+                a: vk::ComponentSwizzle::IDENTITY,
+            })
+            .subresource_range(vk::ImageSubresourceRange {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                base_mip_level: 0,
+                level_count: 1,
+                base_array_layer: 0,
+                layer_count: 1,
+            })
+            .build();
+        let view = ImageView::new(context.clone(), view_create_info).context(CreatePostView {})?;
+
+        let attachments = [view.view()];
+        let framebuffer_create_info = vk::FramebufferCreateInfo::builder()
+            .render_pass(render_pass.render_pass())
+            .attachments(&attachments)
+            .width(extent.width)
+            .height(extent.height)
+            .layers(1)
+            .build();
+        let framebuffer =
+            Framebuffer::new(context, framebuffer_create_info).context(CreatePostFramebuffer {})?;
+
+        Ok(PostImage {
+            _texture: texture,
+            view,
+            framebuffer,
+        })
+    }
+
+    fn create_sampler(context: Arc<VulkanContext>) -> Result<Sampler> {
+        let sampler_info = vk::SamplerCreateInfo::builder()
+            .mag_filter(vk::Filter::LINEAR)
+            .min_filter(vk::Filter::LINEAR)
+            .address_mode_u(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .address_mode_v(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .address_mode_w(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .anisotropy_enable(false)
+            .max_anisotropy(1.0)
+            .unnormalized_coordinates(false)
+            .compare_enable(false)
+            .mipmap_mode(vk::SamplerMipmapMode::NEAREST)
+            .mip_lod_bias(0.0)
+            .min_lod(0.0)
+            .max_lod(0.0)
+            .build();
+        Sampler::new(context, sampler_info).context(CreatePostSampler {})
+    }
+}
@@ -0,0 +1,152 @@
+use nalgebra_glm as glm;
+
+// The curve that maps the scene's high dynamic range colors into the displayable range
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Tonemapper {
+    // Narkowicz's fit of the ACES filmic curve
+    #[default]
+    Aces,
+
+    // Troy Sobotka's AgX, which desaturates bright colors towards white instead of skewing their hue
+    AgX,
+
+    // Reinhard's operator applied to the luminance, which keeps the hue of each color
+    Reinhard,
+
+    // Hable's filmic curve from Uncharted 2, with a white point of 11.2
+    Uncharted2,
+
+    // Khronos' PBR Neutral, which keeps base colors close to their sRGB values
+    Neutral,
+}
+
+impl Tonemapper {
+    pub const ALL: [Tonemapper; 5] = [
+        Tonemapper::Aces,
+        Tonemapper::AgX,
+        Tonemapper::Reinhard,
+        Tonemapper::Uncharted2,
+        Tonemapper::Neutral,
+    ];
+
+    // This needs to match the operators' indices in the tonemap shader
+    fn index(&self) -> f32 {
+        match self {
+            Tonemapper::Aces => 0.0,
+            Tonemapper::AgX => 1.0,
+            Tonemapper::Reinhard => 2.0,
+            Tonemapper::Uncharted2 => 3.0,
+            Tonemapper::Neutral => 4.0,
+        }
+    }
+}
+
+// How the final colors are encoded for the display
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum OutputTransfer {
+    // The sRGB transfer function, which sRGB targets apply themselves on write
+    #[default]
+    Srgb,
+
+    // A pure power curve with the given gamma, such as 2.2
+    Gamma(f32),
+
+    // The colors are written as they are
+    Linear,
+}
+
+// How much of the blurred radiance is added to the scene
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BloomSettings {
+    // The weight of the bloom in the lerp, or its multiplier when thresholded
+    pub intensity: f32,
+
+    // The spread of the upsampling filter, in texels of each level
+    pub radius: f32,
+
+    // Only the radiance above the threshold blooms, with a soft knee, and it's added to the scene.
+    // Without one, all of the radiance blooms and the scene is lerped towards it, conserving energy.
+    pub threshold: Option<f32>,
+}
+
+impl Default for BloomSettings {
+    fn default() -> Self {
+        Self {
+            intensity: 0.04,
+            radius: 1.0,
+            threshold: None,
+        }
+    }
+}
+
+// A fullscreen pass of the post processing chain
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PostEffect {
+    // Blurs the scene's radiance through a mip chain and mixes it back in
+    Bloom(BloomSettings),
+
+    // Scales the scene's radiance by a multiplier
+    Exposure(f32),
+    Tonemap(Tonemapper),
+    Output(OutputTransfer),
+}
+
+impl PostEffect {
+    // Copies its input, for chains whose passes are all disabled
+    pub const COPY_SHADER_PATH: &'static str = "assets/shaders/post/copy.frag.spv";
+
+    pub const SHADER_PATHS: [&'static str; 4] = [
+        "assets/shaders/post/bloom.frag.spv",
+        "assets/shaders/post/exposure.frag.spv",
+        "assets/shaders/post/tonemap.frag.spv",
+        "assets/shaders/post/output.frag.spv",
+    ];
+
+    pub fn fragment_shader_path(&self) -> &'static str {
+        match self {
+            PostEffect::Bloom(_) => Self::SHADER_PATHS[0],
+            PostEffect::Exposure(_) => Self::SHADER_PATHS[1],
+            PostEffect::Tonemap(_) => Self::SHADER_PATHS[2],
+            PostEffect::Output(_) => Self::SHADER_PATHS[3],
+        }
+    }
+
+    // Packs the effect's settings for its shader.
+    // An sRGB target encodes what's written to it, so the output pass decodes its result for it.
+    pub fn parameters(&self, srgb_target: bool) -> glm::Vec4 {
+        let decode = if srgb_target { 1.0 } else { 0.0 };
+        match self {
+            PostEffect::Bloom(settings) => glm::vec4(
+                settings.intensity,
+                settings.radius,
+                settings.threshold.unwrap_or(0.0),
+                if settings.threshold.is_some() {
+                    1.0
+                } else {
+                    0.0
+                },
+            ),
+            PostEffect::Exposure(exposure) => glm::vec4(*exposure, 0.0, 0.0, 0.0),
+            PostEffect::Tonemap(tonemapper) => glm::vec4(tonemapper.index(), 0.0, 0.0, 0.0),
+            PostEffect::Output(OutputTransfer::Srgb) if srgb_target => glm::Vec4::zeros(),
+            PostEffect::Output(OutputTransfer::Srgb) => glm::vec4(1.0, 0.0, 0.0, 0.0),
+            PostEffect::Output(OutputTransfer::Gamma(gamma)) => glm::vec4(2.0, *gamma, decode, 0.0),
+            PostEffect::Output(OutputTransfer::Linear) => glm::vec4(0.0, 0.0, decode, 0.0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PostPass {
+    pub effect: PostEffect,
+    pub enabled: bool,
+}
+
+impl PostPass {
+    pub fn new(effect: PostEffect) -> Self {
+        Self {
+            effect,
+            enabled: true,
+        }
+    }
+}
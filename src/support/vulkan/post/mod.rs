@@ -0,0 +1,11 @@
+pub use self::{
+    bloom::Bloom,
+    chain::{PostProcessBufferObject, PostProcessChain},
+    effect::{BloomSettings, OutputTransfer, PostEffect, PostPass, Tonemapper},
+    target::HdrTarget,
+};
+
+pub mod bloom;
+pub mod chain;
+pub mod effect;
+pub mod target;
@@ -0,0 +1,400 @@
+use crate::vulkan::{Framebuffer, ImageView, RenderPass, Texture, VulkanContext};
+use ash::{version::DeviceV1_0, vk};
+use snafu::{ResultExt, Snafu};
+use std::sync::Arc;
+
+type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility = "pub(crate)")]
+pub enum Error {
+    #[snafu(display("Failed to create hdr render pass: {}", source))]
+    CreateHdrRenderPass {
+        source: crate::vulkan::renderpass::Error,
+    },
+
+    #[snafu(display("Failed to create hdr texture: {}", source))]
+    CreateHdrTexture {
+        source: crate::vulkan::texture::Error,
+    },
+
+    #[snafu(display("Failed to create hdr view: {}", source))]
+    CreateHdrView {
+        source: crate::vulkan::image_view::Error,
+    },
+
+    #[snafu(display("Failed to create hdr framebuffer: {}", source))]
+    CreateHdrFramebuffer {
+        source: crate::vulkan::framebuffer::Error,
+    },
+}
+
+// The attachments depend on the size of the target, the render pass doesn't
+struct HdrAttachments {
+    _color_texture: Texture,
+    _color_view: ImageView,
+    _depth_texture: Texture,
+    _depth_view: ImageView,
+    _resolve_texture: Texture,
+    resolve_view: ImageView,
+    framebuffer: Framebuffer,
+}
+
+// A multisampled floating point target that scenes render their radiance into,
+// resolved into a texture for post processing to read
+pub struct HdrTarget {
+    context: Arc<VulkanContext>,
+    extent: vk::Extent2D,
+    depth_format: vk::Format,
+    render_pass: Arc<RenderPass>,
+    attachments: HdrAttachments,
+}
+
+impl HdrTarget {
+    // Half floats hold the range of the scene's radiance at half the size of full floats
+    pub const FORMAT: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
+
+    pub fn new(context: Arc<VulkanContext>, extent: vk::Extent2D) -> Result<Self> {
+        let depth_format = context.determine_depth_format(
+            vk::ImageTiling::OPTIMAL,
+            vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT,
+        );
+        let render_pass = Arc::new(Self::create_render_pass(context.clone(), depth_format)?);
+        let attachments =
+            Self::create_attachments(context.clone(), &render_pass, extent, depth_format)?;
+
+        Ok(Self {
+            context,
+            extent,
+            depth_format,
+            render_pass,
+            attachments,
+        })
+    }
+
+    // Pipelines created for the render pass stay valid
+    pub fn resize(&mut self, extent: vk::Extent2D) -> Result<()> {
+        self.attachments = Self::create_attachments(
+            self.context.clone(),
+            &self.render_pass,
+            extent,
+            self.depth_format,
+        )?;
+        self.extent = extent;
+        Ok(())
+    }
+
+    pub fn extent(&self) -> vk::Extent2D {
+        self.extent
+    }
+
+    pub fn samples(&self) -> vk::SampleCountFlags {
+        self.context.max_usable_samples()
+    }
+
+    pub fn render_pass(&self) -> Arc<RenderPass> {
+        self.render_pass.clone()
+    }
+
+    // The resolved radiance, readable by shaders once the render pass has ended
+    pub fn view(&self) -> &ImageView {
+        &self.attachments.resolve_view
+    }
+
+    pub fn record<T>(
+        &self,
+        device: &ash::Device,
+        command_buffer: vk::CommandBuffer,
+        clear_color: [f32; 4],
+        mut recorder: T,
+    ) where
+        T: FnMut(),
+    {
+        let clear_values = [
+            vk::ClearValue {
+                color: vk::ClearColorValue {
+                    float32: clear_color,
+                },
+            },
+            vk::ClearValue {
+                depth_stencil: vk::ClearDepthStencilValue {
+                    depth: 1.0,
+                    stencil: 0,
+                },
+            },
+        ];
+        let render_area = vk::Rect2D {
+            offset: vk::Offset2D { x: 0, y: 0 },
+            extent: self.extent,
+        };
+        let render_pass_begin_info = vk::RenderPassBeginInfo::builder()
+            .render_pass(self.render_pass.render_pass())
+            .framebuffer(self.attachments.framebuffer.framebuffer())
+            .render_area(render_area)
+            .clear_values(&clear_values)
+            .build();
+
+        let viewports = [vk::Viewport {
+            x: 0.0,
+            y: 0.0,
+            width: self.extent.width as _,
+            height: self.extent.height as _,
+            min_depth: 0.0,
+            max_depth: 1.0,
+        }];
+        let scissors = [render_area];
+
+        self.render_pass
+            .record(command_buffer, &render_pass_begin_info, || unsafe {
+                device.cmd_set_viewport(command_buffer, 0, &viewports);
+                device.cmd_set_scissor(command_buffer, 0, &scissors);
+                recorder();
+            });
+    }
+
+    fn create_render_pass(
+        context: Arc<VulkanContext>,
+        depth_format: vk::Format,
+    ) -> Result<RenderPass> {
+        let samples = context.max_usable_samples();
+
+        let color_attachment_description = vk::AttachmentDescription::builder()
+            .format(Self::FORMAT)
+            .samples(samples)
+            .load_op(vk::AttachmentLoadOp::CLEAR)
+            .store_op(vk::AttachmentStoreOp::DONT_CARE)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .final_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+            .build();
+
+        let depth_attachment_description = vk::AttachmentDescription::builder()
+            .format(depth_format)
+            .samples(samples)
+            .load_op(vk::AttachmentLoadOp::CLEAR)
+            .store_op(vk::AttachmentStoreOp::DONT_CARE)
+            .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+            .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .final_layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)
+            .build();
+
+        let resolve_attachment_description = vk::AttachmentDescription::builder()
+            .format(Self::FORMAT)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .load_op(vk::AttachmentLoadOp::DONT_CARE)
+            .store_op(vk::AttachmentStoreOp::STORE)
+            .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+            .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .final_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+            .build();
+
+        let attachment_descriptions = [
+            color_attachment_description,
+            depth_attachment_description,
+            resolve_attachment_description,
+        ];
+
+        let color_attachment_references = [vk::AttachmentReference::builder()
+            .attachment(0)
+            .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+            .build()];
+
+        let depth_attachment_reference = vk::AttachmentReference::builder()
+            .attachment(1)
+            .layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)
+            .build();
+
+        let resolve_attachment_references = [vk::AttachmentReference::builder()
+            .attachment(2)
+            .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+            .build()];
+
+        let subpass_description = vk::SubpassDescription::builder()
+            .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+            .color_attachments(&color_attachment_references)
+            .resolve_attachments(&resolve_attachment_references)
+            .depth_stencil_attachment(&depth_attachment_reference)
+            .build();
+        let subpass_descriptions = [subpass_description];
+
+        // The previous frame has to finish with the attachments and its post processing
+        // has to finish reading the resolved radiance before the scene is drawn again,
+        // and the scene has to be resolved before this frame's post processing reads it
+        let subpass_dependencies = [
+            vk::SubpassDependency::builder()
+                .src_subpass(vk::SUBPASS_EXTERNAL)
+                .dst_subpass(0)
+                .src_stage_mask(
+                    vk::PipelineStageFlags::FRAGMENT_SHADER
+                        | vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+                        | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS,
+                )
+                .src_access_mask(
+                    vk::AccessFlags::SHADER_READ
+                        | vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+                        | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+                )
+                .dst_stage_mask(
+                    vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+                        | vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS,
+                )
+                .dst_access_mask(
+                    vk::AccessFlags::COLOR_ATTACHMENT_READ
+                        | vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+                        | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ
+                        | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+                )
+                .build(),
+            vk::SubpassDependency::builder()
+                .src_subpass(0)
+                .dst_subpass(vk::SUBPASS_EXTERNAL)
+                .src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+                .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+                .dst_stage_mask(vk::PipelineStageFlags::FRAGMENT_SHADER)
+                .dst_access_mask(vk::AccessFlags::SHADER_READ)
+                .build(),
+        ];
+
+        let create_info = vk::RenderPassCreateInfo::builder()
+            .attachments(&attachment_descriptions)
+            .subpasses(&subpass_descriptions)
+            .dependencies(&subpass_dependencies)
+            .build();
+
+        RenderPass::new(context, &create_info).context(CreateHdrRenderPass {})
+    }
+
+    fn create_attachments(
+        context: Arc<VulkanContext>,
+        render_pass: &RenderPass,
+        extent: vk::Extent2D,
+        depth_format: vk::Format,
+    ) -> Result<HdrAttachments> {
+        let samples = context.max_usable_samples();
+
+        let color_texture = Self::create_texture(
+            context.clone(),
+            extent,
+            Self::FORMAT,
+            samples,
+            vk::ImageUsageFlags::TRANSIENT_ATTACHMENT | vk::ImageUsageFlags::COLOR_ATTACHMENT,
+        )?;
+        let color_view = Self::create_view(
+            context.clone(),
+            &color_texture,
+            Self::FORMAT,
+            vk::ImageAspectFlags::COLOR,
+        )?;
+
+        let depth_texture = Self::create_texture(
+            context.clone(),
+            extent,
+            depth_format,
+            samples,
+            vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+        )?;
+        let depth_view = Self::create_view(
+            context.clone(),
+            &depth_texture,
+            depth_format,
+            vk::ImageAspectFlags::DEPTH,
+        )?;
+
+        let resolve_texture = Self::create_texture(
+            context.clone(),
+            extent,
+            Self::FORMAT,
+            vk::SampleCountFlags::TYPE_1,
+            vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
+        )?;
+        let resolve_view = Self::create_view(
+            context.clone(),
+            &resolve_texture,
+            Self::FORMAT,
+            vk::ImageAspectFlags::COLOR,
+        )?;
+
+        let attachments = [color_view.view(), depth_view.view(), resolve_view.view()];
+        let create_info = vk::FramebufferCreateInfo::builder()
+            .render_pass(render_pass.render_pass())
+            .attachments(&attachments)
+            .width(extent.width)
+            .height(extent.height)
+            .layers(1)
+            .build();
+        let framebuffer =
+            Framebuffer::new(context, create_info).context(CreateHdrFramebuffer {})?;
+
+        Ok(HdrAttachments {
+            _color_texture: color_texture,
+            _color_view: color_view,
+            _depth_texture: depth_texture,
+            _depth_view: depth_view,
+            _resolve_texture: resolve_texture,
+            resolve_view,
+            framebuffer,
+        })
+    }
+
+    fn create_texture(
+        context: Arc<VulkanContext>,
+        extent: vk::Extent2D,
+        format: vk::Format,
+        samples: vk::SampleCountFlags,
+        usage: vk::ImageUsageFlags,
+    ) -> Result<Texture> {
+        let image_create_info = vk::ImageCreateInfo::builder()
+            .image_type(vk::ImageType::TYPE_2D)
+            .extent(vk::Extent3D {
+                width: extent.width,
+                height: extent.height,
+                depth: 1,
+            })
+            .mip_levels(1)
+            .array_layers(1)
+            .format(format)
+            .tiling(vk::ImageTiling::OPTIMAL)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .usage(usage)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .samples(samples)
+            .flags(vk::ImageCreateFlags::empty())
+            .build();
+
+        let allocation_create_info = vk_mem::AllocationCreateInfo {
+            usage: vk_mem::MemoryUsage::GpuOnly,
+            ..Default::default()
+        };
+
+        Texture::new(context, &allocation_create_info, &image_create_info)
+            .context(CreateHdrTexture {})
+    }
+
+    fn create_view(
+        context: Arc<VulkanContext>,
+        texture: &Texture,
+        format: vk::Format,
+        aspect_mask: vk::ImageAspectFlags,
+    ) -> Result<ImageView> {
+        let create_info = vk::ImageViewCreateInfo::builder()
+            .image(texture.image())
+            .view_type(vk::ImageViewType::TYPE_2D)
+            .format(format)
+            .components(vk::ComponentMapping {
+                r: vk::ComponentSwizzle::IDENTITY,
+                g: vk::ComponentSwizzle::IDENTITY,
+                b: vk::ComponentSwizzle::IDENTITY,
+                a: vk::ComponentSwizzle::IDENTITY,
+            })
+            .subresource_range(vk::ImageSubresourceRange {
+                aspect_mask,
+                base_mip_level: 0,
+                level_count: 1,
+                base_array_layer: 0,
+                layer_count: 1,
+            })
+            .build();
+        ImageView::new(context, create_info).context(CreateHdrView {})
+    }
+}
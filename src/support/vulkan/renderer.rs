@@ -1,6 +1,7 @@
 use crate::vulkan::{
-    core::sync::synchronization_set::SynchronizationSetConstants, CommandPool, RenderPass,
-    ShaderCache, SynchronizationSet, VulkanContext, VulkanSwapchain,
+    core::sync::synchronization_set::SynchronizationSetConstants, CommandPool, PipelineCache,
+    RenderPass, SamplerCache, ShaderCache, SynchronizationSet, UploadManager, VulkanContext,
+    VulkanSwapchain,
 };
 use ash::vk;
 use nalgebra_glm as glm;
@@ -10,6 +11,16 @@ use winit::window::Window;
 // TODO: Device parameter can be removed because it will be accessible through the vulkan context
 // TODO: Rename this to something better
 pub trait Command {
+    // Recorded before the swapchain's render pass begins,
+    // for passes that render into offscreen targets such as shadow maps
+    fn issue_offscreen_commands(
+        &mut self,
+        _: &ash::Device,
+        _: vk::CommandBuffer,
+    ) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
     fn issue_commands(
         &mut self,
         _: &ash::Device,
@@ -18,10 +29,17 @@ pub trait Command {
         Ok(())
     }
 
+    // Called when the swapchain is recreated, before the pipelines are,
+    // for offscreen targets that match the swapchain's size
+    fn recreate_targets(&mut self, _: vk::Extent2D) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
     fn recreate_pipelines(
         &mut self,
         _: Arc<VulkanContext>,
         _: &mut ShaderCache,
+        _: &mut PipelineCache,
         _: Arc<RenderPass>,
     ) -> Result<(), Box<dyn Error>> {
         Ok(())
@@ -31,11 +49,14 @@ pub trait Command {
 pub struct Renderer {
     pub context: Arc<VulkanContext>,
     pub shader_cache: ShaderCache,
+    pub pipeline_cache: PipelineCache,
     pub vulkan_swapchain: Option<VulkanSwapchain>,
     pub synchronization_set: SynchronizationSet,
     pub current_frame: usize,
     pub command_pool: CommandPool,
     pub transient_command_pool: CommandPool,
+    pub upload_manager: UploadManager,
+    pub sampler_cache: SamplerCache,
 }
 
 impl Renderer {
@@ -52,6 +73,11 @@ impl Renderer {
         let transient_command_pool =
             CommandPool::new(context.clone(), vk::CommandPoolCreateFlags::TRANSIENT).unwrap();
 
+        let upload_manager =
+            UploadManager::new(context.clone(), UploadManager::DEFAULT_STAGING_CAPACITY).unwrap();
+
+        let sampler_cache = SamplerCache::new(context.clone());
+
         let logical_size = window.inner_size();
         let dimensions = [logical_size.width as u32, logical_size.height as u32];
 
@@ -62,6 +88,7 @@ impl Renderer {
         ));
 
         Self {
+            pipeline_cache: PipelineCache::new(context.clone()),
             context,
             shader_cache: ShaderCache::default(),
             vulkan_swapchain,
@@ -69,6 +96,8 @@ impl Renderer {
             current_frame: 0,
             command_pool,
             transient_command_pool,
+            upload_manager,
+            sampler_cache,
         }
     }
 
@@ -173,10 +202,20 @@ impl Renderer {
         );
 
         let render_pass = new_swapchain.render_pass.clone();
+        let extent = new_swapchain.swapchain.properties().extent;
         self.vulkan_swapchain = Some(new_swapchain);
 
         command
-            .recreate_pipelines(self.context.clone(), &mut self.shader_cache, render_pass)
+            .recreate_targets(extent)
+            .expect("Failed to recreate targets!");
+
+        command
+            .recreate_pipelines(
+                self.context.clone(),
+                &mut self.shader_cache,
+                &mut self.pipeline_cache,
+                render_pass,
+            )
             .expect("Failed to recreate pipelines!");
         self.record_all_command_buffers(command);
     }
@@ -207,6 +246,10 @@ impl Renderer {
             command_buffer,
             vk::CommandBufferUsageFlags::SIMULTANEOUS_USE,
             || {
+                command
+                    .issue_offscreen_commands(device, command_buffer)
+                    .expect("Failed to issue offscreen vulkan commands!");
+
                 let render_pass_begin_info = vk::RenderPassBeginInfo::builder()
                     .render_pass(self.vulkan_swapchain().render_pass.render_pass())
                     .framebuffer(framebuffer)
@@ -1,6 +1,6 @@
-use crate::vulkan::{CommandPool, VulkanContext};
+use crate::vulkan::{UploadManager, Vertex, VulkanContext};
 use ash::{version::DeviceV1_0, vk};
-use snafu::{ResultExt, Snafu};
+use snafu::{ensure, ResultExt, Snafu};
 use std::sync::Arc;
 
 type Result<T, E = Error> = std::result::Result<T, E>;
@@ -16,12 +16,25 @@ pub enum Error {
 
     #[snafu(display("Failed to unmap memory: {}", source))]
     UnmapMemory { source: vk_mem::error::Error },
+
+    #[snafu(display(
+        "Cannot write {} bytes at offset {} to a buffer of {} bytes",
+        size,
+        offset,
+        capacity
+    ))]
+    WriteOutOfBounds {
+        offset: usize,
+        size: usize,
+        capacity: usize,
+    },
 }
 
 pub struct Buffer {
     buffer: vk::Buffer,
     allocation: vk_mem::Allocation,
     allocation_info: vk_mem::AllocationInfo,
+    size: vk::DeviceSize,
     context: Arc<VulkanContext>,
 }
 
@@ -40,6 +53,7 @@ impl Buffer {
             buffer,
             allocation,
             allocation_info,
+            size: buffer_create_info.size,
             context,
         };
 
@@ -66,12 +80,25 @@ impl Buffer {
         Buffer::new(context, &allocation_create_info, &buffer_create_info)
     }
 
+    // The offset is in bytes and doesn't have to be aligned to the type of the data.
+    // Writes are bounded by the requested size, which the allocation may exceed.
     pub fn upload_to_buffer<T>(&self, data: &[T], offset: usize) -> Result<()> {
-        // TODO: Add checks for size of data being written
+        let size = std::mem::size_of_val(data);
+        let capacity = self.size as usize;
+        ensure!(
+            offset + size <= capacity,
+            WriteOutOfBounds {
+                offset,
+                size,
+                capacity
+            }
+        );
+
         let data_pointer = self.map_memory().context(MapMemory {})?;
         unsafe {
-            data_pointer.add(offset);
-            (data_pointer as *mut T).copy_from_nonoverlapping(data.as_ptr(), data.len());
+            data_pointer
+                .add(offset)
+                .copy_from_nonoverlapping(data.as_ptr() as *const u8, size);
         }
         self.unmap_memory().context(UnmapMemory {})
     }
@@ -108,10 +135,21 @@ impl Buffer {
             .flush_allocation(&self.allocation, offset, size)
     }
 
+    pub fn invalidate(&self, offset: usize, size: usize) -> vk_mem::error::Result<()> {
+        self.context
+            .allocator()
+            .invalidate_allocation(&self.allocation, offset, size)
+    }
+
     pub fn buffer(&self) -> vk::Buffer {
         self.buffer
     }
 
+    // The size the buffer was created with, in bytes
+    pub fn size(&self) -> vk::DeviceSize {
+        self.size
+    }
+
     pub fn allocation(&self) -> &vk_mem::Allocation {
         &self.allocation
     }
@@ -133,19 +171,29 @@ impl Drop for Buffer {
 pub struct GeometryBuffer {
     pub vertex_buffer: Buffer,
     pub index_buffer: Option<Buffer>,
+    pub number_of_vertices: u32,
     pub number_of_indices: u32,
+    pub vertex_stride: u32,
 }
 
 impl GeometryBuffer {
-    pub fn new(command_pool: &CommandPool, vertices: &[f32], indices: Option<&[u32]>) -> Self {
-        let vertex_buffer =
-            Self::create_buffer(command_pool, &vertices, vk::BufferUsageFlags::VERTEX_BUFFER);
+    // The buffers are usable once the upload manager's current batch has been submitted
+    pub fn new<V: Vertex>(
+        upload_manager: &mut UploadManager,
+        vertices: &[V],
+        indices: Option<&[u32]>,
+    ) -> Self {
+        let vertex_buffer = Self::create_buffer(
+            upload_manager,
+            &vertices,
+            vk::BufferUsageFlags::VERTEX_BUFFER,
+        );
 
         let mut number_of_indices = 0;
         let index_buffer = if let Some(indices) = indices {
             number_of_indices = indices.len() as u32;
             let index_buffer =
-                Self::create_buffer(command_pool, &indices, vk::BufferUsageFlags::INDEX_BUFFER);
+                Self::create_buffer(upload_manager, &indices, vk::BufferUsageFlags::INDEX_BUFFER);
             Some(index_buffer)
         } else {
             None
@@ -154,21 +202,20 @@ impl GeometryBuffer {
         Self {
             vertex_buffer,
             index_buffer,
+            number_of_vertices: vertices.len() as u32,
             number_of_indices,
+            vertex_stride: V::stride(),
         }
     }
 
     fn create_buffer<T: Copy>(
-        command_pool: &CommandPool,
+        upload_manager: &mut UploadManager,
         data: &[T],
         usage_flags: vk::BufferUsageFlags,
     ) -> Buffer {
-        let region = vk::BufferCopy {
-            src_offset: 0,
-            dst_offset: 0,
-            size: (data.len() * std::mem::size_of::<T>()) as ash::vk::DeviceSize,
-        };
-        command_pool.create_device_local_buffer(usage_flags, &data, &[region])
+        upload_manager
+            .create_device_local_buffer(usage_flags, data)
+            .expect("Failed to create device local buffer!")
     }
 
     pub fn bind(&self, device: &ash::Device, command_buffer: vk::CommandBuffer) {
@@ -1,4 +1,4 @@
-use crate::vulkan::{Buffer, CurrentFrameSynchronization, Fence, VulkanContext};
+use crate::vulkan::{CurrentFrameSynchronization, Fence, VulkanContext};
 use ash::{version::DeviceV1_0, vk};
 use snafu::{ResultExt, Snafu};
 use std::sync::Arc;
@@ -106,46 +106,6 @@ impl CommandPool {
         self.command_buffers.clear();
     }
 
-    pub fn create_staging_buffer<T: Copy>(&self, data: &[T]) -> Buffer {
-        let buffer_size = (data.len() * std::mem::size_of::<T>()) as ash::vk::DeviceSize;
-
-        let staging_buffer = Buffer::new_mapped_basic(
-            self.context.clone(),
-            buffer_size,
-            vk::BufferUsageFlags::TRANSFER_SRC,
-            vk_mem::MemoryUsage::CpuToGpu,
-        )
-        .unwrap();
-        staging_buffer.upload_to_buffer(&data, 0).unwrap();
-        staging_buffer
-    }
-
-    pub fn create_device_local_buffer<T: Copy>(
-        &self,
-        usage_flags: vk::BufferUsageFlags,
-        data: &[T],
-        regions: &[vk::BufferCopy],
-    ) -> Buffer {
-        let staging_buffer = self.create_staging_buffer(&data);
-
-        let device_local_buffer = Buffer::new_mapped_basic(
-            self.context.clone(),
-            staging_buffer.allocation_info().get_size() as _,
-            vk::BufferUsageFlags::TRANSFER_DST | usage_flags,
-            vk_mem::MemoryUsage::GpuOnly,
-        )
-        .unwrap();
-
-        self.copy_buffer_to_buffer(
-            staging_buffer.buffer(),
-            device_local_buffer.buffer(),
-            &regions,
-        )
-        .unwrap();
-
-        device_local_buffer
-    }
-
     // TODO: refactor this to use less parameters
     pub fn submit_command_buffer(
         &self,
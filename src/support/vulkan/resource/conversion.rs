@@ -0,0 +1,290 @@
+use crate::vulkan::{
+    format_info, linear_format, srgb_format, srgb_to_linear, ColorSpace, TexelEncoding,
+    TextureDescription, VulkanContext,
+};
+use ash::vk;
+use snafu::{ensure, OptionExt, Snafu};
+
+type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility = "pub(crate)")]
+pub enum Error {
+    #[snafu(display(
+        "Texture format {:?} can't be sampled on this device and has no supported alternative",
+        format
+    ))]
+    NoSupportedFormat { format: vk::Format },
+
+    #[snafu(display("Texels can't be converted from {:?} to {:?}", from, to))]
+    UnsupportedConversion { from: vk::Format, to: vk::Format },
+
+    #[snafu(display("Texels in format {:?} can't be decoded", format))]
+    UndecodableFormat { format: vk::Format },
+
+    #[snafu(display(
+        "{} bytes of pixel data is too short for the base level of a {:?} texture",
+        length,
+        format
+    ))]
+    MissingBaseLevel { format: vk::Format, length: usize },
+
+    #[snafu(display(
+        "{} bytes of pixel data is not a whole number of {:?} texels",
+        length,
+        format
+    ))]
+    PartialTexel { format: vk::Format, length: usize },
+}
+
+// The formats a texture can be stored in without losing channels, best first.
+// Three channel formats are rarely supported, so they are widened with an opaque alpha channel.
+pub fn format_candidates(format: vk::Format) -> Vec<vk::Format> {
+    let alternatives: &[vk::Format] = match format {
+        vk::Format::R8G8B8_UNORM => &[vk::Format::R8G8B8A8_UNORM, vk::Format::B8G8R8A8_UNORM],
+        vk::Format::B8G8R8_UNORM => &[vk::Format::B8G8R8A8_UNORM, vk::Format::R8G8B8A8_UNORM],
+        vk::Format::B8G8R8A8_UNORM => &[vk::Format::R8G8B8A8_UNORM],
+        vk::Format::R8_UNORM => &[vk::Format::R8G8B8A8_UNORM],
+        vk::Format::R8G8_UNORM => &[vk::Format::R8G8B8A8_UNORM],
+        vk::Format::R16_UNORM => &[vk::Format::R16_SFLOAT, vk::Format::R32_SFLOAT],
+        vk::Format::R16G16_UNORM => &[vk::Format::R16G16_SFLOAT, vk::Format::R32G32_SFLOAT],
+        vk::Format::R16G16B16_UNORM => &[
+            vk::Format::R16G16B16A16_UNORM,
+            vk::Format::R16G16B16A16_SFLOAT,
+            vk::Format::R32G32B32A32_SFLOAT,
+        ],
+        vk::Format::R16G16B16A16_UNORM => &[
+            vk::Format::R16G16B16A16_SFLOAT,
+            vk::Format::R32G32B32A32_SFLOAT,
+        ],
+        vk::Format::R16G16B16_SFLOAT => &[
+            vk::Format::R16G16B16A16_SFLOAT,
+            vk::Format::R32G32B32A32_SFLOAT,
+        ],
+        vk::Format::R32G32B32_SFLOAT => &[vk::Format::R32G32B32A32_SFLOAT],
+        _ => &[],
+    };
+
+    let mut candidates = vec![format];
+    candidates.extend_from_slice(alternatives);
+
+    // sRGB textures only have sRGB alternatives, so that the sampler still decodes them
+    if let Some(linear) = linear_format(format) {
+        candidates = format_candidates(linear)
+            .into_iter()
+            .filter_map(srgb_format)
+            .collect();
+    }
+
+    candidates
+}
+
+// Picks the best format the device can sample the texture in.
+// Linear filtering and blitting are preferred, but formats without them are still usable
+// with nearest filtering and mipmaps generated on the CPU.
+pub fn negotiate_format(
+    context: &VulkanContext,
+    description: &TextureDescription,
+) -> Result<vk::Format> {
+    let candidates = format_candidates(description.format);
+    let features = |format: vk::Format| {
+        context
+            .physical_device_format_properties(format)
+            .optimal_tiling_features
+    };
+    let source_encoding = TexelEncoding::of(description.format);
+    let convertible = |format: vk::Format| {
+        format == description.format
+            || (source_encoding.is_some() && TexelEncoding::of(format).is_some())
+    };
+    let needs_mipmaps = !description.has_complete_mip_chain();
+    let blit = vk::FormatFeatureFlags::BLIT_SRC | vk::FormatFeatureFlags::BLIT_DST;
+    let mipmappable = |format: vk::Format| {
+        !needs_mipmaps || features(format).contains(blit) || TexelEncoding::of(format).is_some()
+    };
+
+    let sampled = vk::FormatFeatureFlags::SAMPLED_IMAGE;
+    let filtered = sampled | vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR;
+    let preferred = if needs_mipmaps {
+        filtered | blit
+    } else {
+        filtered
+    };
+    [preferred, filtered, sampled]
+        .iter()
+        .find_map(|required| {
+            candidates.iter().copied().find(|format| {
+                convertible(*format)
+                    && mipmappable(*format)
+                    && features(*format).contains(*required)
+            })
+        })
+        .context(NoSupportedFormat {
+            format: description.format,
+        })
+}
+
+// Converts every stored texel to another uncompressed format.
+// The description's component mapping is applied while converting,
+// so the converted texture is viewed with the identity mapping.
+pub fn convert_texels(
+    description: &TextureDescription,
+    format: vk::Format,
+) -> Result<TextureDescription> {
+    ensure!(
+        ColorSpace::of(description.format) == ColorSpace::of(format),
+        UnsupportedConversion {
+            from: description.format,
+            to: format,
+        }
+    );
+    transcode_texels(description, format)
+}
+
+// Converts every stored texel to an uncompressed linear format,
+// decoding the color channels of sRGB textures on the way.
+pub fn linearize_texels(
+    description: &TextureDescription,
+    format: vk::Format,
+) -> Result<TextureDescription> {
+    ensure!(
+        ColorSpace::of(format) == ColorSpace::Linear,
+        UnsupportedConversion {
+            from: description.format,
+            to: format,
+        }
+    );
+    transcode_texels(description, format)
+}
+
+// `usize::is_multiple_of` needs a newer compiler than the rest of the crate
+#[allow(clippy::manual_is_multiple_of)]
+fn transcode_texels(
+    description: &TextureDescription,
+    format: vk::Format,
+) -> Result<TextureDescription> {
+    let conversion = UnsupportedConversion {
+        from: description.format,
+        to: format,
+    };
+    let source = TexelEncoding::of(description.format).context(conversion)?;
+    let target = TexelEncoding::of(format).context(conversion)?;
+
+    let texel_size = format_info(description.format)
+        .context(conversion)?
+        .block_size as usize;
+    ensure!(
+        description.pixels.len() % texel_size == 0,
+        PartialTexel {
+            format: description.format,
+            length: description.pixels.len()
+        }
+    );
+
+    let values = source.decode_channels(&description.pixels);
+
+    let linearize = ColorSpace::of(description.format) != ColorSpace::of(format);
+    let converted = values
+        .chunks_exact(source.channels)
+        .flat_map(|texel| {
+            let mut rgba = to_rgba(texel, is_bgr(description.format));
+            if linearize {
+                rgba.iter_mut()
+                    .take(3)
+                    .for_each(|channel| *channel = srgb_to_linear(*channel));
+            }
+            let rgba = swizzle(rgba, &description.components);
+            from_rgba(rgba, target.channels, is_bgr(format))
+        })
+        .collect::<Vec<_>>();
+
+    Ok(TextureDescription {
+        format,
+        pixels: target.encode_channels(&converted),
+        components: vk::ComponentMapping::default(),
+        ..description.clone()
+    })
+}
+
+// Decodes every layer of the base mip level to linear RGBA, in the order they're stored.
+// The description's component mapping is applied, as it would be when sampling.
+pub fn decode_base_level(description: &TextureDescription) -> Result<Vec<[f32; 4]>> {
+    let format = description.format;
+    let encoding = TexelEncoding::of(format).context(UndecodableFormat { format })?;
+    let texel_size = format_info(format)
+        .context(UndecodableFormat { format })?
+        .block_size as usize;
+    let texels = description.width as usize
+        * description.height as usize
+        * description.depth as usize
+        * description.layers as usize;
+    let length = texels * texel_size;
+    ensure!(
+        description.pixels.len() >= length,
+        MissingBaseLevel {
+            format,
+            length: description.pixels.len()
+        }
+    );
+
+    let srgb = ColorSpace::of(format) == ColorSpace::Srgb;
+    let texels = encoding
+        .decode_channels(&description.pixels[..length])
+        .chunks_exact(encoding.channels)
+        .map(|texel| {
+            let mut rgba = to_rgba(texel, is_bgr(format));
+            if srgb {
+                rgba.iter_mut()
+                    .take(3)
+                    .for_each(|channel| *channel = srgb_to_linear(*channel));
+            }
+            swizzle(rgba, &description.components)
+        })
+        .collect();
+    Ok(texels)
+}
+
+fn is_bgr(format: vk::Format) -> bool {
+    matches!(
+        format,
+        vk::Format::B8G8R8_UNORM
+            | vk::Format::B8G8R8_SRGB
+            | vk::Format::B8G8R8A8_UNORM
+            | vk::Format::B8G8R8A8_SRGB
+    )
+}
+
+// Missing color channels read as zero and missing alpha reads as one, as they do in a shader
+fn to_rgba(texel: &[f32], bgr: bool) -> [f32; 4] {
+    let mut rgba = [0.0, 0.0, 0.0, 1.0];
+    rgba[..texel.len()].copy_from_slice(texel);
+    if bgr {
+        rgba.swap(0, 2);
+    }
+    rgba
+}
+
+fn from_rgba(mut rgba: [f32; 4], channels: usize, bgr: bool) -> Vec<f32> {
+    if bgr {
+        rgba.swap(0, 2);
+    }
+    rgba[..channels].to_vec()
+}
+
+fn swizzle(rgba: [f32; 4], components: &vk::ComponentMapping) -> [f32; 4] {
+    let select = |swizzle: vk::ComponentSwizzle, identity: usize| match swizzle {
+        vk::ComponentSwizzle::ZERO => 0.0,
+        vk::ComponentSwizzle::ONE => 1.0,
+        vk::ComponentSwizzle::R => rgba[0],
+        vk::ComponentSwizzle::G => rgba[1],
+        vk::ComponentSwizzle::B => rgba[2],
+        vk::ComponentSwizzle::A => rgba[3],
+        _ => rgba[identity],
+    };
+    [
+        select(components.r, 0),
+        select(components.g, 1),
+        select(components.b, 2),
+        select(components.a, 3),
+    ]
+}
@@ -0,0 +1,321 @@
+use crate::vulkan::{format_info, TextureDescription};
+use ash::vk;
+use snafu::{ensure, OptionExt, Snafu};
+
+type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility = "pub(crate)")]
+pub enum Error {
+    #[snafu(display("The file is not a DDS file"))]
+    InvalidDdsMagic,
+
+    #[snafu(display("The DDS file ends before its {} is complete", section))]
+    TruncatedDds { section: &'static str },
+
+    #[snafu(display(
+        "DDS pixel format with four character code {:?} is not supported",
+        code
+    ))]
+    UnsupportedDdsFourCc { code: String },
+
+    #[snafu(display("DDS pixel format without a four character code is not supported"))]
+    UnsupportedDdsPixelFormat,
+
+    #[snafu(display("DXGI format {} is not supported", format))]
+    UnsupportedDxgiFormat { format: u32 },
+
+    #[snafu(display("Only two dimensional DDS textures are supported"))]
+    UnsupportedDdsDimension,
+
+    #[snafu(display("DDS cubemaps must contain all six faces"))]
+    IncompleteDdsCubemap,
+}
+
+const MAGIC: &[u8; 4] = b"DDS ";
+const HEADER_END: usize = 128;
+const DX10_HEADER_END: usize = 148;
+
+const FLAG_MIPMAP_COUNT: u32 = 0x20000;
+const PIXEL_FORMAT_FOURCC: u32 = 0x4;
+const PIXEL_FORMAT_RGB: u32 = 0x40;
+const CAPS2_CUBEMAP: u32 = 0x200;
+const CAPS2_CUBEMAP_ALL_FACES: u32 = 0xFC00;
+const CAPS2_VOLUME: u32 = 0x20_0000;
+const DX10_TEXTURE_2D: u32 = 3;
+const DX10_MISC_TEXTURECUBE: u32 = 0x4;
+
+// Reads a DDS file with every stored mip level, array layer and cube face.
+// DDS stores each layer's full mip chain in turn,
+// so the data is reordered to match the level-major layout of a `TextureDescription`.
+pub fn parse_dds(bytes: &[u8]) -> Result<TextureDescription> {
+    ensure!(bytes.len() >= 4 && &bytes[..4] == MAGIC, InvalidDdsMagic);
+    ensure!(
+        bytes.len() >= HEADER_END,
+        TruncatedDds { section: "header" }
+    );
+
+    let flags = read_u32(bytes, 8);
+    let height = read_u32(bytes, 12).max(1);
+    let width = read_u32(bytes, 16).max(1);
+    let mip_map_count = read_u32(bytes, 28);
+    let pixel_format_flags = read_u32(bytes, 80);
+    let four_cc = &bytes[84..88];
+    let caps2 = read_u32(bytes, 112);
+
+    ensure!(caps2 & CAPS2_VOLUME == 0, UnsupportedDdsDimension);
+
+    let stored_mip_levels = if flags & FLAG_MIPMAP_COUNT != 0 {
+        mip_map_count.max(1)
+    } else {
+        1
+    };
+
+    let (format, layers, cubemap, data_start) =
+        if pixel_format_flags & PIXEL_FORMAT_FOURCC != 0 && four_cc == b"DX10" {
+            ensure!(
+                bytes.len() >= DX10_HEADER_END,
+                TruncatedDds {
+                    section: "DX10 header"
+                }
+            );
+            let dxgi_format = read_u32(bytes, 128);
+            let dimension = read_u32(bytes, 132);
+            let misc_flags = read_u32(bytes, 136);
+            let array_size = read_u32(bytes, 140).max(1);
+            ensure!(dimension == DX10_TEXTURE_2D, UnsupportedDdsDimension);
+
+            let format = convert_dxgi_format(dxgi_format).context(UnsupportedDxgiFormat {
+                format: dxgi_format,
+            })?;
+            let cubemap = misc_flags & DX10_MISC_TEXTURECUBE != 0;
+            let layers = if cubemap {
+                array_size.checked_mul(6).context(TruncatedDds {
+                    section: "pixel data",
+                })?
+            } else {
+                array_size
+            };
+            (format, layers, cubemap, DX10_HEADER_END)
+        } else {
+            let format = convert_legacy_format(bytes, pixel_format_flags, four_cc)?;
+            let cubemap = caps2 & CAPS2_CUBEMAP != 0;
+            if cubemap {
+                ensure!(
+                    caps2 & CAPS2_CUBEMAP_ALL_FACES == CAPS2_CUBEMAP_ALL_FACES,
+                    IncompleteDdsCubemap
+                );
+            }
+            let layers = if cubemap { 6 } else { 1 };
+            (format, layers, cubemap, HEADER_END)
+        };
+
+    // Every level holds at least a byte, which keeps bogus level counts from being iterated
+    ensure!(
+        stored_mip_levels as usize <= bytes.len() - data_start,
+        TruncatedDds {
+            section: "pixel data"
+        }
+    );
+
+    // Sizes that overflow describe more data than the file can hold
+    let info = format_info(format).expect("Every DDS format maps to a known block size");
+    let level_sizes = (0..stored_mip_levels)
+        .map(|level| {
+            info.checked_layer_size(
+                width.checked_shr(level).unwrap_or(0).max(1),
+                height.checked_shr(level).unwrap_or(0).max(1),
+            )
+        })
+        .collect::<Option<Vec<_>>>()
+        .context(TruncatedDds {
+            section: "pixel data",
+        })?;
+    let layer_size = level_sizes
+        .iter()
+        .try_fold(0usize, |total, size| total.checked_add(*size))
+        .context(TruncatedDds {
+            section: "pixel data",
+        })?;
+
+    let data = layer_size
+        .checked_mul(layers as usize)
+        .and_then(|size| size.checked_add(data_start))
+        .and_then(|data_end| bytes.get(data_start..data_end))
+        .context(TruncatedDds {
+            section: "pixel data",
+        })?;
+
+    let mut pixels = Vec::with_capacity(data.len());
+    let mut level_offset = 0;
+    for level_size in level_sizes.iter() {
+        for layer in 0..layers as usize {
+            let start = layer * layer_size + level_offset;
+            pixels.extend_from_slice(&data[start..start + level_size]);
+        }
+        level_offset += level_size;
+    }
+
+    // Block compressed formats can't be blitted, so a lone level is left without mips
+    let mip_levels = if stored_mip_levels > 1 || info.is_compressed() {
+        stored_mip_levels
+    } else {
+        TextureDescription::calculate_mip_levels(width, height)
+    };
+
+    Ok(TextureDescription {
+        pixels,
+        mip_levels,
+        layers,
+        cubemap,
+        stored_mip_levels,
+        ..TextureDescription::empty(width, height, format)
+    })
+}
+
+fn convert_legacy_format(
+    bytes: &[u8],
+    pixel_format_flags: u32,
+    four_cc: &[u8],
+) -> Result<vk::Format> {
+    if pixel_format_flags & PIXEL_FORMAT_FOURCC != 0 {
+        let format = match four_cc {
+            b"DXT1" => vk::Format::BC1_RGBA_UNORM_BLOCK,
+            b"DXT2" | b"DXT3" => vk::Format::BC2_UNORM_BLOCK,
+            b"DXT4" | b"DXT5" => vk::Format::BC3_UNORM_BLOCK,
+            b"ATI1" | b"BC4U" => vk::Format::BC4_UNORM_BLOCK,
+            b"BC4S" => vk::Format::BC4_SNORM_BLOCK,
+            b"ATI2" | b"BC5U" => vk::Format::BC5_UNORM_BLOCK,
+            b"BC5S" => vk::Format::BC5_SNORM_BLOCK,
+            _ => {
+                return UnsupportedDdsFourCc {
+                    code: String::from_utf8_lossy(four_cc).to_string(),
+                }
+                .fail()
+            }
+        };
+        return Ok(format);
+    }
+
+    let bit_count = read_u32(bytes, 88);
+    let red_mask = read_u32(bytes, 92);
+    ensure!(
+        pixel_format_flags & PIXEL_FORMAT_RGB != 0 && bit_count == 32,
+        UnsupportedDdsPixelFormat
+    );
+    match red_mask {
+        0x0000_00FF => Ok(vk::Format::R8G8B8A8_UNORM),
+        0x00FF_0000 => Ok(vk::Format::B8G8R8A8_UNORM),
+        _ => UnsupportedDdsPixelFormat.fail(),
+    }
+}
+
+fn convert_dxgi_format(format: u32) -> Option<vk::Format> {
+    let format = match format {
+        2 => vk::Format::R32G32B32A32_SFLOAT,
+        10 => vk::Format::R16G16B16A16_SFLOAT,
+        24 => vk::Format::A2B10G10R10_UNORM_PACK32,
+        26 => vk::Format::B10G11R11_UFLOAT_PACK32,
+        28 => vk::Format::R8G8B8A8_UNORM,
+        29 => vk::Format::R8G8B8A8_SRGB,
+        34 => vk::Format::R16G16_SFLOAT,
+        41 => vk::Format::R32_SFLOAT,
+        49 => vk::Format::R8G8_UNORM,
+        54 => vk::Format::R16_SFLOAT,
+        61 => vk::Format::R8_UNORM,
+        71 => vk::Format::BC1_RGBA_UNORM_BLOCK,
+        72 => vk::Format::BC1_RGBA_SRGB_BLOCK,
+        74 => vk::Format::BC2_UNORM_BLOCK,
+        75 => vk::Format::BC2_SRGB_BLOCK,
+        77 => vk::Format::BC3_UNORM_BLOCK,
+        78 => vk::Format::BC3_SRGB_BLOCK,
+        80 => vk::Format::BC4_UNORM_BLOCK,
+        81 => vk::Format::BC4_SNORM_BLOCK,
+        83 => vk::Format::BC5_UNORM_BLOCK,
+        84 => vk::Format::BC5_SNORM_BLOCK,
+        87 => vk::Format::B8G8R8A8_UNORM,
+        91 => vk::Format::B8G8R8A8_SRGB,
+        95 => vk::Format::BC6H_UFLOAT_BLOCK,
+        96 => vk::Format::BC6H_SFLOAT_BLOCK,
+        98 => vk::Format::BC7_UNORM_BLOCK,
+        99 => vk::Format::BC7_SRGB_BLOCK,
+        _ => return None,
+    };
+    Some(format)
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    let mut word = [0; 4];
+    word.copy_from_slice(&bytes[offset..offset + 4]);
+    u32::from_le_bytes(word)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DXGI_R8G8B8A8_UNORM: u32 = 28;
+
+    fn write_u32(bytes: &mut [u8], offset: usize, value: u32) {
+        bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    fn dx10_header(width: u32, height: u32, misc_flags: u32, array_size: u32) -> Vec<u8> {
+        let mut bytes = vec![0; DX10_HEADER_END];
+        bytes[..4].copy_from_slice(MAGIC);
+        write_u32(&mut bytes, 12, height);
+        write_u32(&mut bytes, 16, width);
+        write_u32(&mut bytes, 80, PIXEL_FORMAT_FOURCC);
+        bytes[84..88].copy_from_slice(b"DX10");
+        write_u32(&mut bytes, 128, DXGI_R8G8B8A8_UNORM);
+        write_u32(&mut bytes, 132, DX10_TEXTURE_2D);
+        write_u32(&mut bytes, 136, misc_flags);
+        write_u32(&mut bytes, 140, array_size);
+        bytes
+    }
+
+    #[test]
+    fn rejects_truncated_header() {
+        let mut bytes = vec![0; HEADER_END - 1];
+        bytes[..4].copy_from_slice(MAGIC);
+        assert!(matches!(
+            parse_dds(&bytes),
+            Err(Error::TruncatedDds { section: "header" })
+        ));
+    }
+
+    #[test]
+    fn rejects_missing_pixel_data() {
+        let mut bytes = dx10_header(2, 2, 0, 1);
+        bytes.extend_from_slice(&[0; 15]);
+        assert!(matches!(
+            parse_dds(&bytes),
+            Err(Error::TruncatedDds {
+                section: "pixel data"
+            })
+        ));
+    }
+
+    #[test]
+    fn rejects_oversized_extent() {
+        let mut bytes = dx10_header(u32::MAX, u32::MAX, DX10_MISC_TEXTURECUBE, u32::MAX);
+        bytes.extend_from_slice(&[0; 4]);
+        assert!(matches!(
+            parse_dds(&bytes),
+            Err(Error::TruncatedDds {
+                section: "pixel data"
+            })
+        ));
+    }
+
+    #[test]
+    fn reads_dx10_cubemap() {
+        let mut bytes = dx10_header(1, 1, DX10_MISC_TEXTURECUBE, 1);
+        bytes.extend((0..24).map(|value| value as u8));
+        let description = parse_dds(&bytes).unwrap();
+        assert!(description.cubemap);
+        assert_eq!(description.layers, 6);
+        assert_eq!(description.format, vk::Format::R8G8B8A8_UNORM);
+        assert_eq!(description.pixels, (0..24).collect::<Vec<u8>>());
+    }
+}
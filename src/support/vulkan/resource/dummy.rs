@@ -1,4 +1,6 @@
-use crate::vulkan::{CommandPool, ImageView, Sampler, Texture, TextureDescription, VulkanContext};
+use crate::vulkan::{
+    ColorSpace, CommandPool, ImageView, Sampler, Texture, TextureDescription, VulkanContext,
+};
 use ash::vk;
 use std::sync::Arc;
 
@@ -11,11 +13,9 @@ pub struct DummyImage {
 impl DummyImage {
     pub fn new(context: Arc<VulkanContext>, command_pool: &CommandPool) -> Self {
         let description = TextureDescription {
-            format: vk::Format::R8G8B8A8_UNORM,
-            width: 1,
-            height: 1,
-            pixels: Vec::new(),
             mip_levels: 1,
+            color_space: ColorSpace::Linear,
+            ..TextureDescription::empty(1, 1, vk::Format::R8G8B8A8_UNORM)
         };
         let image_create_info = vk::ImageCreateInfo::builder()
             .image_type(vk::ImageType::TYPE_2D)
@@ -0,0 +1,282 @@
+use ash::vk;
+
+// The size of a format's texel blocks.
+// Uncompressed formats have 1x1 blocks, so the block size is the size of a texel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FormatInfo {
+    pub block_width: u32,
+    pub block_height: u32,
+    pub block_size: u32,
+}
+
+impl FormatInfo {
+    const fn texel(block_size: u32) -> Self {
+        Self {
+            block_width: 1,
+            block_height: 1,
+            block_size,
+        }
+    }
+
+    const fn block(block_width: u32, block_height: u32, block_size: u32) -> Self {
+        Self {
+            block_width,
+            block_height,
+            block_size,
+        }
+    }
+
+    pub fn is_compressed(&self) -> bool {
+        self.block_width > 1 || self.block_height > 1
+    }
+
+    // The number of bytes in a single layer of an image with the given dimensions
+    pub fn layer_size(&self, width: u32, height: u32) -> usize {
+        let blocks_wide = width.div_ceil(self.block_width) as usize;
+        let blocks_high = height.div_ceil(self.block_height) as usize;
+        blocks_wide * blocks_high * self.block_size as usize
+    }
+
+    // For extents read from files, which may describe more data than can be addressed
+    pub fn checked_layer_size(&self, width: u32, height: u32) -> Option<usize> {
+        let blocks_wide = width.div_ceil(self.block_width) as usize;
+        let blocks_high = height.div_ceil(self.block_height) as usize;
+        blocks_wide
+            .checked_mul(blocks_high)?
+            .checked_mul(self.block_size as usize)
+    }
+}
+
+pub fn format_info(format: vk::Format) -> Option<FormatInfo> {
+    let info = match format {
+        vk::Format::R8_UNORM | vk::Format::R8_SNORM | vk::Format::R8_UINT | vk::Format::R8_SRGB => {
+            FormatInfo::texel(1)
+        }
+        vk::Format::R8G8_UNORM
+        | vk::Format::R8G8_SNORM
+        | vk::Format::R8G8_SRGB
+        | vk::Format::R16_UNORM
+        | vk::Format::R16_SFLOAT => FormatInfo::texel(2),
+        vk::Format::R8G8B8_UNORM
+        | vk::Format::R8G8B8_SRGB
+        | vk::Format::B8G8R8_UNORM
+        | vk::Format::B8G8R8_SRGB => FormatInfo::texel(3),
+        vk::Format::R8G8B8A8_UNORM
+        | vk::Format::R8G8B8A8_SNORM
+        | vk::Format::R8G8B8A8_SRGB
+        | vk::Format::B8G8R8A8_UNORM
+        | vk::Format::B8G8R8A8_SRGB
+        | vk::Format::A2B10G10R10_UNORM_PACK32
+        | vk::Format::B10G11R11_UFLOAT_PACK32
+        | vk::Format::E5B9G9R9_UFLOAT_PACK32
+        | vk::Format::R16G16_UNORM
+        | vk::Format::R16G16_SFLOAT
+        | vk::Format::R32_SFLOAT => FormatInfo::texel(4),
+        vk::Format::R16G16B16_UNORM | vk::Format::R16G16B16_SFLOAT => FormatInfo::texel(6),
+        vk::Format::R16G16B16A16_UNORM
+        | vk::Format::R16G16B16A16_SFLOAT
+        | vk::Format::R32G32_SFLOAT => FormatInfo::texel(8),
+        vk::Format::R32G32B32_SFLOAT => FormatInfo::texel(12),
+        vk::Format::R32G32B32A32_SFLOAT => FormatInfo::texel(16),
+
+        vk::Format::BC1_RGB_UNORM_BLOCK
+        | vk::Format::BC1_RGB_SRGB_BLOCK
+        | vk::Format::BC1_RGBA_UNORM_BLOCK
+        | vk::Format::BC1_RGBA_SRGB_BLOCK
+        | vk::Format::BC4_UNORM_BLOCK
+        | vk::Format::BC4_SNORM_BLOCK => FormatInfo::block(4, 4, 8),
+        vk::Format::BC2_UNORM_BLOCK
+        | vk::Format::BC2_SRGB_BLOCK
+        | vk::Format::BC3_UNORM_BLOCK
+        | vk::Format::BC3_SRGB_BLOCK
+        | vk::Format::BC5_UNORM_BLOCK
+        | vk::Format::BC5_SNORM_BLOCK
+        | vk::Format::BC6H_UFLOAT_BLOCK
+        | vk::Format::BC6H_SFLOAT_BLOCK
+        | vk::Format::BC7_UNORM_BLOCK
+        | vk::Format::BC7_SRGB_BLOCK => FormatInfo::block(4, 4, 16),
+
+        vk::Format::ETC2_R8G8B8_UNORM_BLOCK
+        | vk::Format::ETC2_R8G8B8_SRGB_BLOCK
+        | vk::Format::ETC2_R8G8B8A1_UNORM_BLOCK
+        | vk::Format::ETC2_R8G8B8A1_SRGB_BLOCK
+        | vk::Format::EAC_R11_UNORM_BLOCK
+        | vk::Format::EAC_R11_SNORM_BLOCK => FormatInfo::block(4, 4, 8),
+        vk::Format::ETC2_R8G8B8A8_UNORM_BLOCK
+        | vk::Format::ETC2_R8G8B8A8_SRGB_BLOCK
+        | vk::Format::EAC_R11G11_UNORM_BLOCK
+        | vk::Format::EAC_R11G11_SNORM_BLOCK => FormatInfo::block(4, 4, 16),
+
+        vk::Format::ASTC_4X4_UNORM_BLOCK | vk::Format::ASTC_4X4_SRGB_BLOCK => {
+            FormatInfo::block(4, 4, 16)
+        }
+        vk::Format::ASTC_5X4_UNORM_BLOCK | vk::Format::ASTC_5X4_SRGB_BLOCK => {
+            FormatInfo::block(5, 4, 16)
+        }
+        vk::Format::ASTC_5X5_UNORM_BLOCK | vk::Format::ASTC_5X5_SRGB_BLOCK => {
+            FormatInfo::block(5, 5, 16)
+        }
+        vk::Format::ASTC_6X5_UNORM_BLOCK | vk::Format::ASTC_6X5_SRGB_BLOCK => {
+            FormatInfo::block(6, 5, 16)
+        }
+        vk::Format::ASTC_6X6_UNORM_BLOCK | vk::Format::ASTC_6X6_SRGB_BLOCK => {
+            FormatInfo::block(6, 6, 16)
+        }
+        vk::Format::ASTC_8X5_UNORM_BLOCK | vk::Format::ASTC_8X5_SRGB_BLOCK => {
+            FormatInfo::block(8, 5, 16)
+        }
+        vk::Format::ASTC_8X6_UNORM_BLOCK | vk::Format::ASTC_8X6_SRGB_BLOCK => {
+            FormatInfo::block(8, 6, 16)
+        }
+        vk::Format::ASTC_8X8_UNORM_BLOCK | vk::Format::ASTC_8X8_SRGB_BLOCK => {
+            FormatInfo::block(8, 8, 16)
+        }
+        vk::Format::ASTC_10X5_UNORM_BLOCK | vk::Format::ASTC_10X5_SRGB_BLOCK => {
+            FormatInfo::block(10, 5, 16)
+        }
+        vk::Format::ASTC_10X6_UNORM_BLOCK | vk::Format::ASTC_10X6_SRGB_BLOCK => {
+            FormatInfo::block(10, 6, 16)
+        }
+        vk::Format::ASTC_10X8_UNORM_BLOCK | vk::Format::ASTC_10X8_SRGB_BLOCK => {
+            FormatInfo::block(10, 8, 16)
+        }
+        vk::Format::ASTC_10X10_UNORM_BLOCK | vk::Format::ASTC_10X10_SRGB_BLOCK => {
+            FormatInfo::block(10, 10, 16)
+        }
+        vk::Format::ASTC_12X10_UNORM_BLOCK | vk::Format::ASTC_12X10_SRGB_BLOCK => {
+            FormatInfo::block(12, 10, 16)
+        }
+        vk::Format::ASTC_12X12_UNORM_BLOCK | vk::Format::ASTC_12X12_SRGB_BLOCK => {
+            FormatInfo::block(12, 12, 16)
+        }
+        _ => return None,
+    };
+    Some(info)
+}
+
+// Color textures are stored with the sRGB transfer function
+// and decoded to linear values by the sampler.
+// Data textures such as normal maps are stored as linear values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorSpace {
+    Linear,
+    Srgb,
+}
+
+impl ColorSpace {
+    pub fn of(format: vk::Format) -> Self {
+        if linear_format(format).is_some() {
+            ColorSpace::Srgb
+        } else {
+            ColorSpace::Linear
+        }
+    }
+}
+
+const SRGB_FORMAT_PAIRS: &[(vk::Format, vk::Format)] = &[
+    (vk::Format::R8_UNORM, vk::Format::R8_SRGB),
+    (vk::Format::R8G8_UNORM, vk::Format::R8G8_SRGB),
+    (vk::Format::R8G8B8_UNORM, vk::Format::R8G8B8_SRGB),
+    (vk::Format::B8G8R8_UNORM, vk::Format::B8G8R8_SRGB),
+    (vk::Format::R8G8B8A8_UNORM, vk::Format::R8G8B8A8_SRGB),
+    (vk::Format::B8G8R8A8_UNORM, vk::Format::B8G8R8A8_SRGB),
+    (
+        vk::Format::BC1_RGB_UNORM_BLOCK,
+        vk::Format::BC1_RGB_SRGB_BLOCK,
+    ),
+    (
+        vk::Format::BC1_RGBA_UNORM_BLOCK,
+        vk::Format::BC1_RGBA_SRGB_BLOCK,
+    ),
+    (vk::Format::BC2_UNORM_BLOCK, vk::Format::BC2_SRGB_BLOCK),
+    (vk::Format::BC3_UNORM_BLOCK, vk::Format::BC3_SRGB_BLOCK),
+    (vk::Format::BC7_UNORM_BLOCK, vk::Format::BC7_SRGB_BLOCK),
+    (
+        vk::Format::ETC2_R8G8B8_UNORM_BLOCK,
+        vk::Format::ETC2_R8G8B8_SRGB_BLOCK,
+    ),
+    (
+        vk::Format::ETC2_R8G8B8A1_UNORM_BLOCK,
+        vk::Format::ETC2_R8G8B8A1_SRGB_BLOCK,
+    ),
+    (
+        vk::Format::ETC2_R8G8B8A8_UNORM_BLOCK,
+        vk::Format::ETC2_R8G8B8A8_SRGB_BLOCK,
+    ),
+    (
+        vk::Format::ASTC_4X4_UNORM_BLOCK,
+        vk::Format::ASTC_4X4_SRGB_BLOCK,
+    ),
+    (
+        vk::Format::ASTC_5X4_UNORM_BLOCK,
+        vk::Format::ASTC_5X4_SRGB_BLOCK,
+    ),
+    (
+        vk::Format::ASTC_5X5_UNORM_BLOCK,
+        vk::Format::ASTC_5X5_SRGB_BLOCK,
+    ),
+    (
+        vk::Format::ASTC_6X5_UNORM_BLOCK,
+        vk::Format::ASTC_6X5_SRGB_BLOCK,
+    ),
+    (
+        vk::Format::ASTC_6X6_UNORM_BLOCK,
+        vk::Format::ASTC_6X6_SRGB_BLOCK,
+    ),
+    (
+        vk::Format::ASTC_8X5_UNORM_BLOCK,
+        vk::Format::ASTC_8X5_SRGB_BLOCK,
+    ),
+    (
+        vk::Format::ASTC_8X6_UNORM_BLOCK,
+        vk::Format::ASTC_8X6_SRGB_BLOCK,
+    ),
+    (
+        vk::Format::ASTC_8X8_UNORM_BLOCK,
+        vk::Format::ASTC_8X8_SRGB_BLOCK,
+    ),
+    (
+        vk::Format::ASTC_10X5_UNORM_BLOCK,
+        vk::Format::ASTC_10X5_SRGB_BLOCK,
+    ),
+    (
+        vk::Format::ASTC_10X6_UNORM_BLOCK,
+        vk::Format::ASTC_10X6_SRGB_BLOCK,
+    ),
+    (
+        vk::Format::ASTC_10X8_UNORM_BLOCK,
+        vk::Format::ASTC_10X8_SRGB_BLOCK,
+    ),
+    (
+        vk::Format::ASTC_10X10_UNORM_BLOCK,
+        vk::Format::ASTC_10X10_SRGB_BLOCK,
+    ),
+    (
+        vk::Format::ASTC_12X10_UNORM_BLOCK,
+        vk::Format::ASTC_12X10_SRGB_BLOCK,
+    ),
+    (
+        vk::Format::ASTC_12X12_UNORM_BLOCK,
+        vk::Format::ASTC_12X12_SRGB_BLOCK,
+    ),
+    (
+        vk::Format::A8B8G8R8_UNORM_PACK32,
+        vk::Format::A8B8G8R8_SRGB_PACK32,
+    ),
+];
+
+// The sRGB counterpart of a UNORM format, if one exists
+pub fn srgb_format(format: vk::Format) -> Option<vk::Format> {
+    SRGB_FORMAT_PAIRS
+        .iter()
+        .find(|(linear, _)| *linear == format)
+        .map(|(_, srgb)| *srgb)
+}
+
+// The UNORM counterpart of an sRGB format, if one exists
+pub fn linear_format(format: vk::Format) -> Option<vk::Format> {
+    SRGB_FORMAT_PAIRS
+        .iter()
+        .find(|(_, srgb)| *srgb == format)
+        .map(|(linear, _)| *linear)
+}
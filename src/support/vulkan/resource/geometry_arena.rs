@@ -0,0 +1,588 @@
+use crate::vulkan::{Buffer, UploadManager, UploadTicket, Vertex, VulkanContext};
+use ash::{version::DeviceV1_0, vk};
+use snafu::{OptionExt, ResultExt, Snafu};
+use std::{collections::HashMap, marker::PhantomData, mem, ops::Range, sync::Arc};
+
+type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility = "pub(crate)")]
+pub enum Error {
+    #[snafu(display("Failed to create geometry arena buffer: {}", source))]
+    CreateArenaBuffer {
+        source: crate::vulkan::buffer::Error,
+    },
+
+    #[snafu(display("Failed to upload geometry arena data: {}", source))]
+    UploadArenaData {
+        source: crate::vulkan::upload_manager::Error,
+    },
+
+    #[snafu(display("Failed to copy geometry arena data: {}", source))]
+    CopyArenaData {
+        source: crate::vulkan::upload_manager::Error,
+    },
+
+    #[snafu(display(
+        "Failed to allocate {} vertices and {} index bytes after growing the geometry arena",
+        vertex_count,
+        index_bytes
+    ))]
+    AllocateAfterGrowth { vertex_count: u64, index_bytes: u64 },
+
+    #[snafu(display("Geometry handle {:?} does not belong to this arena", handle))]
+    InvalidGeometryHandle { handle: GeometryHandle },
+}
+
+pub enum IndexData<'a> {
+    U16(&'a [u16]),
+    U32(&'a [u32]),
+}
+
+impl<'a> IndexData<'a> {
+    pub fn index_type(&self) -> vk::IndexType {
+        match self {
+            IndexData::U16(_) => vk::IndexType::UINT16,
+            IndexData::U32(_) => vk::IndexType::UINT32,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            IndexData::U16(indices) => indices.len(),
+            IndexData::U32(indices) => indices.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn index_size(index_type: vk::IndexType) -> u64 {
+        if index_type == vk::IndexType::UINT16 {
+            mem::size_of::<u16>() as _
+        } else {
+            mem::size_of::<u32>() as _
+        }
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            IndexData::U16(indices) => bytes_of(indices),
+            IndexData::U32(indices) => bytes_of(indices),
+        }
+    }
+}
+
+fn bytes_of<T: Copy>(data: &[T]) -> Vec<u8> {
+    let size = mem::size_of_val(data);
+    unsafe { std::slice::from_raw_parts(data.as_ptr() as *const u8, size) }.to_vec()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct GeometryHandle(usize);
+
+// Where a piece of geometry lives in the arena's shared buffers.
+// These values change when the arena is compacted or grown.
+#[derive(Debug, Clone, Copy)]
+pub struct GeometryRange {
+    pub vertex_offset: i32,
+    pub number_of_vertices: u32,
+    pub first_index: u32,
+    pub number_of_indices: u32,
+    pub index_type: vk::IndexType,
+}
+
+impl GeometryRange {
+    fn vertex_range(&self) -> Range<u64> {
+        let start = self.vertex_offset as u64;
+        start..start + self.number_of_vertices as u64
+    }
+
+    fn index_byte_range(&self) -> Range<u64> {
+        let index_size = IndexData::index_size(self.index_type);
+        let start = self.first_index as u64 * index_size;
+        start..start + self.number_of_indices as u64 * index_size
+    }
+}
+
+// First-fit allocator over a linear range, merging neighbouring ranges when freed
+struct RangeAllocator {
+    capacity: u64,
+    free_ranges: Vec<Range<u64>>,
+}
+
+impl RangeAllocator {
+    fn new(capacity: u64) -> Self {
+        let mut allocator = Self {
+            capacity,
+            free_ranges: Vec::new(),
+        };
+        allocator.reset(0);
+        allocator
+    }
+
+    // Marks everything up to `used` as allocated and the rest as free
+    fn reset(&mut self, used: u64) {
+        self.free_ranges.clear();
+        if used < self.capacity {
+            self.free_ranges.push(used..self.capacity);
+        }
+    }
+
+    fn allocate(&mut self, size: u64, alignment: u64) -> Option<u64> {
+        if size == 0 {
+            return Some(0);
+        }
+
+        for index in 0..self.free_ranges.len() {
+            let range = self.free_ranges[index].clone();
+            let start = align_up(range.start, alignment);
+            if start + size > range.end {
+                continue;
+            }
+
+            self.free_ranges.remove(index);
+            if start + size < range.end {
+                self.free_ranges.insert(index, start + size..range.end);
+            }
+            if range.start < start {
+                self.free_ranges.insert(index, range.start..start);
+            }
+            return Some(start);
+        }
+
+        None
+    }
+
+    fn free(&mut self, range: Range<u64>) {
+        if range.start == range.end {
+            return;
+        }
+
+        let index = self
+            .free_ranges
+            .iter()
+            .position(|free_range| free_range.start > range.start)
+            .unwrap_or(self.free_ranges.len());
+        self.free_ranges.insert(index, range);
+
+        // Merge with the following range, then with the preceding one
+        if index + 1 < self.free_ranges.len()
+            && self.free_ranges[index].end == self.free_ranges[index + 1].start
+        {
+            let next = self.free_ranges.remove(index + 1);
+            self.free_ranges[index].end = next.end;
+        }
+        if index > 0 && self.free_ranges[index - 1].end == self.free_ranges[index].start {
+            let current = self.free_ranges.remove(index);
+            self.free_ranges[index - 1].end = current.end;
+        }
+    }
+
+    fn free_space(&self) -> u64 {
+        self.free_ranges
+            .iter()
+            .map(|range| range.end - range.start)
+            .sum()
+    }
+}
+
+fn align_up(value: u64, alignment: u64) -> u64 {
+    value.div_ceil(alignment) * alignment
+}
+
+struct PendingUpload {
+    handle: GeometryHandle,
+    vertices: Vec<u8>,
+    indices: Vec<u8>,
+}
+
+// Buffers replaced by compaction or growth. Besides the batch copying out of them,
+// frames recorded before the generation changed may still be binding them.
+// The ticket is known once the batch is submitted.
+struct RetiredBuffer {
+    ticket: Option<UploadTicket>,
+    _buffer: Buffer,
+}
+
+// Sub-allocates vertex and index ranges of a single vertex type from shared device local buffers.
+// New geometry is kept on the CPU until `flush` stages it through the upload manager's ring
+// and submits it in one batch, without waiting for it.
+pub struct GeometryArena<V: Vertex> {
+    context: Arc<VulkanContext>,
+    vertex_buffer: Buffer,
+    index_buffer: Buffer,
+    vertex_allocator: RangeAllocator,
+    index_allocator: RangeAllocator,
+    ranges: HashMap<GeometryHandle, GeometryRange>,
+    pending_uploads: Vec<PendingUpload>,
+    retired_buffers: Vec<RetiredBuffer>,
+    next_handle: usize,
+    generation: u64,
+    _vertex: PhantomData<V>,
+}
+
+impl<V: Vertex> GeometryArena<V> {
+    // Index ranges are aligned so that both 16 and 32 bit indices can share one buffer
+    const INDEX_ALIGNMENT: u64 = mem::size_of::<u32>() as _;
+
+    pub fn new(
+        context: Arc<VulkanContext>,
+        vertex_capacity: u64,
+        index_capacity_in_bytes: u64,
+    ) -> Result<Self> {
+        let vertex_buffer = Self::create_buffer(
+            context.clone(),
+            (vertex_capacity * V::stride() as u64).max(1),
+            vk::BufferUsageFlags::VERTEX_BUFFER,
+        )?;
+        let index_buffer = Self::create_buffer(
+            context.clone(),
+            index_capacity_in_bytes.max(1),
+            vk::BufferUsageFlags::INDEX_BUFFER,
+        )?;
+
+        Ok(Self {
+            context,
+            vertex_buffer,
+            index_buffer,
+            vertex_allocator: RangeAllocator::new(vertex_capacity),
+            index_allocator: RangeAllocator::new(index_capacity_in_bytes),
+            ranges: HashMap::new(),
+            pending_uploads: Vec::new(),
+            retired_buffers: Vec::new(),
+            next_handle: 0,
+            generation: 0,
+            _vertex: PhantomData,
+        })
+    }
+
+    fn create_buffer(
+        context: Arc<VulkanContext>,
+        size: vk::DeviceSize,
+        usage: vk::BufferUsageFlags,
+    ) -> Result<Buffer> {
+        Buffer::new_mapped_basic(
+            context,
+            size,
+            usage | vk::BufferUsageFlags::TRANSFER_DST | vk::BufferUsageFlags::TRANSFER_SRC,
+            vk_mem::MemoryUsage::GpuOnly,
+        )
+        .context(CreateArenaBuffer)
+    }
+
+    // The geometry is usable once the ticket returned by `flush` is complete.
+    // If the arena has to be compacted or grown, the generation changes
+    // and command buffers referencing the arena must be recorded again
+    // before `release_retired_buffers` frees the replaced buffers.
+    pub fn allocate(
+        &mut self,
+        upload_manager: &mut UploadManager,
+        vertices: &[V],
+        indices: IndexData,
+    ) -> Result<GeometryHandle> {
+        let index_type = indices.index_type();
+        let index_size = IndexData::index_size(index_type);
+        let vertex_count = vertices.len() as u64;
+        let index_bytes = align_up(indices.len() as u64 * index_size, Self::INDEX_ALIGNMENT);
+
+        let (vertex_offset, index_offset) = match self.try_allocate(vertex_count, index_bytes) {
+            Some(offsets) => offsets,
+            None => {
+                self.compact(upload_manager)?;
+                match self.try_allocate(vertex_count, index_bytes) {
+                    Some(offsets) => offsets,
+                    None => {
+                        let vertex_capacity = (self.vertex_allocator.capacity * 2)
+                            .max(self.vertex_allocator.capacity + vertex_count);
+                        let index_capacity = (self.index_allocator.capacity * 2)
+                            .max(self.index_allocator.capacity + index_bytes);
+                        self.reallocate(upload_manager, vertex_capacity, index_capacity)?;
+                        self.try_allocate(vertex_count, index_bytes).context(
+                            AllocateAfterGrowth {
+                                vertex_count,
+                                index_bytes,
+                            },
+                        )?
+                    }
+                }
+            }
+        };
+
+        let handle = GeometryHandle(self.next_handle);
+        self.next_handle += 1;
+
+        self.ranges.insert(
+            handle,
+            GeometryRange {
+                vertex_offset: vertex_offset as _,
+                number_of_vertices: vertices.len() as _,
+                first_index: (index_offset / index_size) as _,
+                number_of_indices: indices.len() as _,
+                index_type,
+            },
+        );
+
+        self.pending_uploads.push(PendingUpload {
+            handle,
+            vertices: bytes_of(vertices),
+            indices: indices.to_bytes(),
+        });
+
+        Ok(handle)
+    }
+
+    fn try_allocate(&mut self, vertex_count: u64, index_bytes: u64) -> Option<(u64, u64)> {
+        let vertex_offset = self.vertex_allocator.allocate(vertex_count, 1)?;
+        match self
+            .index_allocator
+            .allocate(index_bytes, Self::INDEX_ALIGNMENT)
+        {
+            Some(index_offset) => Some((vertex_offset, index_offset)),
+            None => {
+                self.vertex_allocator
+                    .free(vertex_offset..vertex_offset + vertex_count);
+                None
+            }
+        }
+    }
+
+    pub fn free(&mut self, handle: GeometryHandle) -> Result<()> {
+        let range = self
+            .ranges
+            .remove(&handle)
+            .ok_or(Error::InvalidGeometryHandle { handle })?;
+        self.pending_uploads
+            .retain(|pending_upload| pending_upload.handle != handle);
+        self.vertex_allocator.free(range.vertex_range());
+        // Index ranges are allocated with aligned sizes, so they are freed the same way
+        let index_byte_range = range.index_byte_range();
+        self.index_allocator
+            .free(index_byte_range.start..align_up(index_byte_range.end, Self::INDEX_ALIGNMENT));
+        Ok(())
+    }
+
+    pub fn range(&self, handle: GeometryHandle) -> Option<&GeometryRange> {
+        self.ranges.get(&handle)
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    pub fn free_vertices(&self) -> u64 {
+        self.vertex_allocator.free_space()
+    }
+
+    pub fn free_index_bytes(&self) -> u64 {
+        self.index_allocator.free_space()
+    }
+
+    // Stages all pending geometry and submits it, along with any copies made by compaction or growth.
+    // Buffers that were replaced are kept until `release_retired_buffers` is called.
+    pub fn flush(&mut self, upload_manager: &mut UploadManager) -> Result<UploadTicket> {
+        for pending_upload in self.pending_uploads.iter() {
+            let range = self.ranges[&pending_upload.handle];
+            upload_manager
+                .upload_buffer(
+                    &pending_upload.vertices,
+                    self.vertex_buffer.buffer(),
+                    range.vertex_range().start * V::stride() as u64,
+                )
+                .context(UploadArenaData)?;
+            upload_manager
+                .upload_buffer(
+                    &pending_upload.indices,
+                    self.index_buffer.buffer(),
+                    range.index_byte_range().start,
+                )
+                .context(UploadArenaData)?;
+        }
+        self.pending_uploads.clear();
+
+        let ticket = upload_manager.submit().context(UploadArenaData)?;
+        for retired_buffer in self.retired_buffers.iter_mut() {
+            retired_buffer.ticket.get_or_insert(ticket);
+        }
+
+        Ok(ticket)
+    }
+
+    // Frames in flight may still bind the replaced buffers through command buffers
+    // recorded for an earlier generation, so this waits for the device to be idle.
+    // Buffers whose copies have not been submitted by `flush` yet are kept.
+    pub fn release_retired_buffers(&mut self) {
+        if !self
+            .retired_buffers
+            .iter()
+            .any(|retired_buffer| retired_buffer.ticket.is_some())
+        {
+            return;
+        }
+
+        self.context.logical_device().wait_idle();
+        self.retired_buffers
+            .retain(|retired_buffer| retired_buffer.ticket.is_none());
+    }
+
+    // Moves all live geometry to the start of the buffers, removing the gaps left by freed ranges
+    // The copies are recorded into the upload manager's current batch, so they are sent by `flush`
+    pub fn compact(&mut self, upload_manager: &mut UploadManager) -> Result<()> {
+        let vertex_capacity = self.vertex_allocator.capacity;
+        let index_capacity = self.index_allocator.capacity;
+        self.reallocate(upload_manager, vertex_capacity, index_capacity)
+    }
+
+    fn reallocate(
+        &mut self,
+        upload_manager: &mut UploadManager,
+        vertex_capacity: u64,
+        index_capacity: u64,
+    ) -> Result<()> {
+        let vertex_buffer = Self::create_buffer(
+            self.context.clone(),
+            (vertex_capacity * V::stride() as u64).max(1),
+            vk::BufferUsageFlags::VERTEX_BUFFER,
+        )?;
+        let index_buffer = Self::create_buffer(
+            self.context.clone(),
+            index_capacity.max(1),
+            vk::BufferUsageFlags::INDEX_BUFFER,
+        )?;
+
+        let stride = V::stride() as u64;
+        let mut vertex_regions = Vec::new();
+        let mut index_regions = Vec::new();
+        let mut next_vertex = 0;
+        let mut next_index_byte = 0;
+
+        // Sorting keeps the copies in order, which keeps the result deterministic
+        let mut handles = self.ranges.keys().copied().collect::<Vec<_>>();
+        handles.sort_by_key(|handle| handle.0);
+        for handle in handles {
+            // Pending geometry is uploaded to its new range by `flush`,
+            // so copying it as well would write the same range twice in one batch
+            let pending = self
+                .pending_uploads
+                .iter()
+                .any(|pending_upload| pending_upload.handle == handle);
+            let range = self.ranges.get_mut(&handle).expect("Failed to get range!");
+            let vertex_range = range.vertex_range();
+            let index_byte_range = range.index_byte_range();
+
+            if !pending && vertex_range.end > vertex_range.start {
+                vertex_regions.push(vk::BufferCopy {
+                    src_offset: vertex_range.start * stride,
+                    dst_offset: next_vertex * stride,
+                    size: (vertex_range.end - vertex_range.start) * stride,
+                });
+            }
+            if !pending && index_byte_range.end > index_byte_range.start {
+                index_regions.push(vk::BufferCopy {
+                    src_offset: index_byte_range.start,
+                    dst_offset: next_index_byte,
+                    size: index_byte_range.end - index_byte_range.start,
+                });
+            }
+
+            range.vertex_offset = next_vertex as _;
+            range.first_index = (next_index_byte / IndexData::index_size(range.index_type)) as _;
+
+            next_vertex += vertex_range.end - vertex_range.start;
+            next_index_byte = align_up(
+                next_index_byte + (index_byte_range.end - index_byte_range.start),
+                Self::INDEX_ALIGNMENT,
+            );
+        }
+
+        let old_vertex_buffer = self.vertex_buffer.buffer();
+        let old_index_buffer = self.index_buffer.buffer();
+        let new_vertex_buffer = vertex_buffer.buffer();
+        let new_index_buffer = index_buffer.buffer();
+        upload_manager
+            .record(|device, command_buffer| unsafe {
+                if !vertex_regions.is_empty() {
+                    device.cmd_copy_buffer(
+                        command_buffer,
+                        old_vertex_buffer,
+                        new_vertex_buffer,
+                        &vertex_regions,
+                    );
+                }
+                if !index_regions.is_empty() {
+                    device.cmd_copy_buffer(
+                        command_buffer,
+                        old_index_buffer,
+                        new_index_buffer,
+                        &index_regions,
+                    );
+                }
+            })
+            .context(CopyArenaData)?;
+
+        let old_vertex_buffer = mem::replace(&mut self.vertex_buffer, vertex_buffer);
+        let old_index_buffer = mem::replace(&mut self.index_buffer, index_buffer);
+        for buffer in [old_vertex_buffer, old_index_buffer] {
+            self.retired_buffers.push(RetiredBuffer {
+                ticket: None,
+                _buffer: buffer,
+            });
+        }
+        self.vertex_allocator = RangeAllocator::new(vertex_capacity);
+        self.vertex_allocator.reset(next_vertex);
+        self.index_allocator = RangeAllocator::new(index_capacity);
+        self.index_allocator.reset(next_index_byte);
+        self.generation += 1;
+
+        Ok(())
+    }
+
+    pub fn vertex_buffer(&self) -> &Buffer {
+        &self.vertex_buffer
+    }
+
+    pub fn index_buffer(&self) -> &Buffer {
+        &self.index_buffer
+    }
+
+    pub fn bind_vertex_buffer(&self, device: &ash::Device, command_buffer: vk::CommandBuffer) {
+        unsafe {
+            device.cmd_bind_vertex_buffers(command_buffer, 0, &[self.vertex_buffer.buffer()], &[0]);
+        }
+    }
+
+    // The index buffer has to be bound with the index type of the range being drawn
+    pub fn bind_index_buffer(
+        &self,
+        device: &ash::Device,
+        command_buffer: vk::CommandBuffer,
+        index_type: vk::IndexType,
+    ) {
+        unsafe {
+            device.cmd_bind_index_buffer(command_buffer, self.index_buffer.buffer(), 0, index_type);
+        }
+    }
+
+    pub fn draw(
+        &self,
+        device: &ash::Device,
+        command_buffer: vk::CommandBuffer,
+        handle: GeometryHandle,
+        instance_count: u32,
+    ) {
+        if let Some(range) = self.range(handle) {
+            self.bind_index_buffer(device, command_buffer, range.index_type);
+            unsafe {
+                device.cmd_draw_indexed(
+                    command_buffer,
+                    range.number_of_indices,
+                    instance_count,
+                    range.first_index,
+                    range.vertex_offset,
+                    0,
+                );
+            }
+        }
+    }
+}
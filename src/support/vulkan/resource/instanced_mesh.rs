@@ -0,0 +1,247 @@
+use crate::vulkan::{
+    Buffer, GeometryBuffer, Primitive, UploadManager, UploadTicket, Vertex, VulkanContext,
+};
+use ash::{version::DeviceV1_0, vk};
+use nalgebra_glm as glm;
+use snafu::{ensure, ResultExt, Snafu};
+use std::{mem, sync::Arc};
+
+type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility = "pub(crate)")]
+pub enum Error {
+    #[snafu(display("Failed to create instance buffer: {}", source))]
+    CreateInstanceBuffer {
+        source: crate::vulkan::buffer::Error,
+    },
+
+    #[snafu(display("Failed to upload instance data: {}", source))]
+    UploadInstanceData {
+        source: crate::vulkan::upload_manager::Error,
+    },
+
+    #[snafu(display(
+        "Instance count {} exceeds the instance buffer capacity of {}",
+        count,
+        capacity
+    ))]
+    ExceededInstanceCapacity { count: usize, capacity: usize },
+}
+
+#[repr(C)]
+#[derive(Vertex, Debug, Clone, Copy)]
+#[vertex(instance)]
+pub struct InstanceData {
+    pub model: glm::Mat4,
+    pub tint: glm::Vec4,
+    pub custom: glm::Vec4,
+}
+
+impl Default for InstanceData {
+    fn default() -> Self {
+        Self {
+            model: glm::Mat4::identity(),
+            tint: glm::vec4(1.0, 1.0, 1.0, 1.0),
+            custom: glm::vec4(0.0, 0.0, 0.0, 0.0),
+        }
+    }
+}
+
+pub struct InstanceBuffer {
+    pub buffer: Buffer,
+    capacity: usize,
+    count: usize,
+}
+
+impl InstanceBuffer {
+    pub fn new(context: Arc<VulkanContext>, capacity: usize) -> Result<Self> {
+        let buffer = Buffer::new_mapped_basic(
+            context,
+            (capacity * mem::size_of::<InstanceData>()) as vk::DeviceSize,
+            vk::BufferUsageFlags::VERTEX_BUFFER | vk::BufferUsageFlags::TRANSFER_DST,
+            vk_mem::MemoryUsage::GpuOnly,
+        )
+        .context(CreateInstanceBuffer)?;
+
+        Ok(Self {
+            buffer,
+            capacity,
+            count: 0,
+        })
+    }
+
+    // The copy goes through the upload manager's staging ring and is submitted right away.
+    // Earlier frames on the queue finish reading the old instances before it is written.
+    pub fn update(
+        &mut self,
+        upload_manager: &mut UploadManager,
+        instances: &[InstanceData],
+    ) -> Result<UploadTicket> {
+        ensure!(
+            instances.len() <= self.capacity,
+            ExceededInstanceCapacity {
+                count: instances.len(),
+                capacity: self.capacity,
+            }
+        );
+
+        if !instances.is_empty() {
+            upload_manager
+                .record(|device, command_buffer| unsafe {
+                    device.cmd_pipeline_barrier(
+                        command_buffer,
+                        vk::PipelineStageFlags::VERTEX_INPUT,
+                        vk::PipelineStageFlags::TRANSFER,
+                        vk::DependencyFlags::empty(),
+                        &[],
+                        &[],
+                        &[],
+                    );
+                })
+                .context(UploadInstanceData)?;
+            upload_manager
+                .upload_buffer(instances, self.buffer.buffer(), 0)
+                .context(UploadInstanceData)?;
+        }
+        self.count = instances.len();
+
+        upload_manager.submit().context(UploadInstanceData)
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn count(&self) -> usize {
+        self.count
+    }
+}
+
+// Draws every primitive of a mesh once for each instance in the instance buffer.
+// The vertex buffer is bound at binding 0 and the instance buffer at binding 1.
+pub struct InstancedMesh {
+    pub geometry: GeometryBuffer,
+    pub primitives: Vec<Primitive>,
+    pub index_type: vk::IndexType,
+    pub instances: InstanceBuffer,
+}
+
+impl InstancedMesh {
+    pub const VERTEX_BINDING: u32 = 0;
+    pub const INSTANCE_BINDING: u32 = 1;
+
+    pub fn new(
+        context: Arc<VulkanContext>,
+        geometry: GeometryBuffer,
+        primitives: Vec<Primitive>,
+        index_type: vk::IndexType,
+        capacity: usize,
+    ) -> Result<Self> {
+        Ok(Self {
+            geometry,
+            primitives,
+            index_type,
+            instances: InstanceBuffer::new(context, capacity)?,
+        })
+    }
+
+    // Treats the whole index buffer as a single primitive.
+    // Geometry buffers always hold 32-bit indices.
+    pub fn from_geometry(
+        context: Arc<VulkanContext>,
+        geometry: GeometryBuffer,
+        capacity: usize,
+    ) -> Result<Self> {
+        let primitive = Primitive {
+            first_index: 0,
+            number_of_indices: geometry.number_of_indices,
+            material_index: None,
+        };
+        Self::new(
+            context,
+            geometry,
+            vec![primitive],
+            vk::IndexType::UINT32,
+            capacity,
+        )
+    }
+
+    pub fn binding_descriptions<V: Vertex>() -> [vk::VertexInputBindingDescription; 2] {
+        [
+            V::binding_description(Self::VERTEX_BINDING),
+            InstanceData::binding_description(Self::INSTANCE_BINDING),
+        ]
+    }
+
+    // Instance attributes are placed at the locations following the vertex attributes
+    pub fn attribute_descriptions<V: Vertex>() -> Vec<vk::VertexInputAttributeDescription> {
+        let mut descriptions = V::attribute_descriptions(Self::VERTEX_BINDING, 0);
+        descriptions.extend(InstanceData::attribute_descriptions(
+            Self::INSTANCE_BINDING,
+            V::locations(),
+        ));
+        descriptions
+    }
+
+    // Command buffers are recorded ahead of time,
+    // so they must be recorded again whenever the instance count changes
+    pub fn update(
+        &mut self,
+        upload_manager: &mut UploadManager,
+        instances: &[InstanceData],
+    ) -> Result<UploadTicket> {
+        self.instances.update(upload_manager, instances)
+    }
+
+    pub fn instance_count(&self) -> u32 {
+        self.instances.count() as u32
+    }
+
+    pub fn bind(&self, device: &ash::Device, command_buffer: vk::CommandBuffer) {
+        let offsets = [0, 0];
+        let vertex_buffers = [
+            self.geometry.vertex_buffer.buffer(),
+            self.instances.buffer.buffer(),
+        ];
+
+        unsafe {
+            device.cmd_bind_vertex_buffers(
+                command_buffer,
+                Self::VERTEX_BINDING,
+                &vertex_buffers,
+                &offsets,
+            );
+
+            if let Some(index_buffer) = self.geometry.index_buffer.as_ref() {
+                device.cmd_bind_index_buffer(
+                    command_buffer,
+                    index_buffer.buffer(),
+                    0,
+                    self.index_type,
+                );
+            }
+        }
+    }
+
+    pub fn draw(&self, device: &ash::Device, command_buffer: vk::CommandBuffer) {
+        if self.instance_count() == 0 {
+            return;
+        }
+
+        self.bind(device, command_buffer);
+
+        for primitive in self.primitives.iter() {
+            unsafe {
+                device.cmd_draw_indexed(
+                    command_buffer,
+                    primitive.number_of_indices,
+                    self.instance_count(),
+                    primitive.first_index,
+                    0,
+                    0,
+                );
+            }
+        }
+    }
+}
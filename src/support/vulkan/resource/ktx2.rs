@@ -0,0 +1,381 @@
+use crate::vulkan::{
+    format_info, ChannelType, TexelEncoding, TextureDescription, TextureDimension,
+};
+use ash::vk;
+use snafu::{ensure, OptionExt, Snafu};
+
+type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility = "pub(crate)")]
+pub enum Error {
+    #[snafu(display("The file is not a KTX2 container"))]
+    InvalidKtx2Identifier,
+
+    #[snafu(display("The KTX2 container ends before its {} is complete", section))]
+    TruncatedKtx2 { section: &'static str },
+
+    #[snafu(display(
+        "KTX2 textures without a Vulkan format, such as Basis Universal, are not supported"
+    ))]
+    UndefinedKtx2Format,
+
+    #[snafu(display("KTX2 format {:?} is not supported", format))]
+    UnsupportedKtx2Format { format: vk::Format },
+
+    #[snafu(display("KTX2 supercompression scheme {} is not supported", scheme))]
+    UnsupportedKtx2Supercompression { scheme: u32 },
+
+    #[snafu(display("Arrays of three dimensional KTX2 textures are not supported"))]
+    UnsupportedKtx2VolumeArray,
+
+    #[snafu(display("KTX2 textures must have either one or six faces, found {}", faces))]
+    InvalidKtx2FaceCount { faces: u32 },
+
+    #[snafu(display(
+        "KTX2 mip level {} holds {} bytes but {} were expected",
+        level,
+        actual,
+        expected
+    ))]
+    InvalidKtx2LevelSize {
+        level: u32,
+        actual: usize,
+        expected: usize,
+    },
+
+    #[snafu(display(
+        "Texture description holds {} bytes of pixel data but {} are required",
+        actual,
+        expected
+    ))]
+    MissingKtx2LevelData { expected: usize, actual: usize },
+}
+
+const IDENTIFIER: [u8; 12] = [
+    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+const HEADER_LENGTH: usize = 80;
+const LEVEL_INDEX_ENTRY_LENGTH: usize = 24;
+
+// Reads a KTX2 container with every stored mip level, array layer and cube face.
+// One, two and three dimensional textures are supported.
+// Supercompressed and Basis Universal textures are rejected.
+pub fn parse_ktx2(bytes: &[u8]) -> Result<TextureDescription> {
+    ensure!(
+        bytes.len() >= IDENTIFIER.len() && bytes[..IDENTIFIER.len()] == IDENTIFIER,
+        InvalidKtx2Identifier
+    );
+    ensure!(
+        bytes.len() >= HEADER_LENGTH,
+        TruncatedKtx2 { section: "header" }
+    );
+
+    let format = vk::Format::from_raw(read_u32(bytes, 12) as _);
+    let width = read_u32(bytes, 20);
+    let height = read_u32(bytes, 24);
+    let depth = read_u32(bytes, 28);
+    let layers = read_u32(bytes, 32).max(1);
+    let faces = read_u32(bytes, 36);
+    let level_count = read_u32(bytes, 40);
+    let supercompression = read_u32(bytes, 44);
+
+    ensure!(format != vk::Format::UNDEFINED, UndefinedKtx2Format);
+    ensure!(
+        supercompression == 0,
+        UnsupportedKtx2Supercompression {
+            scheme: supercompression
+        }
+    );
+    ensure!(faces == 1 || faces == 6, InvalidKtx2FaceCount { faces });
+    ensure!(
+        depth == 0 || (layers == 1 && faces == 1),
+        UnsupportedKtx2VolumeArray
+    );
+    let info = format_info(format).context(UnsupportedKtx2Format { format })?;
+
+    // More faces than can be counted can't be held by the file
+    ensure!(
+        layers.checked_mul(faces).is_some(),
+        TruncatedKtx2 {
+            section: "level data"
+        }
+    );
+
+    // A height of zero marks a one dimensional texture and a depth of zero a two dimensional one
+    let mut description = if depth > 0 {
+        TextureDescription::empty_3d(width, height, depth, format)
+    } else if height == 0 {
+        TextureDescription {
+            layers,
+            ..TextureDescription::empty_1d(width, format)
+        }
+    } else if faces == 6 {
+        TextureDescription::empty_cube(width, layers, format)
+    } else {
+        TextureDescription::empty_array(width, height, layers, format)
+    };
+
+    // A level count of zero asks for the mip chain to be generated at load time
+    description.stored_mip_levels = level_count.max(1);
+    if level_count > 0 || info.is_compressed() {
+        description.mip_levels = description.stored_mip_levels;
+    }
+
+    for level in 0..description.stored_mip_levels {
+        let entry = HEADER_LENGTH + level as usize * LEVEL_INDEX_ENTRY_LENGTH;
+        ensure!(
+            bytes.len() >= entry + LEVEL_INDEX_ENTRY_LENGTH,
+            TruncatedKtx2 {
+                section: "level index"
+            }
+        );
+        let offset = read_u64(bytes, entry) as usize;
+        let length = read_u64(bytes, entry + 8) as usize;
+
+        // A level too large to address can't be held by the file
+        let expected = description.level_size(level).ok().context(TruncatedKtx2 {
+            section: "level data",
+        })?;
+        ensure!(
+            length == expected,
+            InvalidKtx2LevelSize {
+                level,
+                actual: length,
+                expected
+            }
+        );
+
+        let data = offset
+            .checked_add(length)
+            .and_then(|end| bytes.get(offset..end))
+            .context(TruncatedKtx2 {
+                section: "level data",
+            })?;
+        description.pixels.extend_from_slice(data);
+    }
+
+    Ok(description)
+}
+
+// Writes every stored mip level, array layer and cube face of a description
+// to a KTX2 container that `parse_ktx2` can read back.
+// No data format descriptor or key/value data is written,
+// so other tools may need the format to be supplied separately.
+pub fn write_ktx2(description: &TextureDescription) -> Result<Vec<u8>> {
+    let format = description.format;
+    let info = format_info(format).context(UnsupportedKtx2Format { format })?;
+
+    let level_sizes = (0..description.stored_mip_levels)
+        .map(|level| {
+            description
+                .level_size(level)
+                .expect("Formats with format info have a known level size")
+        })
+        .collect::<Vec<_>>();
+    let expected = level_sizes.iter().sum::<usize>();
+    ensure!(
+        description.pixels.len() >= expected,
+        MissingKtx2LevelData {
+            expected,
+            actual: description.pixels.len()
+        }
+    );
+
+    let (height, depth) = match description.dimension {
+        TextureDimension::D1 => (0, 0),
+        TextureDimension::D2 => (description.height, 0),
+        TextureDimension::D3 => (description.height, description.depth),
+    };
+    let (faces, layers) = if description.cubemap {
+        (6, description.layers / 6)
+    } else {
+        (1, description.layers)
+    };
+    // Non-array textures are marked with a layer count of zero
+    let layers = if layers > 1 { layers } else { 0 };
+    let type_size = match TexelEncoding::of(format).map(|encoding| encoding.channel_type) {
+        Some(ChannelType::U16) | Some(ChannelType::F16) => 2,
+        Some(ChannelType::F32) => 4,
+        _ => 1,
+    };
+
+    let mut bytes = IDENTIFIER.to_vec();
+    for value in &[
+        format.as_raw() as u32,
+        type_size,
+        description.width,
+        height,
+        depth,
+        layers,
+        faces,
+        description.stored_mip_levels,
+        0,
+    ] {
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    // The descriptor, key/value and supercompression sections are all empty
+    bytes.resize(HEADER_LENGTH, 0);
+
+    // Levels are stored smallest first, each aligned to a whole number of texel blocks
+    let index_start = bytes.len();
+    bytes.resize(
+        index_start + level_sizes.len() * LEVEL_INDEX_ENTRY_LENGTH,
+        0,
+    );
+    let alignment = least_common_multiple(info.block_size as usize, 4);
+    let level_offsets = level_sizes
+        .iter()
+        .scan(0, |offset, size| {
+            let start = *offset;
+            *offset += size;
+            Some(start)
+        })
+        .collect::<Vec<_>>();
+    for level in (0..level_sizes.len()).rev() {
+        let padding = (alignment - bytes.len() % alignment) % alignment;
+        bytes.resize(bytes.len() + padding, 0);
+
+        let entry = index_start + level * LEVEL_INDEX_ENTRY_LENGTH;
+        let length = level_sizes[level] as u64;
+        let offset = bytes.len() as u64;
+        write_u64(&mut bytes, entry, offset);
+        write_u64(&mut bytes, entry + 8, length);
+        write_u64(&mut bytes, entry + 16, length);
+
+        let start = level_offsets[level];
+        bytes.extend_from_slice(&description.pixels[start..start + level_sizes[level]]);
+    }
+
+    Ok(bytes)
+}
+
+fn least_common_multiple(a: usize, b: usize) -> usize {
+    let (mut x, mut y) = (a, b);
+    while y != 0 {
+        let remainder = x % y;
+        x = y;
+        y = remainder;
+    }
+    a / x * b
+}
+
+fn write_u64(bytes: &mut [u8], offset: usize, value: u64) {
+    bytes[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    let mut word = [0; 4];
+    word.copy_from_slice(&bytes[offset..offset + 4]);
+    u32::from_le_bytes(word)
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    let mut word = [0; 8];
+    word.copy_from_slice(&bytes[offset..offset + 8]);
+    u64::from_le_bytes(word)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(format: vk::Format, width: u32, height: u32, layers: u32, faces: u32) -> Vec<u8> {
+        let mut bytes = IDENTIFIER.to_vec();
+        for value in &[
+            format.as_raw() as u32,
+            1,
+            width,
+            height,
+            0,
+            layers,
+            faces,
+            1,
+            0,
+        ] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes.resize(HEADER_LENGTH + LEVEL_INDEX_ENTRY_LENGTH, 0);
+        bytes
+    }
+
+    fn with_level(mut bytes: Vec<u8>, offset: u64, length: u64) -> Vec<u8> {
+        write_u64(&mut bytes, HEADER_LENGTH, offset);
+        write_u64(&mut bytes, HEADER_LENGTH + 8, length);
+        bytes
+    }
+
+    #[test]
+    fn rejects_truncated_header() {
+        let bytes = IDENTIFIER.to_vec();
+        assert!(matches!(
+            parse_ktx2(&bytes),
+            Err(Error::TruncatedKtx2 { section: "header" })
+        ));
+    }
+
+    #[test]
+    fn rejects_level_past_end_of_file() {
+        let bytes = header(vk::Format::R8G8B8A8_UNORM, 1, 1, 0, 1);
+        let offset = bytes.len() as u64;
+        let bytes = with_level(bytes, offset, 4);
+        assert!(matches!(
+            parse_ktx2(&bytes),
+            Err(Error::TruncatedKtx2 {
+                section: "level data"
+            })
+        ));
+    }
+
+    #[test]
+    fn rejects_oversized_level_offset() {
+        let bytes = with_level(header(vk::Format::R8G8B8A8_UNORM, 1, 1, 0, 1), u64::MAX, 4);
+        assert!(matches!(
+            parse_ktx2(&bytes),
+            Err(Error::TruncatedKtx2 {
+                section: "level data"
+            })
+        ));
+    }
+
+    #[test]
+    fn rejects_oversized_cube_array() {
+        let bytes = header(vk::Format::R8G8B8A8_UNORM, 1, 1, u32::MAX, 6);
+        assert!(matches!(
+            parse_ktx2(&bytes),
+            Err(Error::TruncatedKtx2 {
+                section: "level data"
+            })
+        ));
+    }
+
+    #[test]
+    fn reads_back_written_1d_array() {
+        let description = TextureDescription {
+            layers: 3,
+            pixels: (0..48).collect(),
+            mip_levels: 1,
+            ..TextureDescription::empty_1d(4, vk::Format::R8G8B8A8_UNORM)
+        };
+        let bytes = write_ktx2(&description).unwrap();
+        let parsed = parse_ktx2(&bytes).unwrap();
+        assert_eq!(parsed.dimension, TextureDimension::D1);
+        assert_eq!(parsed.layers, 3);
+        assert_eq!(parsed.pixels, description.pixels);
+    }
+
+    #[test]
+    fn reads_back_written_cubemap() {
+        let description = TextureDescription {
+            pixels: (0..24).collect(),
+            mip_levels: 1,
+            ..TextureDescription::empty_cube(1, 1, vk::Format::R8G8B8A8_UNORM)
+        };
+        let bytes = write_ktx2(&description).unwrap();
+        let parsed = parse_ktx2(&bytes).unwrap();
+        assert!(parsed.cubemap);
+        assert_eq!(parsed.layers, 6);
+        assert_eq!(parsed.pixels, description.pixels);
+    }
+}
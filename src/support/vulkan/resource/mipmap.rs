@@ -0,0 +1,662 @@
+use crate::vulkan::{format_info, ColorSpace, TextureDescription, TextureDimension};
+use ash::vk;
+use derive_builder::Builder;
+use snafu::{ensure, OptionExt, Snafu};
+use std::f32::consts::PI;
+
+type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility = "pub(crate)")]
+pub enum Error {
+    #[snafu(display("Mipmaps can't be generated on the CPU for format {:?}", format))]
+    UnsupportedMipmapFormat { format: vk::Format },
+
+    #[snafu(display("Mipmaps can't be generated on the CPU for three dimensional textures"))]
+    UnsupportedMipmapDimension,
+
+    #[snafu(display(
+        "Texture description holds {} bytes of pixel data but {} are required",
+        actual,
+        expected
+    ))]
+    MissingMipmapSource { expected: usize, actual: usize },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MipFilter {
+    // Averages each 2x2 block of texels. At odd sizes, the texels between two blocks
+    // are shared by both of them, which makes it a 3 tap filter.
+    Box,
+
+    // A Kaiser windowed sinc, which keeps more detail than a box filter
+    Kaiser,
+}
+
+#[derive(Builder, Debug, Clone, Copy)]
+#[builder(setter(into))]
+pub struct MipmapSettings {
+    #[builder(default = "MipFilter::Box")]
+    pub filter: MipFilter,
+
+    // Filters sRGB textures in linear space instead of on the encoded values
+    #[builder(default = "true")]
+    pub gamma_correct: bool,
+
+    // Scales the alpha of each level so that the fraction of texels passing the alpha test
+    // matches the first level. This keeps alpha tested foliage from thinning out in the distance.
+    #[builder(default, setter(strip_option))]
+    pub alpha_cutoff: Option<f32>,
+}
+
+impl Default for MipmapSettings {
+    fn default() -> Self {
+        Self {
+            filter: MipFilter::Box,
+            gamma_correct: true,
+            alpha_cutoff: None,
+        }
+    }
+}
+
+const KAISER_SUPPORT: f32 = 2.0;
+const KAISER_ALPHA: f32 = 4.0;
+const ALPHA_COVERAGE_ITERATIONS: usize = 16;
+
+// Fills in every level of the mip chain that the description doesn't already store,
+// starting from the last stored level.
+pub fn generate_mip_chain(
+    description: &mut TextureDescription,
+    settings: &MipmapSettings,
+) -> Result<()> {
+    if description.has_complete_mip_chain() {
+        return Ok(());
+    }
+
+    ensure!(
+        description.dimension != TextureDimension::D3,
+        UnsupportedMipmapDimension
+    );
+
+    let format = description.format;
+    let encoding = TexelEncoding::of(format).context(UnsupportedMipmapFormat { format })?;
+    let info = format_info(format).context(UnsupportedMipmapFormat { format })?;
+    let layers = description.layers as usize;
+    let (base_width, base_height) = (description.width, description.height);
+    let level_extent = |level: u32| ((base_width >> level).max(1), (base_height >> level).max(1));
+
+    let last_level = description.stored_mip_levels - 1;
+    let source_offset = (0..last_level)
+        .map(|level| {
+            let (width, height) = level_extent(level);
+            info.layer_size(width, height) * layers
+        })
+        .sum::<usize>();
+    let (width, height) = level_extent(last_level);
+    let layer_size = info.layer_size(width, height);
+    let required = source_offset + layer_size * layers;
+    ensure!(
+        description.pixels.len() >= required,
+        MissingMipmapSource {
+            expected: required,
+            actual: description.pixels.len()
+        }
+    );
+    description.pixels.truncate(required);
+
+    let linearize = settings.gamma_correct && description.color_space == ColorSpace::Srgb;
+    let mut images = (0..layers)
+        .map(|layer| {
+            let start = source_offset + layer * layer_size;
+            let bytes = &description.pixels[start..start + layer_size];
+            MipImage::decode(&encoding, width, height, bytes, linearize)
+        })
+        .collect::<Vec<_>>();
+    let coverage_targets = images
+        .iter()
+        .map(|image| {
+            settings
+                .alpha_cutoff
+                .map(|cutoff| image.alpha_coverage(cutoff, 1.0))
+        })
+        .collect::<Vec<_>>();
+
+    for level in description.stored_mip_levels..description.mip_levels {
+        let (width, height) = level_extent(level);
+        for (image, coverage) in images.iter_mut().zip(coverage_targets.iter()) {
+            *image = image.downsample(width, height, settings.filter);
+            if let (Some(cutoff), Some(coverage)) = (settings.alpha_cutoff, coverage) {
+                image.preserve_alpha_coverage(cutoff, *coverage);
+            }
+            description
+                .pixels
+                .extend(image.encode(&encoding, linearize));
+        }
+    }
+    description.stored_mip_levels = description.mip_levels;
+
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ChannelType {
+    U8,
+    U16,
+    F16,
+    F32,
+}
+
+// How the channels of an uncompressed format are stored, ignoring their order
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct TexelEncoding {
+    pub channel_type: ChannelType,
+    pub channels: usize,
+}
+
+impl TexelEncoding {
+    pub fn of(format: vk::Format) -> Option<Self> {
+        let (channel_type, channels) = match format {
+            vk::Format::R8_UNORM | vk::Format::R8_SRGB => (ChannelType::U8, 1),
+            vk::Format::R8G8_UNORM | vk::Format::R8G8_SRGB => (ChannelType::U8, 2),
+            vk::Format::R8G8B8_UNORM
+            | vk::Format::R8G8B8_SRGB
+            | vk::Format::B8G8R8_UNORM
+            | vk::Format::B8G8R8_SRGB => (ChannelType::U8, 3),
+            vk::Format::R8G8B8A8_UNORM
+            | vk::Format::R8G8B8A8_SRGB
+            | vk::Format::B8G8R8A8_UNORM
+            | vk::Format::B8G8R8A8_SRGB => (ChannelType::U8, 4),
+            vk::Format::R16_UNORM => (ChannelType::U16, 1),
+            vk::Format::R16G16_UNORM => (ChannelType::U16, 2),
+            vk::Format::R16G16B16_UNORM => (ChannelType::U16, 3),
+            vk::Format::R16G16B16A16_UNORM => (ChannelType::U16, 4),
+            vk::Format::R16_SFLOAT => (ChannelType::F16, 1),
+            vk::Format::R16G16_SFLOAT => (ChannelType::F16, 2),
+            vk::Format::R16G16B16_SFLOAT => (ChannelType::F16, 3),
+            vk::Format::R16G16B16A16_SFLOAT => (ChannelType::F16, 4),
+            vk::Format::R32_SFLOAT => (ChannelType::F32, 1),
+            vk::Format::R32G32_SFLOAT => (ChannelType::F32, 2),
+            vk::Format::R32G32B32_SFLOAT => (ChannelType::F32, 3),
+            vk::Format::R32G32B32A32_SFLOAT => (ChannelType::F32, 4),
+            _ => return None,
+        };
+        Some(Self {
+            channel_type,
+            channels,
+        })
+    }
+
+    // Normalized channels are mapped to the [0, 1] range
+    pub fn decode_channels(&self, bytes: &[u8]) -> Vec<f32> {
+        match self.channel_type {
+            ChannelType::U8 => bytes.iter().map(|value| *value as f32 / 255.0).collect(),
+            ChannelType::U16 => bytes
+                .chunks_exact(2)
+                .map(|value| u16::from_le_bytes([value[0], value[1]]) as f32 / 65535.0)
+                .collect(),
+            ChannelType::F16 => bytes
+                .chunks_exact(2)
+                .map(|value| f16_to_f32(u16::from_le_bytes([value[0], value[1]])))
+                .collect(),
+            ChannelType::F32 => bytes
+                .chunks_exact(4)
+                .map(|value| f32::from_le_bytes([value[0], value[1], value[2], value[3]]))
+                .collect(),
+        }
+    }
+
+    pub fn encode_channels(&self, values: &[f32]) -> Vec<u8> {
+        match self.channel_type {
+            ChannelType::U8 => values
+                .iter()
+                .map(|value| (value.clamp(0.0, 1.0) * 255.0).round() as u8)
+                .collect(),
+            ChannelType::U16 => values
+                .iter()
+                .flat_map(|value| ((value.clamp(0.0, 1.0) * 65535.0).round() as u16).to_le_bytes())
+                .collect(),
+            ChannelType::F16 => values
+                .iter()
+                .flat_map(|value| f32_to_f16(*value).to_le_bytes())
+                .collect(),
+            ChannelType::F32 => values
+                .iter()
+                .flat_map(|value| value.to_le_bytes())
+                .collect(),
+        }
+    }
+
+    // Alpha is never sRGB encoded
+    fn is_color_channel(&self, channel: usize) -> bool {
+        alpha_channel(self.channels) != Some(channel)
+    }
+}
+
+// Alpha is the last channel of both luminance and alpha and RGBA texels
+fn alpha_channel(channels: usize) -> Option<usize> {
+    match channels {
+        2 | 4 => Some(channels - 1),
+        _ => None,
+    }
+}
+
+// A single layer of a single mip level, decoded to floating point channels
+struct MipImage {
+    width: u32,
+    height: u32,
+    channels: usize,
+    data: Vec<f32>,
+}
+
+impl MipImage {
+    fn decode(
+        encoding: &TexelEncoding,
+        width: u32,
+        height: u32,
+        bytes: &[u8],
+        linearize: bool,
+    ) -> Self {
+        let data = encoding.decode_channels(bytes);
+
+        let mut image = Self {
+            width,
+            height,
+            channels: encoding.channels,
+            data,
+        };
+        if linearize {
+            image.map_color_channels(encoding, srgb_to_linear);
+        }
+        image
+    }
+
+    fn encode(&self, encoding: &TexelEncoding, linearize: bool) -> Vec<u8> {
+        let mut data = self.data.clone();
+        if linearize {
+            data.iter_mut().enumerate().for_each(|(index, value)| {
+                if encoding.is_color_channel(index % self.channels) {
+                    *value = linear_to_srgb(*value);
+                }
+            });
+        }
+
+        encoding.encode_channels(&data)
+    }
+
+    fn map_color_channels(&mut self, encoding: &TexelEncoding, function: fn(f32) -> f32) {
+        let channels = self.channels;
+        self.data
+            .iter_mut()
+            .enumerate()
+            .filter(|(index, _)| encoding.is_color_channel(index % channels))
+            .for_each(|(_, value)| *value = function(*value));
+    }
+
+    fn downsample(&self, width: u32, height: u32, filter: MipFilter) -> Self {
+        self.resample_horizontal(width, filter)
+            .resample_vertical(height, filter)
+    }
+
+    fn resample_horizontal(&self, width: u32, filter: MipFilter) -> Self {
+        if width == self.width {
+            return self.duplicate();
+        }
+
+        let weights = filter_weights(self.width, width, filter);
+        let mut data = vec![0.0; width as usize * self.height as usize * self.channels];
+        for y in 0..self.height as usize {
+            for (x, taps) in weights.iter().enumerate() {
+                let destination = (y * width as usize + x) * self.channels;
+                for (source_x, weight) in taps.iter() {
+                    let source = (y * self.width as usize + source_x) * self.channels;
+                    for channel in 0..self.channels {
+                        data[destination + channel] += self.data[source + channel] * weight;
+                    }
+                }
+            }
+        }
+
+        Self {
+            width,
+            height: self.height,
+            channels: self.channels,
+            data,
+        }
+    }
+
+    fn resample_vertical(&self, height: u32, filter: MipFilter) -> Self {
+        if height == self.height {
+            return self.duplicate();
+        }
+
+        let weights = filter_weights(self.height, height, filter);
+        let row_length = self.width as usize * self.channels;
+        let mut data = vec![0.0; row_length * height as usize];
+        for (y, taps) in weights.iter().enumerate() {
+            let destination = &mut data[y * row_length..(y + 1) * row_length];
+            for (source_y, weight) in taps.iter() {
+                let source = &self.data[source_y * row_length..(source_y + 1) * row_length];
+                destination
+                    .iter_mut()
+                    .zip(source.iter())
+                    .for_each(|(destination, source)| *destination += source * weight);
+            }
+        }
+
+        Self {
+            width: self.width,
+            height,
+            channels: self.channels,
+            data,
+        }
+    }
+
+    fn duplicate(&self) -> Self {
+        Self {
+            width: self.width,
+            height: self.height,
+            channels: self.channels,
+            data: self.data.clone(),
+        }
+    }
+
+    fn alpha_coverage(&self, cutoff: f32, scale: f32) -> f32 {
+        let alpha = match alpha_channel(self.channels) {
+            Some(alpha) => alpha,
+            None => return 1.0,
+        };
+        let texels = self.data.len() / self.channels;
+        let covered = self
+            .data
+            .chunks_exact(self.channels)
+            .filter(|texel| texel[alpha] * scale > cutoff)
+            .count();
+        covered as f32 / texels as f32
+    }
+
+    // Searches for the alpha scale that gives the closest coverage to the target
+    fn preserve_alpha_coverage(&mut self, cutoff: f32, target: f32) {
+        let alpha = match alpha_channel(self.channels) {
+            Some(alpha) => alpha,
+            None => return,
+        };
+
+        let (mut low, mut high) = (0.0_f32, 4.0_f32);
+        for _ in 0..ALPHA_COVERAGE_ITERATIONS {
+            let middle = (low + high) / 2.0;
+            if self.alpha_coverage(cutoff, middle) < target {
+                low = middle;
+            } else {
+                high = middle;
+            }
+        }
+
+        let scale = (low + high) / 2.0;
+        self.data
+            .chunks_exact_mut(self.channels)
+            .for_each(|texel| texel[alpha] = (texel[alpha] * scale).min(1.0));
+    }
+}
+
+// For each destination texel, the source texels it covers and their normalized weights
+fn filter_weights(
+    source_size: u32,
+    destination_size: u32,
+    filter: MipFilter,
+) -> Vec<Vec<(usize, f32)>> {
+    let scale = source_size as f32 / destination_size as f32;
+    let support = match filter {
+        MipFilter::Box => 0.5,
+        MipFilter::Kaiser => KAISER_SUPPORT,
+    };
+
+    (0..destination_size)
+        .map(|destination| {
+            let center = (destination as f32 + 0.5) * scale;
+            let (start, end) = (center - support * scale, center + support * scale);
+            let mut taps = (start.floor() as i64..=end.ceil() as i64)
+                .filter_map(|source| {
+                    let weight = match filter {
+                        // Weighted by how much of the source texel the destination texel covers
+                        MipFilter::Box => {
+                            (end.min(source as f32 + 1.0) - start.max(source as f32)).max(0.0)
+                        }
+                        MipFilter::Kaiser => kaiser((source as f32 + 0.5 - center) / scale),
+                    };
+                    if weight == 0.0 {
+                        return None;
+                    }
+                    // Texels past the edges are clamped
+                    let source = source.clamp(0, source_size as i64 - 1) as usize;
+                    Some((source, weight))
+                })
+                .collect::<Vec<_>>();
+
+            let total = taps.iter().map(|(_, weight)| weight).sum::<f32>();
+            taps.iter_mut().for_each(|(_, weight)| *weight /= total);
+            taps
+        })
+        .collect()
+}
+
+fn kaiser(distance: f32) -> f32 {
+    let ratio = distance / KAISER_SUPPORT;
+    if ratio.abs() >= 1.0 {
+        return 0.0;
+    }
+    let window = bessel_i0(KAISER_ALPHA * (1.0 - ratio * ratio).sqrt()) / bessel_i0(KAISER_ALPHA);
+    sinc(distance) * window
+}
+
+fn sinc(x: f32) -> f32 {
+    if x.abs() < f32::EPSILON {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+// The zeroth order modified Bessel function of the first kind
+fn bessel_i0(x: f32) -> f32 {
+    let mut sum = 1.0;
+    let mut term = 1.0;
+    let half_squared = x * x / 4.0;
+    for k in 1..32 {
+        term *= half_squared / (k * k) as f32;
+        sum += term;
+        if term < sum * 1e-8 {
+            break;
+        }
+    }
+    sum
+}
+
+pub(crate) fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.003_130_8 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+fn f16_to_f32(half: u16) -> f32 {
+    let sign = ((half >> 15) as u32) << 31;
+    let exponent = ((half >> 10) & 0x1F) as u32;
+    let mantissa = (half & 0x3FF) as u32;
+
+    let bits = match (exponent, mantissa) {
+        (0, 0) => sign,
+        (0, _) => {
+            // Subnormal halves are normal floats
+            let shift = mantissa.leading_zeros() - 21;
+            let mantissa = (mantissa << shift) & 0x3FF;
+            sign | ((113 - shift) << 23) | (mantissa << 13)
+        }
+        (0x1F, _) => sign | 0x7F80_0000 | (mantissa << 13),
+        _ => sign | ((exponent + 112) << 23) | (mantissa << 13),
+    };
+    f32::from_bits(bits)
+}
+
+fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xFF) as i32;
+    let mantissa = bits & 0x7F_FFFF;
+
+    if exponent == 0xFF {
+        let nan = if mantissa != 0 { 0x200 } else { 0 };
+        return sign | 0x7C00 | nan;
+    }
+
+    let exponent = exponent - 127 + 15;
+    if exponent >= 0x1F {
+        sign | 0x7C00
+    } else if exponent <= 0 {
+        if exponent < -10 {
+            return sign;
+        }
+        let mantissa = mantissa | 0x80_0000;
+        let shift = (14 - exponent) as u32;
+        let rounded = (mantissa + (1 << (shift - 1))) >> shift;
+        sign | rounded as u16
+    } else {
+        let rounded = mantissa + 0x1000;
+        if rounded & 0x80_0000 != 0 {
+            // Rounding overflowed into the exponent
+            return f32_to_f16(f32::from_bits((bits & 0xFF80_0000) + 0x80_0000));
+        }
+        sign | ((exponent as u16) << 10) | (rounded >> 13) as u16
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(width: u32, height: u32, channels: usize, data: Vec<f32>) -> MipImage {
+        MipImage {
+            width,
+            height,
+            channels,
+            data,
+        }
+    }
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 1e-5,
+            "expected {}, got {}",
+            expected,
+            actual
+        );
+    }
+
+    #[test]
+    fn box_filter_averages_even_levels() {
+        let source = image(2, 2, 1, vec![0.0, 1.0, 2.0, 3.0]);
+        let level = source.downsample(1, 1, MipFilter::Box);
+        assert_close(level.data[0], 1.5);
+    }
+
+    #[test]
+    fn box_filter_shares_middle_texels_of_odd_levels() {
+        let taps = filter_weights(5, 2, MipFilter::Box);
+        assert_eq!(taps[0], vec![(0, 0.4), (1, 0.4), (2, 0.2)]);
+        assert_eq!(taps[1], vec![(2, 0.2), (3, 0.4), (4, 0.4)]);
+
+        let source = image(3, 3, 1, (0..9).map(|value| value as f32).collect());
+        let level = source.downsample(1, 1, MipFilter::Box);
+        assert_close(level.data[0], 4.0);
+    }
+
+    #[test]
+    fn filters_keep_odd_levels_normalized() {
+        for filter in [MipFilter::Box, MipFilter::Kaiser].iter() {
+            let source = image(7, 5, 2, vec![0.25; 7 * 5 * 2]);
+            let level = source.downsample(3, 2, *filter);
+            assert_eq!(level.data.len(), 3 * 2 * 2);
+            level
+                .data
+                .iter()
+                .for_each(|value| assert_close(*value, 0.25));
+        }
+    }
+
+    #[test]
+    fn srgb_round_trips_through_linear() {
+        for value in 0..=255 {
+            let encoded = value as f32 / 255.0;
+            let decoded = linear_to_srgb(srgb_to_linear(encoded));
+            assert_eq!((decoded * 255.0).round() as u8, value as u8);
+        }
+    }
+
+    #[test]
+    fn srgb_levels_are_filtered_in_linear_space() {
+        let encoding = TexelEncoding::of(vk::Format::R8G8B8A8_SRGB).unwrap();
+        let bytes = [0, 0, 0, 0, 255, 255, 255, 255];
+        let source = MipImage::decode(&encoding, 2, 1, &bytes, true);
+        let level = source.downsample(1, 1, MipFilter::Box);
+        let encoded = level.encode(&encoding, true);
+        // Half of the linear intensity, while alpha is averaged as is
+        assert_eq!(encoded, vec![188, 188, 188, 128]);
+    }
+
+    #[test]
+    fn alpha_coverage_is_preserved() {
+        let cutoff = 0.5;
+        let alpha = [0.6, 0.6, 0.6, 0.2, 0.6, 0.2, 0.2, 0.2];
+        let data = alpha
+            .iter()
+            .flat_map(|alpha| vec![1.0, 1.0, 1.0, *alpha])
+            .collect();
+        let source = image(4, 2, 4, data);
+        let target = source.alpha_coverage(cutoff, 1.0);
+        assert_close(target, 0.5);
+
+        let mut level = source.downsample(2, 1, MipFilter::Box);
+        assert_close(level.alpha_coverage(cutoff, 1.0), 0.0);
+        level.preserve_alpha_coverage(cutoff, target);
+        assert_close(level.alpha_coverage(cutoff, 1.0), target);
+    }
+
+    #[test]
+    fn f16_conversions_round_trip() {
+        let values = [
+            (0.0, 0x0000),
+            (-0.0, 0x8000),
+            (1.0, 0x3C00),
+            (-2.0, 0xC000),
+            (0.5, 0x3800),
+            (65504.0, 0x7BFF),
+            (6.103_515_6e-5, 0x0400),
+            (5.960_464_5e-8, 0x0001),
+        ];
+        for (value, half) in values.iter() {
+            assert_eq!(f32_to_f16(*value), *half);
+            assert_eq!(f16_to_f32(*half), *value);
+        }
+    }
+
+    #[test]
+    fn f16_conversions_handle_special_values() {
+        assert_eq!(f32_to_f16(f32::INFINITY), 0x7C00);
+        assert_eq!(f32_to_f16(f32::NEG_INFINITY), 0xFC00);
+        assert_eq!(f32_to_f16(1e6), 0x7C00);
+        assert_eq!(f32_to_f16(1e-9), 0x0000);
+        assert!(f16_to_f32(f32_to_f16(f32::NAN)).is_nan());
+        assert_eq!(f16_to_f32(0x7C00), f32::INFINITY);
+        // Rounds to the nearest half
+        assert_eq!(f32_to_f16(1.0 + 1.0 / 4096.0), 0x3C00);
+        assert_eq!(f32_to_f16(1.0 + 3.0 / 2048.0), 0x3C02);
+    }
+}
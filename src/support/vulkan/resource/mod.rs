@@ -1,7 +1,22 @@
-pub use self::{buffer::*, command_pool::*, dummy::*, shader::*, texture::*};
+pub use self::{
+    buffer::*, command_pool::*, conversion::*, dds::*, dummy::*, format::*, geometry_arena::*,
+    instanced_mesh::*, ktx2::*, mipmap::*, reflection::*, shader::*, texture::*, typed_buffer::*,
+    upload_manager::*, vertex::*,
+};
 
 pub mod buffer;
 pub mod command_pool;
+pub mod conversion;
+pub mod dds;
 pub mod dummy;
+pub mod format;
+pub mod geometry_arena;
+pub mod instanced_mesh;
+pub mod ktx2;
+pub mod mipmap;
+pub mod reflection;
 pub mod shader;
 pub mod texture;
+pub mod typed_buffer;
+pub mod upload_manager;
+pub mod vertex;
@@ -0,0 +1,191 @@
+use ash::vk;
+use std::collections::HashMap;
+
+const SPIRV_MAGIC_NUMBER: u32 = 0x0723_0203;
+const SPIRV_HEADER_LENGTH: usize = 5;
+
+const OP_TYPE_INT: u32 = 21;
+const OP_TYPE_FLOAT: u32 = 22;
+const OP_TYPE_VECTOR: u32 = 23;
+const OP_TYPE_MATRIX: u32 = 24;
+const OP_TYPE_POINTER: u32 = 32;
+const OP_VARIABLE: u32 = 59;
+const OP_DECORATE: u32 = 71;
+
+const DECORATION_LOCATION: u32 = 30;
+
+const STORAGE_CLASS_INPUT: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NumericType {
+    Float,
+    SignedInteger,
+    UnsignedInteger,
+}
+
+impl NumericType {
+    // Returns None for formats that are not used as vertex attributes
+    pub fn from_format(format: vk::Format) -> Option<Self> {
+        match format {
+            vk::Format::R32_SFLOAT
+            | vk::Format::R32G32_SFLOAT
+            | vk::Format::R32G32B32_SFLOAT
+            | vk::Format::R32G32B32A32_SFLOAT
+            | vk::Format::R16_SFLOAT
+            | vk::Format::R16G16_SFLOAT
+            | vk::Format::R16G16B16A16_SFLOAT
+            | vk::Format::R16_UNORM
+            | vk::Format::R16G16_UNORM
+            | vk::Format::R16G16B16A16_UNORM
+            | vk::Format::R16_SNORM
+            | vk::Format::R16G16_SNORM
+            | vk::Format::R16G16B16A16_SNORM
+            | vk::Format::R8_UNORM
+            | vk::Format::R8G8_UNORM
+            | vk::Format::R8G8B8A8_UNORM
+            | vk::Format::R8_SNORM
+            | vk::Format::R8G8_SNORM
+            | vk::Format::R8G8B8A8_SNORM => Some(NumericType::Float),
+            vk::Format::R32_UINT
+            | vk::Format::R32G32_UINT
+            | vk::Format::R32G32B32_UINT
+            | vk::Format::R32G32B32A32_UINT
+            | vk::Format::R16_UINT
+            | vk::Format::R16G16_UINT
+            | vk::Format::R16G16B16A16_UINT
+            | vk::Format::R8_UINT
+            | vk::Format::R8G8_UINT
+            | vk::Format::R8G8B8A8_UINT => Some(NumericType::UnsignedInteger),
+            vk::Format::R32_SINT
+            | vk::Format::R32G32_SINT
+            | vk::Format::R32G32B32_SINT
+            | vk::Format::R32G32B32A32_SINT
+            | vk::Format::R16_SINT
+            | vk::Format::R16G16_SINT
+            | vk::Format::R16G16B16A16_SINT
+            | vk::Format::R8_SINT
+            | vk::Format::R8G8_SINT
+            | vk::Format::R8G8B8A8_SINT => Some(NumericType::SignedInteger),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShaderInput {
+    pub location: u32,
+    pub numeric_type: NumericType,
+    pub components: u32,
+}
+
+#[derive(Clone, Copy)]
+enum SpirvType {
+    Scalar(NumericType),
+    Vector(u32, u32),
+    Matrix(u32, u32),
+    Pointer(u32),
+}
+
+// Finds the user defined input variables of a SPIR-V module.
+// Only scalar, vector and matrix inputs are reported, with matrices expanded to one input per column.
+pub fn reflect_inputs(words: &[u32]) -> Vec<ShaderInput> {
+    if words.len() < SPIRV_HEADER_LENGTH || words[0] != SPIRV_MAGIC_NUMBER {
+        return Vec::new();
+    }
+
+    let mut types = HashMap::new();
+    let mut locations = HashMap::new();
+    let mut input_variables = Vec::new();
+
+    let mut offset = SPIRV_HEADER_LENGTH;
+    while offset < words.len() {
+        let word_count = (words[offset] >> 16) as usize;
+        let opcode = words[offset] & 0xffff;
+        if word_count == 0 || offset + word_count > words.len() {
+            break;
+        }
+        let operands = &words[offset + 1..offset + word_count];
+
+        match opcode {
+            OP_TYPE_INT if operands.len() >= 3 => {
+                let numeric_type = if operands[2] == 1 {
+                    NumericType::SignedInteger
+                } else {
+                    NumericType::UnsignedInteger
+                };
+                types.insert(operands[0], SpirvType::Scalar(numeric_type));
+            }
+            OP_TYPE_FLOAT if !operands.is_empty() => {
+                types.insert(operands[0], SpirvType::Scalar(NumericType::Float));
+            }
+            OP_TYPE_VECTOR if operands.len() >= 3 => {
+                types.insert(operands[0], SpirvType::Vector(operands[1], operands[2]));
+            }
+            OP_TYPE_MATRIX if operands.len() >= 3 => {
+                types.insert(operands[0], SpirvType::Matrix(operands[1], operands[2]));
+            }
+            OP_TYPE_POINTER if operands.len() >= 3 => {
+                types.insert(operands[0], SpirvType::Pointer(operands[2]));
+            }
+            OP_VARIABLE if operands.len() >= 3 && operands[2] == STORAGE_CLASS_INPUT => {
+                input_variables.push((operands[1], operands[0]));
+            }
+            OP_DECORATE if operands.len() >= 3 && operands[1] == DECORATION_LOCATION => {
+                locations.insert(operands[0], operands[2]);
+            }
+            _ => {}
+        }
+
+        offset += word_count;
+    }
+
+    let mut inputs = Vec::new();
+    for (variable_id, pointer_type_id) in input_variables {
+        let location = match locations.get(&variable_id) {
+            Some(location) => *location,
+            None => continue,
+        };
+
+        let type_id = match types.get(&pointer_type_id) {
+            Some(SpirvType::Pointer(type_id)) => *type_id,
+            _ => continue,
+        };
+
+        match types.get(&type_id) {
+            Some(SpirvType::Matrix(column_type_id, columns)) => {
+                if let Some((numeric_type, components)) = vector_type(&types, *column_type_id) {
+                    for column in 0..*columns {
+                        inputs.push(ShaderInput {
+                            location: location + column,
+                            numeric_type,
+                            components,
+                        });
+                    }
+                }
+            }
+            _ => {
+                if let Some((numeric_type, components)) = vector_type(&types, type_id) {
+                    inputs.push(ShaderInput {
+                        location,
+                        numeric_type,
+                        components,
+                    });
+                }
+            }
+        }
+    }
+
+    inputs.sort_by_key(|input| input.location);
+    inputs
+}
+
+fn vector_type(types: &HashMap<u32, SpirvType>, type_id: u32) -> Option<(NumericType, u32)> {
+    match types.get(&type_id)? {
+        SpirvType::Scalar(numeric_type) => Some((*numeric_type, 1)),
+        SpirvType::Vector(component_type_id, components) => match types.get(component_type_id)? {
+            SpirvType::Scalar(numeric_type) => Some((*numeric_type, *components)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
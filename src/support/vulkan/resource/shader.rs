@@ -1,7 +1,7 @@
-use crate::vulkan::VulkanContext;
+use crate::vulkan::{reflect_inputs, NumericType, ShaderInput, VulkanContext};
 use ash::{version::DeviceV1_0, vk};
 use derive_builder::Builder;
-use snafu::{ResultExt, Snafu};
+use snafu::{ensure, ResultExt, Snafu};
 use std::{
     collections::HashMap,
     ffi::CString,
@@ -25,6 +25,24 @@ pub enum Error {
 
     #[snafu(display("Failed to create shader module: {}", source))]
     CreateShaderModule { source: ash::vk::Result },
+
+    #[snafu(display(
+        "No vertex attribute provided for shader input at location {}",
+        location
+    ))]
+    MissingVertexAttribute { location: u32 },
+
+    #[snafu(display(
+        "Vertex attribute format {:?} at location {} does not match the shader input type {:?}",
+        format,
+        location,
+        numeric_type
+    ))]
+    MismatchedVertexAttribute {
+        location: u32,
+        format: vk::Format,
+        numeric_type: NumericType,
+    },
 }
 
 pub type ShaderMap = HashMap<String, Arc<Shader>>;
@@ -159,10 +177,28 @@ pub struct ShaderSet {
     pub tessellation_control_shader: Option<Arc<Shader>>,
 }
 
+impl ShaderSet {
+    pub fn shaders(&self) -> Vec<&Arc<Shader>> {
+        let mut shaders = vec![&self.vertex_shader];
+        shaders.extend(
+            [
+                &self.fragment_shader,
+                &self.geometry_shader,
+                &self.tessellation_evaluation_shader,
+                &self.tessellation_control_shader,
+            ]
+            .iter()
+            .filter_map(|shader| shader.as_ref()),
+        );
+        shaders
+    }
+}
+
 pub struct Shader {
     context: Arc<VulkanContext>,
     module: vk::ShaderModule,
     state_info: vk::PipelineShaderStageCreateInfo,
+    inputs: Vec<ShaderInput>,
     _entry_point_name: CString,
 }
 
@@ -196,19 +232,57 @@ impl Shader {
             .name(&entry_point_name)
             .build();
 
+        let inputs = reflect_inputs(&shader_source);
+
         let shader = Shader {
             module,
             context,
             state_info,
+            inputs,
             _entry_point_name: entry_point_name,
         };
 
         Ok(shader)
     }
 
+    pub fn module(&self) -> vk::ShaderModule {
+        self.module
+    }
+
     pub fn state_info(&self) -> vk::PipelineShaderStageCreateInfo {
         self.state_info
     }
+
+    pub fn inputs(&self) -> &[ShaderInput] {
+        &self.inputs
+    }
+
+    // Every input the shader reads must be provided by an attribute of the same numeric type
+    pub fn validate_vertex_attributes(
+        &self,
+        attributes: &[vk::VertexInputAttributeDescription],
+    ) -> Result<()> {
+        for input in self.inputs.iter() {
+            let attribute = attributes
+                .iter()
+                .find(|attribute| attribute.location == input.location)
+                .ok_or(Error::MissingVertexAttribute {
+                    location: input.location,
+                })?;
+
+            if let Some(numeric_type) = NumericType::from_format(attribute.format) {
+                ensure!(
+                    numeric_type == input.numeric_type,
+                    MismatchedVertexAttribute {
+                        location: input.location,
+                        format: attribute.format,
+                        numeric_type: input.numeric_type,
+                    }
+                );
+            }
+        }
+        Ok(())
+    }
 }
 
 impl Drop for Shader {
@@ -1,9 +1,13 @@
-use crate::vulkan::{Buffer, CommandPool, ImageView, Sampler, VulkanContext};
+use crate::vulkan::{
+    convert_texels, format_info, generate_mip_chain, linear_format, linearize_texels,
+    negotiate_format, parse_dds, parse_ktx2, srgb_format, Buffer, ColorSpace, CommandPool,
+    ImageView, MipmapSettings, Sampler, SamplerCache, UploadManager, VulkanContext,
+};
 use ash::{version::DeviceV1_0, vk};
 use gltf::image::Format;
-use image::{DynamicImage, ImageBuffer, Pixel, RgbImage};
-use snafu::{OptionExt, ResultExt, Snafu};
-use std::{iter, sync::Arc};
+use image::{DynamicImage, GenericImageView};
+use snafu::{ensure, OptionExt, ResultExt, Snafu};
+use std::{borrow::Cow, iter, sync::Arc};
 
 type Result<T, E = Error> = std::result::Result<T, E>;
 
@@ -28,43 +32,83 @@ pub enum Error {
         path: String,
     },
 
-    #[snafu(display("Failed to create texture: {}", source))]
-    CreateTexture { source: vk_mem::error::Error },
+    #[snafu(display("Failed to read texture file {}: {}", path, source))]
+    OpenTextureFile {
+        source: std::io::Error,
+        path: String,
+    },
 
-    #[snafu(display("Failed to create image buffer"))]
-    CreateImageBuffer,
+    #[snafu(display("Failed to parse KTX2 file {}: {}", path, source))]
+    ParseKtx2 {
+        source: crate::vulkan::resource::ktx2::Error,
+        path: String,
+    },
 
-    #[snafu(display("Failed to image copy buffer: {}", source))]
-    CreateImageCopyBuffer {
-        source: crate::vulkan::resource::buffer::Error,
+    #[snafu(display("Failed to parse DDS file {}: {}", path, source))]
+    ParseDds {
+        source: crate::vulkan::resource::dds::Error,
+        path: String,
     },
 
-    #[snafu(display("Failed to upload to image copy buffer: {}", source))]
-    UploadImageCopyBuffer {
-        source: crate::vulkan::resource::buffer::Error,
+    #[snafu(display("The size of texture format {:?} is unknown", format))]
+    UnknownFormatSize { format: vk::Format },
+
+    #[snafu(display("Mip level {} is too large to be addressed", level))]
+    LevelSizeOverflow { level: u32 },
+
+    #[snafu(display(
+        "Texture description holds {} bytes of pixel data but {} are required",
+        actual,
+        expected
+    ))]
+    MissingPixelData { expected: usize, actual: usize },
+
+    #[snafu(display(
+        "Array layers must be single layer textures with matching sizes, formats and mip counts"
+    ))]
+    MismatchedLayers,
+
+    #[snafu(display(
+        "Can't crop {}x{} texels at ({}, {}) from the first level of a single layer texture",
+        width,
+        height,
+        x,
+        y
+    ))]
+    InvalidCrop {
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
     },
 
-    #[snafu(display("Failed to cubemap image copy buffer: {}", source))]
-    CreateCubemapImageCopyBuffer {
-        source: crate::vulkan::resource::buffer::Error,
+    #[snafu(display("Cubemaps must have six square layers per cube"))]
+    InvalidCubemapDescription,
+
+    #[snafu(display("Failed to generate mipmaps on the CPU: {}", source))]
+    GenerateMipChain {
+        source: crate::vulkan::resource::mipmap::Error,
     },
 
-    #[snafu(display("Failed to upload to a cubemap image copy buffer: {}", source))]
-    UploadCubemapImageCopyBuffer {
-        source: crate::vulkan::resource::buffer::Error,
+    #[snafu(display("Linear blitting is not supported for format: {:?}", format))]
+    LinearBlitUnsupported { format: vk::Format },
+
+    #[snafu(display("Failed to find a texture format supported by this device: {}", source))]
+    NegotiateFormat {
+        source: crate::vulkan::resource::conversion::Error,
     },
 
-    #[snafu(display("Failed to transition mipmap image layout to transfer: {}", source))]
-    TransitionMipLayoutToTransfer {
-        source: crate::vulkan::command_pool::Error,
+    #[snafu(display("Failed to convert texture data: {}", source))]
+    ConvertTexels {
+        source: crate::vulkan::resource::conversion::Error,
     },
 
-    #[snafu(display(
-        "Failed to transition mipmap image layout to fragment shader: {}",
-        source
-    ))]
-    TransitionMipLayoutToFrag {
-        source: crate::vulkan::command_pool::Error,
+    #[snafu(display("Failed to create texture: {}", source))]
+    CreateTexture { source: vk_mem::error::Error },
+
+    #[snafu(display("Failed to upload texture data: {}", source))]
+    UploadTextureData {
+        source: crate::vulkan::resource::upload_manager::Error,
     },
 
     #[snafu(display("Failed to blit from source image to a mipmap: {}", source))]
@@ -87,20 +131,18 @@ pub enum Error {
         source: crate::vulkan::sampler::Error,
     },
 
-    #[snafu(display("Failed to create cubemap image view: {}", source))]
-    CreateCubemapImageView {
-        source: crate::vulkan::image_view::Error,
-    },
-
-    #[snafu(display("Failed to create cubemap sampler: {}", source))]
-    CreateCubemapSampler {
-        source: crate::vulkan::sampler::Error,
+    #[snafu(display("Failed to create texture readback buffer: {}", source))]
+    CreateReadbackBuffer {
+        source: crate::vulkan::buffer::Error,
     },
 
-    #[snafu(display("Failed to copy buffer to image: {}", source))]
-    CopyBufferToImage {
+    #[snafu(display("Failed to copy texture data to the readback buffer: {}", source))]
+    DownloadTextureData {
         source: crate::vulkan::command_pool::Error,
     },
+
+    #[snafu(display("Failed to read texture data from the readback buffer: {}", source))]
+    ReadbackTextureData { source: vk_mem::error::Error },
 }
 
 pub struct ImageLayoutTransition {
@@ -112,25 +154,187 @@ pub struct ImageLayoutTransition {
     pub dst_stage_mask: vk::PipelineStageFlags,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureDimension {
+    D1,
+    D2,
+    D3,
+}
+
+// Pixels are stored level by level, with every layer of a level stored together.
+// Cubemaps have six layers per cube, ordered +X, -X, +Y, -Y, +Z, -Z.
+// Only three dimensional textures have a depth greater than one,
+// and they can't have more than one layer.
+// The component mapping is applied by the image view, which lets grayscale images
+// be stored in a single channel and still be sampled as gray.
+#[derive(Clone)]
 pub struct TextureDescription {
     pub format: vk::Format,
+    pub dimension: TextureDimension,
     pub width: u32,
     pub height: u32,
+    pub depth: u32,
     pub pixels: Vec<u8>,
     pub mip_levels: u32,
+    pub layers: u32,
+    pub cubemap: bool,
+    pub samples: vk::SampleCountFlags,
+    pub stored_mip_levels: u32,
+    pub color_space: ColorSpace,
+    pub components: vk::ComponentMapping,
 }
 
 impl TextureDescription {
     pub fn empty(width: u32, height: u32, format: vk::Format) -> Self {
         Self {
             format,
+            dimension: TextureDimension::D2,
             width,
             height,
+            depth: 1,
             pixels: Vec::new(),
             mip_levels: Self::calculate_mip_levels(width, height),
+            layers: 1,
+            cubemap: false,
+            samples: vk::SampleCountFlags::TYPE_1,
+            stored_mip_levels: 1,
+            color_space: ColorSpace::of(format),
+            components: vk::ComponentMapping::default(),
+        }
+    }
+
+    pub fn empty_1d(width: u32, format: vk::Format) -> Self {
+        Self {
+            dimension: TextureDimension::D1,
+            ..Self::empty(width, 1, format)
+        }
+    }
+
+    pub fn empty_3d(width: u32, height: u32, depth: u32, format: vk::Format) -> Self {
+        Self {
+            dimension: TextureDimension::D3,
+            depth,
+            mip_levels: Self::calculate_mip_levels(width.max(depth), height),
+            ..Self::empty(width, height, format)
         }
     }
 
+    pub fn empty_array(width: u32, height: u32, layers: u32, format: vk::Format) -> Self {
+        Self {
+            layers,
+            ..Self::empty(width, height, format)
+        }
+    }
+
+    pub fn empty_cube(dimension: u32, cubes: u32, format: vk::Format) -> Self {
+        Self {
+            layers: cubes * 6,
+            cubemap: true,
+            ..Self::empty(dimension, dimension, format)
+        }
+    }
+
+    // Multisampled images only have a single mip level
+    pub fn empty_multisampled(
+        width: u32,
+        height: u32,
+        samples: vk::SampleCountFlags,
+        format: vk::Format,
+    ) -> Self {
+        Self {
+            samples,
+            mip_levels: 1,
+            ..Self::empty(width, height, format)
+        }
+    }
+
+    // Combines single layer descriptions of the same size and format into an array,
+    // such as the layers of a terrain texture
+    pub fn from_layers(layers: &[TextureDescription]) -> Result<Self> {
+        let first = layers.first().context(MismatchedLayers {})?;
+        ensure!(
+            layers.iter().all(|layer| {
+                layer.layers == 1
+                    && layer.dimension == first.dimension
+                    && layer.format == first.format
+                    && layer.width == first.width
+                    && layer.height == first.height
+                    && layer.depth == first.depth
+                    && layer.stored_mip_levels == first.stored_mip_levels
+            }),
+            MismatchedLayers {}
+        );
+
+        let mut pixels = Vec::new();
+        let mut level_offset = 0;
+        for level in 0..first.stored_mip_levels {
+            let level_size = first.level_size(level)?;
+            for layer in layers.iter() {
+                let data = layer
+                    .pixels
+                    .get(level_offset..level_offset + level_size)
+                    .context(MissingPixelData {
+                        expected: level_offset + level_size,
+                        actual: layer.pixels.len(),
+                    })?;
+                pixels.extend_from_slice(data);
+            }
+            level_offset += level_size;
+        }
+
+        Ok(Self {
+            pixels,
+            layers: layers.len() as _,
+            ..first.clone()
+        })
+    }
+
+    // Copies a rectangle of texels out of the first level of a two dimensional texture
+    pub fn crop(&self, x: u32, y: u32, width: u32, height: u32) -> Result<Self> {
+        let info = format_info(self.format).context(UnknownFormatSize {
+            format: self.format,
+        })?;
+        ensure!(
+            !info.is_compressed()
+                && self.dimension == TextureDimension::D2
+                && self.layers == 1
+                && x + width <= self.width
+                && y + height <= self.height,
+            InvalidCrop {
+                x,
+                y,
+                width,
+                height
+            }
+        );
+
+        let expected = info.layer_size(self.width, self.height);
+        ensure!(
+            self.pixels.len() >= expected,
+            MissingPixelData {
+                expected,
+                actual: self.pixels.len()
+            }
+        );
+
+        let texel_size = info.block_size as usize;
+        let row_size = self.width as usize * texel_size;
+        let pixels = (y..y + height)
+            .flat_map(|row| {
+                let start = row as usize * row_size + x as usize * texel_size;
+                self.pixels[start..start + width as usize * texel_size].iter()
+            })
+            .copied()
+            .collect();
+
+        Ok(Self {
+            pixels,
+            color_space: self.color_space,
+            components: self.components,
+            ..Self::empty(width, height, self.format)
+        })
+    }
+
     pub fn from_hdr(path: &str) -> Result<Self> {
         let file = std::fs::File::open(&path).context(OpenHdrFile {
             path: path.to_string(),
@@ -144,7 +348,6 @@ impl TextureDescription {
         let format = vk::Format::R32G32B32A32_SFLOAT;
         let width = metadata.width as u32;
         let height = metadata.height as u32;
-        let mip_levels = Self::calculate_mip_levels(width, height);
         let data = decoded
             .iter()
             .flat_map(|pixel| vec![pixel[0], pixel[1], pixel[2], 1.0])
@@ -154,11 +357,8 @@ impl TextureDescription {
                 .to_vec();
 
         let description = Self {
-            format,
-            width,
-            height,
             pixels,
-            mip_levels,
+            ..Self::empty(width, height, format)
         };
 
         Ok(description)
@@ -172,75 +372,224 @@ impl TextureDescription {
     }
 
     pub fn from_image(image: &DynamicImage) -> Result<Self> {
-        let (format, (width, height)) = match image {
-            DynamicImage::ImageRgb8(buffer) => (vk::Format::R8G8B8_UNORM, buffer.dimensions()),
-            DynamicImage::ImageRgba8(buffer) => (vk::Format::R8G8B8A8_UNORM, buffer.dimensions()),
-            DynamicImage::ImageBgr8(buffer) => (vk::Format::B8G8R8_UNORM, buffer.dimensions()),
-            DynamicImage::ImageBgra8(buffer) => (vk::Format::B8G8R8A8_UNORM, buffer.dimensions()),
-            DynamicImage::ImageRgb16(buffer) => (vk::Format::R16G16B16_UNORM, buffer.dimensions()),
-            DynamicImage::ImageRgba16(buffer) => {
-                (vk::Format::R16G16B16A16_UNORM, buffer.dimensions())
-            }
-            _ => panic!("Failed to match the provided image format to a vulkan format!"),
+        let format = match image {
+            DynamicImage::ImageLuma8(_) => vk::Format::R8_UNORM,
+            DynamicImage::ImageLumaA8(_) => vk::Format::R8G8_UNORM,
+            DynamicImage::ImageRgb8(_) => vk::Format::R8G8B8_UNORM,
+            DynamicImage::ImageRgba8(_) => vk::Format::R8G8B8A8_UNORM,
+            DynamicImage::ImageBgr8(_) => vk::Format::B8G8R8_UNORM,
+            DynamicImage::ImageBgra8(_) => vk::Format::B8G8R8A8_UNORM,
+            DynamicImage::ImageLuma16(_) => vk::Format::R16_UNORM,
+            DynamicImage::ImageLumaA16(_) => vk::Format::R16G16_UNORM,
+            DynamicImage::ImageRgb16(_) => vk::Format::R16G16B16_UNORM,
+            DynamicImage::ImageRgba16(_) => vk::Format::R16G16B16A16_UNORM,
         };
 
-        let mut description = Self {
-            format,
-            width,
-            height,
+        let (width, height) = image.dimensions();
+        Ok(Self {
             pixels: image.to_bytes(),
-            mip_levels: Self::calculate_mip_levels(width, height),
-        };
-        description.convert_24bit_formats()?;
-        Ok(description)
+            components: Self::luminance_components(format),
+            ..Self::empty(width, height, format)
+        })
     }
 
-    pub fn from_gltf(data: &gltf::image::Data) -> Result<Self> {
+    // glTF doesn't tag images with a color space,
+    // so it comes from how the material uses the image
+    pub fn from_gltf(data: &gltf::image::Data, color_space: ColorSpace) -> Result<Self> {
         let format = Self::convert_to_vulkan_format(data.format);
         let mut description = Self {
-            format,
-            width: data.width,
-            height: data.height,
             pixels: data.pixels.to_vec(),
-            mip_levels: Self::calculate_mip_levels(data.width, data.height),
+            components: Self::luminance_components(format),
+            ..Self::empty(data.width, data.height, format)
         };
-        description.convert_24bit_formats()?;
+        description.set_color_space(color_space);
         Ok(description)
     }
 
-    pub fn calculate_mip_levels(width: u32, height: u32) -> u32 {
-        ((width.min(height) as f32).log2().floor() + 1.0) as u32
+    // Decoded images with one or two channels are grayscale, optionally with alpha
+    fn luminance_components(format: vk::Format) -> vk::ComponentMapping {
+        let alpha = match format {
+            vk::Format::R8_UNORM | vk::Format::R16_UNORM => vk::ComponentSwizzle::ONE,
+            vk::Format::R8G8_UNORM | vk::Format::R16G16_UNORM => vk::ComponentSwizzle::G,
+            _ => return vk::ComponentMapping::default(),
+        };
+        vk::ComponentMapping {
+            r: vk::ComponentSwizzle::R,
+            g: vk::ComponentSwizzle::R,
+            b: vk::ComponentSwizzle::R,
+            a: alpha,
+        }
     }
 
-    fn convert_24bit_formats(&mut self) -> Result<()> {
-        // 24-bit formats are unsupported, so they
-        // need to have an alpha channel added to make them 32-bit
-        match self.format {
-            vk::Format::R8G8B8_UNORM => {
-                self.format = vk::Format::R8G8B8A8_UNORM;
-                self.attach_alpha_channel()?;
-            }
-            vk::Format::B8G8R8_UNORM => {
-                self.format = vk::Format::B8G8R8A8_UNORM;
-                self.attach_alpha_channel()?;
-            }
-            _ => {}
+    // Retags the texture by switching between the UNORM and sRGB variants of its format.
+    // Formats without an sRGB variant, such as floating point formats, are always linear.
+    pub fn set_color_space(&mut self, color_space: ColorSpace) {
+        let format = match color_space {
+            ColorSpace::Srgb => srgb_format(self.format),
+            ColorSpace::Linear => linear_format(self.format),
         };
 
-        Ok(())
+        if let Some(format) = format {
+            self.format = format;
+        }
+        self.color_space = ColorSpace::of(self.format);
     }
 
-    fn attach_alpha_channel(&mut self) -> Result<()> {
-        let image_buffer: RgbImage =
-            ImageBuffer::from_raw(self.width, self.height, self.pixels.to_vec())
-                .context(CreateImageBuffer {})?;
+    pub fn from_ktx2(path: &str) -> Result<Self> {
+        let bytes = std::fs::read(path).context(OpenTextureFile {
+            path: path.to_string(),
+        })?;
+        parse_ktx2(&bytes).context(ParseKtx2 {
+            path: path.to_string(),
+        })
+    }
 
-        self.pixels = image_buffer
-            .pixels()
-            .flat_map(|pixel| pixel.to_rgba().channels().to_vec())
-            .collect::<Vec<_>>();
+    pub fn from_dds(path: &str) -> Result<Self> {
+        let bytes = std::fs::read(path).context(OpenTextureFile {
+            path: path.to_string(),
+        })?;
+        parse_dds(&bytes).context(ParseDds {
+            path: path.to_string(),
+        })
+    }
 
-        Ok(())
+    // Every mip level is provided, so none need to be generated at runtime
+    pub fn has_complete_mip_chain(&self) -> bool {
+        self.stored_mip_levels >= self.mip_levels
+    }
+
+    // One copy region per stored mip level, each covering every layer
+    pub fn copy_regions(&self) -> Result<Vec<vk::BufferImageCopy>> {
+        self.layer_copy_regions(0, self.layers)
+    }
+
+    // The stored levels are copied into `layer_count` layers starting at `base_layer`
+    pub fn layer_copy_regions(
+        &self,
+        base_layer: u32,
+        layer_count: u32,
+    ) -> Result<Vec<vk::BufferImageCopy>> {
+        let (regions, size) =
+            self.level_copy_regions(base_layer, layer_count, self.stored_mip_levels)?;
+        ensure!(
+            size <= self.pixels.len(),
+            MissingPixelData {
+                expected: size,
+                actual: self.pixels.len()
+            }
+        );
+        Ok(regions)
+    }
+
+    // Tightly packed regions for the first `levels` mip levels, along with their total size
+    fn level_copy_regions(
+        &self,
+        base_layer: u32,
+        layer_count: u32,
+        levels: u32,
+    ) -> Result<(Vec<vk::BufferImageCopy>, usize)> {
+        let mut offset = 0;
+        let mut regions = Vec::new();
+        for level in 0..levels {
+            let region = vk::BufferImageCopy::builder()
+                .buffer_offset(offset as _)
+                .buffer_row_length(0)
+                .buffer_image_height(0)
+                .image_subresource(vk::ImageSubresourceLayers {
+                    aspect_mask: vk::ImageAspectFlags::COLOR,
+                    mip_level: level,
+                    base_array_layer: base_layer,
+                    layer_count,
+                })
+                .image_offset(vk::Offset3D { x: 0, y: 0, z: 0 })
+                .image_extent(self.level_extent(level))
+                .build();
+            regions.push(region);
+            offset += self.level_size(level)?;
+        }
+        Ok((regions, offset))
+    }
+
+    pub fn extent(&self) -> vk::Extent3D {
+        self.level_extent(0)
+    }
+
+    pub fn level_extent(&self, level: u32) -> vk::Extent3D {
+        vk::Extent3D {
+            width: self.width.checked_shr(level).unwrap_or(0).max(1),
+            height: self.height.checked_shr(level).unwrap_or(0).max(1),
+            depth: self.depth.checked_shr(level).unwrap_or(0).max(1),
+        }
+    }
+
+    // The number of bytes in a mip level, across every layer
+    pub fn level_size(&self, level: u32) -> Result<usize> {
+        let info = format_info(self.format).context(UnknownFormatSize {
+            format: self.format,
+        })?;
+        let extent = self.level_extent(level);
+        info.checked_layer_size(extent.width, extent.height)
+            .and_then(|size| size.checked_mul(extent.depth as usize))
+            .and_then(|size| size.checked_mul(self.layers as usize))
+            .context(LevelSizeOverflow { level })
+    }
+
+    pub fn image_type(&self) -> vk::ImageType {
+        match self.dimension {
+            TextureDimension::D1 => vk::ImageType::TYPE_1D,
+            TextureDimension::D2 => vk::ImageType::TYPE_2D,
+            TextureDimension::D3 => vk::ImageType::TYPE_3D,
+        }
+    }
+
+    pub fn view_type(&self) -> vk::ImageViewType {
+        match (self.dimension, self.cubemap, self.layers) {
+            (TextureDimension::D1, _, 1) => vk::ImageViewType::TYPE_1D,
+            (TextureDimension::D1, _, _) => vk::ImageViewType::TYPE_1D_ARRAY,
+            (TextureDimension::D2, true, 6) => vk::ImageViewType::CUBE,
+            (TextureDimension::D2, true, _) => vk::ImageViewType::CUBE_ARRAY,
+            (TextureDimension::D2, false, 1) => vk::ImageViewType::TYPE_2D,
+            (TextureDimension::D2, false, _) => vk::ImageViewType::TYPE_2D_ARRAY,
+            (TextureDimension::D3, _, _) => vk::ImageViewType::TYPE_3D,
+        }
+    }
+
+    pub fn image_create_flags(&self) -> vk::ImageCreateFlags {
+        if self.cubemap {
+            vk::ImageCreateFlags::CUBE_COMPATIBLE
+        } else {
+            vk::ImageCreateFlags::empty()
+        }
+    }
+
+    // The chain continues until both dimensions reach a single texel
+    pub fn calculate_mip_levels(width: u32, height: u32) -> u32 {
+        32 - width.max(height).max(1).leading_zeros()
+    }
+
+    // Generates the missing mip levels on the CPU,
+    // for formats that the device can't blit or when a specific filter is wanted
+    pub fn generate_mipmaps(&mut self, settings: &MipmapSettings) -> Result<()> {
+        generate_mip_chain(self, settings).context(GenerateMipChain {})
+    }
+
+    // The description itself is used when the device supports its format.
+    // Otherwise the texels are converted to the best supported alternative.
+    pub fn negotiate_format(&self, context: &VulkanContext) -> Result<Cow<'_, Self>> {
+        let format = negotiate_format(context, self).context(NegotiateFormat {})?;
+        if format == self.format {
+            Ok(Cow::Borrowed(self))
+        } else {
+            self.convert(format).map(Cow::Owned)
+        }
+    }
+
+    pub fn convert(&self, format: vk::Format) -> Result<Self> {
+        convert_texels(self, format).context(ConvertTexels {})
+    }
+
+    pub fn linearize(&self, format: vk::Format) -> Result<Self> {
+        linearize_texels(self, format).context(ConvertTexels {})
     }
 
     fn convert_to_vulkan_format(format: Format) -> vk::Format {
@@ -248,13 +597,13 @@ impl TextureDescription {
             Format::R8 => vk::Format::R8_UNORM,
             Format::R8G8 => vk::Format::R8G8_UNORM,
             Format::R8G8B8A8 => vk::Format::R8G8B8A8_UNORM,
-            Format::B8G8R8A8 => vk::Format::B8G8R8A8_UNORM,
             Format::R8G8B8 => vk::Format::R8G8B8_UNORM,
-            Format::B8G8R8 => vk::Format::B8G8R8_UNORM,
             Format::R16 => vk::Format::R16_UNORM,
             Format::R16G16 => vk::Format::R16G16_UNORM,
             Format::R16G16B16 => vk::Format::R16G16B16_UNORM,
             Format::R16G16B16A16 => vk::Format::R16G16B16A16_UNORM,
+            Format::R32G32B32FLOAT => vk::Format::R32G32B32_SFLOAT,
+            Format::R32G32B32A32FLOAT => vk::Format::R32G32B32A32_SFLOAT,
         }
     }
 }
@@ -289,41 +638,66 @@ impl Texture {
         Ok(texture)
     }
 
-    pub fn upload_texture_data(
-        &self,
-        command_pool: &CommandPool,
+    // Creates an optimally tiled, device local image matching the description
+    pub fn from_description(
+        context: Arc<VulkanContext>,
         description: &TextureDescription,
-    ) -> Result<()> {
-        let region = vk::BufferImageCopy::builder()
-            .buffer_offset(0)
-            .buffer_row_length(0)
-            .buffer_image_height(0)
-            .image_subresource(vk::ImageSubresourceLayers {
+        usage: vk::ImageUsageFlags,
+    ) -> Result<Self> {
+        let image_create_info = vk::ImageCreateInfo::builder()
+            .image_type(description.image_type())
+            .extent(description.extent())
+            .mip_levels(description.mip_levels)
+            .array_layers(description.layers)
+            .format(description.format)
+            .tiling(vk::ImageTiling::OPTIMAL)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .usage(usage)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .samples(description.samples)
+            .flags(description.image_create_flags())
+            .build();
+
+        let allocation_create_info = vk_mem::AllocationCreateInfo {
+            usage: vk_mem::MemoryUsage::GpuOnly,
+            ..Default::default()
+        };
+
+        Self::new(context, &allocation_create_info, &image_create_info)
+    }
+
+    // Views every level and layer of the image
+    pub fn create_view(&self, description: &TextureDescription) -> Result<ImageView> {
+        let create_info = vk::ImageViewCreateInfo::builder()
+            .image(self.image())
+            .view_type(description.view_type())
+            .format(description.format)
+            .components(description.components)
+            .subresource_range(vk::ImageSubresourceRange {
                 aspect_mask: vk::ImageAspectFlags::COLOR,
-                mip_level: 0,
+                base_mip_level: 0,
+                level_count: description.mip_levels,
                 base_array_layer: 0,
-                layer_count: 1,
-            })
-            .image_offset(vk::Offset3D { x: 0, y: 0, z: 0 })
-            .image_extent(vk::Extent3D {
-                width: description.width,
-                height: description.height,
-                depth: 1,
+                layer_count: description.layers,
             })
             .build();
-        let regions = [region];
+        ImageView::new(self.context.clone(), create_info).context(CreateImageView {})
+    }
 
-        let buffer = Buffer::new_mapped_basic(
-            self.context.clone(),
-            self.allocation_info().get_size() as _,
-            vk::BufferUsageFlags::TRANSFER_SRC,
-            vk_mem::MemoryUsage::CpuToGpu,
-        )
-        .context(CreateImageCopyBuffer {})?;
+    // The copy and mipmap generation are recorded into the upload manager's current batch.
+    // Mipmaps are only generated when the description doesn't provide every level.
+    pub fn upload_texture_data(
+        &self,
+        upload_manager: &mut UploadManager,
+        description: &TextureDescription,
+    ) -> Result<()> {
+        if !description.has_complete_mip_chain() && !self.supports_linear_blit(description.format) {
+            let mut description = description.clone();
+            description.generate_mipmaps(&MipmapSettings::default())?;
+            return self.upload_texture_data(upload_manager, &description);
+        }
 
-        buffer
-            .upload_to_buffer(&description.pixels, 0)
-            .context(UploadImageCopyBuffer {})?;
+        let regions = description.copy_regions()?;
 
         let transition = ImageLayoutTransition {
             old_layout: vk::ImageLayout::UNDEFINED,
@@ -333,13 +707,108 @@ impl Texture {
             src_stage_mask: vk::PipelineStageFlags::TOP_OF_PIPE,
             dst_stage_mask: vk::PipelineStageFlags::TRANSFER,
         };
-        self.transition(&command_pool, &transition, description.mip_levels)?;
+        upload_manager
+            .record(|device, command_buffer| {
+                self.record_layered_transition(
+                    device,
+                    command_buffer,
+                    &transition,
+                    description.mip_levels,
+                    description.layers,
+                )
+            })
+            .context(UploadTextureData {})?;
+
+        upload_manager
+            .upload_image(&description.pixels, self.image(), &regions)
+            .context(UploadTextureData {})?;
+
+        if description.has_complete_mip_chain() {
+            let transition = ImageLayoutTransition {
+                old_layout: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                new_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                src_access_mask: vk::AccessFlags::TRANSFER_WRITE,
+                dst_access_mask: vk::AccessFlags::SHADER_READ,
+                src_stage_mask: vk::PipelineStageFlags::TRANSFER,
+                dst_stage_mask: vk::PipelineStageFlags::FRAGMENT_SHADER,
+            };
+            upload_manager
+                .record(|device, command_buffer| {
+                    self.record_layered_transition(
+                        device,
+                        command_buffer,
+                        &transition,
+                        description.mip_levels,
+                        description.layers,
+                    )
+                })
+                .context(UploadTextureData {})?;
+        } else {
+            upload_manager
+                .record(|device, command_buffer| {
+                    self.record_mipmaps(device, command_buffer, description)
+                })
+                .context(UploadTextureData {})?;
+        }
+
+        Ok(())
+    }
+
+    // Replaces the contents of a single layer of an image that has already been uploaded.
+    // The description holds that layer alone, with the same size, format and mip count as the image.
+    // Missing mip levels are generated on the CPU so that the other layers are left untouched.
+    pub fn upload_layer(
+        &self,
+        upload_manager: &mut UploadManager,
+        layer: u32,
+        description: &TextureDescription,
+    ) -> Result<()> {
+        if !description.has_complete_mip_chain() {
+            let mut description = description.clone();
+            description.generate_mipmaps(&MipmapSettings::default())?;
+            return self.upload_layer(upload_manager, layer, &description);
+        }
+
+        let regions = description.layer_copy_regions(layer, 1)?;
+        let range = vk::ImageSubresourceRange {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            base_mip_level: 0,
+            level_count: description.mip_levels,
+            base_array_layer: layer,
+            layer_count: 1,
+        };
+
+        let transition = ImageLayoutTransition {
+            old_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            new_layout: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            src_access_mask: vk::AccessFlags::SHADER_READ,
+            dst_access_mask: vk::AccessFlags::TRANSFER_WRITE,
+            src_stage_mask: vk::PipelineStageFlags::FRAGMENT_SHADER,
+            dst_stage_mask: vk::PipelineStageFlags::TRANSFER,
+        };
+        upload_manager
+            .record(|device, command_buffer| {
+                self.record_subresource_transition(device, command_buffer, &transition, range)
+            })
+            .context(UploadTextureData {})?;
 
-        command_pool
-            .copy_buffer_to_image(buffer.buffer(), self.image(), &regions)
-            .unwrap();
+        upload_manager
+            .upload_image(&description.pixels, self.image(), &regions)
+            .context(UploadTextureData {})?;
 
-        self.generate_mipmaps(&command_pool, &description)?;
+        let transition = ImageLayoutTransition {
+            old_layout: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            new_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            src_access_mask: vk::AccessFlags::TRANSFER_WRITE,
+            dst_access_mask: vk::AccessFlags::SHADER_READ,
+            src_stage_mask: vk::PipelineStageFlags::TRANSFER,
+            dst_stage_mask: vk::PipelineStageFlags::FRAGMENT_SHADER,
+        };
+        upload_manager
+            .record(|device, command_buffer| {
+                self.record_subresource_transition(device, command_buffer, &transition, range)
+            })
+            .context(UploadTextureData {})?;
 
         Ok(())
     }
@@ -349,34 +818,51 @@ impl Texture {
         command_pool: &CommandPool,
         texture_description: &TextureDescription,
     ) -> Result<()> {
-        let format_properties = self
+        ensure!(
+            self.supports_linear_blit(texture_description.format),
+            LinearBlitUnsupported {
+                format: texture_description.format
+            }
+        );
+
+        let device = self.context.logical_device().logical_device();
+        command_pool
+            .execute_command_once(self.context.graphics_queue(), |command_buffer| {
+                self.record_mipmaps(device, command_buffer, texture_description)
+            })
+            .context(BlitMipMap {})
+    }
+
+    pub fn supports_linear_blit(&self, format: vk::Format) -> bool {
+        let features = self
             .context
-            .physical_device_format_properties(texture_description.format);
-
-        if !format_properties
-            .optimal_tiling_features
-            .contains(vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR)
-        {
-            panic!(
-                "Linear blitting is not supported for format: {:?}",
-                texture_description.format
-            );
-        }
+            .physical_device_format_properties(format)
+            .optimal_tiling_features;
+        features.contains(
+            vk::FormatFeatureFlags::BLIT_SRC
+                | vk::FormatFeatureFlags::BLIT_DST
+                | vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR,
+        )
+    }
 
-        let mut mip_width = texture_description.width as i32;
-        let mut mip_height = texture_description.height as i32;
-        for level in 1..texture_description.mip_levels {
-            let next_mip_width = if mip_width > 1 {
-                mip_width / 2
-            } else {
-                mip_width
-            };
+    // Expects the first mip level to be in the TRANSFER_DST_OPTIMAL layout.
+    // Every level ends up in the SHADER_READ_ONLY_OPTIMAL layout.
+    // The format must support linear blitting.
+    pub fn record_mipmaps(
+        &self,
+        device: &ash::Device,
+        command_buffer: vk::CommandBuffer,
+        texture_description: &TextureDescription,
+    ) {
+        assert!(
+            self.supports_linear_blit(texture_description.format),
+            "Linear blitting is not supported for format: {:?}",
+            texture_description.format
+        );
 
-            let next_mip_height = if mip_height > 1 {
-                mip_height / 2
-            } else {
-                mip_height
-            };
+        for level in 1..texture_description.mip_levels {
+            let source = texture_description.level_extent(level - 1);
+            let destination = texture_description.level_extent(level);
 
             let barrier = vk::ImageMemoryBarrier::builder()
                 .image(self.image())
@@ -385,7 +871,7 @@ impl Texture {
                 .subresource_range(vk::ImageSubresourceRange {
                     aspect_mask: vk::ImageAspectFlags::COLOR,
                     base_array_layer: 0,
-                    layer_count: 1,
+                    layer_count: texture_description.layers,
                     level_count: 1,
                     base_mip_level: level - 1,
                 })
@@ -394,73 +880,46 @@ impl Texture {
                 .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
                 .dst_access_mask(vk::AccessFlags::TRANSFER_READ)
                 .build();
-            let barriers = [barrier];
-
-            command_pool
-                .transition_image_layout(
-                    &barriers,
-                    vk::PipelineStageFlags::TRANSFER,
-                    vk::PipelineStageFlags::TRANSFER,
-                )
-                .context(TransitionMipLayoutToTransfer {})?;
 
             let blit = vk::ImageBlit::builder()
                 .src_offsets([
                     vk::Offset3D { x: 0, y: 0, z: 0 },
                     vk::Offset3D {
-                        x: mip_width,
-                        y: mip_height,
-                        z: 1,
+                        x: source.width as _,
+                        y: source.height as _,
+                        z: source.depth as _,
                     },
                 ])
                 .src_subresource(vk::ImageSubresourceLayers {
                     aspect_mask: vk::ImageAspectFlags::COLOR,
                     mip_level: level - 1,
                     base_array_layer: 0,
-                    layer_count: 1,
+                    layer_count: texture_description.layers,
                 })
                 .dst_offsets([
                     vk::Offset3D { x: 0, y: 0, z: 0 },
                     vk::Offset3D {
-                        x: next_mip_width,
-                        y: next_mip_height,
-                        z: 1,
+                        x: destination.width as _,
+                        y: destination.height as _,
+                        z: destination.depth as _,
                     },
                 ])
                 .dst_subresource(vk::ImageSubresourceLayers {
                     aspect_mask: vk::ImageAspectFlags::COLOR,
                     mip_level: level,
                     base_array_layer: 0,
-                    layer_count: 1,
+                    layer_count: texture_description.layers,
                 })
                 .build();
-            let blits = [blit];
-
-            command_pool
-                .execute_command_once(self.context.graphics_queue(), |command_buffer| unsafe {
-                    self.context
-                        .logical_device()
-                        .logical_device()
-                        .cmd_blit_image(
-                            command_buffer,
-                            self.image(),
-                            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
-                            self.image(),
-                            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-                            &blits,
-                            vk::Filter::LINEAR,
-                        )
-                })
-                .context(BlitMipMap {})?;
 
-            let barrier = vk::ImageMemoryBarrier::builder()
+            let shader_read_barrier = vk::ImageMemoryBarrier::builder()
                 .image(self.image())
                 .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
                 .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
                 .subresource_range(vk::ImageSubresourceRange {
                     aspect_mask: vk::ImageAspectFlags::COLOR,
                     base_array_layer: 0,
-                    layer_count: 1,
+                    layer_count: texture_description.layers,
                     level_count: 1,
                     base_mip_level: level - 1,
                 })
@@ -469,18 +928,38 @@ impl Texture {
                 .src_access_mask(vk::AccessFlags::TRANSFER_READ)
                 .dst_access_mask(vk::AccessFlags::SHADER_READ)
                 .build();
-            let barriers = [barrier];
 
-            command_pool
-                .transition_image_layout(
-                    &barriers,
+            unsafe {
+                device.cmd_pipeline_barrier(
+                    command_buffer,
+                    vk::PipelineStageFlags::TRANSFER,
+                    vk::PipelineStageFlags::TRANSFER,
+                    vk::DependencyFlags::empty(),
+                    &[],
+                    &[],
+                    &[barrier],
+                );
+
+                device.cmd_blit_image(
+                    command_buffer,
+                    self.image(),
+                    vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                    self.image(),
+                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    &[blit],
+                    vk::Filter::LINEAR,
+                );
+
+                device.cmd_pipeline_barrier(
+                    command_buffer,
                     vk::PipelineStageFlags::TRANSFER,
                     vk::PipelineStageFlags::FRAGMENT_SHADER,
-                )
-                .context(TransitionMipLayoutToFrag {})?;
-
-            mip_width = next_mip_width;
-            mip_height = next_mip_height;
+                    vk::DependencyFlags::empty(),
+                    &[],
+                    &[],
+                    &[shader_read_barrier],
+                );
+            }
         }
 
         let barrier = vk::ImageMemoryBarrier::builder()
@@ -490,7 +969,7 @@ impl Texture {
             .subresource_range(vk::ImageSubresourceRange {
                 aspect_mask: vk::ImageAspectFlags::COLOR,
                 base_array_layer: 0,
-                layer_count: 1,
+                layer_count: texture_description.layers,
                 level_count: 1,
                 base_mip_level: texture_description.mip_levels - 1,
             })
@@ -499,17 +978,18 @@ impl Texture {
             .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
             .dst_access_mask(vk::AccessFlags::SHADER_READ)
             .build();
-        let barriers = [barrier];
 
-        command_pool
-            .transition_image_layout(
-                &barriers,
+        unsafe {
+            device.cmd_pipeline_barrier(
+                command_buffer,
                 vk::PipelineStageFlags::TRANSFER,
                 vk::PipelineStageFlags::FRAGMENT_SHADER,
-            )
-            .context(TransitionMipLayoutToFrag {})?;
-
-        Ok(())
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[barrier],
+            );
+        }
     }
 
     pub fn transition(
@@ -518,33 +998,139 @@ impl Texture {
         transition: &ImageLayoutTransition,
         mip_levels: u32,
     ) -> Result<()> {
+        let device = self.context.logical_device().logical_device();
+        command_pool
+            .execute_command_once(self.context.graphics_queue(), |command_buffer| {
+                self.record_transition(device, command_buffer, transition, mip_levels)
+            })
+            .context(TransitionImageLayout {})
+    }
+
+    pub fn record_transition(
+        &self,
+        device: &ash::Device,
+        command_buffer: vk::CommandBuffer,
+        transition: &ImageLayoutTransition,
+        mip_levels: u32,
+    ) {
+        self.record_layered_transition(device, command_buffer, transition, mip_levels, 1)
+    }
+
+    pub fn record_layered_transition(
+        &self,
+        device: &ash::Device,
+        command_buffer: vk::CommandBuffer,
+        transition: &ImageLayoutTransition,
+        mip_levels: u32,
+        layers: u32,
+    ) {
+        let range = vk::ImageSubresourceRange {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            base_mip_level: 0,
+            level_count: mip_levels,
+            base_array_layer: 0,
+            layer_count: layers,
+        };
+        self.record_subresource_transition(device, command_buffer, transition, range)
+    }
+
+    pub fn record_subresource_transition(
+        &self,
+        device: &ash::Device,
+        command_buffer: vk::CommandBuffer,
+        transition: &ImageLayoutTransition,
+        range: vk::ImageSubresourceRange,
+    ) {
         let barrier = vk::ImageMemoryBarrier::builder()
             .old_layout(transition.old_layout)
             .new_layout(transition.new_layout)
             .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
             .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
             .image(self.image())
-            .subresource_range(vk::ImageSubresourceRange {
-                aspect_mask: vk::ImageAspectFlags::COLOR,
-                base_mip_level: 0,
-                level_count: mip_levels,
-                base_array_layer: 0,
-                layer_count: 1,
-            })
+            .subresource_range(range)
             .src_access_mask(transition.src_access_mask)
             .dst_access_mask(transition.dst_access_mask)
             .build();
-        let barriers = [barrier];
 
-        command_pool
-            .transition_image_layout(
-                &barriers,
+        unsafe {
+            device.cmd_pipeline_barrier(
+                command_buffer,
                 transition.src_stage_mask,
                 transition.dst_stage_mask,
-            )
-            .context(TransitionImageLayout {})?;
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[barrier],
+            );
+        }
+    }
 
-        Ok(())
+    // Copies every level and layer of the image back to the host, packed the same way as
+    // `TextureDescription::pixels`. The image is expected to be in `layout` and is left in it.
+    pub fn download(
+        &self,
+        command_pool: &CommandPool,
+        description: &TextureDescription,
+        layout: vk::ImageLayout,
+    ) -> Result<Vec<u8>> {
+        let (regions, size) =
+            description.level_copy_regions(0, description.layers, description.mip_levels)?;
+
+        let buffer = Buffer::new_mapped_basic(
+            self.context.clone(),
+            size as _,
+            vk::BufferUsageFlags::TRANSFER_DST,
+            vk_mem::MemoryUsage::GpuToCpu,
+        )
+        .context(CreateReadbackBuffer {})?;
+
+        let range = vk::ImageSubresourceRange {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            base_mip_level: 0,
+            level_count: description.mip_levels,
+            base_array_layer: 0,
+            layer_count: description.layers,
+        };
+        let to_transfer = ImageLayoutTransition {
+            old_layout: layout,
+            new_layout: vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            src_access_mask: vk::AccessFlags::MEMORY_WRITE,
+            dst_access_mask: vk::AccessFlags::TRANSFER_READ,
+            src_stage_mask: vk::PipelineStageFlags::ALL_COMMANDS,
+            dst_stage_mask: vk::PipelineStageFlags::TRANSFER,
+        };
+        let from_transfer = ImageLayoutTransition {
+            old_layout: vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            new_layout: layout,
+            src_access_mask: vk::AccessFlags::TRANSFER_READ,
+            dst_access_mask: vk::AccessFlags::MEMORY_READ,
+            src_stage_mask: vk::PipelineStageFlags::TRANSFER,
+            dst_stage_mask: vk::PipelineStageFlags::ALL_COMMANDS,
+        };
+
+        let device = self.context.logical_device().logical_device();
+        command_pool
+            .execute_command_once(self.context.graphics_queue(), |command_buffer| {
+                self.record_subresource_transition(device, command_buffer, &to_transfer, range);
+                unsafe {
+                    device.cmd_copy_image_to_buffer(
+                        command_buffer,
+                        self.image,
+                        vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                        buffer.buffer(),
+                        &regions,
+                    );
+                }
+                self.record_subresource_transition(device, command_buffer, &from_transfer, range);
+            })
+            .context(DownloadTextureData {})?;
+
+        buffer.invalidate(0, size).context(ReadbackTextureData {})?;
+        let data = buffer.map_memory().context(ReadbackTextureData {})?;
+        let pixels = unsafe { std::slice::from_raw_parts(data, size) }.to_vec();
+        buffer.unmap_memory().context(ReadbackTextureData {})?;
+
+        Ok(pixels)
     }
 
     pub fn image(&self) -> vk::Image {
@@ -569,6 +1155,7 @@ impl Drop for Texture {
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct CubemapFaces {
     pub right: String,
     pub left: String,
@@ -598,17 +1185,112 @@ impl CubemapFaces {
 pub struct Cubemap {
     pub texture: Texture,
     pub view: ImageView,
-    pub sampler: Sampler,
+    pub sampler: Arc<Sampler>,
     pub description: TextureDescription,
     context: Arc<VulkanContext>,
 }
 
 impl Cubemap {
-    pub fn new(context: Arc<VulkanContext>, dimension: u32, format: vk::Format) -> Result<Self> {
-        let description = TextureDescription::empty(dimension, dimension, format);
-        let texture = Self::create_texture(context.clone(), &description)?;
-        let view = Self::create_view(context.clone(), &texture, &description)?;
-        let sampler = Self::create_sampler(context.clone(), &description)?;
+    pub fn new(
+        context: Arc<VulkanContext>,
+        sampler_cache: &mut SamplerCache,
+        dimension: u32,
+        format: vk::Format,
+    ) -> Result<Self> {
+        Self::with_usage(
+            context,
+            sampler_cache,
+            dimension,
+            format,
+            vk::ImageUsageFlags::empty(),
+        )
+    }
+
+    // Adds usages such as STORAGE on top of the ones every cubemap needs
+    pub fn with_usage(
+        context: Arc<VulkanContext>,
+        sampler_cache: &mut SamplerCache,
+        dimension: u32,
+        format: vk::Format,
+        usage: vk::ImageUsageFlags,
+    ) -> Result<Self> {
+        let description = TextureDescription::empty_cube(dimension, 1, format);
+        Self::empty(context, sampler_cache, &description, usage)
+    }
+
+    // Creates an unfilled cubemap shaped by the description, such as one with a shorter mip chain
+    pub fn empty(
+        context: Arc<VulkanContext>,
+        sampler_cache: &mut SamplerCache,
+        description: &TextureDescription,
+        usage: vk::ImageUsageFlags,
+    ) -> Result<Self> {
+        ensure!(
+            description.cubemap
+                && description.layers == 6
+                && description.width == description.height,
+            InvalidCubemapDescription
+        );
+
+        let description = description.clone();
+        let texture = Texture::from_description(
+            context.clone(),
+            &description,
+            vk::ImageUsageFlags::TRANSFER_SRC
+                | vk::ImageUsageFlags::TRANSFER_DST
+                | vk::ImageUsageFlags::SAMPLED
+                | usage,
+        )?;
+        let view = texture.create_view(&description)?;
+        let sampler = sampler_cache
+            .sampler(Self::sampler_info())
+            .context(CreateSampler {})?;
+
+        let cubemap = Self {
+            texture,
+            view,
+            sampler,
+            description,
+            context,
+        };
+
+        Ok(cubemap)
+    }
+
+    // Uploads every face, converting them to a supported format and generating
+    // any mip levels the description doesn't store
+    pub fn from_description(
+        context: Arc<VulkanContext>,
+        upload_manager: &mut UploadManager,
+        sampler_cache: &mut SamplerCache,
+        description: &TextureDescription,
+    ) -> Result<Self> {
+        ensure!(
+            description.cubemap
+                && description.layers == 6
+                && description.width == description.height,
+            InvalidCubemapDescription
+        );
+
+        let description = description.negotiate_format(&context)?;
+        let texture = Texture::from_description(
+            context.clone(),
+            &description,
+            vk::ImageUsageFlags::TRANSFER_SRC
+                | vk::ImageUsageFlags::TRANSFER_DST
+                | vk::ImageUsageFlags::SAMPLED,
+        )?;
+        texture.upload_texture_data(upload_manager, &description)?;
+        let view = texture.create_view(&description)?;
+        let sampler = sampler_cache
+            .sampler(Self::sampler_info())
+            .context(CreateSampler {})?;
+
+        // The pixels have been uploaded, so they aren't kept around
+        let description = TextureDescription {
+            mip_levels: description.mip_levels,
+            ..TextureDescription::empty_cube(description.width, 1, description.format)
+        };
 
         let cubemap = Self {
             texture,
@@ -623,26 +1305,26 @@ impl Cubemap {
 
     pub fn upload_texture_data(
         &self,
-        command_pool: &CommandPool,
+        upload_manager: &mut UploadManager,
         descriptions: &[TextureDescription],
     ) -> Result<()> {
+        // Faces loaded from image files are converted to the cubemap's format
+        let descriptions = descriptions
+            .iter()
+            .map(|description| {
+                if description.format == self.description.format {
+                    Ok(Cow::Borrowed(description))
+                } else {
+                    description.convert(self.description.format).map(Cow::Owned)
+                }
+            })
+            .collect::<Result<Vec<_>>>()?;
+
         let mut pixels: Vec<u8> = Vec::new();
         descriptions.iter().for_each(|description| {
             pixels.extend(&description.pixels);
         });
 
-        let buffer = Buffer::new_mapped_basic(
-            self.context.clone(),
-            self.texture.allocation_info().get_size() as _,
-            vk::BufferUsageFlags::TRANSFER_SRC,
-            vk_mem::MemoryUsage::CpuToGpu,
-        )
-        .context(CreateCubemapImageCopyBuffer {})?;
-
-        buffer
-            .upload_to_buffer(&pixels, 0)
-            .context(UploadCubemapImageCopyBuffer {})?;
-
         let transition = ImageLayoutTransition {
             old_layout: vk::ImageLayout::UNDEFINED,
             new_layout: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
@@ -651,7 +1333,11 @@ impl Cubemap {
             src_stage_mask: vk::PipelineStageFlags::TOP_OF_PIPE,
             dst_stage_mask: vk::PipelineStageFlags::TRANSFER,
         };
-        self.transition(&command_pool, &transition)?;
+        upload_manager
+            .record(|device, command_buffer| {
+                self.record_transition(device, command_buffer, &transition)
+            })
+            .context(UploadTextureData {})?;
 
         let mut offset = 0;
         let regions = descriptions
@@ -680,9 +1366,9 @@ impl Cubemap {
             })
             .collect::<Vec<_>>();
 
-        command_pool
-            .copy_buffer_to_image(buffer.buffer(), self.texture.image(), &regions)
-            .context(CopyBufferToImage {})?;
+        upload_manager
+            .upload_image(&pixels, self.texture.image(), &regions)
+            .context(UploadTextureData {})?;
 
         let transition = ImageLayoutTransition {
             old_layout: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
@@ -692,77 +1378,19 @@ impl Cubemap {
             src_stage_mask: vk::PipelineStageFlags::TRANSFER,
             dst_stage_mask: vk::PipelineStageFlags::FRAGMENT_SHADER,
         };
-        self.transition(&command_pool, &transition)?;
-
-        Ok(())
-    }
-
-    fn create_texture(
-        context: Arc<VulkanContext>,
-        description: &TextureDescription,
-    ) -> Result<Texture> {
-        let image_create_info = vk::ImageCreateInfo::builder()
-            .image_type(vk::ImageType::TYPE_2D)
-            .extent(vk::Extent3D {
-                width: description.width,
-                height: description.height,
-                depth: 1,
+        upload_manager
+            .record(|device, command_buffer| {
+                self.record_transition(device, command_buffer, &transition)
             })
-            .mip_levels(description.mip_levels)
-            .array_layers(6)
-            .format(description.format)
-            .tiling(vk::ImageTiling::OPTIMAL)
-            .initial_layout(vk::ImageLayout::UNDEFINED)
-            .usage(
-                vk::ImageUsageFlags::TRANSFER_SRC
-                    | vk::ImageUsageFlags::TRANSFER_DST
-                    | vk::ImageUsageFlags::SAMPLED,
-            )
-            .sharing_mode(vk::SharingMode::EXCLUSIVE)
-            .samples(vk::SampleCountFlags::TYPE_1)
-            .flags(vk::ImageCreateFlags::CUBE_COMPATIBLE)
-            .build();
+            .context(UploadTextureData {})?;
 
-        let allocation_create_info = vk_mem::AllocationCreateInfo {
-            usage: vk_mem::MemoryUsage::GpuOnly,
-            ..Default::default()
-        };
-
-        Texture::new(context, &allocation_create_info, &image_create_info)
-    }
-
-    fn create_view(
-        context: Arc<VulkanContext>,
-        texture: &Texture,
-        description: &TextureDescription,
-    ) -> Result<ImageView> {
-        let create_info = vk::ImageViewCreateInfo::builder()
-            .image(texture.image())
-            .view_type(vk::ImageViewType::CUBE)
-            .format(description.format)
-            .components(vk::ComponentMapping {
-                r: vk::ComponentSwizzle::IDENTITY,
-                g: vk::ComponentSwizzle::IDENTITY,
-                b: vk::ComponentSwizzle::IDENTITY,
-                a: vk::ComponentSwizzle::IDENTITY,
-            })
-            .subresource_range(vk::ImageSubresourceRange {
-                aspect_mask: vk::ImageAspectFlags::COLOR,
-                base_mip_level: 0,
-                level_count: description.mip_levels,
-                base_array_layer: 0,
-                layer_count: 6,
-            })
-            .build();
-        let image_view = ImageView::new(context, create_info).context(CreateCubemapImageView {})?;
-        Ok(image_view)
+        Ok(())
     }
 
-    fn create_sampler(
-        context: Arc<VulkanContext>,
-        description: &TextureDescription,
-    ) -> Result<Sampler> {
-        let sampler_info = vk::SamplerCreateInfo::builder()
+    // Linear filtering with clamped coordinates.
+    // The LOD is unclamped so that one sampler serves cubemaps with any number of mip levels.
+    pub fn sampler_info() -> vk::SamplerCreateInfo {
+        vk::SamplerCreateInfo::builder()
             .mag_filter(vk::Filter::LINEAR)
             .min_filter(vk::Filter::LINEAR)
             .address_mode_u(vk::SamplerAddressMode::CLAMP_TO_EDGE)
@@ -777,10 +1405,8 @@ impl Cubemap {
             .mipmap_mode(vk::SamplerMipmapMode::LINEAR)
             .mip_lod_bias(0.0)
             .min_lod(0.0)
-            .max_lod(description.mip_levels as _)
-            .build();
-        let sampler = Sampler::new(context, sampler_info).context(CreateSampler {})?;
-        Ok(sampler)
+            .max_lod(vk::LOD_CLAMP_NONE)
+            .build()
     }
 
     // TODO: Merge this with the texture transition
@@ -789,6 +1415,20 @@ impl Cubemap {
         command_pool: &CommandPool,
         transition: &ImageLayoutTransition,
     ) -> Result<()> {
+        let device = self.context.logical_device().logical_device();
+        command_pool
+            .execute_command_once(self.context.graphics_queue(), |command_buffer| {
+                self.record_transition(device, command_buffer, transition)
+            })
+            .context(TransitionImageLayout {})
+    }
+
+    pub fn record_transition(
+        &self,
+        device: &ash::Device,
+        command_buffer: vk::CommandBuffer,
+        transition: &ImageLayoutTransition,
+    ) {
         let barrier = vk::ImageMemoryBarrier::builder()
             .old_layout(transition.old_layout)
             .new_layout(transition.new_layout)
@@ -800,116 +1440,88 @@ impl Cubemap {
                 base_mip_level: 0,
                 level_count: self.description.mip_levels,
                 base_array_layer: 0,
-                layer_count: 6,
+                layer_count: self.description.layers,
             })
             .src_access_mask(transition.src_access_mask)
             .dst_access_mask(transition.dst_access_mask)
             .build();
-        let barriers = [barrier];
 
-        command_pool
-            .transition_image_layout(
-                &barriers,
+        unsafe {
+            device.cmd_pipeline_barrier(
+                command_buffer,
                 transition.src_stage_mask,
                 transition.dst_stage_mask,
-            )
-            .context(TransitionImageLayout {})?;
-
-        Ok(())
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[barrier],
+            );
+        }
     }
 }
 
+// The image and view are shared between bundles
+// so that one image can be sampled in several ways
 pub struct TextureBundle {
-    pub texture: Texture,
-    pub view: ImageView,
-    pub sampler: Sampler,
+    pub texture: Arc<Texture>,
+    pub view: Arc<ImageView>,
+    pub sampler: Arc<Sampler>,
 }
 
 impl TextureBundle {
     pub fn new(
         context: Arc<VulkanContext>,
-        command_pool: &CommandPool,
+        upload_manager: &mut UploadManager,
+        sampler_cache: &mut SamplerCache,
         description: &TextureDescription,
     ) -> Result<Self> {
-        let texture = Self::create_texture(context.clone(), &description)?;
+        let sampler = sampler_cache
+            .sampler(Self::default_sampler_info())
+            .context(CreateSampler {})?;
+        Self::with_sampler(context, upload_manager, description, sampler)
+    }
 
-        texture.upload_texture_data(&command_pool, &description)?;
+    pub fn with_sampler(
+        context: Arc<VulkanContext>,
+        upload_manager: &mut UploadManager,
+        description: &TextureDescription,
+        sampler: Arc<Sampler>,
+    ) -> Result<Self> {
+        let description = description.negotiate_format(&context)?;
+        let texture = Texture::from_description(
+            context,
+            &description,
+            vk::ImageUsageFlags::TRANSFER_SRC
+                | vk::ImageUsageFlags::TRANSFER_DST
+                | vk::ImageUsageFlags::SAMPLED,
+        )?;
 
-        let view = Self::create_image_view(context.clone(), &texture, &description)?;
+        texture.upload_texture_data(upload_manager, &description)?;
 
-        let sampler = Self::create_sampler(context, description.mip_levels)?;
+        let view = texture.create_view(&description)?;
 
         let texture_bundle = Self {
-            texture,
-            view,
+            texture: Arc::new(texture),
+            view: Arc::new(view),
             sampler,
         };
 
         Ok(texture_bundle)
     }
 
-    fn create_texture(
-        context: Arc<VulkanContext>,
-        description: &TextureDescription,
-    ) -> Result<Texture> {
-        let image_create_info = vk::ImageCreateInfo::builder()
-            .image_type(vk::ImageType::TYPE_2D)
-            .extent(vk::Extent3D {
-                width: description.width,
-                height: description.height,
-                depth: 1,
-            })
-            .mip_levels(description.mip_levels)
-            .array_layers(1)
-            .format(description.format)
-            .tiling(vk::ImageTiling::OPTIMAL)
-            .initial_layout(vk::ImageLayout::UNDEFINED)
-            .usage(
-                vk::ImageUsageFlags::TRANSFER_SRC
-                    | vk::ImageUsageFlags::TRANSFER_DST
-                    | vk::ImageUsageFlags::SAMPLED,
-            )
-            .sharing_mode(vk::SharingMode::EXCLUSIVE)
-            .samples(vk::SampleCountFlags::TYPE_1)
-            .flags(vk::ImageCreateFlags::empty())
-            .build();
-
-        let allocation_create_info = vk_mem::AllocationCreateInfo {
-            usage: vk_mem::MemoryUsage::GpuOnly,
-            ..Default::default()
-        };
-
-        Texture::new(context, &allocation_create_info, &image_create_info)
-    }
-
-    fn create_image_view(
-        context: Arc<VulkanContext>,
-        texture: &Texture,
-        description: &TextureDescription,
-    ) -> Result<ImageView> {
-        let create_info = vk::ImageViewCreateInfo::builder()
-            .image(texture.image())
-            .view_type(vk::ImageViewType::TYPE_2D)
-            .format(description.format)
-            .components(vk::ComponentMapping {
-                r: vk::ComponentSwizzle::IDENTITY,
-                g: vk::ComponentSwizzle::IDENTITY,
-                b: vk::ComponentSwizzle::IDENTITY,
-                a: vk::ComponentSwizzle::IDENTITY,
-            })
-            .subresource_range(vk::ImageSubresourceRange {
-                aspect_mask: vk::ImageAspectFlags::COLOR,
-                base_mip_level: 0,
-                level_count: description.mip_levels,
-                base_array_layer: 0,
-                layer_count: 1,
-            })
-            .build();
-        ImageView::new(context, create_info).context(CreateImageView {})
+    // Shares the image with a bundle that uses a different sampler
+    pub fn resampled(&self, sampler: Arc<Sampler>) -> Self {
+        Self {
+            texture: self.texture.clone(),
+            view: self.view.clone(),
+            sampler,
+        }
     }
 
-    fn create_sampler(context: Arc<VulkanContext>, mip_levels: u32) -> Result<Sampler> {
-        let sampler_info = vk::SamplerCreateInfo::builder()
+    // Linear filtering with repeating coordinates.
+    // The LOD is unclamped so that one sampler serves textures with any number of mip levels.
+    pub fn default_sampler_info() -> vk::SamplerCreateInfo {
+        vk::SamplerCreateInfo::builder()
             .mag_filter(vk::Filter::LINEAR)
             .min_filter(vk::Filter::LINEAR)
             .address_mode_u(vk::SamplerAddressMode::REPEAT)
@@ -924,8 +1536,7 @@ impl TextureBundle {
             .mipmap_mode(vk::SamplerMipmapMode::LINEAR)
             .mip_lod_bias(0.0)
             .min_lod(0.0)
-            .max_lod(mip_levels as _)
-            .build();
-        Sampler::new(context, sampler_info).context(CreateCubemapSampler {})
+            .max_lod(vk::LOD_CLAMP_NONE)
+            .build()
     }
 }
@@ -0,0 +1,228 @@
+use crate::vulkan::{Buffer, VulkanContext};
+use ash::vk;
+use snafu::{ensure, ResultExt, Snafu};
+use std::{marker::PhantomData, mem, ops::Range, sync::Arc};
+
+type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility = "pub(crate)")]
+pub enum Error {
+    #[snafu(display("Failed to create typed buffer: {}", source))]
+    CreateTypedBuffer {
+        source: crate::vulkan::buffer::Error,
+    },
+
+    #[snafu(display("Failed to get memory type properties: {}", source))]
+    GetMemoryTypeProperties { source: vk_mem::error::Error },
+
+    #[snafu(display("Typed buffers must be allocated in host visible memory"))]
+    BufferNotHostVisible,
+
+    #[snafu(display("Range {:?} is out of bounds for a buffer of {} elements", range, len))]
+    RangeOutOfBounds { range: Range<usize>, len: usize },
+
+    #[snafu(display(
+        "Range {:?} holds {} elements but {} were provided",
+        range,
+        range.len(),
+        data_len
+    ))]
+    MismatchedDataLength {
+        range: Range<usize>,
+        data_len: usize,
+    },
+
+    #[snafu(display("Failed to flush typed buffer: {}", source))]
+    FlushTypedBuffer { source: vk_mem::error::Error },
+
+    #[snafu(display("Failed to invalidate typed buffer: {}", source))]
+    InvalidateTypedBuffer { source: vk_mem::error::Error },
+}
+
+// A persistently mapped, host visible buffer of `len` elements of `T`.
+// Each element starts at a multiple of the stride, which may be larger than `T`
+// when the buffer is bound with dynamic offsets.
+pub struct TypedBuffer<T: Copy> {
+    buffer: Buffer,
+    data_pointer: *mut u8,
+    len: usize,
+    stride: usize,
+    coherent: bool,
+    _element: PhantomData<T>,
+}
+
+impl<T: Copy> TypedBuffer<T> {
+    pub fn new(
+        context: Arc<VulkanContext>,
+        len: usize,
+        buffer_usage: vk::BufferUsageFlags,
+        memory_usage: vk_mem::MemoryUsage,
+    ) -> Result<Self> {
+        Self::with_alignment(context, len, buffer_usage, memory_usage, 1)
+    }
+
+    // Elements are aligned to `minUniformBufferOffsetAlignment`
+    // so that each one can be selected with a dynamic offset
+    pub fn new_dynamic_uniform(context: Arc<VulkanContext>, len: usize) -> Result<Self> {
+        let alignment = context
+            .physical_device_properties()
+            .limits
+            .min_uniform_buffer_offset_alignment;
+        Self::with_alignment(
+            context,
+            len,
+            vk::BufferUsageFlags::UNIFORM_BUFFER,
+            vk_mem::MemoryUsage::CpuToGpu,
+            alignment,
+        )
+    }
+
+    pub fn with_alignment(
+        context: Arc<VulkanContext>,
+        len: usize,
+        buffer_usage: vk::BufferUsageFlags,
+        memory_usage: vk_mem::MemoryUsage,
+        alignment: vk::DeviceSize,
+    ) -> Result<Self> {
+        let alignment = alignment.max(1) as usize;
+        let stride = mem::size_of::<T>().div_ceil(alignment) * alignment;
+
+        let allocation_create_info = vk_mem::AllocationCreateInfo {
+            usage: memory_usage,
+            flags: vk_mem::AllocationCreateFlags::MAPPED,
+            ..Default::default()
+        };
+
+        let buffer_create_info = vk::BufferCreateInfo::builder()
+            .size((len.max(1) * stride) as _)
+            .usage(buffer_usage)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .build();
+
+        let buffer = Buffer::new(
+            context.clone(),
+            &allocation_create_info,
+            &buffer_create_info,
+        )
+        .context(CreateTypedBuffer)?;
+
+        let properties = context
+            .allocator()
+            .get_memory_type_properties(buffer.allocation_info().get_memory_type())
+            .context(GetMemoryTypeProperties)?;
+        let data_pointer = buffer.allocation_info().get_mapped_data();
+        ensure!(
+            properties.contains(vk::MemoryPropertyFlags::HOST_VISIBLE) && !data_pointer.is_null(),
+            BufferNotHostVisible
+        );
+
+        Ok(Self {
+            buffer,
+            data_pointer,
+            len,
+            stride,
+            coherent: properties.contains(vk::MemoryPropertyFlags::HOST_COHERENT),
+            _element: PhantomData,
+        })
+    }
+
+    // Non-coherent memory is flushed after writing
+    pub fn write(&self, range: Range<usize>, data: &[T]) -> Result<()> {
+        self.check_range(&range)?;
+        ensure!(
+            range.len() == data.len(),
+            MismatchedDataLength {
+                range,
+                data_len: data.len()
+            }
+        );
+
+        if self.stride == mem::size_of::<T>() {
+            unsafe {
+                std::ptr::copy_nonoverlapping(
+                    data.as_ptr() as *const u8,
+                    self.data_pointer.add(range.start * self.stride),
+                    mem::size_of_val(data),
+                );
+            }
+        } else {
+            for (index, element) in range.clone().zip(data.iter()) {
+                unsafe {
+                    (self.data_pointer.add(index * self.stride) as *mut T)
+                        .write_unaligned(*element);
+                }
+            }
+        }
+
+        if !self.coherent {
+            let (offset, size) = self.byte_range(&range);
+            self.buffer.flush(offset, size).context(FlushTypedBuffer)?;
+        }
+
+        Ok(())
+    }
+
+    // Non-coherent memory is invalidated before reading
+    pub fn read(&self, range: Range<usize>) -> Result<Vec<T>> {
+        self.check_range(&range)?;
+
+        if !self.coherent {
+            let (offset, size) = self.byte_range(&range);
+            self.buffer
+                .invalidate(offset, size)
+                .context(InvalidateTypedBuffer)?;
+        }
+
+        Ok(range
+            .map(|index| unsafe {
+                (self.data_pointer.add(index * self.stride) as *const T).read_unaligned()
+            })
+            .collect())
+    }
+
+    fn check_range(&self, range: &Range<usize>) -> Result<()> {
+        ensure!(
+            range.start <= range.end && range.end <= self.len,
+            RangeOutOfBounds {
+                range: range.clone(),
+                len: self.len
+            }
+        );
+        Ok(())
+    }
+
+    fn byte_range(&self, range: &Range<usize>) -> (usize, usize) {
+        (range.start * self.stride, range.len() * self.stride)
+    }
+
+    // The offset of an element in bytes, suitable for use as a dynamic offset
+    pub fn offset(&self, index: usize) -> u32 {
+        (index * self.stride) as _
+    }
+
+    pub fn stride(&self) -> vk::DeviceSize {
+        self.stride as _
+    }
+
+    // The size of a single element, used as the range of dynamic descriptors
+    pub fn element_size(&self) -> vk::DeviceSize {
+        mem::size_of::<T>() as _
+    }
+
+    pub fn size(&self) -> vk::DeviceSize {
+        (self.len * self.stride) as _
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn buffer(&self) -> vk::Buffer {
+        self.buffer.buffer()
+    }
+}
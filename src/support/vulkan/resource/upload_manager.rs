@@ -0,0 +1,439 @@
+use crate::vulkan::{Buffer, CommandPool, Fence, TypedBuffer, VulkanContext};
+use ash::{version::DeviceV1_0, vk};
+use snafu::{ResultExt, Snafu};
+use std::{collections::VecDeque, mem, sync::Arc};
+
+type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility = "pub(crate)")]
+pub enum Error {
+    #[snafu(display("Failed to create upload command pool: {}", source))]
+    CreateUploadCommandPool {
+        source: crate::vulkan::command_pool::Error,
+    },
+
+    #[snafu(display("Failed to create staging buffer: {}", source))]
+    CreateStagingBuffer {
+        source: crate::vulkan::typed_buffer::Error,
+    },
+
+    #[snafu(display("Failed to write to staging buffer: {}", source))]
+    WriteStagingBuffer {
+        source: crate::vulkan::typed_buffer::Error,
+    },
+
+    #[snafu(display("Failed to create device local buffer: {}", source))]
+    CreateDeviceLocalBuffer {
+        source: crate::vulkan::buffer::Error,
+    },
+
+    #[snafu(display("Failed to allocate upload command buffer: {}", source))]
+    AllocateUploadCommandBuffer { source: vk::Result },
+
+    #[snafu(display("Failed to begin upload command buffer: {}", source))]
+    BeginUploadCommandBuffer { source: vk::Result },
+
+    #[snafu(display("Failed to end upload command buffer: {}", source))]
+    EndUploadCommandBuffer { source: vk::Result },
+
+    #[snafu(display("Failed to create upload fence: {}", source))]
+    CreateUploadFence {
+        source: crate::vulkan::sync::fence::Error,
+    },
+
+    #[snafu(display("Failed to submit upload command buffer: {}", source))]
+    SubmitUploadCommandBuffer { source: vk::Result },
+
+    #[snafu(display("Failed to get upload fence status: {}", source))]
+    GetUploadFenceStatus { source: vk::Result },
+
+    #[snafu(display("Failed to wait for upload fence: {}", source))]
+    WaitForUploadFence { source: vk::Result },
+}
+
+// Identifies a submitted batch of uploads.
+// A ticket is complete once its batch and every batch before it have finished executing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct UploadTicket(u64);
+
+struct UploadBatch {
+    id: u64,
+    command_buffer: vk::CommandBuffer,
+    fence: Fence,
+    ring_end: vk::DeviceSize,
+    // Uploads larger than the ring get their own staging buffer for the lifetime of the batch
+    _dedicated_buffers: Vec<TypedBuffer<u8>>,
+}
+
+// Records buffer and image copies into a single command buffer,
+// staging their data in a persistently mapped ring buffer.
+// Nothing is sent to the GPU until `submit` is called, and submitting does not wait.
+// Work submitted to the graphics queue after a batch will see its results.
+pub struct UploadManager {
+    context: Arc<VulkanContext>,
+    command_pool: CommandPool,
+    staging_buffer: TypedBuffer<u8>,
+    head: vk::DeviceSize,
+    tail: vk::DeviceSize,
+    command_buffer: Option<vk::CommandBuffer>,
+    dedicated_buffers: Vec<TypedBuffer<u8>>,
+    in_flight: VecDeque<UploadBatch>,
+    next_batch: u64,
+}
+
+impl UploadManager {
+    pub const DEFAULT_STAGING_CAPACITY: vk::DeviceSize = 64 * 1024 * 1024;
+
+    // Large enough for any texel block size and the copy offset requirements of buffer to image copies
+    const STAGING_ALIGNMENT: vk::DeviceSize = 16;
+
+    pub fn new(context: Arc<VulkanContext>, staging_capacity: vk::DeviceSize) -> Result<Self> {
+        let command_pool = CommandPool::new(context.clone(), vk::CommandPoolCreateFlags::TRANSIENT)
+            .context(CreateUploadCommandPool)?;
+
+        let staging_buffer = TypedBuffer::new(
+            context.clone(),
+            staging_capacity as _,
+            vk::BufferUsageFlags::TRANSFER_SRC,
+            vk_mem::MemoryUsage::CpuToGpu,
+        )
+        .context(CreateStagingBuffer)?;
+
+        Ok(Self {
+            context,
+            command_pool,
+            staging_buffer,
+            head: 0,
+            tail: 0,
+            command_buffer: None,
+            dedicated_buffers: Vec::new(),
+            in_flight: VecDeque::new(),
+            next_batch: 1,
+        })
+    }
+
+    pub fn upload_buffer<T: Copy>(
+        &mut self,
+        data: &[T],
+        destination: vk::Buffer,
+        destination_offset: vk::DeviceSize,
+    ) -> Result<()> {
+        let size = mem::size_of_val(data) as vk::DeviceSize;
+        if size == 0 {
+            return Ok(());
+        }
+
+        let (source, source_offset) = self.stage(data)?;
+        let region = vk::BufferCopy {
+            src_offset: source_offset,
+            dst_offset: destination_offset,
+            size,
+        };
+        self.record(|device, command_buffer| unsafe {
+            device.cmd_copy_buffer(command_buffer, source, destination, &[region]);
+        })
+    }
+
+    pub fn create_device_local_buffer<T: Copy>(
+        &mut self,
+        usage_flags: vk::BufferUsageFlags,
+        data: &[T],
+    ) -> Result<Buffer> {
+        let buffer = Buffer::new_mapped_basic(
+            self.context.clone(),
+            (mem::size_of_val(data) as vk::DeviceSize).max(1),
+            vk::BufferUsageFlags::TRANSFER_DST | usage_flags,
+            vk_mem::MemoryUsage::GpuOnly,
+        )
+        .context(CreateDeviceLocalBuffer)?;
+        self.upload_buffer(data, buffer.buffer(), 0)?;
+        Ok(buffer)
+    }
+
+    // The image must be in the TRANSFER_DST_OPTIMAL layout when the copy executes.
+    // Buffer offsets in the regions are relative to the start of `data`.
+    pub fn upload_image(
+        &mut self,
+        data: &[u8],
+        image: vk::Image,
+        regions: &[vk::BufferImageCopy],
+    ) -> Result<()> {
+        if data.is_empty() {
+            return Ok(());
+        }
+
+        let (source, source_offset) = self.stage(data)?;
+        let regions = regions
+            .iter()
+            .map(|region| vk::BufferImageCopy {
+                buffer_offset: region.buffer_offset + source_offset,
+                ..*region
+            })
+            .collect::<Vec<_>>();
+        self.record(|device, command_buffer| unsafe {
+            device.cmd_copy_buffer_to_image(
+                command_buffer,
+                source,
+                image,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                &regions,
+            );
+        })
+    }
+
+    // Records arbitrary commands, such as layout transitions, into the current batch
+    pub fn record<F>(&mut self, recorder: F) -> Result<()>
+    where
+        F: FnOnce(&ash::Device, vk::CommandBuffer),
+    {
+        let command_buffer = self.command_buffer()?;
+        recorder(
+            self.context.logical_device().logical_device(),
+            command_buffer,
+        );
+        Ok(())
+    }
+
+    pub fn submit(&mut self) -> Result<UploadTicket> {
+        let command_buffer = match self.command_buffer.take() {
+            Some(command_buffer) => command_buffer,
+            None => return Ok(UploadTicket(self.next_batch - 1)),
+        };
+
+        let device = self.context.logical_device().logical_device();
+
+        // Make the uploaded data visible to everything submitted after this batch
+        let memory_barrier = vk::MemoryBarrier::builder()
+            .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+            .dst_access_mask(vk::AccessFlags::MEMORY_READ | vk::AccessFlags::MEMORY_WRITE)
+            .build();
+
+        let fence = Fence::new(self.context.clone(), vk::FenceCreateFlags::empty())
+            .context(CreateUploadFence)?;
+
+        let command_buffers = [command_buffer];
+        let submit_info = vk::SubmitInfo::builder()
+            .command_buffers(&command_buffers)
+            .build();
+
+        unsafe {
+            device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::ALL_COMMANDS,
+                vk::DependencyFlags::empty(),
+                &[memory_barrier],
+                &[],
+                &[],
+            );
+
+            device
+                .end_command_buffer(command_buffer)
+                .context(EndUploadCommandBuffer)?;
+
+            device
+                .queue_submit(self.context.graphics_queue(), &[submit_info], fence.fence())
+                .context(SubmitUploadCommandBuffer)?;
+        }
+
+        let id = self.next_batch;
+        self.next_batch += 1;
+        self.in_flight.push_back(UploadBatch {
+            id,
+            command_buffer,
+            fence,
+            ring_end: self.head,
+            _dedicated_buffers: self.dedicated_buffers.drain(..).collect(),
+        });
+
+        Ok(UploadTicket(id))
+    }
+
+    // Does not block
+    pub fn is_complete(&mut self, ticket: UploadTicket) -> Result<bool> {
+        self.poll()?;
+        Ok(!self.is_pending(ticket))
+    }
+
+    pub fn wait(&mut self, ticket: UploadTicket) -> Result<()> {
+        while self.is_pending(ticket) {
+            self.retire_oldest(true)?;
+        }
+        Ok(())
+    }
+
+    // Submits the current batch and waits for every upload to finish
+    pub fn flush(&mut self) -> Result<()> {
+        let ticket = self.submit()?;
+        self.wait(ticket)
+    }
+
+    // Releases the staging memory of batches that have finished executing
+    pub fn poll(&mut self) -> Result<()> {
+        while !self.in_flight.is_empty() && self.retire_oldest(false)? {}
+        Ok(())
+    }
+
+    fn is_pending(&self, ticket: UploadTicket) -> bool {
+        self.in_flight
+            .front()
+            .is_some_and(|batch| batch.id <= ticket.0)
+    }
+
+    fn retire_oldest(&mut self, wait: bool) -> Result<bool> {
+        let device = self.context.logical_device().logical_device();
+        let fence = match self.in_flight.front() {
+            Some(batch) => batch.fence.fence(),
+            None => return Ok(false),
+        };
+
+        let finished = unsafe {
+            if wait {
+                device
+                    .wait_for_fences(&[fence], true, u64::MAX)
+                    .context(WaitForUploadFence)?;
+                true
+            } else {
+                device
+                    .get_fence_status(fence)
+                    .context(GetUploadFenceStatus)?
+            }
+        };
+
+        if finished {
+            let batch = self
+                .in_flight
+                .pop_front()
+                .expect("Failed to get upload batch!");
+            unsafe {
+                device.free_command_buffers(self.command_pool.pool(), &[batch.command_buffer]);
+            }
+            self.tail = batch.ring_end;
+        }
+
+        Ok(finished)
+    }
+
+    fn command_buffer(&mut self) -> Result<vk::CommandBuffer> {
+        if let Some(command_buffer) = self.command_buffer {
+            return Ok(command_buffer);
+        }
+
+        let device = self.context.logical_device().logical_device();
+        let allocation_info = vk::CommandBufferAllocateInfo::builder()
+            .level(vk::CommandBufferLevel::PRIMARY)
+            .command_pool(self.command_pool.pool())
+            .command_buffer_count(1)
+            .build();
+        let begin_info = vk::CommandBufferBeginInfo::builder()
+            .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT)
+            .build();
+
+        let command_buffer = unsafe {
+            let command_buffer = device
+                .allocate_command_buffers(&allocation_info)
+                .context(AllocateUploadCommandBuffer)?[0];
+            device
+                .begin_command_buffer(command_buffer, &begin_info)
+                .context(BeginUploadCommandBuffer)?;
+            command_buffer
+        };
+
+        self.command_buffer = Some(command_buffer);
+        Ok(command_buffer)
+    }
+
+    // Copies data into staging memory, returning the buffer and offset it was written to
+    fn stage<T: Copy>(&mut self, data: &[T]) -> Result<(vk::Buffer, vk::DeviceSize)> {
+        let bytes = unsafe {
+            std::slice::from_raw_parts(data.as_ptr() as *const u8, mem::size_of_val(data))
+        };
+        let size = bytes.len() as vk::DeviceSize;
+
+        // Staged data always belongs to the batch being recorded
+        self.command_buffer()?;
+
+        if size > self.staging_buffer.size() {
+            let buffer = TypedBuffer::new(
+                self.context.clone(),
+                bytes.len(),
+                vk::BufferUsageFlags::TRANSFER_SRC,
+                vk_mem::MemoryUsage::CpuToGpu,
+            )
+            .context(CreateStagingBuffer)?;
+            buffer
+                .write(0..bytes.len(), bytes)
+                .context(WriteStagingBuffer)?;
+            let staged = (buffer.buffer(), 0);
+            self.dedicated_buffers.push(buffer);
+            return Ok(staged);
+        }
+
+        let offset = loop {
+            if let Some(offset) = self.allocate(size) {
+                break offset;
+            }
+
+            // Make room by sending what has been recorded so far and waiting for the oldest batch
+            if self.in_flight.is_empty() {
+                self.submit()?;
+            }
+            self.retire_oldest(true)?;
+        };
+
+        let start = offset as usize;
+        self.staging_buffer
+            .write(start..start + bytes.len(), bytes)
+            .context(WriteStagingBuffer)?;
+
+        Ok((self.staging_buffer.buffer(), offset))
+    }
+
+    // The ring is empty when the head and tail meet,
+    // so allocations never fill it completely
+    fn allocate(&mut self, size: vk::DeviceSize) -> Option<vk::DeviceSize> {
+        let capacity = self.staging_buffer.size();
+        if self.head == self.tail {
+            self.head = 0;
+            self.tail = 0;
+        }
+
+        let start = self.head.div_ceil(Self::STAGING_ALIGNMENT) * Self::STAGING_ALIGNMENT;
+        let offset = if self.head >= self.tail {
+            if start + size <= capacity {
+                start
+            } else if size < self.tail {
+                0
+            } else {
+                return None;
+            }
+        } else if start + size < self.tail {
+            start
+        } else {
+            return None;
+        };
+
+        self.head = offset + size;
+        Some(offset)
+    }
+}
+
+impl Drop for UploadManager {
+    fn drop(&mut self) {
+        if let Some(command_buffer) = self.command_buffer.take() {
+            unsafe {
+                let device = self.context.logical_device().logical_device();
+                device
+                    .end_command_buffer(command_buffer)
+                    .expect("Failed to end upload command buffer!");
+                device.free_command_buffers(self.command_pool.pool(), &[command_buffer]);
+            }
+        }
+
+        while !self.in_flight.is_empty() {
+            self.retire_oldest(true)
+                .expect("Failed to wait for uploads to finish!");
+        }
+    }
+}
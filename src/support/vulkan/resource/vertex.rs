@@ -0,0 +1,121 @@
+use ash::vk;
+use nalgebra_glm as glm;
+
+pub use vertex_derive::Vertex;
+
+// Maps a vertex field type to the format the shader reads it as.
+// Matrices span one location per column.
+pub trait VertexAttributeFormat {
+    const FORMAT: vk::Format;
+    const LOCATIONS: u32 = 1;
+}
+
+macro_rules! impl_vertex_attribute_format {
+    ($($type:ty => $format:ident),* $(,)?) => {
+        $(
+            impl VertexAttributeFormat for $type {
+                const FORMAT: vk::Format = vk::Format::$format;
+            }
+        )*
+    };
+}
+
+impl_vertex_attribute_format! {
+    f32 => R32_SFLOAT,
+    [f32; 2] => R32G32_SFLOAT,
+    [f32; 3] => R32G32B32_SFLOAT,
+    [f32; 4] => R32G32B32A32_SFLOAT,
+    glm::Vec2 => R32G32_SFLOAT,
+    glm::Vec3 => R32G32B32_SFLOAT,
+    glm::Vec4 => R32G32B32A32_SFLOAT,
+    u32 => R32_UINT,
+    [u32; 2] => R32G32_UINT,
+    [u32; 3] => R32G32B32_UINT,
+    [u32; 4] => R32G32B32A32_UINT,
+    glm::UVec2 => R32G32_UINT,
+    glm::UVec3 => R32G32B32_UINT,
+    glm::UVec4 => R32G32B32A32_UINT,
+    i32 => R32_SINT,
+    [i32; 2] => R32G32_SINT,
+    [i32; 3] => R32G32B32_SINT,
+    [i32; 4] => R32G32B32A32_SINT,
+    glm::IVec2 => R32G32_SINT,
+    glm::IVec3 => R32G32B32_SINT,
+    glm::IVec4 => R32G32B32A32_SINT,
+    [u16; 2] => R16G16_UINT,
+    [u16; 4] => R16G16B16A16_UINT,
+    [u8; 4] => R8G8B8A8_UNORM,
+}
+
+impl VertexAttributeFormat for glm::Mat4 {
+    const FORMAT: vk::Format = vk::Format::R32G32B32A32_SFLOAT;
+    const LOCATIONS: u32 = 4;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VertexAttribute {
+    pub format: vk::Format,
+    pub offset: u32,
+    pub locations: u32,
+    pub location_stride: u32,
+}
+
+impl VertexAttribute {
+    pub fn of<T: VertexAttributeFormat>(offset: u32) -> Self {
+        Self {
+            format: T::FORMAT,
+            offset,
+            locations: T::LOCATIONS,
+            location_stride: (std::mem::size_of::<T>() as u32) / T::LOCATIONS,
+        }
+    }
+}
+
+// Implement this with `#[derive(Vertex)]` on a `#[repr(C)]` struct
+pub trait Vertex: Copy {
+    fn attributes() -> Vec<VertexAttribute>;
+
+    fn stride() -> u32 {
+        std::mem::size_of::<Self>() as _
+    }
+
+    fn input_rate() -> vk::VertexInputRate {
+        vk::VertexInputRate::VERTEX
+    }
+
+    fn locations() -> u32 {
+        Self::attributes()
+            .iter()
+            .map(|attribute| attribute.locations)
+            .sum()
+    }
+
+    fn binding_description(binding: u32) -> vk::VertexInputBindingDescription {
+        vk::VertexInputBindingDescription::builder()
+            .binding(binding)
+            .stride(Self::stride())
+            .input_rate(Self::input_rate())
+            .build()
+    }
+
+    fn attribute_descriptions(
+        binding: u32,
+        first_location: u32,
+    ) -> Vec<vk::VertexInputAttributeDescription> {
+        let mut location = first_location;
+        let mut descriptions = Vec::new();
+        for attribute in Self::attributes() {
+            for column in 0..attribute.locations {
+                let description = vk::VertexInputAttributeDescription::builder()
+                    .binding(binding)
+                    .location(location)
+                    .format(attribute.format)
+                    .offset(attribute.offset + column * attribute.location_stride)
+                    .build();
+                descriptions.push(description);
+                location += 1;
+            }
+        }
+        descriptions
+    }
+}
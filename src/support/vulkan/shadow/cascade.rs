@@ -0,0 +1,424 @@
+use crate::vulkan::{
+    create_shadow_framebuffers, create_shadow_render_pass, create_shadow_sampler,
+    create_shadow_view, record_shadow_layers, DepthBias, Framebuffer, ImageView, RenderPass,
+    Sampler, Texture, VulkanContext,
+};
+use ash::vk;
+use derive_builder::Builder;
+use nalgebra_glm as glm;
+use snafu::{ResultExt, Snafu};
+use std::sync::Arc;
+
+type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility = "pub(crate)")]
+pub enum Error {
+    #[snafu(display("Failed to create shadow map render pass: {}", source))]
+    CreateShadowRenderPass {
+        source: crate::vulkan::renderpass::Error,
+    },
+
+    #[snafu(display("Failed to create shadow map texture: {}", source))]
+    CreateShadowMapTexture {
+        source: crate::vulkan::texture::Error,
+    },
+
+    #[snafu(display("Failed to create shadow map view: {}", source))]
+    CreateShadowMapView {
+        source: crate::vulkan::image_view::Error,
+    },
+
+    #[snafu(display("Failed to create shadow map framebuffer: {}", source))]
+    CreateShadowFramebuffer {
+        source: crate::vulkan::framebuffer::Error,
+    },
+
+    #[snafu(display("Failed to create shadow map sampler: {}", source))]
+    CreateShadowSampler {
+        source: crate::vulkan::sampler::Error,
+    },
+}
+
+// How the distance shadows are drawn over is divided between the cascades
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CascadeSplitScheme {
+    // Every cascade covers the same distance
+    Uniform,
+
+    // Each cascade covers the same ratio of distances,
+    // which keeps the shadow texels the same size on screen
+    Logarithmic,
+
+    // Blends the logarithmic splits with the uniform ones by lambda,
+    // where zero is uniform and one is logarithmic
+    Practical { lambda: f32 },
+}
+
+impl Default for CascadeSplitScheme {
+    fn default() -> Self {
+        CascadeSplitScheme::Practical { lambda: 0.9 }
+    }
+}
+
+impl CascadeSplitScheme {
+    // The distance from the camera each cascade ends at
+    pub fn splits(&self, cascade_count: usize, near: f32, far: f32) -> Vec<f32> {
+        (1..=cascade_count)
+            .map(|index| {
+                let fraction = index as f32 / cascade_count as f32;
+                let uniform = near + (far - near) * fraction;
+                let logarithmic = near * (far / near).powf(fraction);
+                match self {
+                    CascadeSplitScheme::Uniform => uniform,
+                    CascadeSplitScheme::Logarithmic => logarithmic,
+                    CascadeSplitScheme::Practical { lambda } => {
+                        lambda * logarithmic + (1.0 - lambda) * uniform
+                    }
+                }
+            })
+            .collect()
+    }
+}
+
+#[derive(Builder, Debug, Clone, Copy, PartialEq)]
+#[builder(default, setter(into))]
+pub struct CascadeSettings {
+    // Up to `CascadedShadowMap::MAX_CASCADES`
+    pub cascade_count: usize,
+
+    // The width and height of each cascade's layer
+    pub map_size: u32,
+
+    pub split_scheme: CascadeSplitScheme,
+
+    // Shadows end this far from the camera
+    pub shadow_distance: f32,
+
+    // Casters this far past a cascade towards the light still shadow it
+    pub caster_distance: f32,
+
+    // Applied while rendering the shadow map
+    pub depth_bias: DepthBias,
+
+    // The filter averages this many texels on each side of a fragment
+    pub filter_radius: u32,
+}
+
+impl Default for CascadeSettings {
+    fn default() -> Self {
+        Self {
+            cascade_count: 4,
+            map_size: 2048,
+            split_scheme: CascadeSplitScheme::default(),
+            shadow_distance: 100.0,
+            caster_distance: 100.0,
+            depth_bias: DepthBias {
+                constant_factor: 1.25,
+                slope_factor: 1.75,
+                clamp: 0.0,
+            },
+            filter_radius: 1,
+        }
+    }
+}
+
+// The view a cascade's layer of the shadow map is rendered with
+#[derive(Debug, Clone, Copy)]
+pub struct ShadowCascade {
+    pub index: usize,
+
+    // The distance from the camera this cascade ends at
+    pub split_depth: f32,
+
+    pub view_projection: glm::Mat4,
+}
+
+// The camera the cascades are fit to
+#[derive(Debug, Clone, Copy)]
+pub struct CascadeCamera {
+    pub view: glm::Mat4,
+
+    // The vertical field of view, in radians
+    pub fov_y: f32,
+
+    pub aspect_ratio: f32,
+    pub near: f32,
+}
+
+// Cascades are packed for the shaders
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct ShadowUniform {
+    pub view_projections: [glm::Mat4; CascadedShadowMap::MAX_CASCADES],
+
+    // The distance from the camera each cascade ends at
+    pub splits: glm::Vec4,
+
+    // X is the number of cascades, zero when nothing casts shadows.
+    // Y is the filter radius in texels.
+    // Z is the size of a texel.
+    // W is the index of the light the shadows are cast by.
+    pub parameters: glm::Vec4,
+}
+
+impl ShadowUniform {
+    pub fn disabled() -> Self {
+        Self {
+            view_projections: [glm::Mat4::identity(); CascadedShadowMap::MAX_CASCADES],
+            splits: glm::Vec4::zeros(),
+            parameters: glm::Vec4::zeros(),
+        }
+    }
+}
+
+// Shadows of a directional light, rendered into one layer of a depth array per cascade.
+// Each cascade covers a slice of the camera's view, with the nearest slices
+// getting the most detail.
+pub struct CascadedShadowMap {
+    settings: CascadeSettings,
+    render_pass: Arc<RenderPass>,
+    texture: Texture,
+    view: ImageView,
+    _layer_views: Vec<ImageView>,
+    framebuffers: Vec<Framebuffer>,
+    sampler: Sampler,
+}
+
+impl CascadedShadowMap {
+    // This needs to match the defined value in the shaders
+    pub const MAX_CASCADES: usize = 4;
+
+    pub fn new(context: Arc<VulkanContext>, settings: CascadeSettings) -> Result<Self> {
+        let settings = CascadeSettings {
+            cascade_count: settings.cascade_count.clamp(1, Self::MAX_CASCADES),
+            ..settings
+        };
+
+        let format = context.determine_depth_format(
+            vk::ImageTiling::OPTIMAL,
+            vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT
+                | vk::FormatFeatureFlags::SAMPLED_IMAGE,
+        );
+        let render_pass = Arc::new(
+            create_shadow_render_pass(context.clone(), format)
+                .context(CreateShadowRenderPass {})?,
+        );
+        let texture = Self::create_texture(context.clone(), &settings, format)?;
+        let layers = settings.cascade_count as u32;
+        let view = create_shadow_view(
+            context.clone(),
+            &texture,
+            format,
+            vk::ImageViewType::TYPE_2D_ARRAY,
+            0,
+            layers,
+        )
+        .context(CreateShadowMapView {})?;
+        let layer_views = (0..layers)
+            .map(|layer| {
+                create_shadow_view(
+                    context.clone(),
+                    &texture,
+                    format,
+                    vk::ImageViewType::TYPE_2D,
+                    layer,
+                    1,
+                )
+                .context(CreateShadowMapView {})
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let framebuffers = create_shadow_framebuffers(
+            context.clone(),
+            &render_pass,
+            &layer_views,
+            settings.map_size,
+        )
+        .context(CreateShadowFramebuffer {})?;
+
+        // Clamping to the white border leaves everything outside the cascades lit
+        let sampler = create_shadow_sampler(context, vk::SamplerAddressMode::CLAMP_TO_BORDER)
+            .context(CreateShadowSampler {})?;
+
+        Ok(Self {
+            settings,
+            render_pass,
+            texture,
+            view,
+            _layer_views: layer_views,
+            framebuffers,
+            sampler,
+        })
+    }
+
+    pub fn settings(&self) -> &CascadeSettings {
+        &self.settings
+    }
+
+    // Pipelines that draw casters are created against this render pass.
+    // It only has a single sampled depth attachment.
+    pub fn render_pass(&self) -> Arc<RenderPass> {
+        self.render_pass.clone()
+    }
+
+    pub fn texture(&self) -> &Texture {
+        &self.texture
+    }
+
+    // Views every cascade as a layer of an array
+    pub fn view(&self) -> &ImageView {
+        &self.view
+    }
+
+    // Compares against the stored depth, for sampler2DArrayShadow
+    pub fn sampler(&self) -> &Sampler {
+        &self.sampler
+    }
+
+    // Each cascade is fit to a sphere around its slice of the view.
+    // The sphere's size doesn't change as the camera turns, and its center is
+    // snapped to whole texels, so the shadows' edges don't shimmer as the camera moves.
+    pub fn cascades(
+        &self,
+        camera: &CascadeCamera,
+        light_direction: &glm::Vec3,
+    ) -> Vec<ShadowCascade> {
+        let splits = self.settings.split_scheme.splits(
+            self.settings.cascade_count,
+            camera.near,
+            self.settings.shadow_distance,
+        );
+
+        let inverse_view = glm::inverse(&camera.view);
+        let tangent = (0.5 * camera.fov_y).tan();
+        let light_direction = glm::normalize(light_direction);
+        let up = if light_direction.y.abs() > 0.99 {
+            glm::vec3(0.0, 0.0, 1.0)
+        } else {
+            glm::vec3(0.0, 1.0, 0.0)
+        };
+
+        let mut near = camera.near;
+        splits
+            .into_iter()
+            .enumerate()
+            .map(|(index, far)| {
+                let corners = [near, far]
+                    .iter()
+                    .flat_map(|depth| {
+                        let height = depth * tangent;
+                        let width = height * camera.aspect_ratio;
+                        [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)]
+                            .iter()
+                            .map(|(x, y)| {
+                                let corner = glm::vec4(x * width, y * height, -depth, 1.0);
+                                glm::vec4_to_vec3(&(inverse_view * corner))
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                    .collect::<Vec<_>>();
+                near = far;
+
+                let center = corners
+                    .iter()
+                    .fold(glm::Vec3::zeros(), |sum, corner| sum + corner)
+                    / corners.len() as f32;
+                let radius = corners
+                    .iter()
+                    .map(|corner| glm::distance(corner, &center))
+                    .fold(0.0_f32, f32::max);
+                let radius = (radius * 16.0).ceil() / 16.0;
+
+                let eye = center + light_direction * radius;
+                let view = glm::look_at(&eye, &center, &up);
+                let mut projection = glm::ortho_zo(
+                    -radius,
+                    radius,
+                    -radius,
+                    radius,
+                    -self.settings.caster_distance,
+                    2.0 * radius,
+                );
+
+                // Moves the projection by less than a texel, so the world's origin
+                // lands on a texel's corner
+                let half_size = 0.5 * self.settings.map_size as f32;
+                let origin = (projection * view) * glm::vec4(0.0, 0.0, 0.0, 1.0);
+                let origin = glm::vec2(origin.x, origin.y) * half_size;
+                let offset = (glm::round(&origin) - origin) / half_size;
+                projection[(0, 3)] += offset.x;
+                projection[(1, 3)] += offset.y;
+
+                ShadowCascade {
+                    index,
+                    split_depth: far,
+                    view_projection: projection * view,
+                }
+            })
+            .collect()
+    }
+
+    // The light index is the position of the light casting the shadows in the light buffer
+    pub fn uniform(&self, cascades: &[ShadowCascade], light_index: usize) -> ShadowUniform {
+        let mut uniform = ShadowUniform::disabled();
+        for cascade in cascades.iter().take(Self::MAX_CASCADES) {
+            uniform.view_projections[cascade.index] = cascade.view_projection;
+            uniform.splits[cascade.index] = cascade.split_depth;
+        }
+        uniform.parameters = glm::vec4(
+            cascades.len().min(Self::MAX_CASCADES) as f32,
+            self.settings.filter_radius as f32,
+            1.0 / self.settings.map_size as f32,
+            light_index as f32,
+        );
+        uniform
+    }
+
+    // The recorder is called inside the render pass once per cascade, with the viewport set.
+    // This has to be recorded outside of any other render pass.
+    pub fn record<T>(&self, device: &ash::Device, command_buffer: vk::CommandBuffer, recorder: T)
+    where
+        T: FnMut(usize),
+    {
+        record_shadow_layers(
+            device,
+            command_buffer,
+            &self.render_pass,
+            &self.framebuffers,
+            self.settings.map_size,
+            recorder,
+        );
+    }
+
+    fn create_texture(
+        context: Arc<VulkanContext>,
+        settings: &CascadeSettings,
+        format: vk::Format,
+    ) -> Result<Texture> {
+        let image_create_info = vk::ImageCreateInfo::builder()
+            .image_type(vk::ImageType::TYPE_2D)
+            .extent(vk::Extent3D {
+                width: settings.map_size,
+                height: settings.map_size,
+                depth: 1,
+            })
+            .mip_levels(1)
+            .array_layers(settings.cascade_count as _)
+            .format(format)
+            .tiling(vk::ImageTiling::OPTIMAL)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .usage(vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT | vk::ImageUsageFlags::SAMPLED)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .flags(vk::ImageCreateFlags::empty())
+            .build();
+
+        let allocation_create_info = vk_mem::AllocationCreateInfo {
+            usage: vk_mem::MemoryUsage::GpuOnly,
+            ..Default::default()
+        };
+
+        Texture::new(context, &allocation_create_info, &image_create_info)
+            .context(CreateShadowMapTexture {})
+    }
+}
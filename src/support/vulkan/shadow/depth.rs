@@ -0,0 +1,198 @@
+use crate::vulkan::{Framebuffer, ImageView, RenderPass, Sampler, Texture, VulkanContext};
+use ash::{version::DeviceV1_0, vk};
+use std::sync::Arc;
+
+// The render pass shadow casters are drawn with.
+// It only has a single depth attachment, which is left ready to be sampled.
+pub fn create_shadow_render_pass(
+    context: Arc<VulkanContext>,
+    format: vk::Format,
+) -> Result<RenderPass, crate::vulkan::renderpass::Error> {
+    let depth_attachment_description = vk::AttachmentDescription::builder()
+        .format(format)
+        .samples(vk::SampleCountFlags::TYPE_1)
+        .load_op(vk::AttachmentLoadOp::CLEAR)
+        .store_op(vk::AttachmentStoreOp::STORE)
+        .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+        .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+        .initial_layout(vk::ImageLayout::UNDEFINED)
+        .final_layout(vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL)
+        .build();
+    let attachment_descriptions = [depth_attachment_description];
+
+    let depth_attachment_reference = vk::AttachmentReference::builder()
+        .attachment(0)
+        .layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)
+        .build();
+
+    let subpass_description = vk::SubpassDescription::builder()
+        .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+        .depth_stencil_attachment(&depth_attachment_reference)
+        .build();
+    let subpass_descriptions = [subpass_description];
+
+    // The previous frame's shading has to finish reading before the casters are drawn,
+    // and the casters have to be drawn before this frame's shading reads them
+    let subpass_dependencies = [
+        vk::SubpassDependency::builder()
+            .src_subpass(vk::SUBPASS_EXTERNAL)
+            .dst_subpass(0)
+            .src_stage_mask(vk::PipelineStageFlags::FRAGMENT_SHADER)
+            .src_access_mask(vk::AccessFlags::SHADER_READ)
+            .dst_stage_mask(vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS)
+            .dst_access_mask(vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE)
+            .dependency_flags(vk::DependencyFlags::BY_REGION)
+            .build(),
+        vk::SubpassDependency::builder()
+            .src_subpass(0)
+            .dst_subpass(vk::SUBPASS_EXTERNAL)
+            .src_stage_mask(vk::PipelineStageFlags::LATE_FRAGMENT_TESTS)
+            .src_access_mask(vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE)
+            .dst_stage_mask(vk::PipelineStageFlags::FRAGMENT_SHADER)
+            .dst_access_mask(vk::AccessFlags::SHADER_READ)
+            .dependency_flags(vk::DependencyFlags::BY_REGION)
+            .build(),
+    ];
+
+    let create_info = vk::RenderPassCreateInfo::builder()
+        .attachments(&attachment_descriptions)
+        .subpasses(&subpass_descriptions)
+        .dependencies(&subpass_dependencies)
+        .build();
+
+    RenderPass::new(context, &create_info)
+}
+
+// Views the depth of a range of a shadow texture's layers
+pub fn create_shadow_view(
+    context: Arc<VulkanContext>,
+    texture: &Texture,
+    format: vk::Format,
+    view_type: vk::ImageViewType,
+    base_array_layer: u32,
+    layer_count: u32,
+) -> Result<ImageView, crate::vulkan::image_view::Error> {
+    let create_info = vk::ImageViewCreateInfo::builder()
+        .image(texture.image())
+        .view_type(view_type)
+        .format(format)
+        .components(vk::ComponentMapping {
+            r: vk::ComponentSwizzle::IDENTITY,
+            g: vk::ComponentSwizzle::IDENTITY,
+            b: vk::ComponentSwizzle::IDENTITY,
+            a: vk::ComponentSwizzle::IDENTITY,
+        })
+        .subresource_range(vk::ImageSubresourceRange {
+            aspect_mask: vk::ImageAspectFlags::DEPTH,
+            base_mip_level: 0,
+            level_count: 1,
+            base_array_layer,
+            layer_count,
+        })
+        .build();
+    ImageView::new(context, create_info)
+}
+
+// One framebuffer per view, each a square layer of the given size
+pub fn create_shadow_framebuffers(
+    context: Arc<VulkanContext>,
+    render_pass: &RenderPass,
+    views: &[ImageView],
+    size: u32,
+) -> Result<Vec<Framebuffer>, crate::vulkan::framebuffer::Error> {
+    views
+        .iter()
+        .map(|view| {
+            let attachments = [view.view()];
+            let create_info = vk::FramebufferCreateInfo::builder()
+                .render_pass(render_pass.render_pass())
+                .attachments(&attachments)
+                .width(size)
+                .height(size)
+                .layers(1)
+                .build();
+            Framebuffer::new(context.clone(), create_info)
+        })
+        .collect()
+}
+
+// Compares against the stored depth. Linear filtering of a comparison sampler blends
+// the results of the neighboring texels' comparisons, which smooths the edges of each
+// filter tap. The address mode applies to the U and V coordinates.
+pub fn create_shadow_sampler(
+    context: Arc<VulkanContext>,
+    address_mode: vk::SamplerAddressMode,
+) -> Result<Sampler, crate::vulkan::sampler::Error> {
+    let sampler_info = vk::SamplerCreateInfo::builder()
+        .mag_filter(vk::Filter::LINEAR)
+        .min_filter(vk::Filter::LINEAR)
+        .address_mode_u(address_mode)
+        .address_mode_v(address_mode)
+        .address_mode_w(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+        .border_color(vk::BorderColor::FLOAT_OPAQUE_WHITE)
+        .anisotropy_enable(false)
+        .max_anisotropy(1.0)
+        .unnormalized_coordinates(false)
+        .compare_enable(true)
+        .compare_op(vk::CompareOp::LESS_OR_EQUAL)
+        .mipmap_mode(vk::SamplerMipmapMode::NEAREST)
+        .mip_lod_bias(0.0)
+        .min_lod(0.0)
+        .max_lod(0.0)
+        .build();
+    Sampler::new(context, sampler_info)
+}
+
+// Begins the render pass once per framebuffer, each of which is a square layer of
+// the given size. The recorder is called inside the render pass with the framebuffer's
+// index, after the viewport is set.
+pub fn record_shadow_layers<T>(
+    device: &ash::Device,
+    command_buffer: vk::CommandBuffer,
+    render_pass: &RenderPass,
+    framebuffers: &[Framebuffer],
+    size: u32,
+    mut recorder: T,
+) where
+    T: FnMut(usize),
+{
+    let extent = vk::Extent2D {
+        width: size,
+        height: size,
+    };
+    let clear_values = [vk::ClearValue {
+        depth_stencil: vk::ClearDepthStencilValue {
+            depth: 1.0,
+            stencil: 0,
+        },
+    }];
+    let viewports = [vk::Viewport {
+        x: 0.0,
+        y: 0.0,
+        width: size as _,
+        height: size as _,
+        min_depth: 0.0,
+        max_depth: 1.0,
+    }];
+    let scissors = [vk::Rect2D {
+        offset: vk::Offset2D { x: 0, y: 0 },
+        extent,
+    }];
+
+    for (index, framebuffer) in framebuffers.iter().enumerate() {
+        let render_pass_begin_info = vk::RenderPassBeginInfo::builder()
+            .render_pass(render_pass.render_pass())
+            .framebuffer(framebuffer.framebuffer())
+            .render_area(vk::Rect2D {
+                offset: vk::Offset2D { x: 0, y: 0 },
+                extent,
+            })
+            .clear_values(&clear_values)
+            .build();
+        render_pass.record(command_buffer, &render_pass_begin_info, || unsafe {
+            device.cmd_set_viewport(command_buffer, 0, &viewports);
+            device.cmd_set_scissor(command_buffer, 0, &scissors);
+            recorder(index);
+        });
+    }
+}
@@ -0,0 +1,18 @@
+pub use self::{
+    cascade::{
+        CascadeCamera, CascadeSettings, CascadeSettingsBuilder, CascadeSplitScheme,
+        CascadedShadowMap, ShadowCascade, ShadowUniform,
+    },
+    depth::{
+        create_shadow_framebuffers, create_shadow_render_pass, create_shadow_sampler,
+        create_shadow_view, record_shadow_layers,
+    },
+    point::{
+        PointShadowBufferObject, PointShadowCaster, PointShadowFace, PointShadowMaps,
+        PointShadowSettings, PointShadowSettingsBuilder, PointShadowUniform,
+    },
+};
+
+pub mod cascade;
+pub mod depth;
+pub mod point;
@@ -0,0 +1,376 @@
+use crate::vulkan::{
+    create_shadow_framebuffers, create_shadow_render_pass, create_shadow_sampler,
+    create_shadow_view, cube_face_views, record_shadow_layers, DepthBias, Framebuffer, ImageView,
+    PunctualLight, RenderPass, Sampler, Texture, VulkanContext,
+};
+use ash::vk;
+use derive_builder::Builder;
+use nalgebra_glm as glm;
+use snafu::{ResultExt, Snafu};
+use std::sync::Arc;
+
+type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility = "pub(crate)")]
+pub enum Error {
+    #[snafu(display("Failed to create point shadow render pass: {}", source))]
+    CreatePointShadowRenderPass {
+        source: crate::vulkan::renderpass::Error,
+    },
+
+    #[snafu(display("Failed to create point shadow cube: {}", source))]
+    CreatePointShadowTexture {
+        source: crate::vulkan::texture::Error,
+    },
+
+    #[snafu(display("Failed to create point shadow cube view: {}", source))]
+    CreatePointShadowView {
+        source: crate::vulkan::image_view::Error,
+    },
+
+    #[snafu(display("Failed to create point shadow framebuffer: {}", source))]
+    CreatePointShadowFramebuffer {
+        source: crate::vulkan::framebuffer::Error,
+    },
+
+    #[snafu(display("Failed to create point shadow sampler: {}", source))]
+    CreatePointShadowSampler {
+        source: crate::vulkan::sampler::Error,
+    },
+}
+
+#[derive(Builder, Debug, Clone, Copy, PartialEq)]
+#[builder(default, setter(into))]
+pub struct PointShadowSettings {
+    // Up to `PointShadowMaps::MAX_SHADOWS`.
+    // Every cube is rendered each frame, so this is also the number of
+    // point shadows updated per frame.
+    pub max_shadows: usize,
+
+    // The width and height of each face
+    pub face_size: u32,
+
+    pub near_plane: f32,
+
+    // Shadows of lights without a range end this far from the light
+    pub far_plane: f32,
+
+    // Applied while rendering the cubes
+    pub depth_bias: DepthBias,
+
+    // The filter samples around a fragment out to this many texels
+    pub filter_radius: f32,
+}
+
+impl Default for PointShadowSettings {
+    fn default() -> Self {
+        Self {
+            max_shadows: 4,
+            face_size: 512,
+            near_plane: 0.05,
+            far_plane: 50.0,
+            depth_bias: DepthBias {
+                constant_factor: 1.25,
+                slope_factor: 1.75,
+                clamp: 0.0,
+            },
+            filter_radius: 1.5,
+        }
+    }
+}
+
+// A point light that casts shadows into one of the cubes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PointShadowCaster {
+    // The position of the light in the light buffer
+    pub light_index: usize,
+
+    pub position: glm::Vec3,
+    pub far_plane: f32,
+}
+
+// The view one face of a cube is rendered with
+#[derive(Debug, Clone, Copy)]
+pub struct PointShadowFace {
+    // The face's layer in the cube
+    pub index: usize,
+
+    pub view_projection: glm::Mat4,
+}
+
+// A cube is packed into two vectors for the shaders
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct PointShadowUniform {
+    // XYZ is the light's position.
+    // W is the light's index in the light buffer, or -1 when the cube is unused.
+    pub position: glm::Vec4,
+
+    // X and Y are the near and far planes.
+    // Z is the filter radius in texels.
+    // W is the size of a texel at a distance of one from the light.
+    pub parameters: glm::Vec4,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct PointShadowBufferObject {
+    pub shadows: [PointShadowUniform; PointShadowMaps::MAX_SHADOWS],
+
+    // The faces of each cube in turn, indexed by the face the casters are drawn into
+    pub view_projections: [glm::Mat4; PointShadowMaps::MAX_FACES],
+}
+
+struct PointShadowCube {
+    texture: Texture,
+    view: ImageView,
+    _face_views: Vec<ImageView>,
+    framebuffers: Vec<Framebuffer>,
+}
+
+// Shadows of point lights, each rendered into the faces of a depth cube.
+// Faces are laid out like probe captures, so a cube is sampled with the
+// direction from the light to the fragment.
+pub struct PointShadowMaps {
+    settings: PointShadowSettings,
+    render_pass: Arc<RenderPass>,
+    cubes: Vec<PointShadowCube>,
+    sampler: Sampler,
+
+    // The lights the cubes are rendered for, one per cube from the first
+    casters: Vec<PointShadowCaster>,
+}
+
+impl PointShadowMaps {
+    // This needs to match the defined value in the shaders
+    pub const MAX_SHADOWS: usize = 4;
+
+    pub const MAX_FACES: usize = Self::MAX_SHADOWS * 6;
+
+    pub fn new(context: Arc<VulkanContext>, settings: PointShadowSettings) -> Result<Self> {
+        let settings = PointShadowSettings {
+            max_shadows: settings.max_shadows.clamp(1, Self::MAX_SHADOWS),
+            ..settings
+        };
+
+        let format = context.determine_depth_format(
+            vk::ImageTiling::OPTIMAL,
+            vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT
+                | vk::FormatFeatureFlags::SAMPLED_IMAGE,
+        );
+        let render_pass = Arc::new(
+            create_shadow_render_pass(context.clone(), format)
+                .context(CreatePointShadowRenderPass {})?,
+        );
+        let cubes = (0..settings.max_shadows)
+            .map(|_| Self::create_cube(context.clone(), &render_pass, &settings, format))
+            .collect::<Result<Vec<_>>>()?;
+
+        // Directions are clamped to the face they point through
+        let sampler = create_shadow_sampler(context, vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .context(CreatePointShadowSampler {})?;
+
+        Ok(Self {
+            settings,
+            render_pass,
+            cubes,
+            sampler,
+            casters: Vec::new(),
+        })
+    }
+
+    pub fn settings(&self) -> &PointShadowSettings {
+        &self.settings
+    }
+
+    // Pipelines that draw casters are created against this render pass.
+    // It only has a single sampled depth attachment.
+    pub fn render_pass(&self) -> Arc<RenderPass> {
+        self.render_pass.clone()
+    }
+
+    // The cube view of each slot the shaders sample.
+    // Slots past the number of cubes repeat the first one.
+    pub fn views(&self) -> Vec<&ImageView> {
+        (0..Self::MAX_SHADOWS)
+            .map(|slot| &self.cubes.get(slot).unwrap_or(&self.cubes[0]).view)
+            .collect()
+    }
+
+    pub fn textures(&self) -> Vec<&Texture> {
+        self.cubes.iter().map(|cube| &cube.texture).collect()
+    }
+
+    // Compares against the stored depth, for samplerCubeShadow
+    pub fn sampler(&self) -> &Sampler {
+        &self.sampler
+    }
+
+    // The point lights nearest to the focus get the cubes
+    pub fn casters(&self, lights: &[PunctualLight], focus: &glm::Vec3) -> Vec<PointShadowCaster> {
+        let mut casters = lights
+            .iter()
+            .enumerate()
+            .filter_map(|(light_index, light)| match light {
+                PunctualLight::Point(light) => Some(PointShadowCaster {
+                    light_index,
+                    position: light.position,
+                    far_plane: light.range.unwrap_or(self.settings.far_plane),
+                }),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        casters.sort_by(|first, second| {
+            let first = glm::distance(focus, &first.position);
+            let second = glm::distance(focus, &second.position);
+            first
+                .partial_cmp(&second)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        casters.truncate(self.cubes.len());
+        casters
+    }
+
+    pub fn faces(&self, caster: &PointShadowCaster) -> Vec<PointShadowFace> {
+        let projection = glm::perspective_zo(
+            1.0,
+            90_f32.to_radians(),
+            self.settings.near_plane,
+            caster.far_plane,
+        );
+        cube_face_views(&caster.position)
+            .iter()
+            .enumerate()
+            .map(|(index, view)| PointShadowFace {
+                index,
+                view_projection: projection * view,
+            })
+            .collect()
+    }
+
+    // Picks the lights the cubes are rendered for in the following frames
+    pub fn update(&mut self, casters: &[PointShadowCaster]) {
+        self.casters = casters.iter().take(self.cubes.len()).copied().collect();
+    }
+
+    // Describes the cubes and the faces they're rendered with
+    pub fn uniform(&self) -> PointShadowBufferObject {
+        let unused = PointShadowUniform {
+            position: glm::vec4(0.0, 0.0, 0.0, -1.0),
+            parameters: glm::Vec4::zeros(),
+        };
+        let mut object = PointShadowBufferObject {
+            shadows: [unused; Self::MAX_SHADOWS],
+            view_projections: [glm::Mat4::zeros(); Self::MAX_FACES],
+        };
+
+        // A face spans twice its distance from the light
+        let texel_size = 2.0 / self.settings.face_size as f32;
+        for (slot, caster) in self.casters.iter().enumerate() {
+            object.shadows[slot] = PointShadowUniform {
+                position: caster.position.insert_row(3, caster.light_index as f32),
+                parameters: glm::vec4(
+                    self.settings.near_plane,
+                    caster.far_plane,
+                    self.settings.filter_radius,
+                    texel_size,
+                ),
+            };
+            for face in self.faces(caster) {
+                object.view_projections[slot * 6 + face.index] = face.view_projection;
+            }
+        }
+        object
+    }
+
+    // The recorder is called inside the render pass once per face of every cube,
+    // with the viewport set and the face's index in the uniform's view projections.
+    // Faces of cubes without a caster have no view projection, so they're only cleared.
+    // This has to be recorded outside of any other render pass.
+    pub fn record<T>(
+        &self,
+        device: &ash::Device,
+        command_buffer: vk::CommandBuffer,
+        mut recorder: T,
+    ) where
+        T: FnMut(usize),
+    {
+        for (slot, cube) in self.cubes.iter().enumerate() {
+            record_shadow_layers(
+                device,
+                command_buffer,
+                &self.render_pass,
+                &cube.framebuffers,
+                self.settings.face_size,
+                |face| recorder(slot * 6 + face),
+            );
+        }
+    }
+
+    fn create_cube(
+        context: Arc<VulkanContext>,
+        render_pass: &RenderPass,
+        settings: &PointShadowSettings,
+        format: vk::Format,
+    ) -> Result<PointShadowCube> {
+        let image_create_info = vk::ImageCreateInfo::builder()
+            .image_type(vk::ImageType::TYPE_2D)
+            .extent(vk::Extent3D {
+                width: settings.face_size,
+                height: settings.face_size,
+                depth: 1,
+            })
+            .mip_levels(1)
+            .array_layers(6)
+            .format(format)
+            .tiling(vk::ImageTiling::OPTIMAL)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .usage(vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT | vk::ImageUsageFlags::SAMPLED)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .flags(vk::ImageCreateFlags::CUBE_COMPATIBLE)
+            .build();
+
+        let allocation_create_info = vk_mem::AllocationCreateInfo {
+            usage: vk_mem::MemoryUsage::GpuOnly,
+            ..Default::default()
+        };
+
+        let texture = Texture::new(context.clone(), &allocation_create_info, &image_create_info)
+            .context(CreatePointShadowTexture {})?;
+
+        let view = create_shadow_view(
+            context.clone(),
+            &texture,
+            format,
+            vk::ImageViewType::CUBE,
+            0,
+            6,
+        )
+        .context(CreatePointShadowView {})?;
+        let face_views = (0..6)
+            .map(|face| {
+                create_shadow_view(
+                    context.clone(),
+                    &texture,
+                    format,
+                    vk::ImageViewType::TYPE_2D,
+                    face,
+                    1,
+                )
+                .context(CreatePointShadowView {})
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let framebuffers =
+            create_shadow_framebuffers(context, render_pass, &face_views, settings.face_size)
+                .context(CreatePointShadowFramebuffer {})?;
+
+        Ok(PointShadowCube {
+            texture,
+            view,
+            _face_views: face_views,
+            framebuffers,
+        })
+    }
+}
@@ -0,0 +1,109 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Error, Fields};
+
+// Implements `support::vulkan::Vertex` for a `#[repr(C)]` struct with named fields.
+// Each field becomes an attribute at the next free shader location,
+// with its format taken from the field type's `VertexAttributeFormat` implementation.
+// Structs marked `#[vertex(instance)]` are read once per instance instead of once per vertex.
+#[proc_macro_derive(Vertex, attributes(vertex))]
+pub fn derive_vertex(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_vertex(&input)
+        .unwrap_or_else(|error| error.to_compile_error())
+        .into()
+}
+
+fn expand_vertex(input: &DeriveInput) -> Result<TokenStream2, Error> {
+    if !input.generics.params.is_empty() {
+        return Err(Error::new_spanned(
+            &input.generics,
+            "Vertex cannot be derived for generic structs",
+        ));
+    }
+
+    if !has_repr_c(input) {
+        return Err(Error::new_spanned(
+            &input.ident,
+            "Vertex can only be derived for structs marked #[repr(C)]",
+        ));
+    }
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(Error::new_spanned(
+                    &input.ident,
+                    "Vertex can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(Error::new_spanned(
+                &input.ident,
+                "Vertex can only be derived for structs",
+            ))
+        }
+    };
+
+    let name = &input.ident;
+    let attributes = fields.iter().map(|field| {
+        let field_name = &field.ident;
+        let field_type = &field.ty;
+        quote! {
+            ::support::vulkan::VertexAttribute::of::<#field_type>(
+                ::std::mem::offset_of!(#name, #field_name) as u32
+            )
+        }
+    });
+
+    let input_rate = if is_per_instance(input)? {
+        quote! {
+            fn input_rate() -> ::ash::vk::VertexInputRate {
+                ::ash::vk::VertexInputRate::INSTANCE
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    Ok(quote! {
+        impl ::support::vulkan::Vertex for #name {
+            fn attributes() -> ::std::vec::Vec<::support::vulkan::VertexAttribute> {
+                vec![#(#attributes),*]
+            }
+
+            #input_rate
+        }
+    })
+}
+
+fn is_per_instance(input: &DeriveInput) -> Result<bool, Error> {
+    let mut per_instance = false;
+    for attribute in input
+        .attrs
+        .iter()
+        .filter(|attribute| attribute.path.is_ident("vertex"))
+    {
+        let argument = attribute.parse_args::<syn::Ident>()?;
+        if argument != "instance" {
+            return Err(Error::new_spanned(argument, "Expected #[vertex(instance)]"));
+        }
+        per_instance = true;
+    }
+    Ok(per_instance)
+}
+
+fn has_repr_c(input: &DeriveInput) -> bool {
+    input.attrs.iter().any(|attribute| {
+        attribute.path.is_ident("repr")
+            && attribute
+                .parse_args_with(
+                    syn::punctuated::Punctuated::<syn::Ident, syn::Token![,]>::parse_terminated,
+                )
+                .map(|representations| representations.iter().any(|repr| repr == "C"))
+                .unwrap_or(false)
+    })
+}